@@ -0,0 +1,76 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Number of steps (e.g. candidate terms) between checks for cancellation in
+/// long running operations.
+pub(crate) const CANCEL_CHECK_INTERVAL: usize = 256;
+
+/// Token for cancelling a long running search, either explicitly or once a
+/// deadline is reached.
+///
+/// Tokens are cheap to clone and all clones share the same state, so an
+/// interactive frontend can keep a clone and [cancel](Cancel::cancel) a
+/// stale query from another thread.
+#[derive(Clone, Default, Debug)]
+pub struct Cancel {
+	cancelled: Arc<AtomicBool>,
+	deadline: Option<Instant>,
+}
+
+impl Cancel {
+	/// Returns a new token without a deadline.
+	pub fn new() -> Cancel {
+		Cancel::default()
+	}
+
+	/// Returns a new token that is cancelled once the given time elapses.
+	pub fn timeout(timeout: Duration) -> Cancel {
+		Cancel::new().deadline(Instant::now() + timeout)
+	}
+
+	/// Sets a deadline for the token.
+	pub fn deadline(mut self, deadline: Instant) -> Cancel {
+		self.deadline = Some(deadline);
+		self
+	}
+
+	/// Cancels the operations using this token (or any of its clones).
+	pub fn cancel(&self) {
+		self.cancelled.store(true, Ordering::Relaxed);
+	}
+
+	/// Returns true if the token was cancelled or the deadline was reached.
+	pub fn is_cancelled(&self) -> bool {
+		if self.cancelled.load(Ordering::Relaxed) {
+			return true;
+		}
+		match self.deadline {
+			Some(deadline) => Instant::now() >= deadline,
+			None => false,
+		}
+	}
+
+	/// Returns an error if the token was cancelled.
+	pub fn check(&self) -> Result<(), Cancelled> {
+		if self.is_cancelled() {
+			Err(Cancelled)
+		} else {
+			Ok(())
+		}
+	}
+}
+
+/// Error for an operation cancelled by a [Cancel] token.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "operation cancelled")
+	}
+}
+
+impl Error for Cancelled {}
@@ -0,0 +1,306 @@
+//! Collation for the index keys.
+//!
+//! Index keys are sorted by unicode codepoint order of the key after it is
+//! normalized with [normalize_key]. The same normalization is applied to the
+//! keywords at query time, so the binary search in the index always agrees
+//! with the order used when writing the database.
+//!
+//! Note that for UTF-8 strings the codepoint order is the same as the raw
+//! byte order, which is what `str::cmp` uses, so normalized keys can be
+//! compared directly.
+//!
+//! The normalization is a subset of the Unicode NFKC normalization that is
+//! relevant for Japanese text:
+//!
+//! - Half-width katakana and punctuation are converted to their full-width
+//!   forms (e.g. `ﾀﾍﾞﾙ` to `タベル`).
+//! - Full-width ASCII is converted to ASCII (e.g. `ＡＢＣ` to `ABC`) and the
+//!   ideographic space to a plain space.
+//! - Kana followed by a combining (han)dakuten (`U+3099` and `U+309A`) is
+//!   composed into the respective voiced kana (e.g. `か\u{3099}` to `が`).
+//!
+//! Additionally, the middle dot separating the parts of foreign names and
+//! loanwords is removed, except at the start of the key, so that
+//! `マリー・アントワネット` and `マリーアントワネット` are the same key. The
+//! writer also indexes each part of a dotted expression separately (see
+//! [middle_dot_parts]).
+//!
+//! Terms keep their original strings for display. The original strings for a
+//! normalized key are available with [DB::key_variants].
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+use super::DB;
+
+/// Half-width characters from `U+FF61` to `U+FF9D`.
+const HALF_WIDTH: &'static str = "｡｢｣､･ｦｧｨｩｪｫｬｭｮｯｰｱｲｳｴｵｶｷｸｹｺｻｼｽｾｿﾀﾁﾂﾃﾄﾅﾆﾇﾈﾉﾊﾋﾌﾍﾎﾏﾐﾑﾒﾓﾔﾕﾖﾗﾘﾙﾚﾛﾜﾝ";
+
+/// Full-width equivalent for each character in [HALF_WIDTH].
+const FULL_WIDTH: &'static str = "。「」、・ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン";
+
+const MIDDLE_DOT: char = '・';
+
+const COMBINING_DAKUTEN: char = '\u{3099}';
+const COMBINING_HANDAKUTEN: char = '\u{309A}';
+
+/// Normalizes a key for the index collation. See the [module](self) docs
+/// for the normalization applied.
+///
+/// Returns the key unchanged (and without allocating) if it is already
+/// normalized.
+pub fn normalize_key(key: &str) -> Cow<'_, str> {
+	if key.chars().all(|chr| !needs_normalization(chr)) {
+		return Cow::Borrowed(key);
+	}
+
+	let mut out = String::with_capacity(key.len());
+	for chr in key.chars() {
+		let chr = match chr {
+			'\u{FF61}'..='\u{FF9D}' => {
+				let pos = HALF_WIDTH.chars().position(|x| x == chr).unwrap();
+				FULL_WIDTH.chars().nth(pos).unwrap()
+			}
+			'\u{FF9E}' => COMBINING_DAKUTEN,
+			'\u{FF9F}' => COMBINING_HANDAKUTEN,
+			'\u{FF01}'..='\u{FF5E}' => std::char::from_u32(chr as u32 - 0xFEE0).unwrap(),
+			'\u{3000}' => ' ',
+			_ => chr,
+		};
+
+		if chr == MIDDLE_DOT && out.len() > 0 {
+			continue;
+		}
+		if chr == COMBINING_DAKUTEN || chr == COMBINING_HANDAKUTEN {
+			let composed = out.chars().next_back().and_then(|last| compose(last, chr));
+			if let Some(composed) = composed {
+				out.pop();
+				out.push(composed);
+				continue;
+			}
+		}
+		out.push(chr);
+	}
+	Cow::Owned(out)
+}
+
+/// Returns the parts of a key separated by middle dots (e.g. `マリー` and
+/// `アントワネット` for `マリー・アントワネット`).
+///
+/// Returns an empty list if the key has no middle dot between two parts.
+pub fn middle_dot_parts(key: &str) -> Vec<&str> {
+	let parts: Vec<&str> = key
+		.split(|x| x == MIDDLE_DOT || x == '･')
+		.filter(|x| x.len() > 0)
+		.collect();
+	if parts.len() > 1 {
+		parts
+	} else {
+		Vec::new()
+	}
+}
+
+/// Compares two keys using the index collation.
+pub fn compare_keys(a: &str, b: &str) -> Ordering {
+	normalize_key(a).cmp(&normalize_key(b))
+}
+
+impl<'a> DB<'a> {
+	/// Returns the original strings from the source dictionaries that are
+	/// indexed with the same key as `key` after normalization, in key order
+	/// (e.g. `タベル` and `ﾀﾍﾞﾙ` for `ﾀﾍﾞﾙ`).
+	///
+	/// Terms keep the original expression and reading for display, so this
+	/// is only needed to present the forms a normalized key stands for. The
+	/// list is empty if the normalization did not change any string indexed
+	/// with the key.
+	pub fn key_variants(&self, key: &str) -> Vec<&'a str> {
+		let key = normalize_key(key);
+		let key = key.as_ref();
+		let sta = self.key_variants.partition_point(|x| self.get_str(x.key) < key);
+		self.key_variants[sta..]
+			.iter()
+			.take_while(|x| self.get_str(x.key) == key)
+			.map(|x| self.get_str(x.original))
+			.collect()
+	}
+}
+
+fn needs_normalization(chr: char) -> bool {
+	match chr {
+		'\u{FF01}'..='\u{FF5E}' | '\u{FF61}'..='\u{FF9F}' => true,
+		'\u{3000}' | MIDDLE_DOT | COMBINING_DAKUTEN | COMBINING_HANDAKUTEN => true,
+		_ => false,
+	}
+}
+
+/// Composes a kana with a combining (han)dakuten.
+fn compose(base: char, mark: char) -> Option<char> {
+	const DAKUTEN: &'static str = "かきくけこさしすせそたちつてとはひふへほカキクケコサシスセソタチツテトハヒフヘホゝヽ";
+	const HANDAKUTEN: &'static str = "はひふへほハヒフヘホ";
+	let code = base as u32;
+	let code = if mark == COMBINING_DAKUTEN {
+		match base {
+			'う' => 0x3094,
+			'ウ' => 0x30F4,
+			'ワ' | 'ヰ' | 'ヱ' | 'ヲ' => code + 8,
+			_ if DAKUTEN.contains(base) => code + 1,
+			_ => return None,
+		}
+	} else if HANDAKUTEN.contains(base) {
+		code + 2
+	} else {
+		return None;
+	};
+	std::char::from_u32(code)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::{ResultSet, TagData, TermData, TermFlags, Writer, DB};
+	use super::*;
+
+	const KEYS: &[&str] = &[
+		"たべる",
+		"たべもの",
+		"た",
+		"タベル",
+		"ﾀﾍﾞﾙ",
+		"ﾀﾍﾞﾓﾉ",
+		"は\u{309A}ん",
+		"ぱん",
+		"ﾊﾟﾝ",
+		"パン",
+		"ＡＢＣ",
+		"ABC",
+		"abc",
+		"ab",
+		"漢字",
+		"𠀋",
+		"ー",
+		"ｰ",
+		"ヴ",
+		"ｳﾞ",
+		"ワ\u{3099}",
+		"ヷ",
+		"マリー・アントワネット",
+		"マリーアントワネット",
+		"ﾏﾘｰ･ｱﾝﾄﾜﾈｯﾄ",
+		"・",
+		"・マ",
+	];
+
+	#[test]
+	fn normalizes_keys() {
+		assert_eq!(normalize_key("ﾀﾍﾞﾙ"), "タベル");
+		assert_eq!(normalize_key("ﾊﾟﾝ"), "パン");
+		assert_eq!(normalize_key("は\u{309A}ん"), "ぱん");
+		assert_eq!(normalize_key("ｳﾞｧ"), "ヴァ");
+		assert_eq!(normalize_key("ワ\u{3099}"), "ヷ");
+		assert_eq!(normalize_key("ＡＢＣ　１２３"), "ABC 123");
+		assert_eq!(normalize_key("ﾞ"), "\u{3099}");
+		assert_eq!(normalize_key("マリー・アントワネット"), "マリーアントワネット");
+		assert_eq!(normalize_key("ﾏﾘｰ･ｱﾝﾄﾜﾈｯﾄ"), "マリーアントワネット");
+		assert_eq!(normalize_key("・"), "・");
+		assert!(match normalize_key("たべる") {
+			Cow::Borrowed(_) => true,
+			Cow::Owned(_) => false,
+		});
+	}
+
+	#[test]
+	fn normalization_is_idempotent() {
+		for key in KEYS {
+			let once = normalize_key(key).into_owned();
+			assert_eq!(normalize_key(&once), once, "key {:?}", key);
+		}
+	}
+
+	#[test]
+	fn compare_agrees_with_normalized_order() {
+		for a in KEYS {
+			for b in KEYS {
+				let expected = normalize_key(a).as_bytes().cmp(normalize_key(b).as_bytes());
+				assert_eq!(compare_keys(a, b), expected, "keys {:?} and {:?}", a, b);
+			}
+		}
+	}
+
+	#[test]
+	fn binary_search_agrees_with_sort() {
+		let mut sorted: Vec<String> = KEYS.iter().map(|x| normalize_key(x).into_owned()).collect();
+		sorted.sort();
+		sorted.dedup();
+		for key in KEYS {
+			let pos = sorted.binary_search_by(|x| compare_keys(x, key));
+			assert!(pos.is_ok(), "key {:?} not found", key);
+			assert_eq!(compare_keys(&sorted[pos.unwrap()], key), Ordering::Equal);
+		}
+	}
+
+	#[test]
+	fn database_search_agrees_with_write() {
+		let data = write_keys();
+		let db = DB::load(&data);
+
+		for key in KEYS {
+			let mut exact = ResultSet::default();
+			assert!(db.search_term(key, &mut exact) > 0, "exact search for {:?}", key);
+			for index in exact.iter() {
+				let term = db.term(index).unwrap();
+				assert_eq!(compare_keys(term.expression(), key), Ordering::Equal);
+			}
+
+			let mut prefix = ResultSet::default();
+			db.search_prefix(key, &mut prefix);
+			for index in exact.iter() {
+				assert!(prefix.iter().any(|x| x == index), "prefix search for {:?}", key);
+			}
+		}
+	}
+
+	#[test]
+	fn key_variants_map_to_originals() {
+		let data = write_keys();
+		let db = DB::load(&data);
+		assert_eq!(db.key_variants("ﾀﾍﾞﾙ"), vec!["タベル", "ﾀﾍﾞﾙ"]);
+		assert_eq!(db.key_variants("ぱん"), vec!["は\u{309A}ん", "ぱん"]);
+		assert_eq!(db.key_variants("ﾀﾍﾞﾓﾉ"), vec!["ﾀﾍﾞﾓﾉ"]);
+		assert!(db.key_variants("たべる").is_empty());
+		assert!(db.key_variants("missing").is_empty());
+	}
+
+	fn write_keys() -> Vec<u8> {
+		let mut w = Writer::new();
+		let name = w.intern(String::from("n"));
+		w.push_tag(TagData {
+			name: name,
+			category: 0,
+			order: 0,
+			notes: 0,
+		});
+		for (i, key) in KEYS.iter().enumerate() {
+			let expression = w.intern(key.to_string());
+			w.push_term(TermData {
+				expression: expression,
+				reading: 0,
+				search_key: 0,
+				score: 0,
+				sequence: i as u32,
+				order: i as u32,
+				frequency: 0,
+				glossary: Vec::new(),
+				rules: Vec::new(),
+				term_tags: Vec::new(),
+				definition_tags: Vec::new(),
+				source: 0,
+				flags: TermFlags::default(),
+				pitch: Vec::new(),
+			});
+		}
+
+		let mut data = Vec::new();
+		w.write(&mut data).unwrap();
+		data
+	}
+}
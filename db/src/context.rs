@@ -0,0 +1,181 @@
+//! Context ranking for the terms matched by [DB::scan].
+//!
+//! A surface form can match several terms with different readings (e.g.
+//! `辛い` as `からい` or `つらい`). The terms are ranked by how well their
+//! part of speech fits the neighbouring tokens, using a few transition
+//! heuristics between word classes:
+//!
+//! - Suffixes, particles and auxiliaries follow a noun (e.g. `田中家` as
+//!   `け`), but not a particle or the start of a sentence.
+//! - Prefixes precede a noun.
+//! - Adverbs precede a verb or an adjective.
+//! - Verbs and adjectives precede a noun they modify.
+//!
+//! Katakana, latin and numeric tokens count as nouns. Terms with the same
+//! score keep the order of the database, which is by frequency.
+
+use super::ScanMatch;
+use super::TagCategory;
+use super::TokenKind;
+use super::DB;
+
+/// Word class for the context heuristics, from the part of speech tags.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum WordClass {
+	Noun,
+	Verb,
+	Adjective,
+	Adverb,
+	Particle,
+	Auxiliary,
+	Prefix,
+	Suffix,
+}
+
+impl WordClass {
+	/// Returns the word class for a JMdict part of speech tag (e.g. `v5k` or
+	/// `adj-i`). Tags for the verb transitivity (`vt` and `vi`) and other
+	/// tags have no class.
+	pub fn from_tag(tag: &str) -> Option<WordClass> {
+		let class = match tag {
+			"vt" | "vi" => return None,
+			"prt" => WordClass::Particle,
+			"pref" | "n-pref" => WordClass::Prefix,
+			"suf" | "n-suf" | "ctr" => WordClass::Suffix,
+			"adj-na" | "adj-no" => WordClass::Noun,
+			_ if tag.starts_with("aux") => WordClass::Auxiliary,
+			_ if tag.starts_with("adv") => WordClass::Adverb,
+			_ if tag.starts_with("adj") => WordClass::Adjective,
+			_ if tag.starts_with('v') => WordClass::Verb,
+			_ if tag == "n" || tag.starts_with("n-") || tag == "pn" => WordClass::Noun,
+			_ => return None,
+		};
+		Some(class)
+	}
+}
+
+/// Score for a transition between two adjacent words. `None` is the start
+/// or end of a sentence (i.e. no adjacent token).
+fn transition(prev: Option<WordClass>, next: Option<WordClass>) -> i32 {
+	use self::WordClass::*;
+	match (prev, next) {
+		(Some(Noun), Some(Suffix)) | (Some(Noun), Some(Particle)) | (Some(Noun), Some(Auxiliary)) => 1,
+		(Some(Prefix), Some(Noun)) => 1,
+		(Some(Adverb), Some(Verb)) | (Some(Adverb), Some(Adjective)) => 1,
+		(Some(Verb), Some(Noun)) | (Some(Adjective), Some(Noun)) => 1,
+		(None, Some(Suffix)) | (None, Some(Particle)) | (None, Some(Auxiliary)) => -1,
+		(Some(Particle), Some(Suffix)) | (Some(Particle), Some(Particle)) | (Some(Particle), Some(Auxiliary)) => -1,
+		(Some(Prefix), _) => -1,
+		_ => 0,
+	}
+}
+
+/// Best score for a word with the given classes between two neighbours.
+/// Words without a known class score zero.
+fn score(prev: &[WordClass], classes: &[WordClass], next: &[WordClass]) -> i32 {
+	let neighbour = |list: &[WordClass]| -> Vec<Option<WordClass>> {
+		if list.len() > 0 {
+			list.iter().map(|&x| Some(x)).collect()
+		} else {
+			vec![None]
+		}
+	};
+	let (prev, next) = (neighbour(prev), neighbour(next));
+	classes
+		.iter()
+		.map(|&class| {
+			let before = prev.iter().map(|&x| transition(x, Some(class))).max().unwrap_or(0);
+			let after = next.iter().map(|&x| transition(Some(class), x)).max().unwrap_or(0);
+			before + after
+		})
+		.max()
+		.unwrap_or(0)
+}
+
+impl<'a> DB<'a> {
+	/// Ranks the terms for each match by their context in the text, filling
+	/// [ScanMatch::ranked].
+	pub(crate) fn rank_scan_terms(&self, matches: &mut [ScanMatch]) {
+		// Classes for the top term of each token, used as the context
+		let top: Vec<Vec<WordClass>> = matches
+			.iter()
+			.map(|it| match it.kind {
+				TokenKind::Term => it.terms.iter().next().map(|x| self.word_classes(x)).unwrap_or_default(),
+				TokenKind::Url => Vec::new(),
+				_ => vec![WordClass::Noun],
+			})
+			.collect();
+
+		for i in 0..matches.len() {
+			if matches[i].kind != TokenKind::Term {
+				continue;
+			}
+			let prev = match i {
+				0 => &[][..],
+				_ if matches[i - 1].end == matches[i].start => &top[i - 1][..],
+				_ => &[][..],
+			};
+			let next = match matches.get(i + 1) {
+				Some(next) if next.start == matches[i].end => &top[i + 1][..],
+				_ => &[][..],
+			};
+
+			let mut ranked: Vec<(i32, usize)> = matches[i]
+				.terms
+				.iter()
+				.map(|index| (-score(prev, &self.word_classes(index), next), index))
+				.collect();
+			ranked.sort();
+			matches[i].ranked = ranked.into_iter().map(|x| x.1).collect();
+		}
+	}
+
+	/// Returns the word classes for a term, from its conjugation rules and
+	/// part of speech tags.
+	fn word_classes(&self, index: usize) -> Vec<WordClass> {
+		let term = self.term(index).unwrap();
+		let mut out = Vec::new();
+		let tags = term
+			.rules()
+			.chain(term.definition_tags().filter(|x| x.kind() == TagCategory::PartOfSpeech));
+		for tag in tags {
+			if let Some(class) = WordClass::from_tag(tag.name()) {
+				if !out.contains(&class) {
+					out.push(class);
+				}
+			}
+		}
+		out
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn word_class_from_tag() {
+		assert_eq!(WordClass::from_tag("v5k"), Some(WordClass::Verb));
+		assert_eq!(WordClass::from_tag("vs-i"), Some(WordClass::Verb));
+		assert_eq!(WordClass::from_tag("vt"), None);
+		assert_eq!(WordClass::from_tag("adj-i"), Some(WordClass::Adjective));
+		assert_eq!(WordClass::from_tag("adj-na"), Some(WordClass::Noun));
+		assert_eq!(WordClass::from_tag("adv-to"), Some(WordClass::Adverb));
+		assert_eq!(WordClass::from_tag("aux-v"), Some(WordClass::Auxiliary));
+		assert_eq!(WordClass::from_tag("n-suf"), Some(WordClass::Suffix));
+		assert_eq!(WordClass::from_tag("n-adv"), Some(WordClass::Noun));
+		assert_eq!(WordClass::from_tag("prt"), Some(WordClass::Particle));
+		assert_eq!(WordClass::from_tag("uk"), None);
+	}
+
+	#[test]
+	fn transition_scores() {
+		use self::WordClass::*;
+		assert_eq!(score(&[Noun], &[Suffix], &[]), 1);
+		assert_eq!(score(&[], &[Suffix], &[]), -1);
+		assert_eq!(score(&[], &[Noun, Suffix], &[]), 0);
+		assert_eq!(score(&[Adverb], &[Adjective], &[Noun]), 2);
+		assert_eq!(score(&[Particle], &[Particle], &[]), -1);
+		assert_eq!(score(&[], &[], &[]), 0);
+	}
+}
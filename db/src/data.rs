@@ -1,12 +1,19 @@
+use std::cmp::Ordering;
 use std::fmt;
 
+use super::KanjiRaw;
+use super::Priority;
+use super::RadicalRaw;
+use super::SourceRaw;
 use super::TagRaw;
-use super::TermRaw;
+use super::TermTable;
+use super::VecHandle;
 use super::DB;
 
 /// A tag from the database.
 pub struct Tag<'db, 'a: 'db> {
 	pub(super) data: &'a DB<'db>,
+	pub(super) index: usize,
 	pub(super) item: &'a TagRaw,
 }
 
@@ -21,6 +28,11 @@ impl<'db, 'a: 'db> Tag<'db, 'a> {
 		self.data.get_str(self.item.category)
 	}
 
+	/// Tag category as a [TagCategory].
+	pub fn kind(&self) -> TagCategory {
+		TagCategory::from_name(self.category())
+	}
+
 	/// Tag order. Can be used to sort the list of tags in a search result.
 	pub fn order(&self) -> i32 {
 		self.item.order.into()
@@ -30,6 +42,16 @@ impl<'db, 'a: 'db> Tag<'db, 'a> {
 	pub fn notes(&self) -> &'db str {
 		self.data.get_str(self.item.notes)
 	}
+
+	/// Tag notes in the given language (e.g. `pt`), falling back to the
+	/// notes from the source dictionary if there is no translation.
+	///
+	/// See [DB::tag_languages] for the available languages.
+	pub fn notes_in(&self, language: &str) -> &'db str {
+		self.data
+			.tag_translation(self.index, language)
+			.unwrap_or_else(|| self.notes())
+	}
 }
 
 impl<'db, 'a: 'db> fmt::Display for Tag<'db, 'a> {
@@ -50,45 +72,234 @@ impl<'db, 'a: 'db> fmt::Display for Tag<'db, 'a> {
 	}
 }
 
+/// Common tag categories used by the Yomichan dictionaries.
+///
+/// See [Tag::kind]. Categories are ordered as declared, which is the order
+/// used to display the tags (see [compare_tags]).
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum TagCategory {
+	/// Part of speech (e.g. `v1`, `n`).
+	PartOfSpeech,
+	/// Irregular or unusual expression forms (e.g. `ateji`, `iK`).
+	Expression,
+	/// Archaic or obsolete term.
+	Archaism,
+	/// Popular term (e.g. `P`).
+	Popular,
+	/// Frequently used term.
+	Frequent,
+	/// Field or domain of specialized terms (e.g. `med`, `law` or `comp`).
+	///
+	/// This is not a Yomichan category. The importer assigns it to the JMdict
+	/// field tags.
+	Domain,
+	/// Name entries (e.g. from JMnedict).
+	Name,
+	/// Source dictionary.
+	Dictionary,
+	/// Frequency list.
+	Frequency,
+	/// Search related tags.
+	Search,
+	/// Pronunciation dictionary.
+	Pronunciation,
+	/// Tags without a category or with an unknown category.
+	Other,
+}
+
+impl TagCategory {
+	/// Returns the category for a Yomichan category name.
+	pub fn from_name(name: &str) -> TagCategory {
+		match name {
+			"partOfSpeech" => TagCategory::PartOfSpeech,
+			"expression" => TagCategory::Expression,
+			"archaism" => TagCategory::Archaism,
+			"popular" => TagCategory::Popular,
+			"frequent" => TagCategory::Frequent,
+			"domain" => TagCategory::Domain,
+			"name" => TagCategory::Name,
+			"dictionary" => TagCategory::Dictionary,
+			"frequency" => TagCategory::Frequency,
+			"search" => TagCategory::Search,
+			"pronunciation-dictionary" => TagCategory::Pronunciation,
+			_ => TagCategory::Other,
+		}
+	}
+
+	/// Returns true for the categories that describe the usage of a term
+	/// rather than its grammar or origin. Uncategorized tags (e.g. `uk`) are
+	/// included.
+	pub fn is_usage(&self) -> bool {
+		match self {
+			TagCategory::Expression
+			| TagCategory::Archaism
+			| TagCategory::Popular
+			| TagCategory::Frequent
+			| TagCategory::Domain
+			| TagCategory::Other => true,
+			_ => false,
+		}
+	}
+}
+
+/// Tag category used in the database, with the number of tags in it. See
+/// [DB::tag_categories].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TagCategoryInfo<'a> {
+	/// Category name, as in [Tag::category].
+	pub name: &'a str,
+	/// Category as a [TagCategory].
+	pub kind: TagCategory,
+	/// Number of tags in the category.
+	pub tags: usize,
+}
+
+/// Compares two tags in display order.
+///
+/// Tags are sorted by category (in the [TagCategory] order and then by the
+/// category name), then by the tag `order` and then by name.
+pub fn compare_tags(a: &Tag, b: &Tag) -> Ordering {
+	a.kind()
+		.cmp(&b.kind())
+		.then_with(|| a.category().cmp(b.category()))
+		.then_with(|| a.order().cmp(&b.order()))
+		.then_with(|| a.name().cmp(b.name()))
+}
+
+/// Bit flags with additional information for a [Term].
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct TermFlags(pub u32);
+
+impl TermFlags {
+	/// The term glossary is in Japanese (i.e. from a monolingual dictionary).
+	pub const MONOLINGUAL: TermFlags = TermFlags(1 << 0);
+
+	/// The term expression contains kanji.
+	///
+	/// This and the other script flags are set by the [Writer](super::Writer)
+	/// from the expression.
+	pub const KANJI: TermFlags = TermFlags(1 << 1);
+
+	/// The term expression is written only in kana.
+	pub const KANA: TermFlags = TermFlags(1 << 2);
+
+	/// The term expression is written only in katakana (e.g. loanwords).
+	pub const KATAKANA: TermFlags = TermFlags(1 << 3);
+
+	/// The term glossary was shortened on import because it was over the
+	/// size limit.
+	pub const TRUNCATED: TermFlags = TermFlags(1 << 4);
+
+	/// The term is an abbreviation (JMdict `abbr` tag).
+	///
+	/// This and the other tag flags are set by the [Writer](super::Writer)
+	/// from the term tags (see [from_tag](TermFlags::from_tag)), so that
+	/// filters don't depend on the tag names of each dictionary.
+	pub const ABBREVIATION: TermFlags = TermFlags(1 << 5);
+
+	/// The term is a company name (JMnedict `company` tag).
+	pub const COMPANY: TermFlags = TermFlags(1 << 6);
+
+	/// The term is a product name or trademark (JMnedict `product` tag).
+	pub const PRODUCT: TermFlags = TermFlags(1 << 7);
+
+	/// Returns the flag for a JMdict tag name (`abbr`, `company` or
+	/// `product`), if any.
+	pub fn from_tag(name: &str) -> Option<TermFlags> {
+		match name {
+			"abbr" => Some(TermFlags::ABBREVIATION),
+			"company" => Some(TermFlags::COMPANY),
+			"product" => Some(TermFlags::PRODUCT),
+			_ => None,
+		}
+	}
+
+	/// Returns true if all flags in `other` are set.
+	pub fn contains(&self, other: TermFlags) -> bool {
+		self.0 & other.0 == other.0
+	}
+
+	/// Sets all flags in `other`.
+	pub fn insert(&mut self, other: TermFlags) {
+		self.0 |= other.0;
+	}
+}
+
+impl std::ops::BitOr for TermFlags {
+	type Output = TermFlags;
+
+	fn bitor(self, other: TermFlags) -> TermFlags {
+		TermFlags(self.0 | other.0)
+	}
+}
+
 /// Term from the database.
 pub struct Term<'db, 'a: 'db> {
 	pub(super) pos: usize,
 	pub(super) data: &'a DB<'db>,
-	pub(super) item: &'a TermRaw,
 }
 
 impl<'db, 'a: 'db> Term<'db, 'a> {
+	/// Index of the term in the database (see [DB::term]).
+	pub fn index(&self) -> usize {
+		self.pos
+	}
+
+	/// Stable identifier for the term.
+	///
+	/// Unlike the [index](Term::index), which depends on the relevance order
+	/// of all terms, the identifier is derived from the source dictionary,
+	/// entry sequence, expression and reading. It is kept when the database
+	/// is rebuilt (e.g. with new frequency data or other dictionaries), so it
+	/// can be stored by external systems to reference the term and resolved
+	/// later with [DB::term_by_id].
+	pub fn id(&self) -> u64 {
+		self.data.terms.id[self.pos].into()
+	}
+
 	/// Main Japanese expression for the term.
 	pub fn expression(&self) -> &'db str {
-		self.data.get_str(self.item.expression)
+		self.data.get_str(self.data.terms.expression[self.pos])
 	}
 
 	/// Reading for the term, if available.
 	pub fn reading(&self) -> &'db str {
-		self.data.get_str(self.item.reading)
+		self.data.get_str(self.data.terms.reading[self.pos])
 	}
 
 	/// Search key provides an additional search key for the term. This is
 	/// a filtered version of the expression or reading.
 	pub fn search_key(&self) -> &'db str {
-		self.data.get_str(self.item.search_key)
+		self.data.get_str(self.value(TermTable::SEARCH_KEY).into())
 	}
 
 	/// Score provides an additional attribute in which to order the terms in
 	/// a search result.
 	pub fn score(&self) -> i32 {
-		self.item.score.into()
+		self.value(TermTable::SCORE) as i32
 	}
 
 	/// Sequence number for the entry in the source dictionary.
 	pub fn sequence(&self) -> u32 {
-		self.item.sequence.into()
+		self.value(TermTable::SEQUENCE)
+	}
+
+	/// Position of the term in the source dictionary.
+	///
+	/// Terms are stored ordered by relevance, so this can be used to sort the
+	/// terms from a single source in the original order presented by the
+	/// dictionary (e.g. the senses for an entry).
+	pub fn order(&self) -> u32 {
+		self.value(TermTable::ORDER)
 	}
 
-	/// Number of occurrences for the term in the frequency database (based only
-	/// on the expression).
+	/// Number of occurrences for the term in the frequency database.
+	///
+	/// This is specific to the term reading when the frequency data provides
+	/// it (e.g. 行った as いった or おこなった), otherwise it is based only on
+	/// the expression.
 	pub fn frequency(&self) -> Option<u32> {
-		let frequency: u32 = self.item.frequency.into();
+		let frequency: u32 = self.data.terms.frequency[self.pos].into();
 		if frequency > 0 {
 			Some(frequency)
 		} else {
@@ -96,14 +307,104 @@ impl<'db, 'a: 'db> Term<'db, 'a> {
 		}
 	}
 
+	/// JMdict priority codes for the term (e.g. `news1`, `nf05`), taken from
+	/// the term and definition tags. Empty if the source has no priority data.
+	pub fn priority(&self) -> Priority {
+		Priority(self.value(TermTable::PRIORITY))
+	}
+
 	/// Source dictionary name.
 	pub fn source(&self) -> &'db str {
-		self.data.get_str(self.item.source)
+		self.data.get_str(self.data.terms.source[self.pos])
+	}
+
+	/// Pitch accent positions for the term reading, if available.
+	///
+	/// Each position is the mora after which the pitch drops, with zero
+	/// meaning the flat (heiban) pattern. Terms may have more than one
+	/// accepted pitch accent.
+	pub fn pitch(&self) -> impl 'a + Iterator<Item = u32> {
+		let (sta, end) = self.vector(TermTable::PITCH).range();
+		self.data.vector_data[sta..end].iter().map(|&x| x.into())
+	}
+
+	/// Origins for a loanword, as pairs of the source language code (e.g.
+	/// `por` for Portuguese, as used by JMdict) and the word in that language,
+	/// which can be empty.
+	///
+	/// Empty for native words and for loanwords without origin data.
+	pub fn origins(&'a self) -> impl 'a + Iterator<Item = (&'db str, &'db str)> {
+		let (sta, end) = self.vector(TermTable::ORIGIN).range();
+		self.data.vector_data[sta..end]
+			.chunks(2)
+			.map(move |x| (self.data.get_str(x[0]), self.data.get_str(x[1])))
+	}
+
+	/// Supplemental information for the sense (e.g. "usu. in the negative"),
+	/// if available.
+	pub fn info(&'a self) -> impl 'a + Iterator<Item = &'db str> {
+		self.data.get_strings(self.vector(TermTable::INFO))
+	}
+
+	/// Forms of the source entry the sense is restricted to, as pairs of
+	/// expression and reading (from the JMdict `stagk` and `stagr` data).
+	///
+	/// The term expression and reading is always one of the forms. Empty if
+	/// the sense applies to all the forms of the entry.
+	pub fn restrictions(&'a self) -> impl 'a + Iterator<Item = (&'db str, &'db str)> {
+		let (sta, end) = self.vector(TermTable::RESTRICTIONS).range();
+		self.data.vector_data[sta..end]
+			.chunks(2)
+			.map(move |x| (self.data.get_str(x[0]), self.data.get_str(x[1])))
 	}
 
-	/// English definitions for the term.
+	/// Additional flags for the term.
+	pub fn flags(&self) -> TermFlags {
+		TermFlags(self.data.terms.flags[self.pos].into())
+	}
+
+	/// Returns true if the term glossary is in Japanese (i.e. the term is from
+	/// a monolingual dictionary).
+	pub fn is_monolingual(&self) -> bool {
+		self.flags().contains(TermFlags::MONOLINGUAL)
+	}
+
+	/// Returns true if the term is an abbreviation (see
+	/// [TermFlags::ABBREVIATION]).
+	pub fn is_abbreviation(&self) -> bool {
+		self.flags().contains(TermFlags::ABBREVIATION)
+	}
+
+	/// Returns true if the term glossary was shortened on import (see
+	/// [TermFlags::TRUNCATED]).
+	pub fn is_truncated(&self) -> bool {
+		self.flags().contains(TermFlags::TRUNCATED)
+	}
+
+	/// Returns true if the term expression contains kanji.
+	pub fn has_kanji(&self) -> bool {
+		self.flags().contains(TermFlags::KANJI)
+	}
+
+	/// Returns true if the term expression is written only in kana.
+	pub fn is_kana(&self) -> bool {
+		self.flags().contains(TermFlags::KANA)
+	}
+
+	/// Returns true if the term expression is written only in katakana.
+	pub fn is_katakana(&self) -> bool {
+		self.flags().contains(TermFlags::KATAKANA)
+	}
+
+	/// Number of characters in the term expression.
+	pub fn length(&self) -> usize {
+		self.value(TermTable::LENGTH) as usize
+	}
+
+	/// Definitions for the term. Those are in English, unless the term is
+	/// [monolingual](Term::is_monolingual).
 	pub fn glossary(&'a self) -> impl 'a + Iterator<Item = &'db str> {
-		let (sta, end) = self.item.glossary.range();
+		let (sta, end) = self.data.terms.glossary[self.pos].range();
 		self.data.vector_data[sta..end]
 			.iter()
 			.map(move |&index| self.data.get_str(index))
@@ -111,17 +412,53 @@ impl<'db, 'a: 'db> Term<'db, 'a> {
 
 	/// Semantic rules for the term (tag indexes).
 	pub fn rules(&'a self) -> impl 'a + Iterator<Item = Tag<'db, 'a>> {
-		self.data.get_tags(self.item.rules)
+		self.data.get_tags(self.vector(TermTable::RULES))
 	}
 
 	/// Tag indexes for the japanese term.
 	pub fn term_tags(&'a self) -> impl 'a + Iterator<Item = Tag<'db, 'a>> {
-		self.data.get_tags(self.item.term_tags)
+		self.data.get_tags(self.vector(TermTable::TERM_TAGS))
 	}
 
 	/// Tag indexes for the english definition.
 	pub fn definition_tags(&'a self) -> impl 'a + Iterator<Item = Tag<'db, 'a>> {
-		self.data.get_tags(self.item.definition_tags)
+		self.data.get_tags(self.vector(TermTable::DEFINITION_TAGS))
+	}
+
+	/// All tags for the term (term tags, definition tags and rules) without
+	/// duplicates, sorted in display order (see [compare_tags]).
+	pub fn tags(&'a self) -> Vec<Tag<'db, 'a>> {
+		let mut tags: Vec<_> = self
+			.term_tags()
+			.chain(self.definition_tags())
+			.chain(self.rules())
+			.collect();
+		tags.sort_by(|a, b| compare_tags(a, b).then(a.index.cmp(&b.index)));
+		tags.dedup_by_key(|x| x.index);
+		tags
+	}
+
+	/// Part of speech tags from the term and definition tags.
+	pub fn pos_tags(&'a self) -> impl 'a + Iterator<Item = Tag<'db, 'a>> {
+		self.term_tags()
+			.chain(self.definition_tags())
+			.filter(|x| x.kind() == TagCategory::PartOfSpeech)
+	}
+
+	/// Usage tags from the term and definition tags (see
+	/// [TagCategory::is_usage]).
+	pub fn usage_tags(&'a self) -> impl 'a + Iterator<Item = Tag<'db, 'a>> {
+		self.term_tags()
+			.chain(self.definition_tags())
+			.filter(|x| x.kind().is_usage())
+	}
+
+	fn value(&self, field: u32) -> u32 {
+		self.data.terms.value(self.pos, field)
+	}
+
+	fn vector(&self, field: u32) -> VecHandle {
+		self.data.terms.vector(self.pos, field)
 	}
 }
 
@@ -145,6 +482,11 @@ impl<'db, 'a: 'db> fmt::Display for Term<'db, 'a> {
 			write!(f, " #{}", frequency)?;
 		}
 
+		let pitch: Vec<_> = self.pitch().map(|x| x.to_string()).collect();
+		if pitch.len() > 0 {
+			write!(f, " [pitch: {}]", pitch.join(", "))?;
+		}
+
 		if self.sequence() != 0 || self.score() != 0 {
 			write!(f, " (")?;
 
@@ -201,3 +543,260 @@ impl<'db, 'a: 'db> fmt::Display for Term<'db, 'a> {
 		Ok(())
 	}
 }
+
+/// Kanji from the database.
+pub struct Kanji<'db, 'a: 'db> {
+	pub(super) data: &'a DB<'db>,
+	pub(super) item: &'a KanjiRaw,
+}
+
+impl<'db, 'a: 'db> Kanji<'db, 'a> {
+	/// Kanji character.
+	pub fn character(&self) -> char {
+		let character: u32 = self.item.character.into();
+		std::char::from_u32(character).unwrap()
+	}
+
+	/// Number of occurrences for the kanji in the frequency database.
+	pub fn frequency(&self) -> Option<u32> {
+		let frequency: u32 = self.item.frequency.into();
+		if frequency > 0 {
+			Some(frequency)
+		} else {
+			None
+		}
+	}
+
+	/// Source dictionary name.
+	pub fn source(&self) -> &'db str {
+		self.data.get_str(self.item.source)
+	}
+
+	/// English meanings for the kanji.
+	pub fn meanings(&'a self) -> impl 'a + Iterator<Item = &'db str> {
+		self.data.get_strings(self.item.meanings)
+	}
+
+	/// Onyomi readings for the kanji.
+	pub fn onyomi(&'a self) -> impl 'a + Iterator<Item = &'db str> {
+		self.data.get_strings(self.item.onyomi)
+	}
+
+	/// Kunyomi readings for the kanji.
+	pub fn kunyomi(&'a self) -> impl 'a + Iterator<Item = &'db str> {
+		self.data.get_strings(self.item.kunyomi)
+	}
+
+	/// Tags for the kanji.
+	pub fn tags(&'a self) -> impl 'a + Iterator<Item = Tag<'db, 'a>> {
+		self.data.get_tags(self.item.tags)
+	}
+
+	/// Visually similar kanji that are easily confused with this one (e.g.
+	/// `末` for `未`).
+	pub fn similar(&'a self) -> impl 'a + Iterator<Item = char> {
+		let (sta, end) = self.item.similar.range();
+		self.data.vector_data[sta..end].iter().map(|&chr| {
+			let chr: u32 = chr.into();
+			std::char::from_u32(chr).unwrap()
+		})
+	}
+
+	/// Raw additional information for the kanji as a list of `(stat, value)`
+	/// pairs, where the `stat` tag describes the value.
+	pub fn stats(&'a self) -> impl 'a + Iterator<Item = (Tag<'db, 'a>, &'db str)> {
+		let (sta, end) = self.item.stats.range();
+		self.data.vector_data[sta..end]
+			.chunks(2)
+			.map(move |pair| (self.data.get_tag(pair[0]), self.data.get_str(pair[1])))
+	}
+
+	/// Returns the raw value for a stat by its tag name.
+	pub fn stat(&'a self, name: &str) -> Option<&'db str> {
+		self.stats()
+			.find(|(tag, _)| tag.name() == name)
+			.map(|(_, value)| value)
+	}
+
+	/// Index in the "Remembering The Kanji" books by James Heisig.
+	pub fn heisig(&'a self) -> Option<u32> {
+		self.stat_number("heisig")
+	}
+
+	/// Index in the classic "Modern Reader's Japanese-English Character
+	/// Dictionary" by Andrew Nelson.
+	pub fn nelson(&'a self) -> Option<u32> {
+		self.stat_number("nelson_c")
+	}
+
+	/// Unicode codepoint for the kanji from the source dictionary.
+	pub fn unicode(&'a self) -> Option<u32> {
+		self.stat("ucs")
+			.and_then(|value| u32::from_str_radix(value, 16).ok())
+	}
+
+	/// Frequency-of-use rank for the kanji from the source dictionary (lower is
+	/// more frequent). This is not related to [frequency](Kanji::frequency).
+	pub fn frequency_rank(&'a self) -> Option<u32> {
+		self.stat_number("freq")
+	}
+
+	/// School grade in which the kanji is taught (`9` and `10` are jinmeiyou
+	/// kanji).
+	pub fn grade(&'a self) -> Option<u32> {
+		self.stat_number("grade")
+	}
+
+	/// JLPT level for the kanji (pre-2010 levels, from 4 to 1).
+	pub fn jlpt(&'a self) -> Option<u32> {
+		self.stat_number("jlpt")
+	}
+
+	/// Number of strokes for the kanji.
+	pub fn strokes(&'a self) -> Option<u32> {
+		self.stat_number("strokes")
+	}
+
+	fn stat_number(&'a self, name: &str) -> Option<u32> {
+		self.stat(name).and_then(|value| value.trim().parse().ok())
+	}
+}
+
+impl<'db, 'a: 'db> fmt::Display for Kanji<'db, 'a> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.character())?;
+
+		let onyomi: Vec<_> = self.onyomi().collect();
+		let kunyomi: Vec<_> = self.kunyomi().collect();
+		if onyomi.len() > 0 || kunyomi.len() > 0 {
+			write!(f, " 「")?;
+			if onyomi.len() > 0 {
+				write!(f, "ON: {}", onyomi.join("  "))?;
+			}
+			if kunyomi.len() > 0 {
+				if onyomi.len() > 0 {
+					write!(f, " / ")?;
+				}
+				write!(f, "KUN: {}", kunyomi.join("  "))?;
+			}
+			write!(f, " 」")?;
+		}
+
+		if let Some(frequency) = self.frequency() {
+			write!(f, " #{}", frequency)?;
+		}
+
+		write!(f, " -- source: {}", self.source())?;
+
+		let similar: String = self.similar().collect();
+		if similar.len() > 0 {
+			write!(f, " -- similar: {}", similar)?;
+		}
+
+		let meanings: Vec<_> = self.meanings().collect();
+		if meanings.len() > 0 {
+			write!(f, "\n\n    {}", meanings.join(", "))?;
+		}
+
+		let tags: Vec<_> = self.tags().collect();
+		if tags.len() > 0 {
+			write!(f, "\n\n    Tags:")?;
+			for tag in tags {
+				write!(f, "\n    -> {}", tag)?;
+			}
+		}
+
+		let stats: Vec<_> = self.stats().collect();
+		if stats.len() > 0 {
+			write!(f, "\n\n    Stats:")?;
+			for (tag, value) in stats {
+				write!(f, "\n    -> {}: {}", tag.name(), value)?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Radical for the radical lookup.
+pub struct Radical<'db, 'a: 'db> {
+	pub(super) data: &'a DB<'db>,
+	pub(super) item: &'a RadicalRaw,
+}
+
+impl<'db, 'a: 'db> Radical<'db, 'a> {
+	/// Classical (Kangxi) radical number, from 1 to 214. Returns `None` for
+	/// search radicals that are not classical radicals.
+	pub fn number(&self) -> Option<u32> {
+		let number: u32 = self.item.number.into();
+		if number > 0 {
+			Some(number)
+		} else {
+			None
+		}
+	}
+
+	/// Radical character.
+	pub fn character(&self) -> char {
+		let character: u32 = self.item.character.into();
+		std::char::from_u32(character).unwrap()
+	}
+
+	/// Number of strokes for the radical.
+	pub fn strokes(&self) -> u32 {
+		self.item.strokes.into()
+	}
+
+	/// Radical name.
+	pub fn name(&self) -> &'db str {
+		self.data.get_str(self.item.name)
+	}
+
+	/// Variant glyphs for the radical (e.g. `氵` for `水`).
+	pub fn variants(&'a self) -> impl 'a + Iterator<Item = char> {
+		let (sta, end) = self.item.variants.range();
+		self.data.vector_data[sta..end].iter().map(|&chr| {
+			let chr: u32 = chr.into();
+			std::char::from_u32(chr).unwrap()
+		})
+	}
+}
+
+impl<'db, 'a: 'db> fmt::Display for Radical<'db, 'a> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.character())?;
+		let variants: String = self.variants().collect();
+		if variants.len() > 0 {
+			write!(f, " ({})", variants)?;
+		}
+		if let Some(number) = self.number() {
+			write!(f, " #{}", number)?;
+		}
+		write!(f, " {} -- {} strokes", self.name(), self.strokes())
+	}
+}
+
+/// Source dictionary metadata.
+pub struct Source<'db, 'a: 'db> {
+	pub(super) data: &'a DB<'db>,
+	pub(super) item: &'a SourceRaw,
+}
+
+impl<'db, 'a: 'db> Source<'db, 'a> {
+	/// Source dictionary name.
+	pub fn name(&self) -> &'db str {
+		self.data.get_str(self.item.name)
+	}
+
+	/// Priority for the source, from the import configuration. Higher values
+	/// have precedence.
+	pub fn priority(&self) -> i32 {
+		self.item.priority.into()
+	}
+}
+
+impl<'db, 'a: 'db> fmt::Display for Source<'db, 'a> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} (priority {})", self.name(), self.priority())
+	}
+}
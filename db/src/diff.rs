@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use super::Kanji;
+use super::Section;
+use super::SectionSize;
+use super::Term;
+use super::DB;
+
+/// Differences between two database builds, returned by [DB::diff].
+///
+/// This is meant to review the effect of a new dictionary release or of an
+/// importer change. Terms are matched by their stable [id](Term::id), kanji
+/// by character and source dictionary and tags by name.
+#[derive(Clone, Default, Debug)]
+pub struct DatabaseDiff {
+	/// Terms only in the new database, as indexes in the new database.
+	pub terms_added: Vec<usize>,
+	/// Terms only in the old database, as indexes in the old database.
+	pub terms_removed: Vec<usize>,
+	/// Terms in both databases with different data, in the new database
+	/// order.
+	pub terms_changed: Vec<EntryChange>,
+	/// Kanji only in the new database, as indexes in the new database.
+	pub kanji_added: Vec<usize>,
+	/// Kanji only in the old database, as indexes in the old database.
+	pub kanji_removed: Vec<usize>,
+	/// Kanji in both databases with different data, in the new database
+	/// order.
+	pub kanji_changed: Vec<EntryChange>,
+	/// Names of the tags only in the new database, sorted.
+	pub tags_added: Vec<String>,
+	/// Names of the tags only in the old database, sorted.
+	pub tags_removed: Vec<String>,
+	/// Names of the tags with a different category or notes, sorted.
+	pub tags_changed: Vec<String>,
+	/// Size of each section in both databases, in the order of
+	/// [Section::ALL].
+	pub sections: Vec<SectionDelta>,
+}
+
+impl DatabaseDiff {
+	/// Returns true if no entries or tags differ. Section sizes are not
+	/// considered.
+	pub fn is_empty(&self) -> bool {
+		self.terms_added.len() == 0
+			&& self.terms_removed.len() == 0
+			&& self.terms_changed.len() == 0
+			&& self.kanji_added.len() == 0
+			&& self.kanji_removed.len() == 0
+			&& self.kanji_changed.len() == 0
+			&& self.tags_added.len() == 0
+			&& self.tags_removed.len() == 0
+			&& self.tags_changed.len() == 0
+	}
+}
+
+/// Entry in both databases with different data, see [DatabaseDiff].
+#[derive(Clone, Debug)]
+pub struct EntryChange {
+	/// Index in the old database.
+	pub old: usize,
+	/// Index in the new database.
+	pub new: usize,
+	/// Names of the fields that differ (e.g. `glossary` or `frequency`).
+	pub fields: Vec<&'static str>,
+}
+
+/// Size of a section in both databases, see [DatabaseDiff].
+#[derive(Copy, Clone, Debug)]
+pub struct SectionDelta {
+	pub section: Section,
+	pub old: SectionSize,
+	pub new: SectionSize,
+}
+
+impl SectionDelta {
+	/// Difference in bytes from the old to the new database.
+	pub fn bytes(&self) -> i64 {
+		self.new.bytes as i64 - self.old.bytes as i64
+	}
+
+	/// Difference in entries from the old to the new database.
+	pub fn entries(&self) -> i64 {
+		self.new.entries as i64 - self.old.entries as i64
+	}
+}
+
+impl<'a> DB<'a> {
+	/// Compares this database (the old build) with a `new` build.
+	///
+	/// Both databases should be fully loaded, since sections that were not
+	/// loaded are empty.
+	pub fn diff(&self, new: &DB) -> DatabaseDiff {
+		let mut out = DatabaseDiff::default();
+
+		let old_terms: HashMap<u64, usize> = self.terms().map(|x| (x.id(), x.index())).collect();
+		let mut matched = HashSet::new();
+		for term in new.terms() {
+			match old_terms.get(&term.id()) {
+				Some(&index) => {
+					matched.insert(index);
+					let fields = term_changes(&self.term(index).unwrap(), &term);
+					if fields.len() > 0 {
+						let change = EntryChange {
+							old: index,
+							new: term.index(),
+							fields: fields,
+						};
+						out.terms_changed.push(change);
+					}
+				}
+				None => out.terms_added.push(term.index()),
+			}
+		}
+		out.terms_removed = (0..self.terms.len()).filter(|x| !matched.contains(x)).collect();
+
+		let kanji_key = |kanji: &Kanji| (kanji.character(), kanji.source().to_string());
+		let old_kanji: HashMap<_, usize> = (0..self.kanji.len())
+			.map(|index| (kanji_key(&self.kanji(index).unwrap()), index))
+			.collect();
+		let mut matched = HashSet::new();
+		for index in 0..new.kanji.len() {
+			let kanji = new.kanji(index).unwrap();
+			match old_kanji.get(&kanji_key(&kanji)) {
+				Some(&old) => {
+					matched.insert(old);
+					let fields = kanji_changes(&self.kanji(old).unwrap(), &kanji);
+					if fields.len() > 0 {
+						let change = EntryChange {
+							old: old,
+							new: index,
+							fields: fields,
+						};
+						out.kanji_changed.push(change);
+					}
+				}
+				None => out.kanji_added.push(index),
+			}
+		}
+		out.kanji_removed = (0..self.kanji.len()).filter(|x| !matched.contains(x)).collect();
+
+		let old_tags: HashMap<&str, (&str, &str)> =
+			self.tags().map(|x| (x.name(), (x.category(), x.notes()))).collect();
+		let new_tags: HashMap<&str, (&str, &str)> = new.tags().map(|x| (x.name(), (x.category(), x.notes()))).collect();
+		for (&name, tag) in new_tags.iter() {
+			match old_tags.get(name) {
+				Some(old) if old != tag => out.tags_changed.push(name.to_string()),
+				Some(_) => {}
+				None => out.tags_added.push(name.to_string()),
+			}
+		}
+		out.tags_removed = old_tags
+			.keys()
+			.filter(|x| !new_tags.contains_key(*x))
+			.map(|x| x.to_string())
+			.collect();
+		out.tags_added.sort();
+		out.tags_removed.sort();
+		out.tags_changed.sort();
+
+		out.sections = Section::ALL
+			.iter()
+			.map(|&section| SectionDelta {
+				section: section,
+				old: self.section_size(section),
+				new: new.section_size(section),
+			})
+			.collect();
+		out
+	}
+}
+
+/// Returns the names of the fields that differ between two terms with the
+/// same id.
+fn term_changes(old: &Term, new: &Term) -> Vec<&'static str> {
+	let tags = |term: &Term| -> Vec<String> {
+		let tags = term.rules().chain(term.term_tags()).chain(term.definition_tags());
+		tags.map(|x| x.name().to_string()).collect()
+	};
+	let mut out = Vec::new();
+	if old.frequency() != new.frequency() {
+		out.push("frequency");
+	}
+	if !old.glossary().eq(new.glossary()) {
+		out.push("glossary");
+	}
+	if tags(old) != tags(new) {
+		out.push("tags");
+	}
+	if !old.pitch().eq(new.pitch()) {
+		out.push("pitch");
+	}
+	if old.flags() != new.flags() {
+		out.push("flags");
+	}
+	out
+}
+
+/// Returns the names of the fields that differ between two kanji with the
+/// same character and source.
+fn kanji_changes(old: &Kanji, new: &Kanji) -> Vec<&'static str> {
+	let tags = |kanji: &Kanji| -> Vec<String> { kanji.tags().map(|x| x.name().to_string()).collect() };
+	let mut out = Vec::new();
+	if old.frequency() != new.frequency() {
+		out.push("frequency");
+	}
+	if !old.meanings().eq(new.meanings()) {
+		out.push("meanings");
+	}
+	if !old.onyomi().eq(new.onyomi()) || !old.kunyomi().eq(new.kunyomi()) {
+		out.push("readings");
+	}
+	if tags(old) != tags(new) {
+		out.push("tags");
+	}
+	out
+}
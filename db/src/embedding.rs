@@ -0,0 +1,99 @@
+use super::TopK;
+use super::DB;
+
+/// Scale for the similarity when ranking with [TopK], which requires an
+/// integer key.
+const SIMILARITY_SCALE: f32 = 1_000_000.0;
+
+impl<'a> DB<'a> {
+	/// Number of dimensions for the term embeddings, or zero if the database
+	/// has no embeddings.
+	pub fn embedding_dim(&self) -> usize {
+		self.header.embedding_dim.into()
+	}
+
+	/// Returns the embedding vector for a term, if available.
+	pub fn embedding(&self, index: usize) -> Option<Vec<f32>> {
+		let vector = self.embedding_slice(index)?;
+		let vector: Vec<f32> = vector.iter().map(|&x| f32::from_bits(x.into())).collect();
+		if vector.iter().all(|&x| x == 0.0) {
+			None
+		} else {
+			Some(vector)
+		}
+	}
+
+	/// Returns the terms with the embedding nearest to the given vector by
+	/// cosine similarity, as `(index, similarity)` from the most similar.
+	///
+	/// This is a brute-force search over all terms, which is fast enough for
+	/// interactive use with the usual embedding sizes.
+	pub fn nearest_terms(&self, vector: &[f32], limit: usize) -> Vec<(usize, f32)> {
+		let dim = self.embedding_dim();
+		let norm = magnitude(vector);
+		if dim == 0 || vector.len() != dim || norm == 0.0 {
+			return Vec::new();
+		}
+
+		let mut top = TopK::new(limit);
+		let mut other = vec![0.0; dim];
+		for index in 0..self.terms.len() {
+			let data = self.embedding_slice(index).unwrap();
+			for (value, &raw) in other.iter_mut().zip(data) {
+				*value = f32::from_bits(raw.into());
+			}
+			let other_norm = magnitude(&other);
+			if other_norm > 0.0 {
+				let dot: f32 = vector.iter().zip(other.iter()).map(|(a, b)| a * b).sum();
+				let similarity = dot / (norm * other_norm);
+				top.push(-(similarity * SIMILARITY_SCALE) as i64, index);
+			}
+		}
+
+		top.into_sorted_vec()
+			.into_iter()
+			.map(|index| (index, self.similarity(vector, norm, index)))
+			.collect()
+	}
+
+	/// Returns the terms with the embedding nearest to a term, excluding
+	/// the terms with the same expression (which share the embedding). See
+	/// [nearest_terms](DB::nearest_terms).
+	pub fn similar_terms(&self, index: usize, limit: usize) -> Vec<(usize, f32)> {
+		let vector = match self.embedding(index) {
+			Some(vector) => vector,
+			None => return Vec::new(),
+		};
+		let expression: u32 = self.terms.expression[index].into();
+		let same = self
+			.terms
+			.expression
+			.iter()
+			.filter(|&&x| Into::<u32>::into(x) == expression)
+			.count();
+		self.nearest_terms(&vector, limit + same)
+			.into_iter()
+			.filter(|x| Into::<u32>::into(self.terms.expression[x.0]) != expression)
+			.take(limit)
+			.collect()
+	}
+
+	fn embedding_slice(&self, index: usize) -> Option<&'a [super::raw::RawUint32]> {
+		let dim = self.embedding_dim();
+		if dim == 0 || index >= self.terms.len() {
+			None
+		} else {
+			self.embeddings.get(index * dim..(index + 1) * dim)
+		}
+	}
+
+	fn similarity(&self, vector: &[f32], norm: f32, index: usize) -> f32 {
+		let other = self.embedding(index).unwrap_or_default();
+		let dot: f32 = vector.iter().zip(other.iter()).map(|(a, b)| a * b).sum();
+		dot / (norm * magnitude(&other))
+	}
+}
+
+fn magnitude(vector: &[f32]) -> f32 {
+	vector.iter().map(|x| x * x).sum::<f32>().sqrt()
+}
@@ -0,0 +1,62 @@
+use super::raw::TermTable;
+use super::ResultSet;
+use super::DB;
+
+/// Terms sharing a stem with the same conjugation class.
+///
+/// See [DB::word_family].
+pub struct WordFamily<'a> {
+	/// Conjugation class, from the term rules (e.g. `v1`, `v5` or `adj-i`).
+	/// This is `None` for terms that don't conjugate (e.g. nouns).
+	pub class: Option<&'a str>,
+	/// Indexes of the terms with the class, in order of relevance.
+	pub terms: Vec<usize>,
+}
+
+impl<'a> DB<'a> {
+	/// Returns the terms starting with a stem (e.g. `食べ`), grouped by their
+	/// conjugation class.
+	///
+	/// This can be used to explore a word family, e.g. the verb `食べる` and
+	/// the nouns `食べ物` and `食べ放題`. The stem is matched against both
+	/// the expression and the reading, as in [search_prefix](DB::search_prefix).
+	///
+	/// Groups are ordered by their most relevant term, with the terms without
+	/// a conjugation class last.
+	pub fn word_family<S: AsRef<str>>(&self, stem: S) -> Vec<WordFamily<'a>> {
+		let mut results = ResultSet::default();
+		self.search_prefix(stem, &mut results);
+
+		let mut out: Vec<WordFamily<'a>> = Vec::new();
+		let mut other = Vec::new();
+		for index in results.iter() {
+			let class = self.term_class(index);
+			match class {
+				Some(_) => match out.iter_mut().find(|x| x.class == class) {
+					Some(family) => family.terms.push(index),
+					None => out.push(WordFamily {
+						class: class,
+						terms: vec![index],
+					}),
+				},
+				None => other.push(index),
+			}
+		}
+
+		if other.len() > 0 {
+			out.push(WordFamily {
+				class: None,
+				terms: other,
+			});
+		}
+		out
+	}
+
+	/// Conjugation class for a term, from its first rule.
+	fn term_class(&self, index: usize) -> Option<&'a str> {
+		let (sta, end) = self.terms.vector(index, TermTable::RULES).range();
+		let tag = *self.vector_data[sta..end].first()?;
+		let tag: usize = tag.into();
+		Some(self.get_str(self.tags[tag].name))
+	}
+}
@@ -0,0 +1,255 @@
+//! Word configuration for the glossary text, per glossary language.
+//!
+//! The configuration controls how the glossary is split into keywords for
+//! the related terms (see [DB::related]) and how keywords are matched by the
+//! glossary filters (see [Field::Glossary](super::Field::Glossary)).
+//!
+//! The configuration is stored in the database when it is written, so the
+//! queries use the same rules the related terms were computed with. Terms
+//! from a [monolingual](super::Term::is_monolingual) dictionary have a
+//! Japanese glossary (`ja`), other terms have an English glossary (`en`).
+
+use super::Term;
+use super::DB;
+
+/// Language code for the glossary of monolingual terms.
+pub const GLOSSARY_JAPANESE: &'static str = "ja";
+
+/// Language code for the glossary of the other terms.
+pub const GLOSSARY_ENGLISH: &'static str = "en";
+
+/// Common English words ignored as glossary keywords by default.
+const ENGLISH_STOP_WORDS: &'static [&'static str] = &[
+	"and", "are", "for", "from", "not", "one", "that", "the", "thing", "this", "with", "etc", "something", "someone",
+];
+
+/// How the glossary text is split into words.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Tokenizer {
+	/// Words are separated by non-alphanumeric characters, and keywords are
+	/// matched case insensitively at word boundaries.
+	Words,
+	/// The text is not separated into words (e.g. Japanese), so keywords are
+	/// matched anywhere in the text.
+	Text,
+}
+
+impl Tokenizer {
+	pub(crate) fn to_raw(self) -> u32 {
+		match self {
+			Tokenizer::Words => 0,
+			Tokenizer::Text => 1,
+		}
+	}
+
+	pub(crate) fn from_raw(value: u32) -> Tokenizer {
+		match value {
+			0 => Tokenizer::Words,
+			1 => Tokenizer::Text,
+			_ => panic!("invalid glossary tokenizer: {}", value),
+		}
+	}
+}
+
+impl std::str::FromStr for Tokenizer {
+	type Err = String;
+
+	fn from_str(s: &str) -> std::result::Result<Tokenizer, String> {
+		match s {
+			"words" => Ok(Tokenizer::Words),
+			"text" => Ok(Tokenizer::Text),
+			_ => Err(format!("invalid tokenizer `{}` (expected `words` or `text`)", s)),
+		}
+	}
+}
+
+/// Glossary configuration for a language.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct GlossaryConfig<'a> {
+	/// Language code for the glossary, e.g. `en`.
+	pub language: &'a str,
+	/// How the glossary text is split into words.
+	pub tokenizer: Tokenizer,
+	/// Minimum number of characters for a glossary keyword.
+	pub min_length: usize,
+	/// Lowercase words ignored as glossary keywords.
+	pub stop_words: Vec<&'a str>,
+}
+
+impl<'a> GlossaryConfig<'a> {
+	/// Default configuration for a glossary language. English and unknown
+	/// languages are split into words, while Japanese is not.
+	pub fn default_for(language: &'a str) -> GlossaryConfig<'a> {
+		match language {
+			GLOSSARY_JAPANESE => GlossaryConfig {
+				language: language,
+				tokenizer: Tokenizer::Text,
+				min_length: 1,
+				stop_words: Vec::new(),
+			},
+			GLOSSARY_ENGLISH => GlossaryConfig {
+				language: language,
+				tokenizer: Tokenizer::Words,
+				min_length: 3,
+				stop_words: ENGLISH_STOP_WORDS.to_vec(),
+			},
+			_ => GlossaryConfig {
+				language: language,
+				tokenizer: Tokenizer::Words,
+				min_length: 3,
+				stop_words: Vec::new(),
+			},
+		}
+	}
+
+	/// Returns the lowercase keywords in a glossary text, without the stop
+	/// words and the words shorter than the minimum length.
+	///
+	/// Runs of alphanumeric characters are keywords for both tokenizers, as
+	/// such a run in Japanese text is usually a phrase.
+	pub fn keywords(&self, glossary: &str) -> Vec<String> {
+		glossary
+			.split(|x: char| !x.is_alphanumeric())
+			.filter(|x| x.chars().count() >= self.min_length)
+			.map(|x| x.to_lowercase())
+			.filter(|x| !self.stop_words.contains(&x.as_str()))
+			.collect()
+	}
+
+	/// Returns true if a keyword (a word or a phrase) matches the glossary
+	/// text. Stop words alone never match.
+	pub fn matches(&self, glossary: &str, keyword: &str) -> bool {
+		match self.tokenizer {
+			Tokenizer::Text => {
+				let keyword = keyword.trim();
+				keyword.len() > 0 && glossary.contains(keyword)
+			}
+			Tokenizer::Words => {
+				let keyword = keyword.to_lowercase();
+				if self.stop_words.contains(&keyword.trim()) {
+					return false;
+				}
+				contains_words(&glossary.to_lowercase(), &keyword)
+			}
+		}
+	}
+}
+
+/// Returns the glossary language for a term.
+pub(crate) fn glossary_language(monolingual: bool) -> &'static str {
+	if monolingual {
+		GLOSSARY_JAPANESE
+	} else {
+		GLOSSARY_ENGLISH
+	}
+}
+
+/// Configurations for the glossary languages of the terms, loaded once for
+/// matching many terms.
+pub(crate) struct TermGlossaryConfigs<'a> {
+	japanese: GlossaryConfig<'a>,
+	english: GlossaryConfig<'a>,
+}
+
+impl<'a> TermGlossaryConfigs<'a> {
+	pub fn for_term(&self, term: &Term) -> &GlossaryConfig<'a> {
+		if term.is_monolingual() {
+			&self.japanese
+		} else {
+			&self.english
+		}
+	}
+}
+
+impl<'a> DB<'a> {
+	/// Returns the glossary configurations stored in the database, sorted
+	/// by language.
+	pub fn glossary_configs(&self) -> Vec<GlossaryConfig<'a>> {
+		self.glossary_configs
+			.iter()
+			.map(|it| {
+				let (sta, end) = it.stop_words.range();
+				GlossaryConfig {
+					language: self.get_str(it.language),
+					tokenizer: Tokenizer::from_raw(it.tokenizer.into()),
+					min_length: it.min_length.into(),
+					stop_words: self.vector_data[sta..end].iter().map(|&x| self.get_str(x)).collect(),
+				}
+			})
+			.collect()
+	}
+
+	/// Returns the glossary configuration for a language, or the default
+	/// configuration if the database has none for the language.
+	pub fn glossary_config<'b>(&self, language: &'b str) -> GlossaryConfig<'b>
+	where
+		'a: 'b,
+	{
+		let configs = self.glossary_configs();
+		match configs.into_iter().find(|x| x.language == language) {
+			Some(config) => config,
+			None => GlossaryConfig::default_for(language),
+		}
+	}
+
+	/// Returns the glossary configuration for a term.
+	pub fn term_glossary_config(&self, term: &Term) -> GlossaryConfig<'a> {
+		self.glossary_config(glossary_language(term.is_monolingual()))
+	}
+
+	pub(crate) fn term_glossary_configs(&self) -> TermGlossaryConfigs<'a> {
+		TermGlossaryConfigs {
+			japanese: self.glossary_config(GLOSSARY_JAPANESE),
+			english: self.glossary_config(GLOSSARY_ENGLISH),
+		}
+	}
+}
+
+/// Returns true if `text` contains `words` starting and ending at word
+/// boundaries.
+fn contains_words(text: &str, words: &str) -> bool {
+	if words.len() == 0 {
+		return false;
+	}
+	let mut offset = 0;
+	while let Some(pos) = text[offset..].find(words) {
+		let sta = offset + pos;
+		let end = sta + words.len();
+		let before = text[..sta].chars().next_back();
+		let after = text[end..].chars().next();
+		let is_boundary = |chr: Option<char>| chr.map(|c| !c.is_alphanumeric()).unwrap_or(true);
+		if is_boundary(before) && is_boundary(after) {
+			return true;
+		}
+		offset = sta + text[sta..].chars().next().unwrap().len_utf8();
+	}
+	false
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn keywords_and_matching() {
+		let english = GlossaryConfig::default_for(GLOSSARY_ENGLISH);
+		assert_eq!(english.keywords("to eat (something); the Meal"), vec!["eat", "meal"]);
+		assert!(english.matches("to eat (something)", "EAT"));
+		assert!(!english.matches("to eat (something)", "ea"));
+		assert!(!english.matches("the cat", "the"));
+
+		let japanese = GlossaryConfig::default_for(GLOSSARY_JAPANESE);
+		assert_eq!(japanese.keywords("食べ物を食べる。"), vec!["食べ物を食べる"]);
+		assert!(japanese.matches("食べ物を食べる。", "食べ"));
+
+		let custom = GlossaryConfig {
+			language: "pt",
+			tokenizer: Tokenizer::Words,
+			min_length: 2,
+			stop_words: vec!["de"],
+		};
+		assert_eq!(custom.keywords("casa de campo"), vec!["casa", "campo"]);
+		assert_eq!("text".parse::<Tokenizer>(), Ok(Tokenizer::Text));
+		assert!("chars".parse::<Tokenizer>().is_err());
+	}
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use super::raw::TermTable;
+use super::DB;
+
+/// Search results grouped by headword.
+///
+/// See [DB::group_by_headword].
+pub struct Headword<'a> {
+	/// Expression for the headword.
+	pub expression: &'a str,
+	/// Reading for the headword.
+	pub reading: &'a str,
+	/// Entries for the headword from each source dictionary, ordered by the
+	/// source priority and then by the order they first appear in the results.
+	pub sources: Vec<HeadwordSource<'a>>,
+}
+
+/// Entries from a single source dictionary for a [Headword].
+pub struct HeadwordSource<'a> {
+	/// Source dictionary name.
+	pub source: &'a str,
+	/// Indexes of the terms from this source, in the order they appear in
+	/// the source dictionary.
+	pub terms: Vec<usize>,
+	/// Glossary for all the terms from this source, in the order they appear
+	/// in the source dictionary.
+	pub glossary: Vec<&'a str>,
+	/// Senses from this source (one for each term), in the order they appear
+	/// in the source dictionary.
+	pub senses: Vec<HeadwordSense<'a>>,
+}
+
+/// A single sense in a [HeadwordSource], with its provenance in the source
+/// dictionary.
+///
+/// This allows attributing each definition in a merged entry and reporting
+/// errors upstream.
+pub struct HeadwordSense<'a> {
+	/// Index of the term for the sense.
+	pub term: usize,
+	/// Source dictionary name.
+	pub source: &'a str,
+	/// Sequence number for the entry in the source dictionary.
+	pub sequence: u32,
+	/// Glossary for the sense.
+	pub glossary: Vec<&'a str>,
+	/// Supplemental information for the sense (see [Term::info](super::Term::info)).
+	pub info: Vec<&'a str>,
+}
+
+/// A sense for a [Headword] with the identical senses from all sources
+/// collapsed into one. See [Headword::merged_senses].
+pub struct MergedSense<'a> {
+	/// Glossary for the sense, as in its first source.
+	pub glossary: Vec<&'a str>,
+	/// Source dictionaries with the sense, in the headword source order.
+	pub sources: Vec<&'a str>,
+	/// Indexes of the terms for the sense, from all sources.
+	pub terms: Vec<usize>,
+}
+
+impl<'a> Headword<'a> {
+	/// Returns the list of term indexes for the headword across all sources.
+	pub fn terms(&self) -> Vec<usize> {
+		self.sources.iter().flat_map(|x| x.terms.iter().cloned()).collect()
+	}
+
+	/// Returns the senses for the headword across all sources, collapsing
+	/// identical senses into one that is attributed to all their sources.
+	///
+	/// Senses are identical if they have the same glossary, ignoring case and
+	/// surrounding whitespace. The senses are in order of their first
+	/// appearance, that is by source priority and then source order.
+	pub fn merged_senses(&self) -> Vec<MergedSense<'a>> {
+		let mut out: Vec<MergedSense<'a>> = Vec::new();
+		let mut keys: HashMap<Vec<String>, usize> = HashMap::new();
+		for sense in self.sources.iter().flat_map(|x| x.senses.iter()) {
+			let key: Vec<String> = sense.glossary.iter().map(|x| x.trim().to_lowercase()).collect();
+			let pos = *keys.entry(key).or_insert_with(|| {
+				out.push(MergedSense {
+					glossary: sense.glossary.clone(),
+					sources: Vec::new(),
+					terms: Vec::new(),
+				});
+				out.len() - 1
+			});
+
+			let merged = &mut out[pos];
+			if !merged.sources.contains(&sense.source) {
+				merged.sources.push(sense.source);
+			}
+			merged.terms.push(sense.term);
+		}
+		out
+	}
+}
+
+impl<'a> DB<'a> {
+	/// Groups a list of term indexes (e.g. from a search) by headword, that is
+	/// the term expression and reading.
+	///
+	/// Entries for the same headword from different sources are nested under
+	/// a single headword, ordered by the [source priority](DB::source_priority).
+	/// Headwords are returned in order of their first term in `indexes`.
+	pub fn group_by_headword<I: IntoIterator<Item = usize>>(&self, indexes: I) -> Vec<Headword<'a>> {
+		let mut out: Vec<Headword<'a>> = Vec::new();
+		let mut headwords = HashMap::new();
+		for index in indexes {
+			let expression = self.get_str(self.terms.expression[index]);
+			let reading = self.get_str(self.terms.reading[index]);
+			let source = self.get_str(self.terms.source[index]);
+
+			let pos = *headwords.entry((expression, reading)).or_insert_with(|| {
+				out.push(Headword {
+					expression: expression,
+					reading: reading,
+					sources: Vec::new(),
+				});
+				out.len() - 1
+			});
+
+			let headword = &mut out[pos];
+			let pos = match headword.sources.iter().position(|x| x.source == source) {
+				Some(pos) => pos,
+				None => {
+					headword.sources.push(HeadwordSource {
+						source: source,
+						terms: Vec::new(),
+						glossary: Vec::new(),
+						senses: Vec::new(),
+					});
+					headword.sources.len() - 1
+				}
+			};
+
+			headword.sources[pos].terms.push(index);
+		}
+
+		for headword in out.iter_mut() {
+			headword
+				.sources
+				.sort_by_key(|x| -self.source_priority(x.source));
+		}
+
+		// Present the entries for each source in their original order
+		for entry in out.iter_mut().flat_map(|x| x.sources.iter_mut()) {
+			entry.terms.sort_by_key(|&x| self.terms.value(x, TermTable::ORDER));
+			for &index in entry.terms.iter() {
+				let (sta, end) = self.terms.glossary[index].range();
+				let glossary: Vec<&'a str> = self.vector_data[sta..end].iter().map(|&x| self.get_str(x)).collect();
+				entry.glossary.extend(glossary.iter().cloned());
+				let (sta, end) = self.terms.vector(index, TermTable::INFO).range();
+				entry.senses.push(HeadwordSense {
+					term: index,
+					source: entry.source,
+					sequence: self.terms.value(index, TermTable::SEQUENCE),
+					glossary: glossary,
+					info: self.vector_data[sta..end].iter().map(|&x| self.get_str(x)).collect(),
+				});
+			}
+		}
+		out
+	}
+}
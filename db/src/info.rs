@@ -0,0 +1,250 @@
+use std::io;
+
+use super::raw::*;
+use super::Profile;
+use super::DB;
+
+/// Database statistics stored in the fixed-size file header.
+///
+/// This is available from a loaded database with [DB::info], or can be read
+/// from the start of a database file with [DatabaseInfo::read] without
+/// loading the whole file.
+#[derive(Clone, Debug)]
+pub struct DatabaseInfo {
+	/// Version of the database binary format.
+	pub version: u32,
+	/// Output profile the database was written with.
+	pub profile: Profile,
+	/// Build time in seconds since the Unix epoch.
+	pub build_time: u64,
+	/// Number of terms.
+	pub terms: usize,
+	/// Number of kanji.
+	pub kanji: usize,
+	/// Number of tags.
+	pub tags: usize,
+	/// Number of radicals.
+	pub radicals: usize,
+	/// Number of source dictionaries.
+	pub sources: usize,
+	/// Number of rows in the prefix index.
+	pub prefix_index: usize,
+	/// Number of rows in the suffix index. Zero if not in the file.
+	pub suffix_index: usize,
+	/// Number of characters in the per-character index. Zero if not in the
+	/// file.
+	pub chars_index: usize,
+	/// SHA-256 hash for the source dictionaries. All zeros if not available.
+	pub source_hash: [u8; 32],
+	/// Features built into the database.
+	pub capabilities: Capabilities,
+}
+
+impl DatabaseInfo {
+	/// Number of bytes at the start of a database file needed to read the
+	/// header.
+	pub const SIZE: usize = std::mem::size_of::<u32>() + std::mem::size_of::<HeaderRaw>();
+
+	/// Reads the database information from the start of a database file.
+	///
+	/// Only the first [SIZE](DatabaseInfo::SIZE) bytes are read.
+	pub fn read<R: io::Read>(reader: &mut R) -> io::Result<DatabaseInfo> {
+		let mut data = [0u8; DatabaseInfo::SIZE];
+		reader.read_exact(&mut data)?;
+
+		let invalid = |err: String| io::Error::new(io::ErrorKind::InvalidData, err);
+		let mut count = [0u8; 4];
+		count.copy_from_slice(&data[..4]);
+		if u32::from_le_bytes(count) != 1 {
+			return Err(invalid(format!("missing header")));
+		}
+
+		let header = unsafe { std::ptr::read_unaligned(data[4..].as_ptr() as *const HeaderRaw) };
+		check_header(&header).map_err(invalid)?;
+		Ok(DatabaseInfo::from_raw(&header))
+	}
+
+	/// Build date as `YYYY-MM-DD` (UTC).
+	pub fn build_date(&self) -> String {
+		let (year, month, day) = civil_date(self.build_time / 86400);
+		format!("{:04}-{:02}-{:02}", year, month, day)
+	}
+
+	/// Source hash as a lowercase hexadecimal string.
+	pub fn source_hash_hex(&self) -> String {
+		to_hex(&self.source_hash)
+	}
+
+	fn from_raw(header: &HeaderRaw) -> DatabaseInfo {
+		let profile: u32 = header.profile.into();
+		DatabaseInfo {
+			version: header.version.into(),
+			profile: Profile::from_raw(profile),
+			build_time: header.build_time.into(),
+			terms: header.terms.into(),
+			kanji: header.kanji.into(),
+			tags: header.tags.into(),
+			radicals: header.radicals.into(),
+			sources: header.sources.into(),
+			prefix_index: header.index_prefix_jp.into(),
+			suffix_index: header.index_suffix_jp.into(),
+			chars_index: header.index_chars_jp.into(),
+			source_hash: header.source_hash,
+			capabilities: Capabilities(header.capabilities.into()),
+		}
+	}
+}
+
+/// Bit flags for the optional features built into a database file, stored
+/// in the header.
+///
+/// Client code can check these with [DB::capabilities] (or from
+/// [DatabaseInfo::read] before loading the file) to degrade gracefully when
+/// a feature is not available, instead of getting empty results.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Capabilities(pub u32);
+
+impl Capabilities {
+	/// The suffix index is in the file. Without it, the index is built on
+	/// first use (see [DB::has_suffix_index]).
+	pub const SUFFIX_INDEX: Capabilities = Capabilities(1 << 0);
+
+	/// The per-character index is in the file.
+	pub const CHARS_INDEX: Capabilities = Capabilities(1 << 1);
+
+	/// Terms have pitch accent data.
+	pub const PITCH: Capabilities = Capabilities(1 << 2);
+
+	/// Terms have embeddings for the semantic search.
+	pub const EMBEDDINGS: Capabilities = Capabilities(1 << 3);
+
+	/// The database has media files.
+	pub const MEDIA: Capabilities = Capabilities(1 << 4);
+
+	/// Related terms were computed from the glossary keywords, with the
+	/// glossary configuration stored in the file (see [DB::glossary_configs]).
+	pub const GLOSSARY_INDEX: Capabilities = Capabilities(1 << 5);
+
+	/// Names for each capability, in bit order.
+	const NAMES: &'static [(Capabilities, &'static str)] = &[
+		(Capabilities::SUFFIX_INDEX, "suffix_index"),
+		(Capabilities::CHARS_INDEX, "chars_index"),
+		(Capabilities::PITCH, "pitch"),
+		(Capabilities::EMBEDDINGS, "embeddings"),
+		(Capabilities::MEDIA, "media"),
+		(Capabilities::GLOSSARY_INDEX, "glossary_index"),
+	];
+
+	/// Returns true if all capabilities in `other` are set.
+	pub fn contains(&self, other: Capabilities) -> bool {
+		self.0 & other.0 == other.0
+	}
+
+	/// Sets all capabilities in `other`.
+	pub fn insert(&mut self, other: Capabilities) {
+		self.0 |= other.0;
+	}
+
+	/// Returns the names of the capabilities that are set (e.g. `pitch`), in
+	/// bit order. Unknown bits are ignored.
+	pub fn names(&self) -> Vec<&'static str> {
+		Capabilities::NAMES
+			.iter()
+			.filter(|x| self.contains(x.0))
+			.map(|x| x.1)
+			.collect()
+	}
+}
+
+impl std::ops::BitOr for Capabilities {
+	type Output = Capabilities;
+
+	fn bitor(self, other: Capabilities) -> Capabilities {
+		Capabilities(self.0 | other.0)
+	}
+}
+
+/// Source file used to build the database, see [DB::source_files].
+#[derive(Clone, Debug)]
+pub struct SourceFile<'a> {
+	/// File name in the import directory.
+	pub name: &'a str,
+	/// SHA-256 hash for the file contents.
+	pub hash: [u8; 32],
+}
+
+impl<'a> SourceFile<'a> {
+	/// Hash as a lowercase hexadecimal string.
+	pub fn hash_hex(&self) -> String {
+		to_hex(&self.hash)
+	}
+}
+
+impl<'a> DB<'a> {
+	/// Returns the database statistics from the file header.
+	pub fn info(&self) -> DatabaseInfo {
+		DatabaseInfo::from_raw(self.header)
+	}
+
+	/// Returns the optional features built into the database file.
+	///
+	/// This is from the file header, so it does not depend on the
+	/// [sections](DB::load_sections) that were loaded.
+	pub fn capabilities(&self) -> Capabilities {
+		Capabilities(self.header.capabilities.into())
+	}
+
+	/// Version of the importer that built the database. Empty if not
+	/// available.
+	pub fn importer(&self) -> &str {
+		self.get_str(self.header.importer)
+	}
+
+	/// Returns the source files used to build the database, for provenance
+	/// tracking of published databases.
+	pub fn source_files(&self) -> impl '_ + Iterator<Item = SourceFile<'_>> {
+		self.source_files.iter().map(move |it| SourceFile {
+			name: self.get_str(it.name),
+			hash: it.hash,
+		})
+	}
+}
+
+/// Validates the magic number, format version and profile in the header.
+pub(crate) fn check_header(header: &HeaderRaw) -> Result<(), String> {
+	let magic: u32 = header.magic.into();
+	let version: u32 = header.version.into();
+	let profile: u32 = header.profile.into();
+	if magic != HEADER_MAGIC {
+		Err(format!("wrong header magic"))
+	} else if version != FORMAT_VERSION {
+		Err(format!(
+			"unsupported format version {} (expected {})",
+			version, FORMAT_VERSION
+		))
+	} else if profile > 1 {
+		Err(format!("invalid profile {}", profile))
+	} else {
+		Ok(())
+	}
+}
+
+/// Converts a number of days since the Unix epoch to a `(year, month, day)`
+/// date in the proleptic Gregorian calendar.
+fn civil_date(days: u64) -> (u64, u64, u64) {
+	// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+	let z = days + 719468;
+	let era = z / 146097;
+	let doe = z % 146097;
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+	(year, month, day)
+}
+
+fn to_hex(hash: &[u8; 32]) -> String {
+	hash.iter().map(|x| format!("{:02x}", x)).collect()
+}
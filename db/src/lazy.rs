@@ -0,0 +1,141 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::compare_index_key;
+use super::normalize_key;
+use super::raw::TermIndex;
+use super::IndexMatches;
+use super::ResultSet;
+use super::DB;
+
+/// Number of index rows between calls to the progress callback when building
+/// an index.
+const PROGRESS_INTERVAL: usize = 4096;
+
+/// Indexes built in memory on first use, for a database written without them
+/// (see [Profile::Compact](super::Profile::Compact)).
+#[derive(Default)]
+pub(crate) struct LazyIndexes {
+	suffix_jp: OnceLock<Vec<TermIndex>>,
+}
+
+impl<'a> DB<'a> {
+	/// Returns true if the suffix index is available without building it,
+	/// either from the database file or from a previous build.
+	pub fn has_suffix_index(&self) -> bool {
+		self.index_suffix_jp.len() > 0 || self.lazy.suffix_jp.get().is_some()
+	}
+
+	/// Builds the indexes missing from the database file, calling `progress`
+	/// with the number of processed and total rows while building.
+	///
+	/// Indexes are otherwise built on first use, so this is only needed to
+	/// report progress or to avoid the delay on the first query. It does
+	/// nothing if the indexes are already available.
+	pub fn build_missing_indexes<F: FnMut(usize, usize)>(&self, progress: F) {
+		if !self.has_suffix_index() {
+			self.lazy.suffix_jp.get_or_init(|| self.build_suffix_index(progress));
+		}
+	}
+
+	/// Search for terms in the database ending with the given suffix inserting
+	/// the found term indexes into the `out` result set.
+	///
+	/// Returns the number of matches.
+	///
+	/// The suffix is normalized using [normalize_key] before searching.
+	pub fn search_suffix<S: AsRef<str>>(&self, suffix: S, out: &mut ResultSet) -> usize {
+		let start_count = out.len();
+		out.extend_indexes(self.search_suffix_iter(suffix));
+		out.len() - start_count
+	}
+
+	/// Same as [search_suffix](DB::search_suffix) but returns the matches
+	/// from the index directly, without collecting them into a [ResultSet].
+	///
+	/// This does not allocate, unless the suffix needs to be normalized (see
+	/// [normalize_key]), a key is too long to be reversed inline or the
+	/// suffix index needs to be built.
+	pub fn search_suffix_iter<S: AsRef<str>>(&self, suffix: S) -> IndexMatches<'_> {
+		let suffix = reverse(&normalize_key(suffix.as_ref()));
+		let suffix = suffix.as_ref();
+		if suffix.len() == 0 {
+			return IndexMatches::new(&[]);
+		}
+
+		let index = self.suffix_index();
+		let cmp = |it: &TermIndex| compare_index_key(it, suffix, false, || reverse(self.get_str(it.key)));
+		let sta = index.partition_point(|it| cmp(it) == Ordering::Less);
+		let end = index.partition_point(|it| cmp(it) != Ordering::Greater);
+		IndexMatches::new(&index[sta..end])
+	}
+
+	/// Returns the suffix index, building it if not in the database file.
+	fn suffix_index(&self) -> &[TermIndex] {
+		if self.index_suffix_jp.len() > 0 {
+			self.index_suffix_jp
+		} else {
+			self.lazy.suffix_jp.get_or_init(|| self.build_suffix_index(|_, _| {}))
+		}
+	}
+
+	/// Builds the suffix index from the prefix index, with the same order
+	/// as [Writer::write](super::Writer::write).
+	fn build_suffix_index<F: FnMut(usize, usize)>(&self, mut progress: F) -> Vec<TermIndex> {
+		let total = self.index_prefix_jp.len();
+		let mut reversed: HashMap<u32, ReversedKey> = HashMap::new();
+		let mut rows = Vec::with_capacity(total);
+		for (count, row) in self.index_prefix_jp.iter().enumerate() {
+			if count % PROGRESS_INTERVAL == 0 {
+				progress(count, total);
+			}
+			let key: u32 = row.key.into();
+			let term: u32 = row.term.into();
+			reversed.entry(key).or_insert_with(|| reverse(self.get_str(row.key)));
+			rows.push((key, term));
+		}
+
+		rows.sort_by(|a, b| reversed[&a.0].as_ref().cmp(reversed[&b.0].as_ref()));
+		progress(total, total);
+
+		rows.into_iter()
+			.map(|(key, term)| TermIndex::new(key, term, reversed[&key].as_ref()))
+			.collect()
+	}
+}
+
+/// Maximum length in bytes for a [ReversedKey] to be stored inline.
+const INLINE_KEY_LEN: usize = 96;
+
+/// Key reversed by grapheme for the suffix index. Short keys are stored
+/// inline, so that suffix searches don't allocate.
+enum ReversedKey {
+	Inline([u8; INLINE_KEY_LEN], usize),
+	Heap(String),
+}
+
+impl AsRef<str> for ReversedKey {
+	fn as_ref(&self) -> &str {
+		match self {
+			// The data is a sequence of whole graphemes from a `str`
+			&ReversedKey::Inline(ref data, len) => unsafe { std::str::from_utf8_unchecked(&data[..len]) },
+			&ReversedKey::Heap(ref text) => text.as_str(),
+		}
+	}
+}
+
+fn reverse(text: &str) -> ReversedKey {
+	if text.len() > INLINE_KEY_LEN {
+		return ReversedKey::Heap(text.graphemes(true).rev().collect());
+	}
+	let mut data = [0; INLINE_KEY_LEN];
+	let mut len = 0;
+	for grapheme in text.graphemes(true).rev() {
+		data[len..len + grapheme.len()].copy_from_slice(grapheme.as_bytes());
+		len += grapheme.len();
+	}
+	ReversedKey::Inline(data, len)
+}
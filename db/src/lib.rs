@@ -13,9 +13,75 @@ pub use data::*;
 mod writer;
 pub use writer::*;
 
+mod collation;
+pub use collation::*;
+
+mod mora;
+pub use mora::*;
+
 mod search;
 pub use search::*;
 
+mod scan;
+pub use scan::*;
+
+mod context;
+
+mod numeric;
+pub use numeric::{number_reading, Numeric, NumericValue};
+
+mod query;
+pub use query::*;
+
+mod parser;
+pub use parser::*;
+
+mod group;
+pub use group::*;
+
+mod family;
+pub use family::*;
+
+mod related;
+pub use related::RELATED_LIMIT;
+
+mod glossary;
+pub use glossary::*;
+
+mod embedding;
+
+mod warm;
+pub use warm::*;
+
+mod rank;
+use rank::*;
+
+mod cancel;
+pub use cancel::*;
+
+mod lazy;
+use lazy::*;
+
+mod info;
+pub use info::*;
+
+mod locale;
+
+mod media;
+pub use media::*;
+
+mod okurigana;
+pub use okurigana::*;
+
+mod priority;
+pub use priority::*;
+
+mod strings;
+pub use strings::*;
+
+mod diff;
+pub use diff::*;
+
 /// Root structure for the Japanese database.
 ///
 /// The structure can be loaded from a binary blob using the [load](DB::load)
@@ -23,15 +89,29 @@ pub use search::*;
 ///
 /// [Writer] can be used to generate a binary blob for the database.
 pub struct DB<'a> {
+	header: &'a HeaderRaw,
 	tags: &'a [TagRaw],
-	terms: &'a [TermRaw],
+	tag_translations: &'a [TagTranslationRaw],
+	terms: TermTable<'a>,
 	kanji: &'a [KanjiRaw],
+	radicals: &'a [RadicalRaw],
+	sources: &'a [SourceRaw],
+	source_files: &'a [SourceFileRaw],
+	glossary_configs: &'a [GlossaryConfigRaw],
 	index_prefix_jp: &'a [TermIndex],
+	index_prefix_dir: &'a [IndexShard],
 	index_suffix_jp: &'a [TermIndex],
 	index_chars_jp: &'a [CharIndex],
+	key_variants: &'a [KeyVariant],
+	index_term_id: &'a [RawUint32],
+	embeddings: &'a [RawUint32],
+	media: &'a [MediaRaw],
+	media_data: &'a [u8],
 	vector_data: &'a [RawUint32],
 	string_list: &'a [StrHandle],
 	string_data: &'a str,
+	lazy: LazyIndexes,
+	sections: Vec<Section>,
 }
 
 impl<'db> DB<'db> {
@@ -40,17 +120,140 @@ impl<'db> DB<'db> {
 			Some(Term {
 				pos: index,
 				data: self,
-				item: &self.terms[index],
 			})
 		} else {
 			None
 		}
 	}
 
+	/// Returns the term with the given stable identifier (see [Term::id]).
+	pub fn term_by_id<'a: 'db>(&'a self, id: u64) -> Option<Term<'db, 'a>> {
+		let term_id = |row: &RawUint32| -> u64 {
+			let index: usize = (*row).into();
+			self.terms.id[index].into()
+		};
+		let pos = self.index_term_id.partition_point(|x| term_id(x) < id);
+		match self.index_term_id.get(pos) {
+			Some(row) if term_id(row) == id => self.term((*row).into()),
+			_ => None,
+		}
+	}
+
+	pub fn kanji<'a: 'db>(&'a self, index: usize) -> Option<Kanji<'db, 'a>> {
+		if index < self.kanji.len() {
+			Some(Kanji {
+				data: self,
+				item: &self.kanji[index],
+			})
+		} else {
+			None
+		}
+	}
+
+	/// Returns an iterator over all terms in the database, in order of
+	/// relevance.
+	///
+	/// The terms borrow directly from the database data, so this can be used
+	/// to stream the whole database without building owned structures.
+	pub fn terms<'a: 'db>(&'a self) -> impl 'a + ExactSizeIterator<Item = Term<'db, 'a>> {
+		(0..self.terms.len()).map(move |index| Term {
+			pos: index,
+			data: self,
+		})
+	}
+
+	/// Returns an iterator over all kanji in the database, in order of
+	/// frequency.
+	///
+	/// See also [terms](DB::terms).
+	pub fn kanji_iter<'a: 'db>(&'a self) -> impl 'a + ExactSizeIterator<Item = Kanji<'db, 'a>> {
+		self.kanji.iter().map(move |item| Kanji {
+			data: self,
+			item: item,
+		})
+	}
+
+	/// Searches for a kanji by its character.
+	pub fn search_kanji<'a: 'db>(&'a self, character: char) -> Option<Kanji<'db, 'a>> {
+		let character = character as u32;
+		self.kanji
+			.iter()
+			.position(|it| {
+				let other: u32 = it.character.into();
+				other == character
+			})
+			.and_then(|index| self.kanji(index))
+	}
+
+	/// Returns the list of radicals for the radical lookup.
+	///
+	/// The classical Kangxi radicals come first, sorted by their number,
+	/// followed by the additional search radicals.
+	pub fn radicals<'a: 'db>(&'a self) -> impl 'a + Iterator<Item = Radical<'db, 'a>> {
+		self.radicals.iter().map(move |item| Radical {
+			data: self,
+			item: item,
+		})
+	}
+
+	/// Returns the list of source dictionaries with their metadata.
+	pub fn sources<'a: 'db>(&'a self) -> impl 'a + ExactSizeIterator<Item = Source<'db, 'a>> {
+		self.sources.iter().map(move |item| Source {
+			data: self,
+			item: item,
+		})
+	}
+
+	/// Returns a source dictionary by name.
+	pub fn source<'a: 'db>(&'a self, name: &str) -> Option<Source<'db, 'a>> {
+		self.sources().find(|it| it.name() == name)
+	}
+
+	/// Returns all tags in the database, in the order of the tag table.
+	///
+	/// See also [tag_categories](DB::tag_categories) and [domains](DB::domains).
+	pub fn tags<'a: 'db>(&'a self) -> impl 'a + ExactSizeIterator<Item = Tag<'db, 'a>> {
+		(0..self.tags.len()).map(move |index| self.get_tag((index as u32).into()))
+	}
+
+	/// Returns the tag categories used by the tags in the database, sorted by
+	/// [TagCategory] and then by name.
+	///
+	/// Distinct category names are listed separately, even if they map to the
+	/// same [TagCategory] (e.g. unknown categories map to `Other`). Tags
+	/// without a category are listed with an empty name.
+	pub fn tag_categories<'a: 'db>(&'a self) -> Vec<TagCategoryInfo<'db>> {
+		let mut out: Vec<TagCategoryInfo> = Vec::new();
+		for tag in self.tags() {
+			let name = tag.category();
+			match out.iter_mut().find(|x| x.name == name) {
+				Some(info) => info.tags += 1,
+				None => out.push(TagCategoryInfo {
+					name: name,
+					kind: tag.kind(),
+					tags: 1,
+				}),
+			}
+		}
+		out.sort_by(|a, b| a.kind.cmp(&b.kind).then_with(|| a.name.cmp(b.name)));
+		out
+	}
+
+	/// Returns the priority for a source dictionary by name. Sources without
+	/// a configured priority have a priority of zero.
+	pub fn source_priority(&self, name: &str) -> i32 {
+		self.sources
+			.iter()
+			.find(|it| self.get_str(it.name) == name)
+			.map(|it| it.priority.into())
+			.unwrap_or(0)
+	}
+
 	fn get_tag<'a: 'db>(&'a self, index: RawUint32) -> Tag<'db, 'a> {
 		let index: usize = index.into();
 		Tag {
 			data: self,
+			index: index,
 			item: &self.tags[index],
 		}
 	}
@@ -62,6 +265,13 @@ impl<'db> DB<'db> {
 			.map(move |&index| self.get_tag(index))
 	}
 
+	fn get_strings<'a: 'db>(&'a self, strings: VecHandle) -> impl 'a + Iterator<Item = &'db str> {
+		let (sta, end) = strings.range();
+		self.vector_data[sta..end]
+			.iter()
+			.map(move |&index| self.get_str(index))
+	}
+
 	fn get_str(&self, index: RawUint32) -> &'db str {
 		let index: usize = index.into();
 		let string = &self.string_list[index];
@@ -76,21 +286,63 @@ impl<'a> DB<'a> {
 	pub fn check(&self) {
 		let start = Instant::now();
 
+		let info = self.info();
+		assert_eq!(info.terms, self.terms.len(), "header: term count");
+		assert_eq!(info.kanji, self.kanji.len(), "header: kanji count");
+		assert_eq!(info.tags, self.tags.len(), "header: tag count");
+		assert_eq!(info.radicals, self.radicals.len(), "header: radical count");
+		assert_eq!(info.sources, self.sources.len(), "header: source count");
+		assert_eq!(info.prefix_index, self.index_prefix_jp.len(), "header: prefix index");
+		assert_eq!(info.suffix_index, self.index_suffix_jp.len(), "header: suffix index");
+		assert_eq!(info.chars_index, self.index_chars_jp.len(), "header: chars index");
+		assert_eq!(
+			self.embeddings.len(),
+			self.terms.len() * self.embedding_dim(),
+			"embeddings: size does not match the terms"
+		);
+
+		for it in self.tag_translations.iter() {
+			let tag: usize = it.tag.into();
+			assert!(tag < self.tags.len(), "tag translation: tag index out of bounds");
+			self.check_string(it.language, "tag translation language");
+			self.check_string(it.notes, "tag translation notes");
+		}
+
 		for tag in self.tags.iter() {
 			self.check_string(tag.name, "tag name");
 			self.check_string(tag.category, "tag category");
 			self.check_string(tag.notes, "tag notes");
 		}
 
-		for term in self.terms.iter() {
-			self.check_string(term.expression, "term expression");
-			self.check_string(term.reading, "term reading");
-			self.check_string(term.search_key, "term search key");
-			self.check_string(term.source, "term source");
-			self.check_vector_strings(term.glossary, "term glossary");
-			self.check_vector_tags(term.rules, "term rules");
-			self.check_vector_tags(term.term_tags, "term tags");
-			self.check_vector_tags(term.definition_tags, "term definition tags");
+		let terms = &self.terms;
+		for column in [terms.reading, terms.frequency, terms.source, terms.flags, terms.fields, terms.payload].iter() {
+			assert_eq!(column.len(), terms.len(), "term columns: length mismatch");
+		}
+		assert_eq!(terms.glossary.len(), terms.len(), "term columns: length mismatch");
+		assert_eq!(terms.id.len(), terms.len(), "term columns: length mismatch");
+
+		for index in 0..terms.len() {
+			self.check_string(terms.expression[index], "term expression");
+			self.check_string(terms.reading[index], "term reading");
+			self.check_string(terms.source[index], "term source");
+			self.check_vector_strings(terms.glossary[index], "term glossary");
+
+			let fields: u32 = terms.fields[index].into();
+			let payload: usize = terms.payload[index].into();
+			assert!(
+				payload + TermTable::payload_len(fields) <= self.vector_data.len(),
+				"term payload out of bounds"
+			);
+			let search_key = terms.value(index, TermTable::SEARCH_KEY);
+			self.check_string(search_key.into(), "term search key");
+			self.check_vector_tags(terms.vector(index, TermTable::RULES), "term rules");
+			self.check_vector_tags(terms.vector(index, TermTable::TERM_TAGS), "term tags");
+			self.check_vector_tags(terms.vector(index, TermTable::DEFINITION_TAGS), "term definition tags");
+			self.check_vector_terms(terms.vector(index, TermTable::RELATED), "term related");
+			self.check_vector(terms.vector(index, TermTable::PITCH), "term pitch");
+			self.check_vector_strings(terms.vector(index, TermTable::ORIGIN), "term origin");
+			self.check_vector_strings(terms.vector(index, TermTable::INFO), "term info");
+			self.check_vector_strings(terms.vector(index, TermTable::RESTRICTIONS), "term restrictions");
 		}
 
 		for kanji in self.kanji.iter() {
@@ -99,6 +351,7 @@ impl<'a> DB<'a> {
 			self.check_vector_strings(kanji.kunyomi, "kanji kunyomi");
 			self.check_string(kanji.source, "kanji source");
 			self.check_vector_tags(kanji.tags, "kanji tags");
+			self.check_vector(kanji.similar, "kanji similar");
 
 			self.check_vector(kanji.stats, "kanji stats");
 			let (sta, end) = kanji.stats.range();
@@ -112,8 +365,63 @@ impl<'a> DB<'a> {
 			}
 		}
 
+		for (index, media) in self.media.iter().enumerate() {
+			self.check_string(media.source, "media source");
+			self.check_string(media.path, "media path");
+			let offset: u32 = media.offset.into();
+			let length: u32 = media.length.into();
+			assert!(
+				(offset + length) as usize <= self.media_data.len(),
+				"media data out of bounds"
+			);
+			let key: u64 = media.key.into();
+			let source = self.get_str(media.source);
+			let path = self.get_str(media.path);
+			assert_eq!(key, media_key(source, path), "media key does not match");
+			if index > 0 {
+				let prev: u64 = self.media[index - 1].key.into();
+				assert!(prev <= key, "media not sorted by key");
+			}
+		}
+
+		self.check_string(self.header.importer, "importer");
+		for file in self.source_files.iter() {
+			self.check_string(file.name, "source file name");
+		}
+
+		for source in self.sources.iter() {
+			self.check_string(source.name, "source name");
+		}
+
+		for config in self.glossary_configs.iter() {
+			self.check_string(config.language, "glossary config language");
+			self.check_vector_strings(config.stop_words, "glossary config stop words");
+		}
+
+		for radical in self.radicals.iter() {
+			self.check_string(radical.name, "radical name");
+			self.check_vector(radical.variants, "radical variants");
+		}
+
 		for row in self.index_prefix_jp.iter() {
 			self.check_term_index(*row, "prefix index");
+			let prefix = row.prefix;
+			assert!(
+				prefix == key_prefix(self.get_str(row.key)),
+				"prefix index: key prefix does not match"
+			);
+		}
+
+		let mut last_end = 0;
+		for row in self.index_prefix_dir.iter() {
+			let (sta, end) = row.range();
+			assert!(sta >= last_end, "prefix index shard: overlapping range");
+			assert!(sta < end, "prefix index shard: empty range");
+			assert!(
+				end <= self.index_prefix_jp.len(),
+				"prefix index shard: range out of bounds"
+			);
+			last_end = end;
 		}
 
 		for row in self.index_suffix_jp.iter() {
@@ -129,8 +437,27 @@ impl<'a> DB<'a> {
 			chars_max = std::cmp::max(chars_max, count);
 			self.check_vector_terms(row.indexes, "index chars row");
 		}
+		assert_eq!(self.index_term_id.len(), terms.len(), "term id index: length mismatch");
+		let mut last_id = None;
+		for &row in self.index_term_id.iter() {
+			let index: usize = row.into();
+			assert!(index < terms.len(), "term id index: term out of bounds");
+			let id: u64 = terms.id[index].into();
+			assert!(last_id <= Some(id), "term id index: not sorted");
+			last_id = Some(id);
+		}
+
+		for row in self.key_variants.iter() {
+			self.check_string(row.key, "key variant key");
+			self.check_string(row.original, "key variant original");
+			assert!(
+				normalize_key(self.get_str(row.original)) == self.get_str(row.key),
+				"key variant: original does not normalize to the key"
+			);
+		}
+
 		let chars_len = self.index_chars_jp.len();
-		let chars_avg = chars_cnt / chars_len;
+		let chars_avg = chars_cnt / std::cmp::max(chars_len, 1);
 
 		for (index, s) in self.string_list.iter().enumerate() {
 			let (sta, end) = s.range();
@@ -148,10 +475,11 @@ impl<'a> DB<'a> {
 
 		println!("Database check finished (elapsed {:?})", start.elapsed());
 		println!(
-			"-> {} terms / {} kanji / {} tags",
+			"-> {} terms / {} kanji / {} tags / {} radicals",
 			self.terms.len(),
 			self.kanji.len(),
-			self.tags.len()
+			self.tags.len(),
+			self.radicals.len()
 		);
 		println!(
 			"-> {} indexed terms / {} chars ({} avg / {} max / {} total)",
@@ -161,6 +489,7 @@ impl<'a> DB<'a> {
 			chars_max,
 			chars_cnt,
 		);
+		println!("-> {} prefix index shards", self.index_prefix_dir.len());
 		println!(
 			"-> {} vector data",
 			bytes(self.vector_data.len() * std::mem::size_of::<u32>())
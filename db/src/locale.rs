@@ -0,0 +1,37 @@
+use super::Tag;
+use super::TagCategory;
+use super::DB;
+
+impl<'a> DB<'a> {
+	/// Returns the languages with tag translations, sorted and without
+	/// duplicates.
+	///
+	/// Translated tag notes are available with [Tag::notes_in](super::Tag::notes_in).
+	pub fn tag_languages(&self) -> Vec<&'a str> {
+		let mut out: Vec<&'a str> = self.tag_translations.iter().map(|x| self.get_str(x.language)).collect();
+		out.sort();
+		out.dedup();
+		out
+	}
+
+	/// Returns the domain tags (e.g. `med` or `law`), sorted by name.
+	///
+	/// Terms for a domain can be searched with [Field::Domain](super::Field::Domain).
+	pub fn domains<'b: 'a>(&'b self) -> Vec<Tag<'a, 'b>> {
+		let mut out: Vec<Tag> = self.tags().filter(|x| x.kind() == TagCategory::Domain).collect();
+		out.sort_by(|a, b| a.name().cmp(b.name()));
+		out
+	}
+
+	/// Returns the translated notes for a tag index, if available.
+	pub(crate) fn tag_translation(&self, tag: usize, language: &str) -> Option<&'a str> {
+		let tag = tag as u32;
+		self.tag_translations
+			.binary_search_by(|it| {
+				let other: u32 = it.tag.into();
+				other.cmp(&tag).then_with(|| self.get_str(it.language).cmp(language))
+			})
+			.ok()
+			.map(|pos| self.get_str(self.tag_translations[pos].notes))
+	}
+}
@@ -0,0 +1,98 @@
+use super::raw::*;
+use super::DB;
+
+/// Media file stored in the database, such as an image referenced by a
+/// structured content definition. See [DB::media].
+#[derive(Copy, Clone)]
+pub struct Media<'a> {
+	source: &'a str,
+	path: &'a str,
+	data: &'a [u8],
+}
+
+impl<'a> Media<'a> {
+	/// Name of the source dictionary for the file.
+	pub fn source(&self) -> &'a str {
+		self.source
+	}
+
+	/// Path of the file in the source dictionary, as referenced by the
+	/// definitions.
+	pub fn path(&self) -> &'a str {
+		self.path
+	}
+
+	/// File contents.
+	pub fn data(&self) -> &'a [u8] {
+		self.data
+	}
+
+	/// MIME type for the file, from the path extension.
+	pub fn mime_type(&self) -> &'static str {
+		let extension = match self.path.rfind('.') {
+			Some(pos) => self.path[pos + 1..].to_lowercase(),
+			None => String::new(),
+		};
+		match extension.as_str() {
+			"png" => "image/png",
+			"jpg" | "jpeg" => "image/jpeg",
+			"gif" => "image/gif",
+			"svg" => "image/svg+xml",
+			"webp" => "image/webp",
+			"bmp" => "image/bmp",
+			"avif" => "image/avif",
+			_ => "application/octet-stream",
+		}
+	}
+}
+
+impl<'a> DB<'a> {
+	/// Returns the media file with the given path in a source dictionary.
+	///
+	/// Media files are only available if imported and the
+	/// [Section::Media](super::Section::Media) section is loaded.
+	pub fn media(&self, source: &str, path: &str) -> Option<Media<'a>> {
+		let key = media_key(source, path);
+		let start = self.media.partition_point(|x| {
+			let x: u64 = x.key.into();
+			x < key
+		});
+		self.media[start..]
+			.iter()
+			.take_while(|x| {
+				let x: u64 = x.key.into();
+				x == key
+			})
+			.map(|x| self.get_media(x))
+			.find(|x| x.source == source && x.path == path)
+	}
+
+	/// Returns all media files in the database, in no particular order.
+	pub fn media_files<'b>(&'b self) -> impl 'b + Iterator<Item = Media<'a>> {
+		self.media.iter().map(move |x| self.get_media(x))
+	}
+
+	fn get_media(&self, media: &MediaRaw) -> Media<'a> {
+		let offset: u32 = media.offset.into();
+		let length: u32 = media.length.into();
+		let (sta, end) = (offset as usize, (offset + length) as usize);
+		Media {
+			source: self.get_str(media.source),
+			path: self.get_str(media.path),
+			data: &self.media_data[sta..end],
+		}
+	}
+}
+
+/// Key for the media lookup, a 64-bit FNV-1a hash of the source name and
+/// path.
+pub(crate) fn media_key(source: &str, path: &str) -> u64 {
+	const OFFSET: u64 = 0xcbf29ce484222325;
+	const PRIME: u64 = 0x100000001b3;
+
+	let mut hash = OFFSET;
+	for &byte in source.as_bytes().iter().chain(&[0]).chain(path.as_bytes()) {
+		hash = (hash ^ byte as u64).wrapping_mul(PRIME);
+	}
+	hash
+}
@@ -0,0 +1,163 @@
+//! Mora segmentation and pitch accent utilities.
+//!
+//! A mora is the unit of timing in Japanese and the unit used for the pitch
+//! accent positions. For kana text:
+//!
+//! - Each kana is one mora, except for small kana (e.g. `ゃ` in `きゃ` or
+//!   `ァ` in `ファ`), which combine with the previous kana.
+//! - The sokuon `っ`, the moraic nasal `ん` and the long vowel mark `ー` are
+//!   a mora each.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Small kana that combine with the previous kana into a single mora.
+const SMALL_KANA: &'static str = "ぁぃぅぇぉゃゅょゎァィゥェォャュョヮ";
+
+/// Splits a kana string into morae.
+pub fn morae(text: &str) -> Vec<&str> {
+	let mut out: Vec<&str> = Vec::new();
+	let mut start = 0;
+	for (pos, chr) in text.char_indices() {
+		if pos > 0 && !is_small_kana(chr) {
+			out.push(&text[start..pos]);
+			start = pos;
+		}
+	}
+	if start < text.len() {
+		out.push(&text[start..]);
+	}
+	out
+}
+
+/// Returns the number of morae in a kana string.
+pub fn count_morae(text: &str) -> usize {
+	text.chars().filter(|&x| !is_small_kana(x)).count()
+}
+
+/// Returns true for the small kana that combine with the previous kana.
+pub fn is_small_kana(chr: char) -> bool {
+	SMALL_KANA.contains(chr)
+}
+
+/// Pitch accent pattern for a word.
+///
+/// The pattern is given by the accent position, that is the mora after
+/// which the pitch drops, with zero for no drop.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PitchPattern {
+	/// Flat pattern with no drop (position zero).
+	Heiban,
+	/// Drop after the first mora.
+	Atamadaka,
+	/// Drop after a middle mora.
+	Nakadaka,
+	/// Drop after the last mora (only noticeable on a following particle).
+	Odaka,
+}
+
+impl PitchPattern {
+	/// Returns the pattern for an accent position in a word with the given
+	/// number of morae.
+	///
+	/// A single mora word with an accent is considered atamadaka.
+	pub fn from_position(position: u32, morae: usize) -> PitchPattern {
+		let morae = morae as u32;
+		if position == 0 {
+			PitchPattern::Heiban
+		} else if position == 1 {
+			PitchPattern::Atamadaka
+		} else if position >= morae {
+			PitchPattern::Odaka
+		} else {
+			PitchPattern::Nakadaka
+		}
+	}
+
+	/// Pattern name, as used in the query language.
+	pub fn name(&self) -> &'static str {
+		match self {
+			PitchPattern::Heiban => "heiban",
+			PitchPattern::Atamadaka => "atamadaka",
+			PitchPattern::Nakadaka => "nakadaka",
+			PitchPattern::Odaka => "odaka",
+		}
+	}
+}
+
+impl fmt::Display for PitchPattern {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.name())
+	}
+}
+
+impl FromStr for PitchPattern {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<PitchPattern, String> {
+		match s.trim().to_lowercase().as_str() {
+			"heiban" => Ok(PitchPattern::Heiban),
+			"atamadaka" => Ok(PitchPattern::Atamadaka),
+			"nakadaka" => Ok(PitchPattern::Nakadaka),
+			"odaka" => Ok(PitchPattern::Odaka),
+			_ => Err(format!("invalid pitch pattern `{}`", s)),
+		}
+	}
+}
+
+/// Returns the pitch (high or low) for each mora of a word with the given
+/// accent position, followed by the pitch of a following particle.
+///
+/// For example, `はし` with position 2 (odaka) is low-high and a following
+/// particle is low: `[false, true, false]`.
+pub fn pitch_contour(position: u32, morae: usize) -> Vec<bool> {
+	let position = position as usize;
+	(0..morae + 1)
+		.map(|i| match position {
+			0 => i > 0,
+			1 => i == 0,
+			_ => i > 0 && i < position,
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn counts_morae() {
+		assert_eq!(count_morae("きょう"), 2);
+		assert_eq!(count_morae("ファン"), 2);
+		assert_eq!(count_morae("きって"), 3);
+		assert_eq!(count_morae("ラーメン"), 4);
+		assert_eq!(count_morae("しんぶん"), 4);
+		assert_eq!(count_morae(""), 0);
+		assert_eq!(morae("きょうっ"), vec!["きょ", "う", "っ"]);
+		assert_eq!(morae("ファーン"), vec!["ファ", "ー", "ン"]);
+	}
+
+	#[test]
+	fn patterns_from_position() {
+		// Boundaries for a word with 3 morae: 0, 1, n - 1 and n
+		let morae = count_morae("さくら");
+		assert_eq!(PitchPattern::from_position(0, morae), PitchPattern::Heiban);
+		assert_eq!(PitchPattern::from_position(1, morae), PitchPattern::Atamadaka);
+		assert_eq!(PitchPattern::from_position(2, morae), PitchPattern::Nakadaka);
+		assert_eq!(PitchPattern::from_position(3, morae), PitchPattern::Odaka);
+
+		// Positions past the end are odaka and one mora is atamadaka
+		assert_eq!(PitchPattern::from_position(4, morae), PitchPattern::Odaka);
+		assert_eq!(PitchPattern::from_position(1, 1), PitchPattern::Atamadaka);
+		assert_eq!(PitchPattern::from_position(2, 2), PitchPattern::Odaka);
+		assert_eq!("Nakadaka".parse(), Ok(PitchPattern::Nakadaka));
+	}
+
+	#[test]
+	fn contours() {
+		assert_eq!(pitch_contour(0, 2), vec![false, true, true]);
+		assert_eq!(pitch_contour(1, 2), vec![true, false, false]);
+		assert_eq!(pitch_contour(2, 2), vec![false, true, false]);
+		assert_eq!(pitch_contour(2, 3), vec![false, true, false, false]);
+	}
+}
@@ -0,0 +1,624 @@
+//! Recognition of numeric expressions for [DB::scan](super::DB::scan).
+//!
+//! Numbers can be written with ASCII or full-width digits (e.g. `2,000` or
+//! `３`), with kanji numerals (e.g. `二千` or `二〇二四`) or mixed (`3万`).
+//! Besides plain numbers, this recognizes:
+//!
+//! - Dates with a year, month and day, as in `2024年3月5日` or `3月5日`.
+//!   Years in a Japanese era (e.g. `令和六年` or `平成元年`) are converted to
+//!   the western calendar.
+//! - Times with an hour, optional minutes (or `半`) and seconds, as in
+//!   `三時半` or `15時30分`.
+//! - Counts with a common counter, as in `三本` or `2,000円`.
+//!
+//! Each expression has a normalized [NumericValue] and its reading, taking
+//! into account the sound changes between the number and the counter (e.g.
+//! `いっぽん` and `さんぼん`) and the irregular readings (e.g. `ついたち`,
+//! `ふたり` or `よじ`).
+
+use std::fmt;
+
+/// Japanese eras with their reading and first year in the western calendar.
+const ERAS: &[(&str, &str, u64)] = &[
+	("令和", "れいわ", 2019),
+	("平成", "へいせい", 1989),
+	("昭和", "しょうわ", 1926),
+	("大正", "たいしょう", 1912),
+	("明治", "めいじ", 1868),
+];
+
+/// Reading for the digits from one to nine.
+const DIGITS: [&str; 10] = ["", "いち", "に", "さん", "よん", "ご", "ろく", "なな", "はち", "きゅう"];
+
+/// Readings for a number of days, used for both the day of month and the
+/// duration. Other days are read as the number followed by `にち`.
+const DAYS: &[(u64, &str)] = &[
+	(1, "いちにち"),
+	(2, "ふつか"),
+	(3, "みっか"),
+	(4, "よっか"),
+	(5, "いつか"),
+	(6, "むいか"),
+	(7, "なのか"),
+	(8, "ようか"),
+	(9, "ここのか"),
+	(10, "とおか"),
+	(14, "じゅうよっか"),
+	(20, "はつか"),
+	(24, "にじゅうよっか"),
+];
+
+/// Sound changes between a number and a counter.
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Sound {
+	/// No sound changes.
+	Plain,
+	/// Gemination after 1, 8 and 10 (e.g. `いっさつ`).
+	Light,
+	/// Gemination after 1, 6, 8, 10 and 100 (e.g. `ろっこ`).
+	Hard,
+	/// Same as `Hard`, voiced after `ん` (e.g. `さんがい`).
+	Voiced,
+	/// Same as `Hard`, with `p` after the gemination and `b` after `ん`
+	/// (e.g. `いっぽん` and `さんぼん`).
+	HRow,
+	/// Same as `Hard`, with `p` after the gemination and `ん` (e.g.
+	/// `さんぷん`).
+	Fun,
+}
+
+/// Common counters with their reading.
+const COUNTERS: &[(&str, &str, Sound)] = &[
+	("本", "ほん", Sound::HRow),
+	("匹", "ひき", Sound::HRow),
+	("杯", "はい", Sound::HRow),
+	("発", "はつ", Sound::HRow),
+	("分", "ふん", Sound::Fun),
+	("個", "こ", Sound::Hard),
+	("回", "かい", Sound::Hard),
+	("件", "けん", Sound::Hard),
+	("ヶ月", "かげつ", Sound::Hard),
+	("か月", "かげつ", Sound::Hard),
+	("カ月", "かげつ", Sound::Hard),
+	("階", "かい", Sound::Voiced),
+	("軒", "けん", Sound::Voiced),
+	("冊", "さつ", Sound::Light),
+	("歳", "さい", Sound::Light),
+	("才", "さい", Sound::Light),
+	("週間", "しゅうかん", Sound::Light),
+	("頭", "とう", Sound::Light),
+	("通", "つう", Sound::Light),
+	("点", "てん", Sound::Light),
+	("人", "にん", Sound::Plain),
+	("枚", "まい", Sound::Plain),
+	("台", "だい", Sound::Plain),
+	("円", "えん", Sound::Plain),
+	("秒", "びょう", Sound::Plain),
+	("度", "ど", Sound::Plain),
+	("番", "ばん", Sound::Plain),
+	("倍", "ばい", Sound::Plain),
+	("年", "ねん", Sound::Plain),
+	("時間", "じかん", Sound::Plain),
+	("日", "にち", Sound::Plain),
+];
+
+/// Normalized value for a [Numeric] expression.
+#[derive(Clone, PartialEq, Debug)]
+pub enum NumericValue {
+	/// Integer number.
+	Integer(u64),
+	/// Decimal number, as normalized ASCII text (e.g. `3.5`).
+	Decimal(String),
+	/// Number with a counter, e.g. `Count(3, "本")` for `三本`.
+	Count(u64, &'static str),
+	/// Calendar date. The year is in the western calendar.
+	Date {
+		year: Option<u64>,
+		month: Option<u32>,
+		day: Option<u32>,
+	},
+	/// Time of day.
+	Time { hour: u32, minute: u32, second: Option<u32> },
+}
+
+impl fmt::Display for NumericValue {
+	/// Formats the value as normalized text. Dates and times are formatted as
+	/// in ISO 8601 (e.g. `2024-03-05`, `--03-05` without a year or `03:30`).
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			NumericValue::Integer(value) => write!(f, "{}", value),
+			NumericValue::Decimal(value) => write!(f, "{}", value),
+			NumericValue::Count(value, counter) => write!(f, "{}{}", value, counter),
+			&NumericValue::Date { year, month, day } => {
+				match year {
+					Some(year) => write!(f, "{:04}", year)?,
+					None => write!(f, "-")?,
+				}
+				match month {
+					Some(month) => write!(f, "-{:02}", month)?,
+					None if day.is_some() => write!(f, "-")?,
+					None => {}
+				}
+				if let Some(day) = day {
+					write!(f, "-{:02}", day)?;
+				}
+				Ok(())
+			}
+			&NumericValue::Time { hour, minute, second } => {
+				write!(f, "{:02}:{:02}", hour, minute)?;
+				if let Some(second) = second {
+					write!(f, ":{:02}", second)?;
+				}
+				Ok(())
+			}
+		}
+	}
+}
+
+/// Numeric expression recognized by [DB::scan](super::DB::scan). See
+/// [ScanMatch::numeric](super::ScanMatch::numeric).
+#[derive(Clone, PartialEq, Debug)]
+pub struct Numeric {
+	/// Normalized value.
+	pub value: NumericValue,
+	/// Reading in hiragana (e.g. `れいわろくねん` for `令和六年`).
+	pub reading: String,
+}
+
+/// Parses the numeric expression at the start of the text, returning the
+/// longest expression and its length in bytes.
+pub(crate) fn parse(text: &str) -> Option<(Numeric, usize)> {
+	let candidates = vec![parse_date(text), parse_time(text), parse_count(text), parse_plain(text)];
+	let mut out: Option<(Numeric, usize)> = None;
+	for (numeric, len) in candidates.into_iter().flatten() {
+		if out.as_ref().map(|x| len > x.1) != Some(false) {
+			out = Some((numeric, len));
+		}
+	}
+	out
+}
+
+/// Returns the reading for a number (e.g. `にせんにじゅうよん`).
+pub fn number_reading(value: u64) -> String {
+	if value == 0 {
+		return String::from("ぜろ");
+	}
+	let mut out = String::new();
+	let mut rest = value;
+	for &(unit, name) in [(1_000_000_000_000, "ちょう"), (100_000_000, "おく"), (10_000, "まん")].iter() {
+		let group = rest / unit;
+		rest %= unit;
+		if group >= 10_000 {
+			out.push_str(&number_reading(group));
+			out.push_str(name);
+		} else if group > 0 {
+			// `いっせんまん` instead of `せんまん`
+			if group == 1000 {
+				out.push_str("いっせん");
+			} else {
+				out.push_str(&group_reading(group));
+			}
+			out.push_str(name);
+		}
+	}
+	out.push_str(&group_reading(rest));
+	out
+}
+
+/// Reading for a number below 10000, empty for zero.
+fn group_reading(value: u64) -> String {
+	const THOUSANDS: [&str; 10] = [
+		"", "せん", "にせん", "さんぜん", "よんせん", "ごせん", "ろくせん", "ななせん", "はっせん", "きゅうせん",
+	];
+	const HUNDREDS: [&str; 10] = [
+		"", "ひゃく", "にひゃく", "さんびゃく", "よんひゃく", "ごひゃく", "ろっぴゃく", "ななひゃく", "はっぴゃく", "きゅうひゃく",
+	];
+	let (thousands, hundreds, tens, ones) = (value / 1000 % 10, value / 100 % 10, value / 10 % 10, value % 10);
+	let mut out = String::new();
+	out.push_str(THOUSANDS[thousands as usize]);
+	out.push_str(HUNDREDS[hundreds as usize]);
+	if tens > 1 {
+		out.push_str(DIGITS[tens as usize]);
+	}
+	if tens > 0 {
+		out.push_str("じゅう");
+	}
+	out.push_str(DIGITS[ones as usize]);
+	out
+}
+
+/// Returns the reading for a count with one of the [COUNTERS].
+fn count_reading(value: u64, counter: &str, counter_reading: &str, sound: Sound) -> String {
+	match (counter, value) {
+		("人", 1) => return String::from("ひとり"),
+		("人", 2) => return String::from("ふたり"),
+		("日", _) => {
+			if let Some(&(_, reading)) = DAYS.iter().find(|x| x.0 == value) {
+				return String::from(reading);
+			}
+		}
+		_ => {}
+	}
+
+	let mut number = number_reading(value);
+	let replace_end = |number: &mut String, from: &str, to: &str| {
+		if number.ends_with(from) {
+			let len = number.len() - from.len();
+			number.truncate(len);
+			number.push_str(to);
+		}
+	};
+	match counter {
+		"人" | "円" | "年" | "時" | "時間" => replace_end(&mut number, "よん", "よ"),
+		"月" => replace_end(&mut number, "よん", "し"),
+		_ => {}
+	}
+	if let "時" | "時間" | "月" | "日" = counter {
+		replace_end(&mut number, "なな", "しち");
+		replace_end(&mut number, "きゅう", "く");
+	}
+
+	let geminate: &[&str] = match sound {
+		Sound::Plain => &[],
+		Sound::Light => &["いち", "はち", "じゅう"],
+		_ => &["いち", "ろく", "はち", "じゅう", "ひゃく"],
+	};
+	if let Some(&end) = geminate.iter().find(|&x| number.ends_with(x)) {
+		let mut geminated: String = end.chars().take(end.chars().count() - 1).collect();
+		geminated.push('っ');
+		replace_end(&mut number, end, &geminated);
+	}
+
+	let after_gemination = number.ends_with('っ');
+	let after_n = number.ends_with('ん') && !number.ends_with("よん");
+	let reading = match sound {
+		Sound::HRow if after_gemination => mutate(counter_reading, HANDAKUTEN),
+		Sound::HRow | Sound::Voiced if after_n => mutate(counter_reading, DAKUTEN),
+		Sound::Fun if after_gemination || number.ends_with('ん') => mutate(counter_reading, HANDAKUTEN),
+		_ => counter_reading.to_string(),
+	};
+	number.push_str(&reading);
+	number
+}
+
+/// Kana rows for the sound changes in [mutate].
+const PLAIN: &str = "かきくけこさしすせそたちつてとはひふへほ";
+const DAKUTEN: &str = "がぎぐげござじずぜぞだぢづでどばびぶべぼ";
+const HANDAKUTEN: &str = "かきくけこさしすせそたちつてとぱぴぷぺぽ";
+
+/// Replaces the first kana of the reading with the kana in the same position
+/// of `row`.
+fn mutate(reading: &str, row: &str) -> String {
+	let mut chars = reading.chars();
+	let first = chars.next().unwrap();
+	match PLAIN.chars().position(|x| x == first) {
+		Some(pos) => {
+			let mut out: String = row.chars().nth(pos).into_iter().collect();
+			out.extend(chars);
+			out
+		}
+		None => reading.to_string(),
+	}
+}
+
+/// Number parsed from the start of a text by [parse_number].
+struct Number {
+	value: u64,
+	/// Decimal digits for a decimal number.
+	decimals: Option<String>,
+	/// Length in bytes.
+	len: usize,
+}
+
+/// Parses the number at the start of the text.
+fn parse_number(text: &str) -> Option<Number> {
+	let mut total: u64 = 0;
+	let mut section: u64 = 0;
+	let mut current: Option<u64> = None;
+	let mut units = false;
+	let mut len = 0;
+	let mut decimals = None;
+	let mut prev = ' ';
+	for (pos, chr) in text.char_indices() {
+		let end = pos + chr.len_utf8();
+		let next = text[end..].chars().next().unwrap_or(' ');
+		if let Some(digit) = digit_value(chr) {
+			current = Some(current.unwrap_or(0).checked_mul(10)?.checked_add(digit)?);
+		} else if let Some(unit) = unit_value(chr) {
+			section = section.checked_add(current.unwrap_or(1).checked_mul(unit)?)?;
+			current = None;
+			units = true;
+		} else if let Some(unit) = big_unit_value(chr) {
+			let value = section + current.unwrap_or(0);
+			if value == 0 {
+				break;
+			}
+			total = total.checked_add(value.checked_mul(unit)?)?;
+			section = 0;
+			current = None;
+			units = true;
+		} else if (chr == ',' || chr == '，') && is_digit(prev) && is_digit(next) && !units {
+			// Thousands separator, skipped
+		} else if (chr == '.' || chr == '．') && is_digit(prev) && is_digit(next) && !units {
+			let digits: String = text[end..].chars().take_while(|&x| is_digit(x)).collect();
+			len = end + digits.len();
+			decimals = Some(digits.chars().map(|x| (b'0' + digit_value(x).unwrap() as u8) as char).collect());
+			break;
+		} else {
+			break;
+		}
+		len = end;
+		prev = chr;
+	}
+
+	if len == 0 {
+		return None;
+	}
+	Some(Number {
+		value: total.checked_add(section)?.checked_add(current.unwrap_or(0))?,
+		decimals: decimals,
+		len: len,
+	})
+}
+
+/// Parses an integer at the start of the text, returning the value and its
+/// length in bytes.
+fn parse_integer(text: &str) -> Option<(u64, usize)> {
+	match parse_number(text)? {
+		Number { decimals: None, value, len } => Some((value, len)),
+		_ => None,
+	}
+}
+
+/// Parses a plain number, with no counter.
+fn parse_plain(text: &str) -> Option<(Numeric, usize)> {
+	let number = parse_number(text)?;
+	let mut reading = number_reading(number.value);
+	let value = match number.decimals {
+		Some(decimals) => {
+			reading.push_str("てん");
+			for chr in decimals.chars() {
+				let digit = chr as usize - '0' as usize;
+				reading.push_str(if digit == 0 { "ぜろ" } else { DIGITS[digit] });
+			}
+			NumericValue::Decimal(format!("{}.{}", number.value, decimals))
+		}
+		None => NumericValue::Integer(number.value),
+	};
+	let numeric = Numeric {
+		value: value,
+		reading: reading,
+	};
+	Some((numeric, number.len))
+}
+
+/// Parses a number followed by one of the [COUNTERS].
+fn parse_count(text: &str) -> Option<(Numeric, usize)> {
+	let (value, len) = parse_integer(text)?;
+	let rest = &text[len..];
+	let &(counter, counter_reading, sound) = COUNTERS
+		.iter()
+		.filter(|x| rest.starts_with(x.0))
+		.max_by_key(|x| x.0.len())?;
+	let numeric = Numeric {
+		value: NumericValue::Count(value, counter),
+		reading: count_reading(value, counter, counter_reading, sound),
+	};
+	Some((numeric, len + counter.len()))
+}
+
+/// Parses a number followed by the given unit (e.g. `月`) and within the
+/// range, returning the value, its reading and the length in bytes.
+fn parse_unit(text: &str, unit: &str, min: u64, max: u64, reading: &str) -> Option<(u64, String, usize)> {
+	let (value, len) = parse_integer(text)?;
+	if !text[len..].starts_with(unit) || value < min || value > max {
+		return None;
+	}
+	let sound = if unit == "分" { Sound::Fun } else { Sound::Plain };
+	Some((value, count_reading(value, unit, reading, sound), len + unit.len()))
+}
+
+/// Parses a date with an optional year, month and day.
+fn parse_date(text: &str) -> Option<(Numeric, usize)> {
+	let mut reading = String::new();
+	let mut len = 0;
+
+	// Year in a Japanese era
+	let mut year = None;
+	for &(era, era_reading, start) in ERAS.iter() {
+		if !text.starts_with(era) {
+			continue;
+		}
+		let rest = &text[era.len()..];
+		let (value, value_reading, value_len) = if rest.starts_with("元年") {
+			(1, String::from("がんねん"), "元年".len())
+		} else {
+			parse_unit(rest, "年", 1, 99, "ねん")?
+		};
+		year = Some(start + value - 1);
+		reading.push_str(era_reading);
+		reading.push_str(&value_reading);
+		len = era.len() + value_len;
+		break;
+	}
+
+	// Year in the western calendar, only with a month
+	if year.is_none() {
+		if let Some((value, value_reading, value_len)) = parse_unit(text, "年", 1, 9999, "ねん") {
+			if parse_unit(&text[value_len..], "月", 1, 12, "がつ").is_some() {
+				year = Some(value);
+				reading.push_str(&value_reading);
+				len = value_len;
+			}
+		}
+	}
+
+	let mut month = None;
+	if let Some((value, value_reading, value_len)) = parse_unit(&text[len..], "月", 1, 12, "がつ") {
+		month = Some(value as u32);
+		reading.push_str(&value_reading);
+		len += value_len;
+	}
+
+	let mut day = None;
+	if month.is_some() {
+		if let Some((value, value_reading, value_len)) = parse_unit(&text[len..], "日", 1, 31, "にち") {
+			day = Some(value as u32);
+			reading.push_str(if value == 1 { "ついたち" } else { &value_reading });
+			len += value_len;
+		}
+	}
+
+	if year.is_none() && month.is_none() {
+		return None;
+	}
+	let numeric = Numeric {
+		value: NumericValue::Date {
+			year: year,
+			month: month,
+			day: day,
+		},
+		reading: reading,
+	};
+	Some((numeric, len))
+}
+
+/// Parses a time with an hour and optional minutes and seconds.
+fn parse_time(text: &str) -> Option<(Numeric, usize)> {
+	let (hour, mut reading, mut len) = parse_unit(text, "時", 0, 24, "じ")?;
+	if text[len..].starts_with("間") {
+		// A duration in hours, see the counters
+		return None;
+	}
+
+	let mut minute = 0;
+	let mut second = None;
+	if text[len..].starts_with("半") {
+		minute = 30;
+		reading.push_str("はん");
+		len += "半".len();
+	} else if let Some((value, value_reading, value_len)) = parse_unit(&text[len..], "分", 0, 59, "ふん") {
+		minute = value;
+		reading.push_str(&value_reading);
+		len += value_len;
+		if let Some((value, value_reading, value_len)) = parse_unit(&text[len..], "秒", 0, 59, "びょう") {
+			second = Some(value as u32);
+			reading.push_str(&value_reading);
+			len += value_len;
+		}
+	}
+
+	let numeric = Numeric {
+		value: NumericValue::Time {
+			hour: hour as u32,
+			minute: minute as u32,
+			second: second,
+		},
+		reading: reading,
+	};
+	Some((numeric, len))
+}
+
+fn is_digit(chr: char) -> bool {
+	match chr {
+		'0'..='9' | '０'..='９' => true,
+		_ => false,
+	}
+}
+
+fn digit_value(chr: char) -> Option<u64> {
+	let value = match chr {
+		'0'..='9' => chr as u64 - '0' as u64,
+		'０'..='９' => chr as u64 - '０' as u64,
+		'〇' | '零' => 0,
+		'一' => 1,
+		'二' => 2,
+		'三' => 3,
+		'四' => 4,
+		'五' => 5,
+		'六' => 6,
+		'七' => 7,
+		'八' => 8,
+		'九' => 9,
+		_ => return None,
+	};
+	Some(value)
+}
+
+fn unit_value(chr: char) -> Option<u64> {
+	match chr {
+		'十' => Some(10),
+		'百' => Some(100),
+		'千' => Some(1000),
+		_ => None,
+	}
+}
+
+fn big_unit_value(chr: char) -> Option<u64> {
+	match chr {
+		'万' => Some(10_000),
+		'億' => Some(100_000_000),
+		'兆' => Some(1_000_000_000_000),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn check(text: &str) -> (String, String, usize) {
+		let (numeric, len) = parse(text).unwrap();
+		(numeric.value.to_string(), numeric.reading, text[..len].chars().count())
+	}
+
+	#[test]
+	fn parses_numbers() {
+		assert_eq!(check("2,000"), ("2000".into(), "にせん".into(), 5));
+		assert_eq!(check("３．５"), ("3.5".into(), "さんてんご".into(), 3));
+		assert_eq!(check("二千二十四"), ("2024".into(), "にせんにじゅうよん".into(), 5));
+		assert_eq!(check("二〇二四"), ("2024".into(), "にせんにじゅうよん".into(), 4));
+		assert_eq!(check("3万5000"), ("35000".into(), "さんまんごせん".into(), 6));
+		assert_eq!(check("一千万"), ("10000000".into(), "いっせんまん".into(), 3));
+		assert_eq!(check("三百六"), ("306".into(), "さんびゃくろく".into(), 3));
+		assert!(parse("万一").is_none());
+		assert!(parse("猫").is_none());
+	}
+
+	#[test]
+	fn parses_counts() {
+		assert_eq!(check("三本"), ("3本".into(), "さんぼん".into(), 2));
+		assert_eq!(check("1本"), ("1本".into(), "いっぽん".into(), 2));
+		assert_eq!(check("四本"), ("4本".into(), "よんほん".into(), 2));
+		assert_eq!(check("十本"), ("10本".into(), "じゅっぽん".into(), 2));
+		assert_eq!(check("六個"), ("6個".into(), "ろっこ".into(), 2));
+		assert_eq!(check("三階"), ("3階".into(), "さんがい".into(), 2));
+		assert_eq!(check("八冊"), ("8冊".into(), "はっさつ".into(), 2));
+		assert_eq!(check("一人"), ("1人".into(), "ひとり".into(), 2));
+		assert_eq!(check("四人"), ("4人".into(), "よにん".into(), 2));
+		assert_eq!(check("2,000円"), ("2000円".into(), "にせんえん".into(), 6));
+		assert_eq!(check("二十日"), ("20日".into(), "はつか".into(), 3));
+		assert_eq!(check("九時間"), ("9時間".into(), "くじかん".into(), 3));
+	}
+
+	#[test]
+	fn parses_dates() {
+		assert_eq!(check("令和六年"), ("2024".into(), "れいわろくねん".into(), 4));
+		assert_eq!(check("平成元年"), ("1989".into(), "へいせいがんねん".into(), 4));
+		assert_eq!(
+			check("2024年4月1日"),
+			("2024-04-01".into(), "にせんにじゅうよねんしがつついたち".into(), 9)
+		);
+		assert_eq!(check("九月二十日"), ("--09-20".into(), "くがつはつか".into(), 5));
+		assert_eq!(check("13月"), ("13".into(), "じゅうさん".into(), 2));
+		assert_eq!(check("2024年"), ("2024年".into(), "にせんにじゅうよねん".into(), 5));
+	}
+
+	#[test]
+	fn parses_times() {
+		assert_eq!(check("三時半"), ("03:30".into(), "さんじはん".into(), 3));
+		assert_eq!(check("15時30分"), ("15:30".into(), "じゅうごじさんじゅっぷん".into(), 6));
+		assert_eq!(check("四時三分十秒"), ("04:03:10".into(), "よじさんぷんじゅうびょう".into(), 6));
+		assert_eq!(check("七時"), ("07:00".into(), "しちじ".into(), 2));
+	}
+}
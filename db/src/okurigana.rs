@@ -0,0 +1,106 @@
+//! Okurigana variants for the expressions in the index.
+//!
+//! Compound words are commonly written with part of their okurigana omitted
+//! (e.g. 受け付け as 受付け or 受付). Dictionaries usually list a single
+//! spelling, so the writer indexes the variants generated here as additional
+//! keys for the term. Variants that are already a key for another term are
+//! not indexed, so they never shadow an actual word.
+
+/// Maximum length in characters of an okurigana run that can be omitted.
+/// Longer kana runs between kanji are likely separate words.
+const MAX_OKURIGANA: usize = 2;
+
+/// Returns the spellings of an expression with the okurigana omitted.
+///
+/// The okurigana between kanji is omitted first (受け付け to 受付け). With
+/// `trailing`, the okurigana at the end is also omitted (受付). This should
+/// only be used for terms that don't conjugate, since for verbs and
+/// adjectives the trailing kana is the inflection.
+///
+/// Returns an empty list if the expression has no okurigana between kanji or
+/// has characters other than kanji and hiragana.
+pub fn okurigana_variants(expression: &str, trailing: bool) -> Vec<String> {
+	// Split the expression into alternating runs of kanji and hiragana
+	let mut runs: Vec<(bool, &str)> = Vec::new();
+	let mut start = 0;
+	for (offset, chr) in expression.char_indices() {
+		let kanji = if is_kanji(chr) {
+			true
+		} else if is_hiragana(chr) {
+			false
+		} else {
+			return Vec::new();
+		};
+		match runs.last() {
+			Some(&(last, _)) if last == kanji => {}
+			Some(_) => {
+				runs.last_mut().unwrap().1 = &expression[start..offset];
+				runs.push((kanji, ""));
+				start = offset;
+			}
+			None => runs.push((kanji, "")),
+		}
+	}
+	if let Some(last) = runs.last_mut() {
+		last.1 = &expression[start..];
+	}
+
+	let is_okurigana = |run: &(bool, &str)| !run.0 && run.1.chars().count() <= MAX_OKURIGANA;
+	let interior = if runs.len() > 2 { &runs[1..runs.len() - 1] } else { &[] };
+	if !interior.iter().any(|x| !x.0) || !interior.iter().all(|x| x.0 || is_okurigana(x)) {
+		return Vec::new();
+	}
+
+	let mut out = Vec::new();
+	let omitted: Vec<(usize, &str)> = runs
+		.iter()
+		.enumerate()
+		.filter(|&(i, run)| run.0 || i == 0 || i == runs.len() - 1)
+		.map(|(i, run)| (i, run.1))
+		.collect();
+	out.push(omitted.iter().map(|x| x.1).collect::<String>());
+
+	let last = runs.len() - 1;
+	if trailing && is_okurigana(&runs[last]) {
+		out.push(omitted.iter().filter(|x| x.0 != last).map(|x| x.1).collect());
+	}
+	out
+}
+
+fn is_kanji(chr: char) -> bool {
+	match chr {
+		'\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' | '々' => true,
+		_ => false,
+	}
+}
+
+fn is_hiragana(chr: char) -> bool {
+	match chr {
+		'\u{3041}'..='\u{309F}' => true,
+		_ => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn omits_okurigana() {
+		assert_eq!(okurigana_variants("受け付け", true), vec!["受付け", "受付"]);
+		assert_eq!(okurigana_variants("受け付け", false), vec!["受付け"]);
+		assert_eq!(okurigana_variants("取り扱い", true), vec!["取扱い", "取扱"]);
+		assert_eq!(okurigana_variants("申し込み書", true), vec!["申込書"]);
+		assert_eq!(okurigana_variants("書き込む", false), vec!["書込む"]);
+		assert_eq!(okurigana_variants("お見舞い", true), Vec::<String>::new());
+	}
+
+	#[test]
+	fn ignores_other_expressions() {
+		assert_eq!(okurigana_variants("食べる", false), Vec::<String>::new());
+		assert_eq!(okurigana_variants("学生", true), Vec::<String>::new());
+		assert_eq!(okurigana_variants("たべもの", true), Vec::<String>::new());
+		assert_eq!(okurigana_variants("受け付けテスト", true), Vec::<String>::new());
+		assert_eq!(okurigana_variants("見たことのある人", true), Vec::<String>::new());
+	}
+}
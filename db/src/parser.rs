@@ -0,0 +1,352 @@
+//! Parser for the query language.
+//!
+//! The query language supports:
+//!
+//! - Plain words, matched against any field (e.g. `eat`, `たべる`).
+//! - Quoted phrases matched as a whole (e.g. `"to eat"`).
+//! - Field prefixes: `expression:`, `reading:`, `glossary:`, `tag:`,
+//!   `source:`, `pitch:`, `morae:`, `origin:`, `domain:`, `priority:`,
+//!   `script:`, `length:` and `flag:` (e.g. `reading:たべる tag:vt`,
+//!   `pitch:heiban morae:3 tag:n`, `domain:med`, `priority:common`,
+//!   `script:kana` or `-flag:abbr`). Other words containing a colon (e.g.
+//!   `12:30` or a URL) are plain keywords.
+//! - Boolean operators `AND`, `OR` and `NOT` (or `-` prefix), with `AND`
+//!   being implicit between terms. `AND` has precedence over `OR`.
+//! - Parenthesis for grouping (e.g. `(eat OR drink) NOT tag:vs`).
+
+use std::error::Error;
+use std::fmt;
+
+use super::Field;
+use super::Filter;
+use super::Query;
+
+/// Error parsing a query.
+#[derive(Debug)]
+pub struct ParseError {
+	/// Byte position of the error in the query text.
+	pub position: usize,
+	/// Error description.
+	pub message: String,
+}
+
+impl fmt::Display for ParseError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} (at position {})", self.message, self.position)
+	}
+}
+
+impl Error for ParseError {}
+
+impl Query {
+	/// Parses a query from the query language.
+	pub fn parse<S: AsRef<str>>(input: S) -> Result<Query, ParseError> {
+		Ok(match parse_filter(input.as_ref())? {
+			Some(filter) => Query::new().filter(filter),
+			None => Query::new(),
+		})
+	}
+}
+
+/// Parses the filter for a query, or `None` for an empty query.
+fn parse_filter(input: &str) -> Result<Option<Filter>, ParseError> {
+	let tokens = tokenize(input)?;
+	let mut parser = Parser {
+		tokens: tokens,
+		next: 0,
+		end: input.len(),
+	};
+	if parser.tokens.len() == 0 {
+		return Ok(None);
+	}
+
+	let filter = parser.parse_or()?;
+	if let Some(token) = parser.tokens.get(parser.next) {
+		return Err(ParseError {
+			position: token.position,
+			message: String::from("unexpected token"),
+		});
+	}
+	Ok(Some(filter))
+}
+
+#[derive(PartialEq, Debug)]
+enum TokenKind {
+	Word(String),
+	Phrase(String),
+	Field(Field),
+	And,
+	Or,
+	Not,
+	Open,
+	Close,
+}
+
+struct Token {
+	kind: TokenKind,
+	position: usize,
+}
+
+struct Parser {
+	tokens: Vec<Token>,
+	next: usize,
+	end: usize,
+}
+
+impl Parser {
+	fn parse_or(&mut self) -> Result<Filter, ParseError> {
+		let mut list = vec![self.parse_and()?];
+		while self.accept(TokenKind::Or) {
+			list.push(self.parse_and()?);
+		}
+		Ok(if list.len() == 1 {
+			list.pop().unwrap()
+		} else {
+			Filter::Or(list)
+		})
+	}
+
+	fn parse_and(&mut self) -> Result<Filter, ParseError> {
+		let mut list = vec![self.parse_unary()?];
+		loop {
+			if self.accept(TokenKind::And) {
+				list.push(self.parse_unary()?);
+			} else {
+				match self.peek() {
+					None | Some(&TokenKind::Or) | Some(&TokenKind::Close) => break,
+					_ => list.push(self.parse_unary()?),
+				}
+			}
+		}
+		Ok(if list.len() == 1 {
+			list.pop().unwrap()
+		} else {
+			Filter::And(list)
+		})
+	}
+
+	fn parse_unary(&mut self) -> Result<Filter, ParseError> {
+		if self.accept(TokenKind::Not) {
+			Ok(Filter::Not(Box::new(self.parse_unary()?)))
+		} else {
+			self.parse_primary()
+		}
+	}
+
+	fn parse_primary(&mut self) -> Result<Filter, ParseError> {
+		let position = self.position();
+		if self.accept(TokenKind::Open) {
+			let filter = self.parse_or()?;
+			if !self.accept(TokenKind::Close) {
+				return Err(self.error("expected `)`"));
+			}
+			return Ok(filter);
+		}
+
+		let field = match self.peek() {
+			Some(&TokenKind::Field(field)) => {
+				self.next += 1;
+				field
+			}
+			_ => Field::Any,
+		};
+
+		let keyword = match self.tokens.get(self.next).map(|x| &x.kind) {
+			Some(&TokenKind::Word(ref text)) | Some(&TokenKind::Phrase(ref text)) => text.clone(),
+			_ => {
+				return Err(if field == Field::Any {
+					self.error("expected a keyword")
+				} else {
+					ParseError {
+						position: position,
+						message: String::from("expected a keyword after the field"),
+					}
+				})
+			}
+		};
+		self.next += 1;
+		Ok(Filter::Match(field, keyword))
+	}
+
+	fn peek(&self) -> Option<&TokenKind> {
+		self.tokens.get(self.next).map(|x| &x.kind)
+	}
+
+	fn accept(&mut self, kind: TokenKind) -> bool {
+		if self.peek() == Some(&kind) {
+			self.next += 1;
+			true
+		} else {
+			false
+		}
+	}
+
+	fn position(&self) -> usize {
+		self.tokens.get(self.next).map(|x| x.position).unwrap_or(self.end)
+	}
+
+	fn error(&self, message: &str) -> ParseError {
+		ParseError {
+			position: self.position(),
+			message: message.to_string(),
+		}
+	}
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+	let mut out = Vec::new();
+	let mut chars = input.char_indices().peekable();
+	while let Some(&(position, chr)) = chars.peek() {
+		let push = |out: &mut Vec<Token>, kind: TokenKind| {
+			out.push(Token {
+				kind: kind,
+				position: position,
+			})
+		};
+		match chr {
+			c if c.is_whitespace() => {
+				chars.next();
+			}
+			'(' => {
+				chars.next();
+				push(&mut out, TokenKind::Open);
+			}
+			')' => {
+				chars.next();
+				push(&mut out, TokenKind::Close);
+			}
+			'-' => {
+				chars.next();
+				push(&mut out, TokenKind::Not);
+			}
+			'"' => {
+				chars.next();
+				let mut text = String::new();
+				let mut closed = false;
+				while let Some((_, chr)) = chars.next() {
+					if chr == '"' {
+						closed = true;
+						break;
+					}
+					text.push(chr);
+				}
+				if !closed {
+					return Err(ParseError {
+						position: position,
+						message: String::from("unterminated phrase"),
+					});
+				}
+				push(&mut out, TokenKind::Phrase(text.trim().to_string()));
+			}
+			_ => {
+				// A colon ends the word only after a field name, so keywords
+				// such as `12:30` or URLs are kept whole
+				let mut text = String::new();
+				let mut field = None;
+				while let Some(&(_, chr)) = chars.peek() {
+					if chr.is_whitespace() || chr == '(' || chr == ')' || chr == '"' {
+						break;
+					}
+					chars.next();
+					if chr == ':' && field.is_none() && !text.contains(':') {
+						field = field_name(&text);
+						if field.is_some() {
+							break;
+						}
+					}
+					text.push(chr);
+				}
+
+				// A name followed by a lone colon can only be meant as a field
+				let name = text.trim_end_matches(':');
+				let is_name = name.len() > 0 && name.chars().all(|x| x.is_ascii_alphabetic());
+				let kind = match field {
+					Some(field) => TokenKind::Field(field),
+					None if is_name && text.len() == name.len() + 1 => {
+						return Err(ParseError {
+							position: position,
+							message: format!("unknown field `{}`", text),
+						})
+					}
+					None => match text.as_str() {
+						"AND" => TokenKind::And,
+						"OR" => TokenKind::Or,
+						"NOT" => TokenKind::Not,
+						_ => TokenKind::Word(text),
+					},
+				};
+				push(&mut out, kind);
+			}
+		}
+	}
+	Ok(out)
+}
+
+fn field_name(name: &str) -> Option<Field> {
+	match name.to_lowercase().as_str() {
+		"expression" | "expr" => Some(Field::Expression),
+		"reading" => Some(Field::Reading),
+		"glossary" | "gloss" => Some(Field::Glossary),
+		"tag" => Some(Field::Tag),
+		"source" => Some(Field::Source),
+		"pitch" => Some(Field::Pitch),
+		"morae" | "mora" => Some(Field::Morae),
+		"origin" => Some(Field::Origin),
+		"domain" | "field" => Some(Field::Domain),
+		"priority" | "pri" => Some(Field::Priority),
+		"script" => Some(Field::Script),
+		"length" | "len" => Some(Field::Length),
+		"flag" => Some(Field::Flag),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(input: &str) -> Filter {
+		parse_filter(input).unwrap().unwrap()
+	}
+
+	fn error(input: &str) -> String {
+		parse_filter(input).unwrap_err().to_string()
+	}
+
+	fn word(field: Field, text: &str) -> Filter {
+		Filter::Match(field, text.to_string())
+	}
+
+	#[test]
+	fn fields_and_operators() {
+		assert_eq!(parse("reading:たべる"), word(Field::Reading, "たべる"));
+		assert_eq!(parse("TAG:vt"), word(Field::Tag, "vt"));
+		assert_eq!(
+			parse("eat -tag:vs OR \"to drink\""),
+			Filter::Or(vec![
+				Filter::And(vec![word(Field::Any, "eat"), Filter::Not(Box::new(word(Field::Tag, "vs")))]),
+				word(Field::Any, "to drink"),
+			])
+		);
+		assert_eq!(parse_filter("  ").unwrap(), None);
+	}
+
+	#[test]
+	fn keywords_with_colons() {
+		assert_eq!(parse("12:30"), word(Field::Any, "12:30"));
+		assert_eq!(parse("http://example.com"), word(Field::Any, "http://example.com"));
+		assert_eq!(parse("glossary:a:b"), word(Field::Glossary, "a:b"));
+		assert_eq!(parse("expression:12:30"), word(Field::Expression, "12:30"));
+	}
+
+	#[test]
+	fn errors() {
+		assert_eq!(error("foo: cat"), "unknown field `foo:` (at position 0)");
+		assert_eq!(error("(eat OR drink"), "expected `)` (at position 13)");
+		assert_eq!(error("eat)"), "unexpected token (at position 3)");
+		assert_eq!(error("eat -"), "expected a keyword (at position 5)");
+		assert_eq!(error("reading:"), "expected a keyword after the field (at position 0)");
+		assert_eq!(error("tag: OR x"), "expected a keyword after the field (at position 0)");
+		assert_eq!(error("\"to eat"), "unterminated phrase (at position 0)");
+	}
+}
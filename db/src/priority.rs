@@ -0,0 +1,155 @@
+//! JMdict priority codes for the popularity of a term.
+//!
+//! JMdict marks the common words with the lists they appear in (e.g. `news1`
+//! for the top words in the Mainichi Shimbun corpus, `ichi1` for the Ichimango
+//! goi bunruishuu and `nf01` to `nf48` for the frequency rank in sets of 500
+//! words). Yomichan exports can include those codes as term tags, which the
+//! writer turns into a structured [Priority] for each term.
+
+use std::fmt;
+
+/// Priority codes for a term, from [Term::priority](super::Term::priority).
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Priority(pub u32);
+
+/// Names for the priority list codes, by bit.
+const CODES: [&str; 8] = ["news1", "news2", "ichi1", "ichi2", "spec1", "spec2", "gai1", "gai2"];
+
+/// Codes that make a term common, as defined by JMdict.
+const COMMON: [&str; 5] = ["news1", "ichi1", "spec1", "spec2", "gai1"];
+
+/// Maximum `nfXX` frequency rank.
+const MAX_NF: u32 = 48;
+
+/// Bit offset for the `nfXX` rank.
+const NF_SHIFT: u32 = 8;
+
+impl Priority {
+	/// Returns the priority for a single code (e.g. `news1` or `nf05`), or
+	/// `None` if not a priority code.
+	pub fn from_code(code: &str) -> Option<Priority> {
+		if let Some(bit) = CODES.iter().position(|&x| x == code) {
+			return Some(Priority(1 << bit));
+		}
+		if code.len() == 4 && code.starts_with("nf") {
+			if let Ok(rank) = code[2..].parse::<u32>() {
+				if (1..=MAX_NF).contains(&rank) {
+					return Some(Priority(rank << NF_SHIFT));
+				}
+			}
+		}
+		None
+	}
+
+	/// Returns the priority for a list of codes, ignoring the items that are
+	/// not priority codes (e.g. other tags).
+	pub fn from_codes<'a, I: IntoIterator<Item = &'a str>>(codes: I) -> Priority {
+		let mut out = Priority::default();
+		for code in codes {
+			if let Some(priority) = Priority::from_code(code) {
+				out.insert(priority);
+			}
+		}
+		out
+	}
+
+	/// Returns true if the term has no priority codes.
+	pub fn is_empty(&self) -> bool {
+		self.0 == 0
+	}
+
+	/// Adds the codes from `other`. For the `nfXX` rank the best (lowest)
+	/// rank is kept.
+	pub fn insert(&mut self, other: Priority) {
+		let nf = match (self.nf(), other.nf()) {
+			(Some(a), Some(b)) => a.min(b),
+			(a, b) => a.or(b).unwrap_or(0),
+		};
+		let lists = (self.0 | other.0) & ((1 << NF_SHIFT) - 1);
+		self.0 = lists | (nf << NF_SHIFT);
+	}
+
+	/// Returns true if the term has the given code (e.g. `news1`, `nf05`).
+	pub fn contains(&self, code: &str) -> bool {
+		match Priority::from_code(code) {
+			Some(Priority(bits)) if bits >= 1 << NF_SHIFT => self.nf() == Some(bits >> NF_SHIFT),
+			Some(Priority(bits)) => self.0 & bits != 0,
+			None => false,
+		}
+	}
+
+	/// Returns true if the term is a common word, that is if it has any of
+	/// the `news1`, `ichi1`, `spec1`, `spec2` or `gai1` codes.
+	pub fn is_common(&self) -> bool {
+		COMMON.iter().any(|x| self.contains(x))
+	}
+
+	/// Frequency rank from the `nfXX` code, from 1 (most frequent) to 48.
+	pub fn nf(&self) -> Option<u32> {
+		match self.0 >> NF_SHIFT {
+			0 => None,
+			rank => Some(rank),
+		}
+	}
+
+	/// Returns the codes in the JMdict format (e.g. `news1`, `nf05`).
+	pub fn codes(&self) -> Vec<String> {
+		let mut out: Vec<String> = CODES
+			.iter()
+			.enumerate()
+			.filter(|&(bit, _)| self.0 & (1 << bit) != 0)
+			.map(|(_, code)| code.to_string())
+			.collect();
+		if let Some(nf) = self.nf() {
+			out.push(format!("nf{:02}", nf));
+		}
+		out
+	}
+
+	/// Rank for sorting terms by popularity, higher is more popular.
+	///
+	/// Common words rank first, then by the `nfXX` rank and then by the
+	/// number of lists the word appears in.
+	pub fn rank(&self) -> u32 {
+		let common = if self.is_common() { 1 << 16 } else { 0 };
+		let nf = self.nf().map(|x| MAX_NF + 1 - x).unwrap_or(0);
+		let lists = (self.0 & ((1 << NF_SHIFT) - 1)).count_ones();
+		common | (nf << 8) | lists
+	}
+}
+
+impl fmt::Display for Priority {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.codes().join(", "))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_codes() {
+		let priority = Priority::from_codes(vec!["news1", "P", "nf12", "spec2", "nf05"]);
+		assert_eq!(priority.codes(), vec!["news1", "spec2", "nf05"]);
+		assert!(priority.contains("nf05"));
+		assert!(!priority.contains("nf12"));
+		assert!(priority.is_common());
+		assert_eq!(priority.nf(), Some(5));
+
+		assert_eq!(Priority::from_code("nf49"), None);
+		assert_eq!(Priority::from_code("ichi3"), None);
+		assert!(Priority::from_codes(vec!["news2", "ichi2"]).rank() > 0);
+		assert!(!Priority::from_codes(vec!["news2", "ichi2"]).is_common());
+	}
+
+	#[test]
+	fn ranks_common_words_first() {
+		let common = Priority::from_codes(vec!["ichi1"]);
+		let frequent = Priority::from_codes(vec!["news2", "nf01"]);
+		let rare = Priority::from_codes(vec!["news2", "nf40"]);
+		assert!(common.rank() > frequent.rank());
+		assert!(frequent.rank() > rare.rank());
+		assert!(rare.rank() > Priority::default().rank());
+	}
+}
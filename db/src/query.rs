@@ -0,0 +1,546 @@
+use std::cell::Cell;
+
+use super::normalize_key;
+use super::{count_morae, PitchPattern};
+use super::{Cancel, Cancelled, CANCEL_CHECK_INTERVAL};
+use super::{GroupLimit, TopK};
+use super::ResultSet;
+use super::TagCategory;
+use super::Term;
+use super::TermFlags;
+use super::TermGlossaryConfigs;
+use super::DB;
+
+/// Term field to match a [Filter] keyword against.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Field {
+	/// Matches either the expression, the reading or the glossary.
+	Any,
+	/// Matches the term expression.
+	Expression,
+	/// Matches the term reading or search key.
+	Reading,
+	/// Matches words and phrases in the term glossary.
+	Glossary,
+	/// Matches the name of any of the term tags (including rules).
+	Tag,
+	/// Matches the term source dictionary name.
+	Source,
+	/// Matches the pitch accent pattern of the term reading. The keyword is
+	/// either a pattern name (`heiban`, `atamadaka`, `nakadaka` or `odaka`)
+	/// or the accent position (e.g. `2`).
+	Pitch,
+	/// Matches the number of morae in the term reading (e.g. `3`).
+	Morae,
+	/// Matches the source language code for a loanword (e.g. `por` for
+	/// Portuguese), from the term [origins](Term::origins). The keyword `*`
+	/// matches any loanword with an origin.
+	Origin,
+	/// Matches the name of a [domain](super::TagCategory::Domain) tag (e.g.
+	/// `med`), ignoring case. See [DB::domains] for the available domains.
+	Domain,
+	/// Matches a JMdict priority code from the term [priority](Term::priority)
+	/// (e.g. `news1` or `nf05`). The keyword `common` matches the common words
+	/// and `*` matches any term with priority data.
+	Priority,
+	/// Matches the script of the term expression: `kanji` for expressions
+	/// containing kanji, `kana` for kana-only expressions and `katakana` for
+	/// katakana-only expressions.
+	Script,
+	/// Matches the number of characters in the term expression (e.g. `2`).
+	Length,
+	/// Matches a term flag by its JMdict tag name: `abbr` for abbreviations,
+	/// `company` for company names and `product` for product names (see
+	/// [TermFlags::from_tag](super::TermFlags::from_tag)). Abbreviations can
+	/// be hidden with `NOT flag:abbr`.
+	Flag,
+}
+
+/// Filter expression for a [Query].
+#[derive(Clone, PartialEq, Debug)]
+pub enum Filter {
+	/// Matches a keyword in the given field.
+	///
+	/// Keywords for the expression and reading must match the whole field. A
+	/// keyword ending in `*` matches by prefix instead.
+	///
+	/// For the glossary, the keyword can be a word or a phrase and is matched
+	/// with the [GlossaryConfig](super::GlossaryConfig) for the glossary
+	/// language: by default case insensitively against whole words, ignoring
+	/// stop words. Japanese text has no word boundaries, so for
+	/// [monolingual](Term::is_monolingual) glossaries the keyword is matched
+	/// anywhere in the text.
+	Match(Field, String),
+	/// Matches only if all filters match.
+	And(Vec<Filter>),
+	/// Matches if any of the filters match.
+	Or(Vec<Filter>),
+	/// Matches if the filter does not match.
+	Not(Box<Filter>),
+}
+
+/// Preferred language for term definitions in a [Query].
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Definitions {
+	/// Prefer terms with Japanese definitions (i.e. from J-J dictionaries).
+	Monolingual,
+	/// Prefer terms with English definitions.
+	Bilingual,
+}
+
+/// Query builder for searching terms in the database.
+///
+/// A query can be built programmatically or parsed from the query language
+/// using [Query::parse].
+#[derive(Clone, Default, Debug)]
+pub struct Query {
+	filter: Option<Filter>,
+	limit: Option<usize>,
+	prefer: Option<Definitions>,
+	min_frequency: Option<u32>,
+	max_per_headword: Option<usize>,
+	max_per_source: Option<usize>,
+	max_per_pos: Option<usize>,
+}
+
+impl Query {
+	/// Returns a new empty query, matching all terms.
+	pub fn new() -> Query {
+		Query::default()
+	}
+
+	/// Adds a filter to the query. Multiple filters are combined with `AND`.
+	pub fn filter(mut self, filter: Filter) -> Query {
+		self.filter = Some(match self.filter {
+			None => filter,
+			Some(Filter::And(mut list)) => {
+				list.push(filter);
+				Filter::And(list)
+			}
+			Some(other) => Filter::And(vec![other, filter]),
+		});
+		self
+	}
+
+	/// Limits the number of results returned by the query.
+	pub fn limit(mut self, limit: usize) -> Query {
+		self.limit = Some(limit);
+		self
+	}
+
+	/// Ignores terms with a frequency lower than `frequency`, including the
+	/// terms without frequency data (see [Term::frequency]).
+	///
+	/// Terms are stored by decreasing frequency, so the rare terms are never
+	/// inspected. This makes broad queries that require a full scan (e.g. a
+	/// glossary search) considerably cheaper.
+	pub fn min_frequency(mut self, frequency: u32) -> Query {
+		self.min_frequency = Some(frequency);
+		self
+	}
+
+	/// Returns at most `count` results for each headword (i.e. term
+	/// expression and reading), keeping the most relevant ones.
+	///
+	/// This and the other per-group limits are applied in relevance order,
+	/// before sorting by the [preferred](Query::prefer) definitions. Results
+	/// over a group limit do not count towards the query [limit](Query::limit).
+	pub fn max_results_per_headword(mut self, count: usize) -> Query {
+		self.max_per_headword = Some(count);
+		self
+	}
+
+	/// Returns at most `count` results from each source dictionary, keeping
+	/// the most relevant ones.
+	///
+	/// This diversifies the results for broad queries (e.g. a short kana
+	/// prefix), where the top results could otherwise be all variants from a
+	/// single dictionary.
+	pub fn max_results_per_source(mut self, count: usize) -> Query {
+		self.max_per_source = Some(count);
+		self
+	}
+
+	/// Returns at most `count` results for each part of speech, keeping the
+	/// most relevant ones. Terms are grouped by their first
+	/// [part of speech](TagCategory::PartOfSpeech) tag in display order.
+	/// Terms without a part of speech are not limited.
+	pub fn max_results_per_pos(mut self, count: usize) -> Query {
+		self.max_per_pos = Some(count);
+		self
+	}
+
+	/// Sorts results with the preferred definition language first. Otherwise
+	/// results are ordered by relevance.
+	pub fn prefer(mut self, definitions: Definitions) -> Query {
+		self.prefer = Some(definitions);
+		self
+	}
+
+	/// Matches a keyword in the given field. Shortcut for
+	/// `filter(Filter::Match(field, keyword))`.
+	pub fn with<S: Into<String>>(self, field: Field, keyword: S) -> Query {
+		self.filter(Filter::Match(field, keyword.into()))
+	}
+}
+
+/// Index lookup used to find the candidate terms for a query.
+#[derive(Clone, PartialEq, Debug)]
+pub enum IndexLookup {
+	/// Exact lookup in the prefix index.
+	Exact(String),
+	/// Prefix lookup in the prefix index.
+	Prefix(String),
+}
+
+/// Details on how a query was executed, returned by [DB::explain].
+#[derive(Clone, Default, Debug)]
+pub struct Explain {
+	/// Index lookups used to find the candidate terms. `None` if the query
+	/// required a full scan of the terms.
+	pub lookups: Option<Vec<IndexLookup>>,
+	/// Number of candidate terms returned by the index lookups. For a full
+	/// scan this is the total number of terms.
+	pub candidates: usize,
+	/// Number of candidate terms checked against the query filter. This can
+	/// be less than the number of candidates once the limit is reached or
+	/// with a [minimum frequency](Query::min_frequency).
+	pub inspected: usize,
+	/// Ranking details for each result, in the order they were returned.
+	pub results: Vec<ExplainResult>,
+}
+
+/// Ranking details for a query result in [Explain].
+///
+/// Results are ranked by the term frequency, then by the JMdict
+/// [priority](Term::priority) and then by the dictionary score (this is the
+/// order terms are stored in the database), unless the query has a
+/// [preferred](Query::prefer) definition language.
+#[derive(Clone, Debug)]
+pub struct ExplainResult {
+	/// Term index.
+	pub index: usize,
+	/// Term frequency, if available.
+	pub frequency: Option<u32>,
+	/// Dictionary score for the term.
+	pub score: i32,
+	/// True if the term has the preferred definition language. `None` if the
+	/// query has no preference.
+	pub preferred: Option<bool>,
+}
+
+impl<'a> DB<'a> {
+	/// Runs a query returning the list of matching term indexes, ordered by
+	/// relevance.
+	pub fn query(&self, query: &Query) -> Vec<usize> {
+		self.run_query(query, None, None).unwrap()
+	}
+
+	/// Same as [query](DB::query) but periodically checks the `cancel` token
+	/// while inspecting the candidate terms, returning [Cancelled] once it is
+	/// cancelled.
+	///
+	/// Queries that require a full scan of the terms (e.g. a glossary search)
+	/// can take a while, so this allows interactive frontends to abandon
+	/// stale queries.
+	pub fn query_cancellable(&self, query: &Query, cancel: &Cancel) -> Result<Vec<usize>, Cancelled> {
+		cancel.check()?;
+		self.run_query(query, None, Some(cancel))
+	}
+
+	/// Runs a query returning details about its execution and the ranking
+	/// of each result. This is meant for debugging why a term does or does
+	/// not appear in the results.
+	pub fn explain(&self, query: &Query) -> Explain {
+		let mut explain = Explain::default();
+		let results = self.run_query(query, Some(&mut explain), None).unwrap();
+		explain.results = results
+			.into_iter()
+			.map(|index| {
+				let term = self.term(index).unwrap();
+				ExplainResult {
+					index: index,
+					frequency: term.frequency(),
+					score: term.score(),
+					preferred: query
+						.prefer
+						.map(|x| term.is_monolingual() == (x == Definitions::Monolingual)),
+				}
+			})
+			.collect();
+		explain
+	}
+
+	fn run_query(
+		&self,
+		query: &Query,
+		explain: Option<&mut Explain>,
+		cancel: Option<&Cancel>,
+	) -> Result<Vec<usize>, Cancelled> {
+		let limit = query.limit.unwrap_or(std::usize::MAX);
+		let inspected = Cell::new(0);
+		let cancelled = Cell::new(false);
+		let is_live = || {
+			if let Some(cancel) = cancel {
+				if inspected.get() % CANCEL_CHECK_INTERVAL == 0 && cancel.is_cancelled() {
+					cancelled.set(true);
+				}
+			}
+			!cancelled.get()
+		};
+		let glossary = self.term_glossary_configs();
+		let check = |index: usize| {
+			inspected.set(inspected.get() + 1);
+			match query.filter {
+				Some(ref filter) => self.matches(&self.term(index).unwrap(), filter, &glossary),
+				None => true,
+			}
+		};
+
+		let mut per_headword = query.max_per_headword.map(GroupLimit::new);
+		let mut per_source = query.max_per_source.map(GroupLimit::new);
+		let mut per_pos = query.max_per_pos.map(GroupLimit::new);
+		let mut within_group_limits = |index: usize| {
+			if per_headword.is_none() && per_source.is_none() && per_pos.is_none() {
+				return true;
+			}
+			let headword = (
+				self.get_str(self.terms.expression[index]),
+				self.get_str(self.terms.reading[index]),
+			);
+			let source = self.get_str(self.terms.source[index]);
+			let pos = match per_pos {
+				Some(_) => {
+					let term = self.term(index).unwrap();
+					let tag = term.definition_tags().find(|x| x.kind() == TagCategory::PartOfSpeech);
+					// The tag view only lives as long as the term, so use the
+					// name from the tag table.
+					tag.map(|x| self.get_str(self.tags[x.index].name))
+				}
+				None => None,
+			};
+
+			// A result only counts towards its groups if within all limits
+			let is_full = per_headword.as_ref().map_or(false, |x| x.is_full(&headword))
+				|| per_source.as_ref().map_or(false, |x| x.is_full(&source))
+				|| pos.map_or(false, |pos| per_pos.as_ref().map_or(false, |x| x.is_full(&pos)));
+			if is_full {
+				return false;
+			}
+			if let Some(limit) = per_headword.as_mut() {
+				limit.add(headword);
+			}
+			if let Some(limit) = per_source.as_mut() {
+				limit.add(source);
+			}
+			if let (Some(limit), Some(pos)) = (per_pos.as_mut(), pos) {
+				limit.add(pos);
+			}
+			true
+		};
+
+		// Candidate indexes are sorted, so the terms under the frequency
+		// cutoff can be skipped by stopping at it.
+		let cutoff = self.frequency_cutoff(query.min_frequency.unwrap_or(0));
+		let candidates = query.filter.as_ref().and_then(|x| self.candidates(x));
+		let out: Vec<usize> = {
+			let indexes: Box<dyn Iterator<Item = usize>> = match candidates {
+				Some((ref candidates, _)) => Box::new(candidates.iter().take_while(|&x| x < cutoff)),
+				None => Box::new(0..cutoff),
+			};
+			let matches = indexes
+				.take_while(|_| is_live())
+				.filter(|&x| check(x))
+				.filter(|&x| within_group_limits(x));
+
+			// Term indexes are sorted by relevance, so we can just stop once
+			// we reach the limit, unless we need to rank the results by
+			// preference.
+			match query.prefer {
+				None => matches.take(limit).collect(),
+				Some(prefer) => {
+					let monolingual = prefer == Definitions::Monolingual;
+					let is_other = |index: usize| self.term(index).unwrap().is_monolingual() != monolingual;
+					if limit < std::usize::MAX {
+						let mut top = TopK::new(limit);
+						for index in matches {
+							top.push(is_other(index), index);
+							// Once the selection is full of preferred terms no
+							// later (less relevant) candidate can rank higher.
+							if top.is_full() && top.worst() == Some(&false) {
+								break;
+							}
+						}
+						top.into_sorted_vec()
+					} else {
+						let mut out: Vec<usize> = matches.collect();
+						out.sort_by_key(|&x| is_other(x));
+						out
+					}
+				}
+			}
+		};
+
+		if let Some(explain) = explain {
+			explain.inspected = inspected.get();
+			match candidates {
+				Some((candidates, lookups)) => {
+					explain.candidates = candidates.len();
+					explain.lookups = Some(lookups);
+				}
+				None => {
+					explain.candidates = self.terms.len();
+					explain.lookups = None;
+				}
+			}
+		}
+
+		if cancelled.get() {
+			Err(Cancelled)
+		} else {
+			Ok(out)
+		}
+	}
+
+	/// Returns the number of terms with at least the given frequency. Terms
+	/// are sorted by decreasing frequency, so these are the terms before the
+	/// returned index.
+	fn frequency_cutoff(&self, min_frequency: u32) -> usize {
+		if min_frequency == 0 {
+			return self.terms.len();
+		}
+		self.terms.frequency.partition_point(|&x| {
+			let x: u32 = x.into();
+			x >= min_frequency
+		})
+	}
+
+	/// Uses the indexes to return the set of candidate terms for a filter,
+	/// along with the index lookups used.
+	///
+	/// Returns `None` if the filter requires a full scan of the terms.
+	fn candidates(&self, filter: &Filter) -> Option<(ResultSet, Vec<IndexLookup>)> {
+		match filter {
+			&Filter::Match(Field::Expression, ref keyword) | &Filter::Match(Field::Reading, ref keyword) => {
+				let mut out = ResultSet::default();
+				let lookup = if keyword.ends_with('*') {
+					let prefix = keyword.trim_end_matches('*');
+					self.search_prefix(prefix, &mut out);
+					IndexLookup::Prefix(prefix.to_string())
+				} else {
+					self.search_term(keyword, &mut out);
+					IndexLookup::Exact(keyword.clone())
+				};
+				Some((out, vec![lookup]))
+			}
+			&Filter::And(ref list) => list
+				.iter()
+				.filter_map(|x| self.candidates(x))
+				.min_by_key(|x| x.0.len()),
+			&Filter::Or(ref list) => {
+				let mut out = ResultSet::default();
+				let mut lookups = Vec::new();
+				for it in list.iter() {
+					let (candidates, mut list) = self.candidates(it)?;
+					out.extend(&candidates);
+					lookups.append(&mut list);
+				}
+				Some((out, lookups))
+			}
+			_ => None,
+		}
+	}
+
+	fn matches(&self, term: &Term, filter: &Filter, glossary: &TermGlossaryConfigs) -> bool {
+		match filter {
+			&Filter::Match(field, ref keyword) => match field {
+				Field::Any => {
+					match_key(term.expression(), keyword)
+						|| match_key(term.reading(), keyword)
+						|| match_key(term.search_key(), keyword)
+						|| match_glossary(term, keyword, glossary)
+				}
+				Field::Expression => match_key(term.expression(), keyword),
+				Field::Reading => match_key(term.reading(), keyword) || match_key(term.search_key(), keyword),
+				Field::Glossary => match_glossary(term, keyword, glossary),
+				Field::Tag => term
+					.rules()
+					.chain(term.term_tags())
+					.chain(term.definition_tags())
+					.any(|tag| tag.name() == keyword),
+				Field::Source => term.source().eq_ignore_ascii_case(keyword),
+				Field::Pitch => match_pitch(term, keyword),
+				Field::Morae => keyword.trim().parse::<usize>().ok() == Some(term_morae(term)),
+				Field::Domain => term
+					.term_tags()
+					.chain(term.definition_tags())
+					.any(|tag| tag.kind() == TagCategory::Domain && tag.name().eq_ignore_ascii_case(keyword)),
+				Field::Origin => term
+					.origins()
+					.any(|(language, _)| keyword == "*" || language.eq_ignore_ascii_case(keyword)),
+				Field::Priority => match_priority(term, keyword),
+				Field::Script => match keyword.trim().to_lowercase().as_str() {
+					"kanji" => term.has_kanji(),
+					"kana" => term.is_kana(),
+					"katakana" => term.is_katakana(),
+					_ => false,
+				},
+				Field::Length => keyword.trim().parse::<usize>().ok() == Some(term.length()),
+				Field::Flag => match TermFlags::from_tag(&keyword.trim().to_lowercase()) {
+					Some(flag) => term.flags().contains(flag),
+					None => false,
+				},
+			},
+			&Filter::And(ref list) => list.iter().all(|x| self.matches(term, x, glossary)),
+			&Filter::Or(ref list) => list.iter().any(|x| self.matches(term, x, glossary)),
+			&Filter::Not(ref filter) => !self.matches(term, filter, glossary),
+		}
+	}
+}
+
+/// Matches the priority codes for a term. See [Field::Priority].
+fn match_priority(term: &Term, keyword: &str) -> bool {
+	let priority = term.priority();
+	match keyword.trim().to_lowercase().as_str() {
+		"*" => !priority.is_empty(),
+		"common" => priority.is_common(),
+		code => priority.contains(code),
+	}
+}
+
+/// Matches a key using the same collation as the index lookups.
+fn match_key(key: &str, keyword: &str) -> bool {
+	let key = normalize_key(key);
+	if keyword.ends_with('*') {
+		key.len() > 0 && key.starts_with(&*normalize_key(keyword.trim_end_matches('*')))
+	} else {
+		key == normalize_key(keyword)
+	}
+}
+
+/// Matches any of the term pitch accents by pattern or position.
+fn match_pitch(term: &Term, keyword: &str) -> bool {
+	let morae = term_morae(term);
+	match keyword.parse::<PitchPattern>() {
+		Ok(pattern) => term.pitch().any(|p| PitchPattern::from_position(p, morae) == pattern),
+		Err(_) => {
+			let position = keyword.trim().parse::<u32>().ok();
+			term.pitch().any(|p| Some(p) == position)
+		}
+	}
+}
+
+/// Number of morae in the term reading (or expression for kana terms).
+fn term_morae(term: &Term) -> usize {
+	let reading = if term.reading().len() > 0 {
+		term.reading()
+	} else {
+		term.expression()
+	};
+	count_morae(reading)
+}
+
+fn match_glossary(term: &Term, keyword: &str, glossary: &TermGlossaryConfigs) -> bool {
+	let config = glossary.for_term(term);
+	term.glossary().any(|text| config.matches(text, keyword))
+}
@@ -0,0 +1,147 @@
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// Bounded selection of the best `limit` results out of a sequence of
+/// candidates, without sorting all of them.
+///
+/// Results are ranked by a key (lower is better) and then by the term index,
+/// which is the default relevance order of the database. Selection is
+/// `O(n log k)` for `n` candidates and uses `O(k)` memory.
+///
+/// Repeated term indexes (e.g. a term matching by both expression and
+/// reading) are only selected once.
+pub(crate) struct TopK<K: Ord> {
+	limit: usize,
+	heap: BinaryHeap<(K, usize)>,
+	selected: HashSet<usize>,
+}
+
+impl<K: Ord> TopK<K> {
+	pub fn new(limit: usize) -> TopK<K> {
+		TopK {
+			limit: limit,
+			heap: BinaryHeap::with_capacity(limit.saturating_add(1).min(1024)),
+			selected: HashSet::new(),
+		}
+	}
+
+	/// Adds a candidate, discarding the worst result if over the limit.
+	pub fn push(&mut self, key: K, index: usize) {
+		if self.limit == 0 || self.selected.contains(&index) {
+			return;
+		}
+		if self.heap.len() >= self.limit {
+			match self.heap.peek() {
+				Some(worst) if (&key, index) >= (&worst.0, worst.1) => return,
+				_ => {}
+			}
+			if let Some((_, index)) = self.heap.pop() {
+				self.selected.remove(&index);
+			}
+		}
+		self.selected.insert(index);
+		self.heap.push((key, index));
+	}
+
+	/// Returns true once the selection has `limit` results.
+	pub fn is_full(&self) -> bool {
+		self.heap.len() >= self.limit
+	}
+
+	/// Key for the worst result in the selection.
+	pub fn worst(&self) -> Option<&K> {
+		self.heap.peek().map(|x| &x.0)
+	}
+
+	/// Returns the selected term indexes from best to worst.
+	pub fn into_sorted_vec(self) -> Vec<usize> {
+		self.heap.into_sorted_vec().into_iter().map(|x| x.1).collect()
+	}
+}
+
+/// Returns the `limit` lowest term indexes (i.e. the most relevant terms)
+/// from the sequence.
+pub(crate) fn top_indexes<I: IntoIterator<Item = usize>>(indexes: I, limit: usize) -> Vec<usize> {
+	let mut top = TopK::new(limit);
+	for index in indexes {
+		top.push((), index);
+	}
+	top.into_sorted_vec()
+}
+
+/// Limits the number of results for each group of results (e.g. for each
+/// source dictionary), to diversify the top results.
+pub(crate) struct GroupLimit<K: Hash + Eq> {
+	max: usize,
+	counts: HashMap<K, usize>,
+}
+
+impl<K: Hash + Eq> GroupLimit<K> {
+	pub fn new(max: usize) -> GroupLimit<K> {
+		GroupLimit {
+			max: max,
+			counts: HashMap::new(),
+		}
+	}
+
+	/// Returns true if the group already has the maximum number of results.
+	pub fn is_full(&self, key: &K) -> bool {
+		self.counts.get(key).cloned().unwrap_or(0) >= self.max
+	}
+
+	/// Counts a result for the group.
+	pub fn add(&mut self, key: K) {
+		*self.counts.entry(key).or_insert(0) += 1;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn selects_best_by_key_then_index() {
+		let mut top = TopK::new(3);
+		for &(key, index) in [(2, 10), (1, 7), (2, 3), (0, 9), (1, 2), (2, 1)].iter() {
+			top.push(key, index);
+		}
+		assert_eq!(top.into_sorted_vec(), vec![9, 2, 7]);
+
+		// Ties are resolved by the lower index, in any push order
+		let mut top = TopK::new(2);
+		for &index in [8, 5, 6, 5, 4].iter() {
+			top.push(0, index);
+		}
+		assert_eq!(top.into_sorted_vec(), vec![4, 5]);
+	}
+
+	#[test]
+	fn full_and_worst() {
+		let mut top = TopK::new(2);
+		assert!(!top.is_full());
+		assert_eq!(top.worst(), None);
+		top.push(5, 1);
+		top.push(5, 1);
+		assert!(!top.is_full());
+		top.push(3, 2);
+		assert!(top.is_full());
+		assert_eq!(top.worst(), Some(&5));
+		top.push(1, 3);
+		assert_eq!(top.worst(), Some(&3));
+		top.push(9, 4);
+		assert_eq!(top.into_sorted_vec(), vec![3, 2]);
+	}
+
+	#[test]
+	fn zero_limit() {
+		let mut top = TopK::new(0);
+		assert!(top.is_full());
+		top.push(0, 1);
+		assert_eq!(top.worst(), None);
+		assert!(top.into_sorted_vec().is_empty());
+		assert!(top_indexes(vec![3, 1, 2], 0).is_empty());
+		assert_eq!(top_indexes(vec![3, 1, 2, 1], 2), vec![1, 2]);
+	}
+}
@@ -40,6 +40,28 @@ impl std::convert::Into<usize> for RawUint32 {
 	}
 }
 
+/// Unsigned 64 bit integer in LE (little endian) byte order.
+///
+/// See also `RawUint32`. This is packed so that it can be stored in the
+/// database sections, which are only aligned to 4 bytes.
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct RawUint64(u64);
+
+impl std::convert::From<u64> for RawUint64 {
+	#[inline]
+	fn from(item: u64) -> Self {
+		Self(item.to_le())
+	}
+}
+
+impl std::convert::Into<u64> for RawUint64 {
+	#[inline]
+	fn into(self) -> u64 {
+		u64::from_le(self.0)
+	}
+}
+
 /// Signed 32 bit integer in LE (little endian) byte order.
 ///
 /// See also `RawUint32`
@@ -106,6 +128,66 @@ impl VecHandle {
 	}
 }
 
+/// Magic number at the start of the database header (`JPDB` in ASCII).
+pub const HEADER_MAGIC: u32 = 0x4244_504A;
+
+/// Version of the database binary format. This must be incremented on any
+/// change to the format, along with the reference database for the tests
+/// (`tests/snapshots/mini-dict.db`).
+pub const FORMAT_VERSION: u32 = 19;
+
+/// Raw structure for the database header.
+///
+/// The header is the first section of the database and always contains a
+/// single entry. It has a fixed size, so the database statistics can be read
+/// without loading the whole file (see `DatabaseInfo`).
+#[repr(C, packed)]
+pub struct HeaderRaw {
+	pub magic: RawUint32,
+	pub version: RawUint32,
+	pub profile: RawUint32,
+	/// Build time in seconds since the Unix epoch.
+	pub build_time: RawUint64,
+	pub terms: RawUint32,
+	pub kanji: RawUint32,
+	pub tags: RawUint32,
+	pub radicals: RawUint32,
+	pub sources: RawUint32,
+	pub index_prefix_jp: RawUint32,
+	pub index_suffix_jp: RawUint32,
+	pub index_chars_jp: RawUint32,
+	/// SHA-256 hash for the source dictionaries.
+	pub source_hash: [u8; 32],
+	/// Version of the importer that built the database (interned string).
+	pub importer: RawUint32,
+	/// Number of dimensions for the term embeddings. Zero if the database
+	/// has no embeddings.
+	pub embedding_dim: RawUint32,
+	/// Features built into the database (see `Capabilities`).
+	pub capabilities: RawUint32,
+}
+
+/// Raw structure for a media file (e.g. an image referenced by a structured
+/// content definition).
+///
+/// Media files are sorted by key (see `media_key`) for lookup, with the data
+/// as a range in the media data section.
+#[repr(C, packed)]
+pub struct MediaRaw {
+	pub key: RawUint64,
+	pub source: RawUint32,
+	pub path: RawUint32,
+	pub offset: RawUint32,
+	pub length: RawUint32,
+}
+
+/// Raw structure for a source file used to build the database.
+#[repr(C, packed)]
+pub struct SourceFileRaw {
+	pub name: RawUint32,
+	pub hash: [u8; 32],
+}
+
 /// Raw structure for a serialized Tag.
 #[repr(C, packed)]
 pub struct TagRaw {
@@ -126,30 +208,208 @@ pub struct KanjiRaw {
 	pub kunyomi: VecHandle,
 	pub tags: VecHandle,
 	pub stats: VecHandle,
+	pub similar: VecHandle,
 }
 
-/// Raw structure for a serialized Term.
+/// Raw structure for a localized tag description.
+///
+/// Translations are sorted by tag index and language.
 #[repr(C, packed)]
-pub struct TermRaw {
-	pub expression: RawUint32,
-	pub reading: RawUint32,
-	pub search_key: RawUint32,
-	pub score: RawInt32,
-	pub sequence: RawUint32,
-	pub frequency: RawUint32,
-	pub source: RawUint32,
-	pub glossary: VecHandle,
-	pub rules: VecHandle,
-	pub term_tags: VecHandle,
-	pub definition_tags: VecHandle,
+pub struct TagTranslationRaw {
+	pub tag: RawUint32,
+	pub language: RawUint32,
+	pub notes: RawUint32,
 }
 
+/// Raw structure for the glossary configuration of a language.
+///
+/// Configurations are sorted by language, with the stop words as interned
+/// strings in the vector data.
+#[repr(C, packed)]
+pub struct GlossaryConfigRaw {
+	pub language: RawUint32,
+	pub tokenizer: RawUint32,
+	pub min_length: RawUint32,
+	pub stop_words: VecHandle,
+}
+
+/// Term table, stored as parallel column arrays with one entry per term.
+///
+/// Each column is a separate section of the database, so operations that
+/// only need a few fields (e.g. ranking by frequency) don't have to load the
+/// whole term records.
+///
+/// Fields missing from most terms are not stored as columns. Those are
+/// packed in the vector data starting at the term `payload`, with the term
+/// `fields` bitmap indicating which are present. Present fields are stored
+/// in the order of their bits, with scalars taking one item and vectors two
+/// (a [VecHandle]). Missing fields are zero or empty.
+#[derive(Copy, Clone, Default)]
+pub struct TermTable<'a> {
+	pub expression: &'a [RawUint32],
+	pub reading: &'a [RawUint32],
+	pub frequency: &'a [RawUint32],
+	pub source: &'a [RawUint32],
+	pub flags: &'a [RawUint32],
+	pub glossary: &'a [VecHandle],
+	pub fields: &'a [RawUint32],
+	pub payload: &'a [RawUint32],
+	pub id: &'a [RawUint64],
+	pub vector_data: &'a [RawUint32],
+}
+
+impl<'a> TermTable<'a> {
+	pub const SEARCH_KEY: u32 = 1 << 0;
+	pub const SCORE: u32 = 1 << 1;
+	pub const SEQUENCE: u32 = 1 << 2;
+	pub const ORDER: u32 = 1 << 3;
+	pub const RULES: u32 = 1 << 4;
+	pub const TERM_TAGS: u32 = 1 << 5;
+	pub const DEFINITION_TAGS: u32 = 1 << 6;
+	pub const PITCH: u32 = 1 << 7;
+	pub const RELATED: u32 = 1 << 8;
+	pub const ORIGIN: u32 = 1 << 9;
+	pub const INFO: u32 = 1 << 10;
+	pub const RESTRICTIONS: u32 = 1 << 11;
+	pub const PRIORITY: u32 = 1 << 12;
+	pub const LENGTH: u32 = 1 << 13;
+
+	/// Bits for the optional fields stored as a [VecHandle].
+	pub const VECTOR_FIELDS: u32 = 0b11111111 << 4;
+
+	/// Number of terms.
+	pub fn len(&self) -> usize {
+		self.expression.len()
+	}
+
+	/// Number of items in the payload for the given fields bitmap.
+	pub fn payload_len(fields: u32) -> usize {
+		let vectors = fields & Self::VECTOR_FIELDS;
+		let scalars = fields & !Self::VECTOR_FIELDS;
+		(scalars.count_ones() + 2 * vectors.count_ones()) as usize
+	}
+
+	/// Returns the position of an optional field for a term in the vector
+	/// data, if the field is present.
+	pub fn field_offset(&self, index: usize, field: u32) -> Option<usize> {
+		let fields: u32 = self.fields[index].into();
+		if fields & field == 0 {
+			None
+		} else {
+			let payload: usize = self.payload[index].into();
+			Some(payload + Self::payload_len(fields & (field - 1)))
+		}
+	}
+
+	/// Returns the value for an optional scalar field, or zero if missing.
+	pub fn value(&self, index: usize, field: u32) -> u32 {
+		match self.field_offset(index, field) {
+			Some(offset) => self.vector_data[offset].into(),
+			None => 0,
+		}
+	}
+
+	/// Returns the handle for an optional vector field, or an empty handle
+	/// if missing.
+	pub fn vector(&self, index: usize, field: u32) -> VecHandle {
+		match self.field_offset(index, field) {
+			Some(offset) => VecHandle {
+				offset: self.vector_data[offset],
+				length: self.vector_data[offset + 1],
+			},
+			None => VecHandle {
+				offset: 0u32.into(),
+				length: 0u32.into(),
+			},
+		}
+	}
+}
+
+/// Raw structure for a serialized radical.
+#[repr(C, packed)]
+pub struct RadicalRaw {
+	pub number: RawUint32,
+	pub character: RawUint32,
+	pub strokes: RawUint32,
+	pub name: RawUint32,
+	pub variants: VecHandle,
+}
+
+/// Raw structure for a serialized source dictionary.
+#[repr(C, packed)]
+pub struct SourceRaw {
+	pub name: RawUint32,
+	pub priority: RawInt32,
+}
+
+/// Number of bytes from the sort key stored in each [TermIndex] row.
+pub const KEY_PREFIX_LEN: usize = 8;
+
 /// Serialized row in the term index.
+///
+/// Besides the key, each row stores the first bytes of the key the index is
+/// sorted by (the reversed key for the suffix index), so most comparisons
+/// when searching the index don't need to load the key from the string data.
 #[repr(C, packed)]
 #[derive(Copy, Clone)]
 pub struct TermIndex {
 	pub key: RawUint32,
 	pub term: RawUint32,
+	pub prefix: [u8; KEY_PREFIX_LEN],
+}
+
+impl TermIndex {
+	pub fn new(key: u32, term: u32, sort_key: &str) -> TermIndex {
+		TermIndex {
+			key: key.into(),
+			term: term.into(),
+			prefix: key_prefix(sort_key),
+		}
+	}
+}
+
+/// Returns the first [KEY_PREFIX_LEN] bytes of a sort key, padded with
+/// zeros.
+///
+/// Keys never contain a zero byte, so comparing the prefixes gives the same
+/// order as comparing the keys, unless the prefixes are equal.
+pub fn key_prefix(key: &str) -> [u8; KEY_PREFIX_LEN] {
+	let mut prefix = [0u8; KEY_PREFIX_LEN];
+	let bytes = key.as_bytes();
+	let len = std::cmp::min(bytes.len(), KEY_PREFIX_LEN);
+	prefix[..len].copy_from_slice(&bytes[..len]);
+	prefix
+}
+
+/// Serialized row mapping a normalized index key to an original string that
+/// is indexed with it (see `collation`).
+///
+/// Rows are only stored for keys with an original string that is not the
+/// same as the key, sorted by key and original string.
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct KeyVariant {
+	pub key: RawUint32,
+	pub original: RawUint32,
+}
+
+/// Serialized row in the directory for the prefix index.
+///
+/// Each row maps the first character of the index keys to the `start..end`
+/// range of rows in the prefix index with keys starting with it.
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct IndexShard {
+	pub character: RawUint32,
+	pub start: RawUint32,
+	pub end: RawUint32,
+}
+
+impl IndexShard {
+	#[inline]
+	pub fn range(&self) -> (usize, usize) {
+		(self.start.into(), self.end.into())
+	}
 }
 
 /// Serialized row in the character index.
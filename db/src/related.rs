@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use super::raw::TermTable;
+use super::GlossaryConfig;
+use super::DB;
+
+/// Maximum number of related terms stored for each term.
+pub const RELATED_LIMIT: usize = 10;
+
+/// Kanji and keywords shared by more terms than this are too common to
+/// relate terms and are ignored. This also bounds the cost of computing the
+/// related terms.
+const MAX_POSTINGS: usize = 500;
+
+/// Score for each shared kanji. Shared glossary keywords score one.
+const KANJI_SCORE: u32 = 3;
+
+/// Term data used to compute the related terms at write time.
+pub(crate) struct RelatedInput<'a> {
+	pub expression: &'a str,
+	pub reading: &'a str,
+	pub glossary: Vec<&'a str>,
+	pub frequency: u32,
+	/// Configuration for the glossary language, used to split the keywords.
+	pub config: &'a GlossaryConfig<'a>,
+}
+
+/// Computes the top related terms for each term, by shared kanji, shared
+/// glossary keywords and frequency proximity.
+pub(crate) fn related_terms(terms: &[RelatedInput]) -> Vec<Vec<u32>> {
+	let mut kanji_postings: HashMap<char, Vec<u32>> = HashMap::new();
+	let mut keyword_postings: HashMap<String, Vec<u32>> = HashMap::new();
+	let mut term_kanji = Vec::with_capacity(terms.len());
+	let mut term_keywords = Vec::with_capacity(terms.len());
+	for (index, term) in terms.iter().enumerate() {
+		let index = index as u32;
+
+		let mut kanji: Vec<char> = term.expression.chars().filter(|&x| is_kanji(x)).collect();
+		kanji.sort();
+		kanji.dedup();
+		for &chr in kanji.iter() {
+			kanji_postings.entry(chr).or_insert_with(Vec::new).push(index);
+		}
+		term_kanji.push(kanji);
+
+		let keywords: HashSet<String> = term.glossary.iter().flat_map(|x| term.config.keywords(x)).collect();
+		for word in keywords.iter() {
+			keyword_postings.entry(word.clone()).or_insert_with(Vec::new).push(index);
+		}
+		term_keywords.push(keywords);
+	}
+
+	let mut out = Vec::with_capacity(terms.len());
+	let mut scores: HashMap<u32, u32> = HashMap::new();
+	for (index, term) in terms.iter().enumerate() {
+		scores.clear();
+		for chr in term_kanji[index].iter() {
+			let postings = &kanji_postings[chr];
+			if postings.len() <= MAX_POSTINGS {
+				for &other in postings.iter() {
+					*scores.entry(other).or_insert(0) += KANJI_SCORE;
+				}
+			}
+		}
+		for word in term_keywords[index].iter() {
+			let postings = &keyword_postings[word];
+			if postings.len() <= MAX_POSTINGS {
+				for &other in postings.iter() {
+					*scores.entry(other).or_insert(0) += 1;
+				}
+			}
+		}
+
+		// Rank by score with the frequency proximity as a fraction, so it
+		// only breaks ties between the same number of shared features.
+		let mut ranked: Vec<(u64, u32)> = scores
+			.iter()
+			.filter(|&(&other, _)| {
+				let other = &terms[other as usize];
+				other.expression != term.expression || other.reading != term.reading
+			})
+			.map(|(&other, &score)| {
+				let proximity = frequency_proximity(term.frequency, terms[other as usize].frequency);
+				let rank = (score as u64) * 1000 + (proximity * 999.0) as u64;
+				(rank, other)
+			})
+			.collect();
+		ranked.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+		// Skip other senses for the same headword from different sources
+		let mut related: Vec<u32> = Vec::new();
+		for (_, other) in ranked {
+			let item = &terms[other as usize];
+			let duplicate = related.iter().any(|&x| {
+				let x = &terms[x as usize];
+				x.expression == item.expression && x.reading == item.reading
+			});
+			if !duplicate {
+				related.push(other);
+				if related.len() >= RELATED_LIMIT {
+					break;
+				}
+			}
+		}
+		out.push(related);
+	}
+	out
+}
+
+impl<'a> DB<'a> {
+	/// Returns the terms related to a term (by index), from the most related.
+	///
+	/// Related terms share kanji or glossary keywords (see
+	/// [GlossaryConfig::keywords]) with the term, with
+	/// ties broken by the proximity in frequency. The list is computed when
+	/// the database is written, with at most [RELATED_LIMIT] terms.
+	pub fn related(&self, index: usize) -> Vec<usize> {
+		if index < self.terms.len() {
+			let (sta, end) = self.terms.vector(index, TermTable::RELATED).range();
+			self.vector_data[sta..end].iter().map(|&x| x.into()).collect()
+		} else {
+			Vec::new()
+		}
+	}
+}
+
+/// Returns a value from 0 to 1 for how close two frequencies are, in orders
+/// of magnitude. Terms without a frequency are not close to any other.
+fn frequency_proximity(a: u32, b: u32) -> f64 {
+	if a == 0 || b == 0 {
+		0.0
+	} else {
+		let distance = ((a as f64).ln() - (b as f64).ln()).abs();
+		1.0 / (1.0 + distance)
+	}
+}
+
+fn is_kanji(chr: char) -> bool {
+	match chr {
+		'\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' => true,
+		_ => false,
+	}
+}
@@ -0,0 +1,316 @@
+use super::numeric;
+use super::{Numeric, NumericValue};
+use super::ResultSet;
+use super::DB;
+use super::{Cancel, Cancelled, CANCEL_CHECK_INTERVAL};
+
+/// Maximum length in characters for a term matched by [DB::scan].
+const MAX_SCAN_LENGTH: usize = 16;
+
+/// A token from [DB::scan], either a term match or text not in the
+/// dictionary classified by its [kind](TokenKind).
+pub struct ScanMatch {
+	/// Byte offset for the start of the match in the scanned text.
+	pub start: usize,
+	/// Byte offset for the end of the match in the scanned text.
+	pub end: usize,
+	/// Kind of the token.
+	pub kind: TokenKind,
+	/// Terms matching the text. Empty if not a [TokenKind::Term].
+	pub terms: ResultSet,
+	/// Same as [terms](ScanMatch::terms), ordered from the most likely term
+	/// for the context in the text.
+	///
+	/// For a surface form with several readings (e.g. `辛い` as `からい` or
+	/// `つらい`), the terms are ranked by how well their part of speech fits
+	/// the adjacent tokens and then by frequency.
+	pub ranked: Vec<usize>,
+	/// Numeric expression for the text, with its normalized value and
+	/// reading. This is set for the [Number](TokenKind::Number),
+	/// [Date](TokenKind::Date), [Time](TokenKind::Time) and
+	/// [Counter](TokenKind::Counter) tokens, and for a term with the same
+	/// text as a numeric expression (e.g. `三日`).
+	pub numeric: Option<Numeric>,
+}
+
+/// Kind of a token from [DB::scan].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TokenKind {
+	/// Text matching terms in the dictionary.
+	Term,
+	/// Katakana not in the dictionary, usually a loanword or a name.
+	Katakana,
+	/// Latin letters, e.g. an English word or abbreviation.
+	Latin,
+	/// Number in ASCII or full-width digits or kanji numerals, including the
+	/// separators between digits (e.g. `2,000`, `3.5` or `二千`).
+	Number,
+	/// Date with a year, month and day (e.g. `令和六年` or `3月5日`).
+	Date,
+	/// Time of day (e.g. `三時半`).
+	Time,
+	/// Number with a counter (e.g. `三本` or `2,000円`).
+	Counter,
+	/// URL starting with `http://`, `https://` or `www.`.
+	Url,
+}
+
+impl TokenKind {
+	/// Name for the kind, e.g. for JSON output.
+	pub fn name(&self) -> &'static str {
+		match self {
+			TokenKind::Term => "term",
+			TokenKind::Katakana => "katakana",
+			TokenKind::Latin => "latin",
+			TokenKind::Number => "number",
+			TokenKind::Date => "date",
+			TokenKind::Time => "time",
+			TokenKind::Counter => "counter",
+			TokenKind::Url => "url",
+		}
+	}
+
+	/// Returns the kind for a token starting at the given text, if it can be
+	/// classified.
+	fn classify(text: &str) -> Option<TokenKind> {
+		let chr = text.chars().next()?;
+		if is_katakana(chr) {
+			Some(TokenKind::Katakana)
+		} else if is_latin(chr) {
+			Some(TokenKind::Latin)
+		} else {
+			None
+		}
+	}
+
+	/// Returns true if a token of this kind continues at the given text.
+	fn continues(&self, text: &str) -> bool {
+		let mut chars = text.chars();
+		let chr = chars.next().unwrap();
+		let next = chars.next().unwrap_or(' ');
+		match self {
+			TokenKind::Katakana => is_katakana(chr) || (chr == '・' && is_katakana(next)),
+			TokenKind::Latin => is_latin(chr) || is_digit(chr) || ((chr == '\'' || chr == '-') && is_latin(next)),
+			_ => false,
+		}
+	}
+}
+
+/// Scan results for a single glossary entry from [DB::scan_glossary].
+pub struct GlossaryScan<'a> {
+	/// Glossary text.
+	pub text: &'a str,
+	/// Term matches in the glossary text.
+	pub matches: Vec<ScanMatch>,
+}
+
+impl ScanMatch {
+	/// Returns the matched text from the original scanned `text`.
+	pub fn text<'a>(&self, text: &'a str) -> &'a str {
+		&text[self.start..self.end]
+	}
+}
+
+impl<'a> DB<'a> {
+	/// Scans a text for terms in the database.
+	///
+	/// At each position of the text, this looks for the longest exact term
+	/// match and then skips past the matched text. Positions with no matches
+	/// are skipped one character at a time.
+	///
+	/// Text not in the dictionary is returned as tokens of the other kinds in
+	/// [TokenKind] (e.g. katakana loanwords, latin words, numbers and URLs),
+	/// so that clients can display it without their own heuristics. Other
+	/// text without matches is not returned.
+	///
+	/// Returns the list of tokens in the order they appear in the text. The
+	/// terms for each match are also ranked by their context in the text
+	/// (see [ScanMatch::ranked]).
+	pub fn scan<S: AsRef<str>>(&self, text: S) -> Vec<ScanMatch> {
+		self.do_scan(text.as_ref(), None).unwrap()
+	}
+
+	/// Same as [scan](DB::scan) but periodically checks the `cancel` token,
+	/// returning [Cancelled] once it is cancelled. Useful for long texts.
+	pub fn scan_cancellable<S: AsRef<str>>(&self, text: S, cancel: &Cancel) -> Result<Vec<ScanMatch>, Cancelled> {
+		self.do_scan(text.as_ref(), Some(cancel))
+	}
+
+	fn do_scan(&self, text: &str, cancel: Option<&Cancel>) -> Result<Vec<ScanMatch>, Cancelled> {
+		let mut out = Vec::new();
+		let mut start = 0;
+		let mut steps = 0;
+
+		// Pending token with the kind and start for the text not in the
+		// dictionary, pushed once the token ends.
+		let mut unknown: Option<(TokenKind, usize)> = None;
+		let push_unknown = |out: &mut Vec<ScanMatch>, unknown: &mut Option<(TokenKind, usize)>, end: usize| {
+			if let Some((kind, start)) = unknown.take() {
+				out.push(ScanMatch {
+					start: start,
+					end: end,
+					kind: kind,
+					terms: ResultSet::default(),
+					ranked: Vec::new(),
+					numeric: None,
+				});
+			}
+		};
+
+		while start < text.len() {
+			if let Some(cancel) = cancel {
+				if steps % CANCEL_CHECK_INTERVAL == 0 {
+					cancel.check()?;
+				}
+			}
+			steps += 1;
+
+			if let Some(end) = url_end(&text[start..]) {
+				push_unknown(&mut out, &mut unknown, start);
+				out.push(ScanMatch {
+					start: start,
+					end: start + end,
+					kind: TokenKind::Url,
+					terms: ResultSet::default(),
+					ranked: Vec::new(),
+					numeric: None,
+				});
+				start += end;
+				continue;
+			}
+
+			let ends: Vec<usize> = text[start..]
+				.char_indices()
+				.skip(1)
+				.map(|(offset, _)| start + offset)
+				.chain(std::iter::once(text.len()))
+				.take(MAX_SCAN_LENGTH)
+				.collect();
+
+			// Numbers continuing a latin token are part of it (e.g. `A4`)
+			let numeric = match unknown {
+				Some((TokenKind::Latin, _)) if TokenKind::Latin.continues(&text[start..]) => None,
+				_ => numeric::parse(&text[start..]).map(|(numeric, len)| (numeric, start + len)),
+			};
+
+			let mut found = false;
+			for &end in ends.iter().rev() {
+				let mut terms = ResultSet::default();
+				if numeric.as_ref().map(|x| x.1 > end) == Some(true) {
+					break;
+				}
+				if self.search_term(&text[start..end], &mut terms) > 0 {
+					push_unknown(&mut out, &mut unknown, start);
+					out.push(ScanMatch {
+						start: start,
+						end: end,
+						kind: TokenKind::Term,
+						terms: terms,
+						ranked: Vec::new(),
+						numeric: numeric.as_ref().filter(|x| x.1 == end).map(|x| x.0.clone()),
+					});
+					start = end;
+					found = true;
+					break;
+				}
+			}
+
+			// Numeric expressions longer than any term match
+			if let (false, Some((numeric, end))) = (found, numeric) {
+				push_unknown(&mut out, &mut unknown, start);
+				let kind = match numeric.value {
+					NumericValue::Integer(_) | NumericValue::Decimal(_) => TokenKind::Number,
+					NumericValue::Count(..) => TokenKind::Counter,
+					NumericValue::Date { .. } => TokenKind::Date,
+					NumericValue::Time { .. } => TokenKind::Time,
+				};
+				out.push(ScanMatch {
+					start: start,
+					end: end,
+					kind: kind,
+					terms: ResultSet::default(),
+					ranked: Vec::new(),
+					numeric: Some(numeric),
+				});
+				start = end;
+				continue;
+			}
+
+			if !found {
+				let rest = &text[start..];
+				let kind = match unknown {
+					Some((kind, _)) if kind.continues(rest) => Some(kind),
+					_ => TokenKind::classify(rest),
+				};
+				if kind != unknown.map(|x| x.0) {
+					push_unknown(&mut out, &mut unknown, start);
+					unknown = kind.map(|kind| (kind, start));
+				}
+				start = ends[0];
+			}
+		}
+		push_unknown(&mut out, &mut unknown, text.len());
+		self.rank_scan_terms(&mut out);
+		Ok(out)
+	}
+
+	/// Scans the glossary of a term for links to other terms.
+	///
+	/// This is meant for monolingual (J-J) definitions, allowing clients to
+	/// look up words within a definition. Returns one [GlossaryScan] for each
+	/// glossary entry of the term, or `None` if the term does not exist.
+	pub fn scan_glossary(&self, index: usize) -> Option<Vec<GlossaryScan<'a>>> {
+		let (sta, end) = self.terms.glossary.get(index)?.range();
+		let out = self.vector_data[sta..end]
+			.iter()
+			.map(|&x| {
+				let text = self.get_str(x);
+				GlossaryScan {
+					text: text,
+					matches: self.scan(text),
+				}
+			})
+			.collect();
+		Some(out)
+	}
+}
+
+/// Returns the length of the URL at the start of the text, if any.
+///
+/// The URL extends up to the first whitespace or non-ASCII character, with
+/// any trailing punctuation excluded.
+fn url_end(text: &str) -> Option<usize> {
+	let bytes = text.as_bytes();
+	let starts_with = |prefix: &str| match bytes.get(..prefix.len()) {
+		Some(head) => head.eq_ignore_ascii_case(prefix.as_bytes()),
+		None => false,
+	};
+	if !(starts_with("http://") || starts_with("https://") || starts_with("www.")) {
+		return None;
+	}
+	let end = text.find(|x: char| !x.is_ascii_graphic()).unwrap_or(text.len());
+	let url = text[..end].trim_end_matches(|x| ".,;:!?)]}'\"".contains(x));
+	Some(url.len())
+}
+
+fn is_katakana(chr: char) -> bool {
+	match chr {
+		'\u{30A1}'..='\u{30FA}' | '\u{30FC}'..='\u{30FE}' | '\u{FF66}'..='\u{FF9F}' => true,
+		_ => false,
+	}
+}
+
+fn is_latin(chr: char) -> bool {
+	match chr {
+		'A'..='Z' | 'a'..='z' | 'Ａ'..='Ｚ' | 'ａ'..='ｚ' => true,
+		'\u{00C0}'..='\u{024F}' => chr.is_alphabetic(),
+		_ => false,
+	}
+}
+
+fn is_digit(chr: char) -> bool {
+	match chr {
+		'0'..='9' | '０'..='９' => true,
+		_ => false,
+	}
+}
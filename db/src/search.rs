@@ -1,6 +1,11 @@
+use std::cmp::Ordering;
 use std::collections::BTreeSet;
 
+use super::key_prefix;
+use super::normalize_key;
+use super::top_indexes;
 use super::TermIndex;
+use super::KEY_PREFIX_LEN;
 use super::DB;
 
 /// Store the search results for a DB.
@@ -14,6 +19,15 @@ impl ResultSet {
 		self.indexes.len()
 	}
 
+	/// Adds all the indexes from `other` to this result set.
+	pub fn extend(&mut self, other: &ResultSet) {
+		self.indexes.extend(other.indexes.iter().cloned());
+	}
+
+	pub(crate) fn extend_indexes<I: IntoIterator<Item = usize>>(&mut self, indexes: I) {
+		self.indexes.extend(indexes);
+	}
+
 	pub fn iter<'a>(&'a self) -> ResultSetIter<'a> {
 		ResultSetIter {
 			iter: self.indexes.iter(),
@@ -41,39 +55,121 @@ impl<'a> std::iter::Iterator for ResultSetIter<'a> {
 	}
 }
 
+/// Term indexes for the index rows matching a search, returned by
+/// [DB::search_term_iter], [DB::search_prefix_iter] and
+/// [DB::search_suffix_iter].
+///
+/// Indexes are in the order of the index keys and a term can appear more
+/// than once (e.g. matching by both expression and reading). Iterating does
+/// not allocate.
+pub struct IndexMatches<'b> {
+	rows: std::slice::Iter<'b, TermIndex>,
+}
+
+impl<'b> IndexMatches<'b> {
+	pub(crate) fn new(rows: &'b [TermIndex]) -> IndexMatches<'b> {
+		IndexMatches { rows: rows.iter() }
+	}
+}
+
+impl<'b> Iterator for IndexMatches<'b> {
+	type Item = usize;
+
+	fn next(&mut self) -> Option<usize> {
+		self.rows.next().map(|x| x.term.into())
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.rows.size_hint()
+	}
+}
+
+impl<'b> ExactSizeIterator for IndexMatches<'b> {}
+
 impl<'a> DB<'a> {
 	/// Search for an exact term in the database inserting the found term
 	/// indexes into the `out` result set.
 	///
 	/// Returns the number of matches.
+	///
+	/// The term is normalized using [normalize_key] before searching.
 	pub fn search_term<S: AsRef<str>>(&self, term: S, out: &mut ResultSet) -> usize {
-		self.do_search_index(term, true, self.index_prefix_jp, out)
+		let start_count = out.len();
+		out.extend_indexes(self.search_term_iter(term));
+		out.len() - start_count
 	}
 
 	/// Search for term in the database by the given prefix inserting the found
 	/// term indexes into the `out` result set.
 	///
 	/// Returns the number of matches.
+	///
+	/// The prefix is normalized using [normalize_key] before searching.
 	pub fn search_prefix<S: AsRef<str>>(&self, prefix: S, out: &mut ResultSet) -> usize {
-		self.do_search_index(prefix, false, self.index_prefix_jp, out)
+		let start_count = out.len();
+		out.extend_indexes(self.search_prefix_iter(prefix));
+		out.len() - start_count
 	}
 
-	fn do_search_index<S: AsRef<str>>(
-		&self,
-		keyword: S,
-		full_match: bool,
-		index: &[TermIndex],
-		out: &mut ResultSet,
-	) -> usize {
-		if let Some((sta, end)) = self.do_search_index_range(keyword, full_match, index) {
-			let start_count = out.len();
-			for index in sta..=end {
-				let index: usize = self.index_prefix_jp[index].term.into();
-				out.indexes.insert(index);
+	/// Same as [search_term](DB::search_term) but returns the matches from
+	/// the index directly, without collecting them into a [ResultSet].
+	///
+	/// This does not allocate, unless the term needs to be normalized (see
+	/// [normalize_key]).
+	pub fn search_term_iter<S: AsRef<str>>(&self, term: S) -> IndexMatches<'a> {
+		let term = normalize_key(term.as_ref());
+		let index = self.prefix_shard(&term);
+		match self.do_search_index_range(term, true, index) {
+			Some((sta, end)) => IndexMatches::new(&index[sta..=end]),
+			None => IndexMatches::new(&[]),
+		}
+	}
+
+	/// Same as [search_prefix](DB::search_prefix) but returns the matches
+	/// from the index directly, without collecting them into a [ResultSet].
+	///
+	/// This does not allocate, unless the prefix needs to be normalized (see
+	/// [normalize_key]).
+	pub fn search_prefix_iter<S: AsRef<str>>(&self, prefix: S) -> IndexMatches<'a> {
+		let prefix = normalize_key(prefix.as_ref());
+		let index = self.prefix_shard(&prefix);
+		match self.do_search_index_range(prefix, false, index) {
+			Some((sta, end)) => IndexMatches::new(&index[sta..=end]),
+			None => IndexMatches::new(&[]),
+		}
+	}
+
+	/// Search for terms by prefix returning at most `limit` term indexes,
+	/// from the most relevant.
+	///
+	/// Unlike [search_prefix](DB::search_prefix) this does not collect all
+	/// the matches, so the cost for broad prefixes (e.g. a single kana that
+	/// matches a large part of the database) is bounded by the limit instead
+	/// of the number of matches.
+	pub fn search_prefix_top<S: AsRef<str>>(&self, prefix: S, limit: usize) -> Vec<usize> {
+		top_indexes(self.search_prefix_iter(prefix), limit)
+	}
+
+	/// Returns the range of rows in the prefix index that can contain the
+	/// given keyword, based on its first character.
+	///
+	/// Each shard is a small contiguous slice of the index, so searches only
+	/// touch the pages of the shard instead of the entire index.
+	fn prefix_shard(&self, keyword: &str) -> &'a [TermIndex] {
+		let chr = match keyword.chars().next() {
+			Some(chr) => chr as u32,
+			None => return &[],
+		};
+		let shard = self.index_prefix_dir.binary_search_by(|it| {
+			let other: u32 = it.character.into();
+			other.cmp(&chr)
+		});
+		match shard {
+			Ok(pos) => {
+				let (sta, end) = self.index_prefix_dir[pos].range();
+				&self.index_prefix_jp[sta..end]
 			}
-			out.len() - start_count
-		} else {
-			0
+			Err(_) => &[],
 		}
 	}
 
@@ -85,43 +181,22 @@ impl<'a> DB<'a> {
 		full_match: bool,
 		index: &[TermIndex],
 	) -> Option<(usize, usize)> {
-		use std::cmp::Ordering;
-
 		let keyword = keyword.as_ref();
 
 		if keyword.len() > 0 {
-			let cmp: Box<dyn (FnMut(&TermIndex) -> Ordering)> = if full_match {
-				// For `full_match` use a straightforward comparison
-				Box::from(|it: &TermIndex| {
-					let other = self.get_str(it.key);
-					other.cmp(keyword)
-				})
-			} else {
-				// In prefix mode, first compare the prefix
-				Box::from(|it: &TermIndex| {
-					let other = self.get_str(it.key);
-					if other.starts_with(keyword) {
-						std::cmp::Ordering::Equal
-					} else {
-						other.cmp(keyword)
-					}
-				})
-			};
-
+			let cmp = |it: &TermIndex| compare_index_key(it, keyword, full_match, || self.get_str(it.key));
 			if let Ok(pos) = index.binary_search_by(cmp) {
 				let last = index.len() - 1;
 				let mut sta = pos;
 				let mut end = pos;
 
-				// In prefix mode, expand the result range to include all
-				// prefixed results
-				if !full_match {
-					while sta > 0 && self.get_str(index[sta - 1].key).starts_with(keyword) {
-						sta -= 1;
-					}
-					while end < last && self.get_str(index[end + 1].key).starts_with(keyword) {
-						end += 1;
-					}
+				// Expand the result range to include all matching results,
+				// since the binary search can land anywhere in the range.
+				while sta > 0 && cmp(&index[sta - 1]) == Ordering::Equal {
+					sta -= 1;
+				}
+				while end < last && cmp(&index[end + 1]) == Ordering::Equal {
+					end += 1;
 				}
 
 				Some((sta, end))
@@ -133,3 +208,36 @@ impl<'a> DB<'a> {
 		}
 	}
 }
+
+/// Compares the sort key for an index row with `keyword`. If `full_match` is
+/// false, keys starting with `keyword` compare as equal.
+///
+/// This uses the key prefix stored in the row when possible, only calling
+/// `load_key` for the full sort key when the prefix is not enough.
+pub(crate) fn compare_index_key<K: AsRef<str>, F: FnOnce() -> K>(
+	row: &TermIndex,
+	keyword: &str,
+	full_match: bool,
+	load_key: F,
+) -> Ordering {
+	let len = keyword.len();
+	let prefix = row.prefix;
+	if !full_match && len <= KEY_PREFIX_LEN {
+		return prefix[..len].cmp(keyword.as_bytes());
+	}
+
+	match prefix.cmp(&key_prefix(keyword)) {
+		// Keys don't contain zeros, so the key ends with the keyword
+		Ordering::Equal if len < KEY_PREFIX_LEN => Ordering::Equal,
+		Ordering::Equal => {
+			let key = load_key();
+			let key = key.as_ref();
+			if !full_match && key.starts_with(keyword) {
+				Ordering::Equal
+			} else {
+				key.cmp(keyword)
+			}
+		}
+		other => other,
+	}
+}
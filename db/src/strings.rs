@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use super::raw::*;
+use super::DB;
+
+/// Upper bounds (in bytes) for the buckets in [StringStats::histogram]. The
+/// last bucket has every longer string.
+const LENGTH_BUCKETS: [usize; 9] = [0, 4, 8, 16, 32, 64, 128, 256, 1024];
+
+/// Analysis of the string table of a database, returned by
+/// [DB::string_stats].
+///
+/// This is meant as a diagnostic to guide optimizations of the string data
+/// (e.g. deduplication or overlapping strings).
+#[derive(Clone, Debug)]
+pub struct StringStats {
+	/// Number of strings in the table.
+	pub count: usize,
+	/// Sum of the length of all strings, in bytes.
+	pub bytes: usize,
+	/// Size of the string data, in bytes. This is less than [bytes] when
+	/// strings share data.
+	///
+	/// [bytes]: StringStats::bytes
+	pub data_bytes: usize,
+	/// Length histogram, in order of length.
+	pub histogram: Vec<LengthBucket>,
+	/// Groups of distinct strings that differ only in case or whitespace,
+	/// sorted by the bytes that could be saved by merging them.
+	pub near_duplicates: Vec<NearDuplicate>,
+	/// Strings used by the terms of each source dictionary, sorted by bytes.
+	pub sources: Vec<SourceStrings>,
+	/// Strings used by the terms of more than one source.
+	pub shared: SourceStrings,
+	/// Strings not used by any term (e.g. tags and kanji data).
+	pub other: SourceStrings,
+}
+
+/// Bucket for the length histogram in [StringStats].
+#[derive(Clone, Debug)]
+pub struct LengthBucket {
+	/// Maximum length for the bucket, in bytes. `None` for the last bucket.
+	pub max_length: Option<usize>,
+	/// Number of strings in the bucket.
+	pub count: usize,
+	/// Sum of the length of the strings in the bucket.
+	pub bytes: usize,
+}
+
+/// Strings that differ only in case or whitespace, see [StringStats].
+#[derive(Clone, Debug)]
+pub struct NearDuplicate {
+	/// Strings in the group, in table order.
+	pub strings: Vec<String>,
+	/// Bytes that could be saved by keeping only the first string.
+	pub wasted_bytes: usize,
+}
+
+/// Strings attributed to a source dictionary in [StringStats].
+#[derive(Clone, Default, Debug)]
+pub struct SourceStrings {
+	/// Source dictionary name. Empty for the shared and other strings.
+	pub name: String,
+	/// Number of strings.
+	pub count: usize,
+	/// Sum of the length of the strings, in bytes.
+	pub bytes: usize,
+}
+
+impl<'a> DB<'a> {
+	/// Analyzes the string table of the database. See [StringStats].
+	///
+	/// This goes through all strings and terms, so it is slow and should be
+	/// used only for diagnostics.
+	pub fn string_stats(&self) -> StringStats {
+		let length = |index: usize| {
+			let (sta, end) = self.string_list[index].range();
+			end - sta
+		};
+		let count = self.string_list.len();
+
+		let mut histogram: Vec<LengthBucket> = LENGTH_BUCKETS
+			.iter()
+			.map(|&max| Some(max))
+			.chain(Some(None))
+			.map(|max| LengthBucket {
+				max_length: max,
+				count: 0,
+				bytes: 0,
+			})
+			.collect();
+		for index in 0..count {
+			let length = length(index);
+			let bucket = LENGTH_BUCKETS.iter().position(|&max| length <= max);
+			let bucket = &mut histogram[bucket.unwrap_or(LENGTH_BUCKETS.len())];
+			bucket.count += 1;
+			bucket.bytes += length;
+		}
+
+		// Group the strings by a normalized form ignoring case and whitespace
+		let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+		for index in 0..count {
+			let text = self.get_str((index as u32).into());
+			let key = text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+			groups.entry(key).or_insert_with(Vec::new).push(index);
+		}
+		let mut near_duplicates: Vec<NearDuplicate> = groups
+			.into_iter()
+			.filter(|(_, list)| list.len() > 1)
+			.map(|(_, mut list)| {
+				list.sort();
+				NearDuplicate {
+					wasted_bytes: list[1..].iter().map(|&x| length(x)).sum(),
+					strings: list.iter().map(|&x| self.get_str((x as u32).into()).to_string()).collect(),
+				}
+			})
+			.collect();
+		near_duplicates.sort_by(|a, b| b.wasted_bytes.cmp(&a.wasted_bytes).then(a.strings.cmp(&b.strings)));
+
+		// Attribute each string to the source of the terms using it
+		const NONE: u32 = std::u32::MAX;
+		const SHARED: u32 = std::u32::MAX - 1;
+		let mut owner = vec![NONE; count];
+		let terms = &self.terms;
+		for index in 0..terms.len() {
+			let source: u32 = terms.source[index].into();
+			let mut strings: Vec<RawUint32> = vec![terms.expression[index], terms.reading[index]];
+			let vectors = [
+				terms.glossary[index],
+				terms.vector(index, TermTable::ORIGIN),
+				terms.vector(index, TermTable::INFO),
+				terms.vector(index, TermTable::RESTRICTIONS),
+			];
+			for vector in vectors.iter() {
+				let (sta, end) = vector.range();
+				strings.extend_from_slice(&self.vector_data[sta..end]);
+			}
+			for string in strings {
+				let string: usize = string.into();
+				owner[string] = match owner[string] {
+					NONE => source,
+					current if current == source => source,
+					_ => SHARED,
+				};
+			}
+		}
+
+		let mut sources: HashMap<u32, SourceStrings> = HashMap::new();
+		let mut shared = SourceStrings::default();
+		let mut other = SourceStrings::default();
+		for (index, &source) in owner.iter().enumerate() {
+			let entry = match source {
+				NONE => &mut other,
+				SHARED => &mut shared,
+				source => sources.entry(source).or_insert_with(|| SourceStrings {
+					name: self.get_str(source.into()).to_string(),
+					count: 0,
+					bytes: 0,
+				}),
+			};
+			entry.count += 1;
+			entry.bytes += length(index);
+		}
+		let mut sources: Vec<SourceStrings> = sources.into_iter().map(|x| x.1).collect();
+		sources.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.name.cmp(&b.name)));
+
+		StringStats {
+			count: count,
+			bytes: (0..count).map(length).sum(),
+			data_bytes: self.string_data.len(),
+			histogram: histogram,
+			near_duplicates: near_duplicates,
+			sources: sources,
+			shared: shared,
+			other: other,
+		}
+	}
+}
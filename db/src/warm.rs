@@ -0,0 +1,227 @@
+use super::DB;
+
+/// Assumed page size for [DB::warm]. Touching more often than needed is
+/// harmless, so this errs on the small side.
+const PAGE_SIZE: usize = 4096;
+
+/// Sections of the database data that can be loaded with [DB::warm] or
+/// selected with [DB::load_sections].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Section {
+	/// Tag definitions.
+	Tags,
+	/// Term table (all columns).
+	Terms,
+	/// Kanji entries.
+	Kanji,
+	/// Radicals for the radical lookup.
+	Radicals,
+	/// Prefix index used for exact and prefix searches (includes the shard
+	/// directory and the key variants).
+	PrefixIndex,
+	/// Suffix index.
+	SuffixIndex,
+	/// Per-character index.
+	CharsIndex,
+	/// Term embeddings for the semantic search.
+	Embeddings,
+	/// Media files (e.g. images for structured content definitions).
+	Media,
+	/// Vector data used by term and kanji lists (e.g. glossary and tags).
+	Vectors,
+	/// String table and data.
+	Strings,
+}
+
+impl Section {
+	/// All database sections.
+	pub const ALL: &'static [Section] = &[
+		Section::Tags,
+		Section::Terms,
+		Section::Kanji,
+		Section::Radicals,
+		Section::PrefixIndex,
+		Section::SuffixIndex,
+		Section::CharsIndex,
+		Section::Embeddings,
+		Section::Media,
+		Section::Vectors,
+		Section::Strings,
+	];
+
+	/// Other sections the data in this section refers to, which must also be
+	/// loaded for it to be usable (see [DB::load_sections]).
+	pub fn requires(&self) -> &'static [Section] {
+		match self {
+			Section::Tags => &[Section::Strings],
+			Section::Terms => &[Section::Tags, Section::Vectors, Section::Strings],
+			Section::Kanji => &[Section::Tags, Section::Vectors, Section::Strings],
+			Section::Radicals => &[Section::Vectors, Section::Strings],
+			Section::Media => &[Section::Strings],
+			Section::PrefixIndex | Section::SuffixIndex | Section::CharsIndex | Section::Embeddings => &[Section::Terms],
+			Section::Vectors | Section::Strings => &[],
+		}
+	}
+
+	/// Returns the given sections with all the sections they require.
+	pub(crate) fn with_requirements(sections: &[Section]) -> Vec<Section> {
+		let mut out: Vec<Section> = Vec::new();
+		let mut pending: Vec<Section> = sections.to_vec();
+		while let Some(section) = pending.pop() {
+			if !out.contains(&section) {
+				out.push(section);
+				pending.extend_from_slice(section.requires());
+			}
+		}
+		Section::ALL.iter().cloned().filter(|x| out.contains(x)).collect()
+	}
+}
+
+/// Size of a database section, as returned by [DB::section_size].
+#[derive(Copy, Clone, Default, Debug)]
+pub struct SectionSize {
+	/// Number of entries in the section (e.g. terms or index keys). For
+	/// strings this is the number of strings.
+	pub entries: usize,
+	/// Size of the section data in bytes.
+	pub bytes: usize,
+}
+
+impl<'a> DB<'a> {
+	/// Returns the sections loaded from the database data, in the order of
+	/// [Section::ALL]. This is every section unless the database was loaded
+	/// with [DB::load_sections].
+	pub fn loaded_sections(&self) -> Vec<Section> {
+		self.sections.clone()
+	}
+
+	/// Returns true if the section was loaded from the database data.
+	pub fn is_loaded(&self, section: Section) -> bool {
+		self.sections.contains(&section)
+	}
+
+	/// Returns the size of a section in the database data. The size is zero
+	/// for sections that were not [loaded](DB::loaded_sections).
+	pub fn section_size(&self, section: Section) -> SectionSize {
+		match section {
+			Section::Tags => size(self.tags),
+			Section::Terms => {
+				let terms = &self.terms;
+				let columns = [
+					size(terms.expression),
+					size(terms.reading),
+					size(terms.frequency),
+					size(terms.source),
+					size(terms.flags),
+					size(terms.glossary),
+					size(terms.fields),
+					size(terms.payload),
+					size(terms.id),
+					size(self.index_term_id),
+				];
+				SectionSize {
+					entries: terms.len(),
+					bytes: columns.iter().map(|x| x.bytes).sum(),
+				}
+			}
+			Section::Kanji => size(self.kanji),
+			Section::Radicals => size(self.radicals),
+			Section::PrefixIndex => {
+				let dir = size(self.index_prefix_dir);
+				let index = size(self.index_prefix_jp);
+				let variants = size(self.key_variants);
+				SectionSize {
+					entries: index.entries,
+					bytes: index.bytes + dir.bytes + variants.bytes,
+				}
+			}
+			Section::SuffixIndex => size(self.index_suffix_jp),
+			Section::CharsIndex => size(self.index_chars_jp),
+			Section::Embeddings => SectionSize {
+				entries: self.embeddings.len() / std::cmp::max(self.embedding_dim(), 1),
+				bytes: std::mem::size_of_val(self.embeddings),
+			},
+			Section::Media => {
+				let list = size(self.media);
+				SectionSize {
+					entries: list.entries,
+					bytes: list.bytes + self.media_data.len(),
+				}
+			}
+			Section::Vectors => size(self.vector_data),
+			Section::Strings => {
+				let list = size(self.string_list);
+				SectionSize {
+					entries: list.entries,
+					bytes: list.bytes + self.string_data.len(),
+				}
+			}
+		}
+	}
+
+	/// Loads the pages for the given sections of the database data into
+	/// memory by touching them.
+	///
+	/// When the database is embedded in the executable or memory mapped, its
+	/// data is paged in from disk lazily on first access. This can be called
+	/// on startup (e.g. from a background thread) to avoid latency spikes for
+	/// the first queries.
+	pub fn warm(&self, sections: &[Section]) {
+		for &section in sections {
+			match section {
+				Section::Tags => touch(self.tags),
+				Section::Terms => {
+					let terms = &self.terms;
+					touch(terms.expression);
+					touch(terms.reading);
+					touch(terms.frequency);
+					touch(terms.source);
+					touch(terms.flags);
+					touch(terms.glossary);
+					touch(terms.fields);
+					touch(terms.payload);
+					touch(terms.id);
+					touch(self.index_term_id);
+				}
+				Section::Kanji => touch(self.kanji),
+				Section::Radicals => touch(self.radicals),
+				Section::PrefixIndex => {
+					touch(self.index_prefix_jp);
+					touch(self.index_prefix_dir);
+					touch(self.key_variants);
+				}
+				Section::SuffixIndex => touch(self.index_suffix_jp),
+				Section::CharsIndex => touch(self.index_chars_jp),
+				Section::Embeddings => touch(self.embeddings),
+				Section::Media => {
+					touch(self.media);
+					touch(self.media_data);
+				}
+				Section::Vectors => touch(self.vector_data),
+				Section::Strings => {
+					touch(self.string_list);
+					touch(self.string_data.as_bytes());
+				}
+			}
+		}
+	}
+}
+
+fn size<T>(data: &[T]) -> SectionSize {
+	SectionSize {
+		entries: data.len(),
+		bytes: data.len() * std::mem::size_of::<T>(),
+	}
+}
+
+/// Reads one byte from every page of the slice.
+fn touch<T>(data: &[T]) {
+	let size = data.len() * std::mem::size_of::<T>();
+	let data = data.as_ptr() as *const u8;
+	let mut offset = 0;
+	while offset < size {
+		// Volatile so the read is not optimized away
+		unsafe { std::ptr::read_volatile(data.add(offset)) };
+		offset += PAGE_SIZE;
+	}
+}
@@ -1,14 +1,28 @@
 //! Serialization support for the database.
 
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::io;
 use std::io::Result;
-use std::time::Instant;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use unicode_segmentation::UnicodeSegmentation;
 
+use super::media_key;
+use super::middle_dot_parts;
+use super::normalize_key;
+use super::okurigana_variants;
 use super::raw::*;
+use super::related::{related_terms, RelatedInput};
+use super::Capabilities;
+use super::GlossaryConfig;
+use super::Priority;
+use super::TagCategory;
+use super::TermFlags;
+use super::Tokenizer;
+use super::{GLOSSARY_ENGLISH, GLOSSARY_JAPANESE};
 
 /// Writer helper for the database. Provides methods for adding terms, kanji
 /// and tags to the database and a [write](Writer::write) method for outputting
@@ -18,6 +32,10 @@ use super::raw::*;
 /// - All tags are added to the writer using [push_tag](Writer::push_tag).
 /// - Terms and kanji are added using [push_term](Writer::push_term) and
 ///   [push_kanji](Writer::push_kanji) methods.
+/// - Radicals for the radical lookup are added using
+///   [push_radical](Writer::push_radical).
+/// - Source dictionaries metadata (e.g. priority) is added using
+///   [push_source](Writer::push_source).
 ///   - Term and kanji tags must be converted to their respective indexes
 ///     using [get_tag](Writer::get_tag) or [get_tags](Writer::get_tags).
 /// - The database is written using [write](Writer::write). During the write
@@ -26,13 +44,34 @@ use super::raw::*;
 ///
 /// All strings used in tags, terms and kanji must be interned using the
 /// [intern](Writer::intern) method.
+///
+/// Data from separate writers (e.g. terms and kanji built independently) can
+/// be combined before writing using [merge](Writer::merge).
 pub struct Writer {
+	profile: Profile,
+	build_time: Option<SystemTime>,
+	source_hash: [u8; 32],
+
 	terms: Vec<TermData>,
 	kanji: Vec<KanjiData>,
+	radicals: Vec<RadicalData>,
+	sources: Vec<SourceData>,
+	source_files: Vec<SourceFileData>,
+	importer: u32,
+	embedding_dim: usize,
+	embeddings: HashMap<u32, Vec<f32>>,
+	media: Vec<MediaData>,
+	validate: bool,
 
 	tags: Vec<TagData>,
+	tag_translations: Vec<TagTranslationData>,
 	tag_index: HashMap<String, u32>,
 
+	glossary_configs: Vec<GlossaryConfigData>,
+
+	origins: Vec<OriginData>,
+	sense_info: Vec<SenseInfoData>,
+
 	string_list: Vec<(u32, u32)>,
 	string_data: String,
 	string_hash: HashMap<String, u32>,
@@ -42,12 +81,30 @@ impl Writer {
 	/// Returns a new empty instance of a Writer.
 	pub fn new() -> Writer {
 		let mut out = Writer {
+			profile: Profile::Full,
+			build_time: None,
+			source_hash: [0; 32],
+
 			terms: Default::default(),
 			kanji: Default::default(),
+			radicals: Default::default(),
+			sources: Default::default(),
+			source_files: Default::default(),
+			importer: 0,
+			embedding_dim: 0,
+			embeddings: Default::default(),
+			media: Default::default(),
+			validate: cfg!(debug_assertions),
 
 			tags: Default::default(),
+			tag_translations: Default::default(),
 			tag_index: Default::default(),
 
+			glossary_configs: Default::default(),
+
+			origins: Default::default(),
+			sense_info: Default::default(),
+
 			string_list: Default::default(),
 			string_data: Default::default(),
 			string_hash: Default::default(),
@@ -59,6 +116,31 @@ impl Writer {
 		out
 	}
 
+	/// Sets the output profile for the database. The default is
+	/// [Profile::Full].
+	pub fn set_profile(&mut self, profile: Profile) {
+		self.profile = profile;
+	}
+
+	/// Sets the build time recorded in the database header. The default is
+	/// the time the database is written.
+	pub fn set_build_time(&mut self, time: SystemTime) {
+		self.build_time = Some(time);
+	}
+
+	/// Enables the [validate](Writer::validate) check before writing, which
+	/// fails the write if the data is invalid. The default is to validate
+	/// only in debug builds.
+	pub fn set_validate(&mut self, validate: bool) {
+		self.validate = validate;
+	}
+
+	/// Sets the SHA-256 hash for the source dictionaries, recorded in the
+	/// database header.
+	pub fn set_source_hash(&mut self, hash: [u8; 32]) {
+		self.source_hash = hash;
+	}
+
 	/// Add a new tag to write to the database.
 	///
 	/// All tags for the database should be added before trying to add terms and
@@ -69,6 +151,44 @@ impl Writer {
 		self.tags.push(tag);
 	}
 
+	/// Sets the embedding vector for the terms with the given expression
+	/// (interned string), for the semantic search with [DB::nearest_terms].
+	///
+	/// All vectors must have the same number of dimensions. Terms without an
+	/// embedding are not returned by the semantic search.
+	pub fn set_embedding(&mut self, expression: u32, vector: Vec<f32>) {
+		if self.embeddings.len() == 0 {
+			self.embedding_dim = vector.len();
+		}
+		assert_eq!(vector.len(), self.embedding_dim, "embedding dimensions do not match");
+		self.embeddings.insert(expression, vector);
+	}
+
+	/// Add a localized description for a tag.
+	pub fn push_tag_translation(&mut self, translation: TagTranslationData) {
+		self.tag_translations.push(translation);
+	}
+
+	/// Sets the glossary configuration for a language, used for the related
+	/// terms and stored in the database for the glossary queries. The last
+	/// configuration for a language takes precedence.
+	///
+	/// Languages without a configuration use [GlossaryConfig::default_for].
+	pub fn push_glossary_config(&mut self, config: GlossaryConfigData) {
+		self.glossary_configs.push(config);
+	}
+
+	/// Add the origin of a loanword. The origin applies to all terms with
+	/// the expression and reading, from any source dictionary.
+	pub fn push_origin(&mut self, origin: OriginData) {
+		self.origins.push(origin);
+	}
+
+	/// Add a supplemental note for a sense (e.g. "usually in the negative").
+	pub fn push_sense_info(&mut self, info: SenseInfoData) {
+		self.sense_info.push(info);
+	}
+
 	/// Add a new term to write to the database.
 	pub fn push_term(&mut self, term: TermData) {
 		self.terms.push(term);
@@ -79,6 +199,35 @@ impl Writer {
 		self.kanji.push(kanji);
 	}
 
+	/// Add a new radical to write to the database.
+	pub fn push_radical(&mut self, radical: RadicalData) {
+		self.radicals.push(radical);
+	}
+
+	/// Add metadata for a source dictionary to write to the database.
+	pub fn push_source(&mut self, source: SourceData) {
+		self.sources.push(source);
+	}
+
+	/// Add a source file used to build the database, for provenance
+	/// tracking.
+	pub fn push_source_file(&mut self, file: SourceFileData) {
+		self.source_files.push(file);
+	}
+
+	/// Add a media file to write to the database, for lookup with
+	/// [DB::media]. If more than one file has the same source and path, the
+	/// first one is written.
+	pub fn push_media(&mut self, media: MediaData) {
+		self.media.push(media);
+	}
+
+	/// Sets the version of the importer building the database (interned
+	/// string).
+	pub fn set_importer(&mut self, importer: u32) {
+		self.importer = importer;
+	}
+
 	/// Builds a `Vec<u32>` of tag indexes from a list of tag names.
 	pub fn get_tags<T: IntoIterator<Item = S>, S: AsRef<str>>(&self, names: T) -> Vec<u32> {
 		let mut out = Vec::new();
@@ -108,6 +257,11 @@ impl Writer {
 		}
 	}
 
+	/// Returns the index for a string if it is interned.
+	pub fn find_string(&self, value: &str) -> Option<u32> {
+		self.string_hash.get(value).cloned()
+	}
+
 	/// Return an interned string from its index.
 	pub fn string(&self, index: u32) -> &str {
 		let (offset, length) = self.string_list[index as usize];
@@ -116,26 +270,437 @@ impl Writer {
 		&self.string_data[sta..end]
 	}
 
+	/// Merges all data from another writer into this one.
+	///
+	/// Strings from the other writer are re-interned and tag indexes are
+	/// remapped to this writer. Tags with the same name as an existing tag
+	/// are mapped to the existing tag.
+	pub fn merge(&mut self, other: Writer) {
+		let strings: Vec<u32> = (0..other.string_list.len() as u32)
+			.map(|index| self.intern(other.string(index).to_string()))
+			.collect();
+		let str_map = |index: u32| strings[index as usize];
+		let str_vec = |list: Vec<u32>| list.into_iter().map(|x| strings[x as usize]).collect::<Vec<_>>();
+
+		let mut tags = Vec::with_capacity(other.tags.len());
+		for tag in other.tags.iter() {
+			let name = other.string(tag.name);
+			let index = match self.tag_index.get(name) {
+				Some(&index) => index,
+				None => {
+					let index = self.tags.len() as u32;
+					self.push_tag(TagData {
+						name: str_map(tag.name),
+						category: str_map(tag.category),
+						order: tag.order,
+						notes: str_map(tag.notes),
+					});
+					index
+				}
+			};
+			tags.push(index);
+		}
+		let tag_vec = |list: Vec<u32>| list.into_iter().map(|x| tags[x as usize]).collect::<Vec<_>>();
+
+		for it in other.tag_translations {
+			self.tag_translations.push(TagTranslationData {
+				tag: tags[it.tag as usize],
+				language: str_map(it.language),
+				notes: str_map(it.notes),
+			});
+		}
+
+		for it in other.glossary_configs {
+			self.glossary_configs.push(GlossaryConfigData {
+				language: str_map(it.language),
+				tokenizer: it.tokenizer,
+				min_length: it.min_length,
+				stop_words: str_vec(it.stop_words),
+			});
+		}
+
+		for it in other.origins {
+			self.origins.push(OriginData {
+				expression: str_map(it.expression),
+				reading: str_map(it.reading),
+				language: str_map(it.language),
+				word: str_map(it.word),
+			});
+		}
+
+		for it in other.sense_info {
+			self.sense_info.push(SenseInfoData {
+				source: str_map(it.source),
+				sequence: it.sequence,
+				sense: it.sense,
+				info: str_map(it.info),
+			});
+		}
+
+		for term in other.terms {
+			self.terms.push(TermData {
+				expression: str_map(term.expression),
+				reading: str_map(term.reading),
+				search_key: str_map(term.search_key),
+				score: term.score,
+				sequence: term.sequence,
+				order: term.order,
+				frequency: term.frequency,
+				glossary: str_vec(term.glossary),
+				rules: tag_vec(term.rules),
+				term_tags: tag_vec(term.term_tags),
+				definition_tags: tag_vec(term.definition_tags),
+				source: str_map(term.source),
+				flags: term.flags,
+				pitch: term.pitch,
+			});
+		}
+
+		for kanji in other.kanji {
+			self.kanji.push(KanjiData {
+				character: kanji.character,
+				frequency: kanji.frequency,
+				meanings: str_vec(kanji.meanings),
+				onyomi: str_vec(kanji.onyomi),
+				kunyomi: str_vec(kanji.kunyomi),
+				tags: tag_vec(kanji.tags),
+				stats: kanji
+					.stats
+					.into_iter()
+					.map(|(stat, info)| (tags[stat as usize], str_map(info)))
+					.collect(),
+				similar: kanji.similar,
+				source: str_map(kanji.source),
+			});
+		}
+
+		for radical in other.radicals {
+			self.radicals.push(RadicalData {
+				number: radical.number,
+				character: radical.character,
+				strokes: radical.strokes,
+				name: str_map(radical.name),
+				variants: radical.variants,
+			});
+		}
+
+		for source in other.sources {
+			self.sources.push(SourceData {
+				name: str_map(source.name),
+				priority: source.priority,
+			});
+		}
+
+		for file in other.source_files {
+			self.source_files.push(SourceFileData {
+				name: str_map(file.name),
+				hash: file.hash,
+			});
+		}
+
+		for (expression, vector) in other.embeddings {
+			self.set_embedding(str_map(expression), vector);
+		}
+
+		for media in other.media {
+			self.media.push(MediaData {
+				source: str_map(media.source),
+				path: str_map(media.path),
+				data: media.data,
+			});
+		}
+	}
+
+	/// Returns the interned key for the index with the index collation
+	/// normalization applied.
+	fn index_key(&mut self, key: u32) -> u32 {
+		let normalized = match normalize_key(self.string(key)) {
+			Cow::Borrowed(_) => return key,
+			Cow::Owned(normalized) => normalized,
+		};
+		self.intern(normalized)
+	}
+
+	/// Returns the position of each tag in display order, by tag index.
+	///
+	/// This is the same order as [compare_tags](super::compare_tags).
+	fn tag_rank(&self) -> Vec<usize> {
+		let key = |tag: &TagData| {
+			let category = self.string(tag.category);
+			(TagCategory::from_name(category), category, tag.order, self.string(tag.name))
+		};
+		let mut sorted: Vec<usize> = (0..self.tags.len()).collect();
+		sorted.sort_by(|&a, &b| key(&self.tags[a]).cmp(&key(&self.tags[b])).then(a.cmp(&b)));
+
+		let mut rank = vec![0; self.tags.len()];
+		for (position, index) in sorted.into_iter().enumerate() {
+			rank[index] = position;
+		}
+		rank
+	}
+
+	/// Tiebreaker for terms with the same relevance, so the term order does
+	/// not depend on the order the terms were added. Terms are ordered by
+	/// sequence, source, expression, reading and order in the entry.
+	fn compare_term_ties(&self, a: &TermData, b: &TermData) -> Ordering {
+		a.sequence
+			.cmp(&b.sequence)
+			.then_with(|| self.string(a.source).cmp(self.string(b.source)))
+			.then_with(|| self.string(a.expression).cmp(self.string(b.expression)))
+			.then_with(|| self.string(a.reading).cmp(self.string(b.reading)))
+			.then_with(|| a.order.cmp(&b.order))
+	}
+
+	/// Returns the JMdict priority code for each tag, by tag index. Tags that
+	/// are not priority codes have an empty priority.
+	fn tag_priority(&self) -> Vec<Priority> {
+		self.tags
+			.iter()
+			.map(|tag| Priority::from_code(self.string(tag.name)).unwrap_or_default())
+			.collect()
+	}
+
+	/// Checks the invariants for the data before writing: every interned
+	/// string and tag index is in bounds. Returns the list of problems found,
+	/// which is empty if the data is valid.
+	///
+	/// Invalid indexes would otherwise be written to the database as is and
+	/// only found on load (see [DB::check]).
+	pub fn validate(&self) -> Vec<String> {
+		let mut v = Validator {
+			strings: self.string_list.len(),
+			tags: self.tags.len(),
+			problems: Vec::new(),
+		};
+
+		for (index, tag) in self.tags.iter().enumerate() {
+			let item = format!("tag {}", index);
+			v.string(&item, "name", tag.name);
+			v.string(&item, "category", tag.category);
+			v.string(&item, "notes", tag.notes);
+		}
+		for (name, &index) in self.tag_index.iter() {
+			v.tag(&format!("tag `{}`", name), "index", index);
+		}
+		for (index, it) in self.tag_translations.iter().enumerate() {
+			let item = format!("tag translation {}", index);
+			v.tag(&item, "tag", it.tag);
+			v.string(&item, "language", it.language);
+			v.string(&item, "notes", it.notes);
+		}
+		for (index, it) in self.glossary_configs.iter().enumerate() {
+			let item = format!("glossary config {}", index);
+			v.string(&item, "language", it.language);
+			v.strings(&item, "stop words", &it.stop_words);
+		}
+
+		for (index, term) in self.terms.iter().enumerate() {
+			let item = format!("term {}", index);
+			v.string(&item, "expression", term.expression);
+			v.string(&item, "reading", term.reading);
+			v.string(&item, "search key", term.search_key);
+			v.string(&item, "source", term.source);
+			v.strings(&item, "glossary", &term.glossary);
+			v.tags(&item, "rules", &term.rules);
+			v.tags(&item, "term tags", &term.term_tags);
+			v.tags(&item, "definition tags", &term.definition_tags);
+		}
+
+		for (index, kanji) in self.kanji.iter().enumerate() {
+			let item = format!("kanji {}", index);
+			v.strings(&item, "meanings", &kanji.meanings);
+			v.strings(&item, "onyomi", &kanji.onyomi);
+			v.strings(&item, "kunyomi", &kanji.kunyomi);
+			v.string(&item, "source", kanji.source);
+			v.tags(&item, "tags", &kanji.tags);
+			for &(stat, info) in kanji.stats.iter() {
+				v.tag(&item, "stat", stat);
+				v.string(&item, "stat info", info);
+			}
+		}
+
+		for (index, radical) in self.radicals.iter().enumerate() {
+			v.string(&format!("radical {}", index), "name", radical.name);
+		}
+		for (index, source) in self.sources.iter().enumerate() {
+			v.string(&format!("source {}", index), "name", source.name);
+		}
+		for (index, file) in self.source_files.iter().enumerate() {
+			v.string(&format!("source file {}", index), "name", file.name);
+		}
+		v.string("header", "importer", self.importer);
+
+		for (index, it) in self.origins.iter().enumerate() {
+			let item = format!("origin {}", index);
+			v.string(&item, "expression", it.expression);
+			v.string(&item, "reading", it.reading);
+			v.string(&item, "language", it.language);
+			v.string(&item, "word", it.word);
+		}
+		for (index, it) in self.sense_info.iter().enumerate() {
+			let item = format!("sense info {}", index);
+			v.string(&item, "source", it.source);
+			v.string(&item, "info", it.info);
+		}
+		for &expression in self.embeddings.keys() {
+			v.string("embedding", "expression", expression);
+		}
+		for (index, it) in self.media.iter().enumerate() {
+			let item = format!("media {}", index);
+			v.string(&item, "source", it.source);
+			v.string(&item, "path", it.path);
+		}
+
+		v.problems
+	}
+
 	/// Writes the database data to an `std::io::Write`.
 	///
 	/// The binary representation of the database is designed to be memory
 	/// mapped on load. Note that `u32` are written in LE format.
+	///
+	/// If [validation](Writer::set_validate) is enabled, this fails with an
+	/// [InvalidData](io::ErrorKind::InvalidData) error without writing
+	/// anything if the data is invalid.
+	///
+	/// The output depends only on the data and not on the order terms and
+	/// kanji were added: every sort has a total order, with terms that are
+	/// equally relevant ordered by sequence, source, expression, reading and
+	/// order (see [compare_term_ties](Writer::compare_term_ties)), and index
+	/// rows with the same key ordered by term index.
 	pub fn write<W: std::io::Write>(mut self, writer: &mut W) -> std::io::Result<()> {
 		let start = Instant::now();
 
+		if self.validate {
+			/// Maximum number of problems included in the error message.
+			const MAX_PROBLEMS: usize = 10;
+
+			let problems = self.validate();
+			if problems.len() > 0 {
+				let mut message = format!("invalid database data ({} problems)", problems.len());
+				for it in problems.iter().take(MAX_PROBLEMS) {
+					message.push_str("\n- ");
+					message.push_str(it);
+				}
+				if problems.len() > MAX_PROBLEMS {
+					message.push_str("\n- ...");
+				}
+				return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+			}
+			println!("... validated data in {:?}", start.elapsed());
+		}
+
+		// Glossary configurations are sorted by language for lookup, with the
+		// last configuration for a language taking precedence. The defaults
+		// are written for the term glossary languages, so the database always
+		// records the configuration used for the related terms.
+		let mut glossary_configs = std::mem::replace(&mut self.glossary_configs, Vec::new());
+		for &language in [GLOSSARY_ENGLISH, GLOSSARY_JAPANESE].iter() {
+			let default = GlossaryConfig::default_for(language);
+			let config = GlossaryConfigData {
+				language: self.intern(language.to_string()),
+				tokenizer: default.tokenizer,
+				min_length: default.min_length as u32,
+				stop_words: default.stop_words.iter().map(|x| self.intern(x.to_string())).collect(),
+			};
+			glossary_configs.insert(0, config);
+		}
+		glossary_configs.reverse();
+		glossary_configs.sort_by(|a, b| self.string(a.language).cmp(self.string(b.language)));
+		glossary_configs.dedup_by(|a, b| a.language == b.language);
+
 		//
 		// Sort terms and kanji by relevance
 		//
 
-		self.terms.sort_by(|a, b| {
-			if a.frequency != b.frequency {
-				b.frequency.cmp(&a.frequency)
-			} else {
-				b.score.cmp(&a.score)
+		// The source priority is used as a tiebreaker, so that the results
+		// follow the configured dictionary order. Remaining ties are broken
+		// by `compare_term_ties`.
+		let priority: HashMap<u32, i32> = self.sources.iter().map(|x| (x.name, x.priority)).collect();
+		let priority = |source: u32| priority.get(&source).cloned().unwrap_or(0);
+		let tag_priority = self.tag_priority();
+		let term_priority = |term: &TermData| {
+			let mut out = Priority::default();
+			for &tag in term.term_tags.iter().chain(term.definition_tags.iter()) {
+				out.insert(tag_priority[tag as usize]);
 			}
+			out
+		};
+		let mut terms = std::mem::replace(&mut self.terms, Vec::new());
+		terms.sort_by(|a, b| {
+			b.frequency
+				.cmp(&a.frequency)
+				.then_with(|| term_priority(b).rank().cmp(&term_priority(a).rank()))
+				.then_with(|| b.score.cmp(&a.score))
+				.then_with(|| priority(b.source).cmp(&priority(a.source)))
+				.then_with(|| self.compare_term_ties(a, b))
 		});
+		self.terms = terms;
 
-		self.kanji.sort_by(|a, b| b.frequency.cmp(&a.frequency));
+		let mut kanji = std::mem::replace(&mut self.kanji, Vec::new());
+		kanji.sort_by(|a, b| {
+			let source = || self.string(a.source).cmp(self.string(b.source));
+			b.frequency.cmp(&a.frequency).then(a.character.cmp(&b.character)).then_with(source)
+		});
+		self.kanji = kanji;
+
+		// Sort the tags for each entry in display order (see `compare_tags`)
+		let tag_rank = self.tag_rank();
+		let sort_tags = |tags: &mut Vec<u32>| tags.sort_by_key(|&x| tag_rank[x as usize]);
+		for term in self.terms.iter_mut() {
+			sort_tags(&mut term.rules);
+			sort_tags(&mut term.term_tags);
+			sort_tags(&mut term.definition_tags);
+		}
+		for kanji in self.kanji.iter_mut() {
+			sort_tags(&mut kanji.tags);
+		}
+
+		// Script and length of each expression and the flags for the term
+		// tags, so that queries don't need to decode the strings. Tag flags
+		// are derived here so that they also apply to data imported before
+		// the flags existed.
+		let tag_flags: Vec<TermFlags> = self
+			.tags
+			.iter()
+			.map(|tag| TermFlags::from_tag(self.string(tag.name)).unwrap_or_default())
+			.collect();
+		let scripts: Vec<(TermFlags, u32)> = self
+			.terms
+			.iter()
+			.map(|term| {
+				let expression = self.string(term.expression);
+				let mut flags = script_flags(expression);
+				for &tag in term.term_tags.iter().chain(term.definition_tags.iter()) {
+					flags.insert(tag_flags[tag as usize]);
+				}
+				(flags, expression.chars().count() as u32)
+			})
+			.collect();
+
+		// Stable identifiers for the terms (see `Term::id`). Terms from the
+		// same source entry with the same headword are numbered in the source
+		// order to tell them apart.
+		let mut entries: HashMap<(u32, u32, u32, u32), Vec<(u32, usize)>> = HashMap::new();
+		for (i, it) in self.terms.iter().enumerate() {
+			let entry = (it.source, it.expression, it.reading, it.sequence);
+			entries.entry(entry).or_insert_with(Vec::new).push((it.order, i));
+		}
+		//
+		// The same numbering is the sense number in the entry, starting at
+		// one, used to match the sense info.
+		let mut term_ids = vec![0; self.terms.len()];
+		let mut term_senses = vec![0; self.terms.len()];
+		for ((source, expression, reading, sequence), mut terms) in entries {
+			terms.sort();
+			for (nth, &(_, i)) in terms.iter().enumerate() {
+				let fields = [self.string(source), self.string(expression), self.string(reading)];
+				term_ids[i] = term_id(&fields, sequence, nth as u32);
+				term_senses[i] = nth as u32 + 1;
+			}
+		}
+		let mut index_term_id: Vec<u32> = (0..self.terms.len() as u32).collect();
+		index_term_id.sort_by_key(|&x| (term_ids[x as usize], x));
 
 		//
 		// Build indexes
@@ -144,20 +709,105 @@ impl Writer {
 		// The prefix index stores a one-to-one mapping of the japanese key
 		// (expression, reading or key) to the term index. The keys are sorted
 		// to enable a simple binary search for a prefix.
+		//
+		// Keys are normalized for the index collation (see `collation`), so
+		// the byte order used for sorting is the same used when searching.
 
 		let mut index_prefix_jp = Vec::new();
-		for (i, it) in self.terms.iter().enumerate() {
+		let mut originals: HashMap<u32, HashSet<u32>> = HashMap::new();
+		for i in 0..self.terms.len() {
 			let index = i as u32;
-			index_prefix_jp.push((it.expression, index));
-			if it.reading > 0 {
-				index_prefix_jp.push((it.reading, index));
+			let (expression, reading, search_key) = {
+				let it = &self.terms[i];
+				(it.expression, it.reading, it.search_key)
+			};
+			// The expression is always indexed, the others only if not empty
+			for &original in [expression, reading, search_key].iter() {
+				if original == expression || original > 0 {
+					let key = self.index_key(original);
+					index_prefix_jp.push((key, index));
+					originals.entry(key).or_insert_with(HashSet::new).insert(original);
+				}
 			}
-			if it.search_key > 0 {
-				index_prefix_jp.push((it.search_key, index));
+		}
+
+		// Index the okurigana variants of the expressions (e.g. 受付 for
+		// 受け付け), unless the variant is already a key for another term.
+		let keys: HashSet<u32> = index_prefix_jp.iter().map(|x| x.0).collect();
+		for i in 0..self.terms.len() {
+			let (expression, trailing) = {
+				let it = &self.terms[i];
+				(it.expression, it.rules.len() == 0)
+			};
+			for variant in okurigana_variants(self.string(expression), trailing) {
+				let variant = self.intern(variant);
+				let key = self.index_key(variant);
+				if !keys.contains(&key) {
+					index_prefix_jp.push((key, i as u32));
+				}
+			}
+		}
+
+		// Index each part of dotted names and loanwords (e.g. アントワネット
+		// for マリー・アントワネット), so they can be found by any of the parts.
+		for i in 0..self.terms.len() {
+			let (expression, reading) = {
+				let it = &self.terms[i];
+				(it.expression, it.reading)
+			};
+			let mut parts: Vec<String> = Vec::new();
+			for &original in [expression, reading].iter() {
+				for part in middle_dot_parts(self.string(original)) {
+					if !parts.iter().any(|x| x == part) {
+						parts.push(part.to_string());
+					}
+				}
+			}
+			for part in parts {
+				let part = self.intern(part);
+				let key = self.index_key(part);
+				index_prefix_jp.push((key, i as u32));
+			}
+		}
+
+		index_prefix_jp.sort_by(|a, b| self.string(a.0).cmp(self.string(b.0)).then(a.1.cmp(&b.1)));
+
+		// The same key is pushed more than once for a term when its strings
+		// normalize to the same key (e.g. a kana expression equal to the
+		// reading). Interned keys are unique, so the duplicate pairs are
+		// adjacent after sorting.
+		let index_rows = index_prefix_jp.len();
+		index_prefix_jp.dedup();
+		let index_duplicates = index_rows - index_prefix_jp.len();
+
+		// Map the keys changed by the normalization back to the original
+		// strings, including the keys that are also an original string when
+		// another string was normalized to them.
+		let mut key_variants: Vec<(u32, u32)> = Vec::new();
+		for (key, originals) in originals {
+			if originals.iter().any(|&x| x != key) {
+				key_variants.extend(originals.into_iter().map(|x| (key, x)));
 			}
 		}
+		key_variants.sort_by(|a, b| {
+			let key = self.string(a.0).cmp(self.string(b.0));
+			key.then_with(|| self.string(a.1).cmp(self.string(b.1)))
+		});
 
-		index_prefix_jp.sort_by(|a, b| self.string(a.0).cmp(self.string(b.0)));
+		// Directory of shards for the prefix index by the first character of
+		// the key. Keys are sorted, so each shard is a contiguous range.
+		let mut index_prefix_dir: Vec<(u32, u32, u32)> = Vec::new();
+		for (i, &(key, _)) in index_prefix_jp.iter().enumerate() {
+			let chr = match self.string(key).chars().next() {
+				Some(chr) => chr as u32,
+				None => continue,
+			};
+			let index = i as u32;
+			match index_prefix_dir.last_mut() {
+				Some(last) if last.0 == chr => last.2 = index + 1,
+				_ => index_prefix_dir.push((chr, index, index + 1)),
+			}
+		}
 
 		// The suffix index is exactly like the prefix but keys are sorted by
 		// the reverse string. When searching for a suffix, the search string
@@ -173,20 +823,32 @@ impl Writer {
 			entry.clone()
 		};
 
-		// Clone the prefix index and sort by the reversed key
-		let mut index_suffix_jp = index_prefix_jp.clone();
+		// Clone the prefix index and sort by the reversed key. The compact
+		// profile omits the suffix and per-character indexes.
+		let full = self.profile == Profile::Full;
+		let mut index_suffix_jp = if full { index_prefix_jp.clone() } else { Vec::new() };
 		index_suffix_jp.sort_by(|a, b| {
 			let rev_a = rev(a.0);
 			let rev_b = rev(b.0);
-			rev_a.cmp(&rev_b)
+			rev_a.cmp(&rev_b).then(a.1.cmp(&b.1))
 		});
 
+		// Store the prefix of the sort key with each row (see `TermIndex`)
+		let index_prefix_jp: Vec<TermIndex> = index_prefix_jp
+			.into_iter()
+			.map(|(key, term)| TermIndex::new(key, term, self.string(key)))
+			.collect();
+		let index_suffix_jp: Vec<TermIndex> = index_suffix_jp
+			.into_iter()
+			.map(|(key, term)| TermIndex::new(key, term, &rev(key)))
+			.collect();
+
 		// Per-character index used for "contains" style queries and fuzzy
 		// searching.
 		let mut index_chars_jp = HashMap::new();
 		let mut total_indexes = 0;
 		let mut max_indexes = 0;
-		for (i, it) in self.terms.iter().enumerate() {
+		for (i, it) in self.terms.iter().enumerate().filter(|_| full) {
 			let index = i as u32;
 			let mut key = String::new();
 			key.push_str(self.string(it.expression));
@@ -202,22 +864,89 @@ impl Writer {
 			max_indexes = std::cmp::max(max_indexes, entries.len());
 		}
 
+		// Duplicate rows would be in both the prefix and suffix indexes
 		let num_char_keys = index_chars_jp.len();
+		let index_copies = if full { 2 } else { 1 };
 		println!(
-			"... built index in {:?} (terms = {}, chars = {} / avg {} / max {})",
+			"... built index in {:?} (terms = {}, chars = {} / avg {} / max {}, duplicates = {} / {} bytes saved)",
 			start.elapsed(),
 			index_prefix_jp.len(),
 			num_char_keys,
-			total_indexes / num_char_keys,
+			total_indexes / std::cmp::max(num_char_keys, 1),
 			max_indexes,
+			index_duplicates,
+			index_duplicates * index_copies * std::mem::size_of::<TermIndex>(),
 		);
 
+		//
+		// Related terms
+		//
+
+		let start = Instant::now();
+		let related = {
+			let glossary: Vec<GlossaryConfig> = glossary_configs
+				.iter()
+				.map(|it| GlossaryConfig {
+					language: self.string(it.language),
+					tokenizer: it.tokenizer,
+					min_length: it.min_length as usize,
+					stop_words: it.stop_words.iter().map(|&x| self.string(x)).collect(),
+				})
+				.collect();
+			let config_for = |language: &str| glossary.iter().find(|x| x.language == language).unwrap();
+			let (japanese, english) = (config_for(GLOSSARY_JAPANESE), config_for(GLOSSARY_ENGLISH));
+			let inputs: Vec<RelatedInput> = self
+				.terms
+				.iter()
+				.map(|it| RelatedInput {
+					expression: self.string(it.expression),
+					reading: self.string(it.reading),
+					glossary: it.glossary.iter().map(|&x| self.string(x)).collect(),
+					frequency: it.frequency,
+					config: if it.flags.contains(TermFlags::MONOLINGUAL) {
+						japanese
+					} else {
+						english
+					},
+				})
+				.collect();
+			related_terms(&inputs)
+		};
+		println!("... computed related terms in {:?}", start.elapsed());
+
 		//
 		// Serialization
 		//
 
 		let start = Instant::now();
 
+		// Tag translations are sorted for lookup by tag and language, with the
+		// last translation for a given tag and language taking precedence.
+		let mut tag_translations = std::mem::replace(&mut self.tag_translations, Vec::new());
+		tag_translations.reverse();
+		tag_translations.sort_by(|a, b| {
+			(a.tag, self.string(a.language)).cmp(&(b.tag, self.string(b.language)))
+		});
+		tag_translations.dedup_by(|a, b| a.tag == b.tag && a.language == b.language);
+
+		// Media files are sorted by key for lookup, keeping the first file for
+		// a given source and path. The sort is stable, so this is the first
+		// file pushed.
+		let mut media: Vec<(u64, MediaData)> = std::mem::replace(&mut self.media, Vec::new())
+			.into_iter()
+			.map(|x| (media_key(self.string(x.source), self.string(x.path)), x))
+			.collect();
+		media.sort_by_key(|x| x.0);
+		media.dedup_by(|a, b| a.0 == b.0 && a.1.source == b.1.source && a.1.path == b.1.path);
+
+		let profile = self.profile;
+		let source_hash = self.source_hash;
+		let importer = self.importer;
+		let embedding_dim = self.embedding_dim;
+		let has_pitch = self.terms.iter().any(|x| x.pitch.len() > 0);
+		let build_time = self.build_time.unwrap_or_else(SystemTime::now);
+		let build_time = build_time.duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or(0);
+
 		let mut raw = Raw::default();
 		let mut vector_data: Vec<u32> = Vec::new();
 
@@ -247,6 +976,23 @@ impl Writer {
 			});
 		}
 
+		for it in tag_translations {
+			raw.tag_translations.push(TagTranslationRaw {
+				tag: it.tag.into(),
+				language: it.language.into(),
+				notes: it.notes.into(),
+			});
+		}
+
+		for it in glossary_configs {
+			raw.glossary_configs.push(GlossaryConfigRaw {
+				language: it.language.into(),
+				tokenizer: it.tokenizer.to_raw().into(),
+				min_length: it.min_length.into(),
+				stop_words: push_vec(it.stop_words),
+			});
+		}
+
 		for kanji in self.kanji {
 			raw.kanji.push(KanjiRaw {
 				character: (kanji.character as u32).into(),
@@ -263,42 +1009,227 @@ impl Writer {
 						.flat_map(|x| vec![x.0, x.1])
 						.collect(),
 				),
+				similar: push_vec(kanji.similar.into_iter().map(|x| x as u32).collect()),
 			});
 		}
 
-		for term in self.terms {
-			raw.terms.push(TermRaw {
-				expression: term.expression.into(),
-				reading: term.reading.into(),
-				search_key: term.search_key.into(),
-				score: term.score.into(),
-				sequence: term.sequence.into(),
-				frequency: term.frequency.into(),
-				source: term.source.into(),
-				glossary: push_vec(term.glossary),
-				rules: push_vec(term.rules),
-				term_tags: push_vec(term.term_tags),
-				definition_tags: push_vec(term.definition_tags),
+		for radical in self.radicals {
+			raw.radicals.push(RadicalRaw {
+				number: radical.number.into(),
+				character: (radical.character as u32).into(),
+				strokes: radical.strokes.into(),
+				name: radical.name.into(),
+				variants: push_vec(radical.variants.into_iter().map(|x| x as u32).collect()),
 			});
 		}
 
-		raw.index_prefix_jp = index_prefix_jp
-			.into_iter()
-			.map(|(key, term)| TermIndex {
+		for (key, it) in media {
+			raw.media.push(MediaRaw {
 				key: key.into(),
-				term: term.into(),
+				source: it.source.into(),
+				path: it.path.into(),
+				offset: (raw.media_data.len() as u32).into(),
+				length: (it.data.len() as u32).into(),
+			});
+			raw.media_data.extend_from_slice(&it.data);
+		}
+
+		for source in self.sources {
+			raw.sources.push(SourceRaw {
+				name: source.name.into(),
+				priority: source.priority.into(),
+			});
+		}
+
+		for file in self.source_files {
+			raw.source_files.push(SourceFileRaw {
+				name: file.name.into(),
+				hash: file.hash,
+			});
+		}
+
+		// Embeddings are stored as one fixed-size vector for each term in
+		// order, with zeros for terms without an embedding.
+		if embedding_dim > 0 {
+			let zeros = vec![0.0; embedding_dim];
+			for term in self.terms.iter() {
+				let vector = self.embeddings.get(&term.expression).unwrap_or(&zeros);
+				raw.embeddings.extend(vector.iter().map(|x| RawUint32::from(x.to_bits())));
+			}
+		}
+
+		// Origins are stored with the terms as pairs of language and source
+		// word. An origin without a reading applies to any reading, as does
+		// one for a term without reading (e.g. katakana loanwords).
+		let mut origins: HashMap<u32, Vec<&OriginData>> = HashMap::new();
+		for it in self.origins.iter() {
+			origins.entry(it.expression).or_insert_with(Vec::new).push(it);
+		}
+		let origins: Vec<Vec<u32>> = self
+			.terms
+			.iter()
+			.map(|term| {
+				let mut out = Vec::new();
+				for it in origins.get(&term.expression).map(|x| x.as_slice()).unwrap_or(&[]) {
+					if it.reading == 0 || term.reading == 0 || it.reading == term.reading {
+						out.push(it.language);
+						out.push(it.word);
+					}
+				}
+				out
+			})
+			.collect();
+
+		// JMdict senses can be restricted to some of the kanji and reading
+		// forms of the entry. The source has a term for each form a sense
+		// applies to, so a sense is restricted if the terms with its glossary
+		// don't cover all the forms in the entry. Restrictions are stored as
+		// pairs of expression and reading for the forms the sense applies to.
+		let all_terms = &self.terms;
+		let mut entries: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+		for (i, it) in all_terms.iter().enumerate() {
+			if it.sequence > 0 {
+				entries.entry((it.source, it.sequence)).or_insert_with(Vec::new).push(i);
+			}
+		}
+		let mut restrictions: Vec<Vec<u32>> = vec![Vec::new(); self.terms.len()];
+		for (_, terms) in entries {
+			let mut forms: Vec<(u32, u32)> = terms
+				.iter()
+				.map(|&i| (all_terms[i].expression, all_terms[i].reading))
+				.collect();
+			forms.sort();
+			forms.dedup();
+			if forms.len() < 2 {
+				continue;
+			}
+			let mut senses: HashMap<&Vec<u32>, Vec<(u32, u32)>> = HashMap::new();
+			for &i in terms.iter() {
+				let it = &all_terms[i];
+				let entry = senses.entry(&it.glossary).or_insert_with(Vec::new);
+				if !entry.contains(&(it.expression, it.reading)) {
+					entry.push((it.expression, it.reading));
+				}
+			}
+			for &i in terms.iter() {
+				let sense_forms = &senses[&all_terms[i].glossary];
+				if sense_forms.len() < forms.len() {
+					restrictions[i] = sense_forms.iter().flat_map(|x| vec![x.0, x.1]).collect();
+				}
+			}
+		}
+
+		let mut sense_info: HashMap<(u32, u32, u32), Vec<u32>> = HashMap::new();
+		for it in self.sense_info.iter() {
+			let entry = sense_info.entry((it.source, it.sequence, it.sense)).or_insert_with(Vec::new);
+			if !entry.contains(&it.info) {
+				entry.push(it.info);
+			}
+		}
+		let info: Vec<Vec<u32>> = self
+			.terms
+			.iter()
+			.zip(term_senses)
+			.map(|(term, sense)| {
+				let key = (term.source, term.sequence, sense);
+				sense_info.get(&key).cloned().unwrap_or_default()
 			})
 			.collect();
 
-		raw.index_suffix_jp = index_suffix_jp
+		let terms = self
+			.terms
 			.into_iter()
-			.map(|(key, term)| TermIndex {
+			.zip(related)
+			.zip(origins)
+			.zip(info)
+			.zip(restrictions)
+			.zip(scripts)
+			.zip(term_ids);
+		for ((((((term, related), origin), info), restrictions), (script, length)), id) in terms {
+			// Pack the optional fields that are present, in the order of
+			// their bits (see `TermTable`).
+			let mut fields = 0;
+			let mut payload = Vec::new();
+			let priority = term_priority(&term);
+			let scalars = [
+				(TermTable::SEARCH_KEY, term.search_key),
+				(TermTable::SCORE, term.score as u32),
+				(TermTable::SEQUENCE, term.sequence),
+				(TermTable::ORDER, term.order),
+			];
+			for &(field, value) in scalars.iter() {
+				if value != 0 {
+					fields |= field;
+					payload.push(value);
+				}
+			}
+
+			let vectors = vec![
+				(TermTable::RULES, term.rules),
+				(TermTable::TERM_TAGS, term.term_tags),
+				(TermTable::DEFINITION_TAGS, term.definition_tags),
+				(TermTable::PITCH, term.pitch),
+				(TermTable::RELATED, related),
+				(TermTable::ORIGIN, origin),
+				(TermTable::INFO, info),
+				(TermTable::RESTRICTIONS, restrictions),
+			];
+			for (field, value) in vectors {
+				if value.len() > 0 {
+					let handle = push_vec(value);
+					fields |= field;
+					payload.push(handle.offset.into());
+					payload.push(handle.length.into());
+				}
+			}
+
+			// Scalar fields with bits after the vector fields
+			let scalars = [(TermTable::PRIORITY, priority.0), (TermTable::LENGTH, length)];
+			for &(field, value) in scalars.iter() {
+				if value != 0 {
+					fields |= field;
+					payload.push(value);
+				}
+			}
+
+			raw.term_expression.push(term.expression.into());
+			raw.term_reading.push(term.reading.into());
+			raw.term_frequency.push(term.frequency.into());
+			raw.term_source.push(term.source.into());
+			raw.term_flags.push((term.flags | script).0.into());
+			raw.term_glossary.push(push_vec(term.glossary));
+			raw.term_fields.push(fields.into());
+			raw.term_payload.push(push_vec(payload).offset);
+			raw.term_id.push(id.into());
+		}
+
+		raw.index_prefix_jp = index_prefix_jp;
+
+		raw.index_prefix_dir = index_prefix_dir
+			.into_iter()
+			.map(|(chr, sta, end)| IndexShard {
+				character: chr.into(),
+				start: sta.into(),
+				end: end.into(),
+			})
+			.collect();
+
+		raw.index_suffix_jp = index_suffix_jp;
+
+		raw.key_variants = key_variants
+			.into_iter()
+			.map(|(key, original)| KeyVariant {
 				key: key.into(),
-				term: term.into(),
+				original: original.into(),
 			})
 			.collect();
 
-		// Convert the chars index into a mappable format
+		raw.index_term_id = index_term_id.into_iter().map(|x| x.into()).collect();
+
+		// Convert the chars index into a mappable format, sorted by character
+		// so the output does not depend on the hash map order.
+		let mut index_chars_jp: Vec<(char, HashSet<u32>)> = index_chars_jp.into_iter().collect();
+		index_chars_jp.sort_by_key(|x| x.0);
 		raw.index_chars_jp = index_chars_jp
 			.into_iter()
 			.map(|(key, val)| {
@@ -323,12 +1254,94 @@ impl Writer {
 		raw.string_data = self.string_data;
 		raw.vector_data = vector_data;
 
+		// Features built into the file, for `DB::capabilities`. The glossary
+		// configuration is always written (see above).
+		let mut capabilities = Capabilities::GLOSSARY_INDEX;
+		let features = [
+			(Capabilities::SUFFIX_INDEX, raw.index_suffix_jp.len() > 0),
+			(Capabilities::CHARS_INDEX, raw.index_chars_jp.len() > 0),
+			(Capabilities::PITCH, has_pitch),
+			(Capabilities::EMBEDDINGS, raw.embeddings.len() > 0),
+			(Capabilities::MEDIA, raw.media.len() > 0),
+		];
+		for &(capability, present) in features.iter() {
+			if present {
+				capabilities.insert(capability);
+			}
+		}
+
+		raw.header.push(HeaderRaw {
+			magic: HEADER_MAGIC.into(),
+			version: FORMAT_VERSION.into(),
+			profile: profile.to_raw().into(),
+			build_time: build_time.into(),
+			terms: (raw.term_expression.len() as u32).into(),
+			kanji: (raw.kanji.len() as u32).into(),
+			tags: (raw.tags.len() as u32).into(),
+			radicals: (raw.radicals.len() as u32).into(),
+			sources: (raw.sources.len() as u32).into(),
+			index_prefix_jp: (raw.index_prefix_jp.len() as u32).into(),
+			index_suffix_jp: (raw.index_suffix_jp.len() as u32).into(),
+			index_chars_jp: (raw.index_chars_jp.len() as u32).into(),
+			source_hash: source_hash,
+			importer: importer.into(),
+			embedding_dim: (embedding_dim as u32).into(),
+			capabilities: capabilities.0.into(),
+		});
+
 		println!("... prepared raw data in {:?}", start.elapsed());
 
 		raw.write(writer)
 	}
 }
 
+/// Output profile for the database, set with [Writer::set_profile].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Profile {
+	/// Database with all indexes.
+	Full,
+	/// Smaller database for size-constrained deployments, without the suffix
+	/// and per-character indexes.
+	Compact,
+}
+
+impl Profile {
+	/// Profile name, as used in the import command line.
+	pub fn name(&self) -> &'static str {
+		match self {
+			Profile::Full => "full",
+			Profile::Compact => "compact",
+		}
+	}
+
+	fn to_raw(&self) -> u32 {
+		match self {
+			Profile::Full => 0,
+			Profile::Compact => 1,
+		}
+	}
+
+	pub(crate) fn from_raw(value: u32) -> Profile {
+		match value {
+			0 => Profile::Full,
+			1 => Profile::Compact,
+			_ => panic!("invalid database profile: {}", value),
+		}
+	}
+}
+
+impl std::str::FromStr for Profile {
+	type Err = String;
+
+	fn from_str(s: &str) -> std::result::Result<Profile, String> {
+		match s {
+			"full" => Ok(Profile::Full),
+			"compact" => Ok(Profile::Compact),
+			_ => Err(format!("invalid profile `{}` (expected `full` or `compact`)", s)),
+		}
+	}
+}
+
 /// Tag data for writing.
 pub struct TagData {
 	/// Tag name (interned string).
@@ -341,6 +1354,56 @@ pub struct TagData {
 	pub notes: u32,
 }
 
+/// Localized tag description for writing.
+pub struct TagTranslationData {
+	/// Tag index.
+	pub tag: u32,
+	/// Language code, e.g. `pt` (interned string).
+	pub language: u32,
+	/// Tag notes in the language (interned string).
+	pub notes: u32,
+}
+
+/// Glossary configuration for a language for writing (see [GlossaryConfig]).
+pub struct GlossaryConfigData {
+	/// Language code for the glossary, e.g. `en` (interned string).
+	pub language: u32,
+	/// How the glossary text is split into words.
+	pub tokenizer: Tokenizer,
+	/// Minimum number of characters for a glossary keyword.
+	pub min_length: u32,
+	/// Lowercase words ignored as glossary keywords (interned strings).
+	pub stop_words: Vec<u32>,
+}
+
+/// Loanword origin for writing, from the JMdict `lsource` data.
+pub struct OriginData {
+	/// Expression for the terms (interned string).
+	pub expression: u32,
+	/// Reading for the terms, or empty to apply to any reading (interned
+	/// string).
+	pub reading: u32,
+	/// Source language code, e.g. `por` (interned string).
+	pub language: u32,
+	/// Word in the source language, if available (interned string).
+	pub word: u32,
+}
+
+/// Supplemental information for a sense, from the JMdict `s_inf` data.
+///
+/// Senses are the terms for an entry in the source dictionary, numbered from
+/// one in the source order for each headword (expression and reading).
+pub struct SenseInfoData {
+	/// Source dictionary name (interned string).
+	pub source: u32,
+	/// Sequence number for the entry in the source dictionary.
+	pub sequence: u32,
+	/// Sense number in the entry, starting at one.
+	pub sense: u32,
+	/// Information for the sense (interned string).
+	pub info: u32,
+}
+
 /// Kanji data for writing.
 pub struct KanjiData {
 	/// Kanji character.
@@ -359,10 +1422,54 @@ pub struct KanjiData {
 	/// Additional information for the kanji as a list of `(stat, info)` where
 	/// the `stat` is a tag index and `info` is an interned string.
 	pub stats: Vec<(u32, u32)>,
+	/// Visually similar kanji that are easily confused with this one.
+	pub similar: Vec<char>,
 	/// Source database name.
 	pub source: u32,
 }
 
+/// Radical data for writing.
+pub struct RadicalData {
+	/// Classical (Kangxi) radical number, from 1 to 214. Zero for search
+	/// radicals that are not classical radicals.
+	pub number: u32,
+	/// Radical character.
+	pub character: char,
+	/// Number of strokes for the radical.
+	pub strokes: u32,
+	/// Radical name (interned string).
+	pub name: u32,
+	/// Variant glyphs for the radical.
+	pub variants: Vec<char>,
+}
+
+/// Source dictionary data for writing.
+pub struct SourceData {
+	/// Source dictionary name (interned string).
+	pub name: u32,
+	/// Priority for the source. Higher values have precedence.
+	pub priority: i32,
+}
+
+/// Media file data for writing.
+pub struct MediaData {
+	/// Name of the source dictionary (interned string).
+	pub source: u32,
+	/// Path of the file in the source dictionary, as referenced by the
+	/// definitions (interned string).
+	pub path: u32,
+	/// File contents.
+	pub data: Vec<u8>,
+}
+
+/// Source file data for writing.
+pub struct SourceFileData {
+	/// File name (interned string).
+	pub name: u32,
+	/// SHA-256 hash for the file contents.
+	pub hash: [u8; 32],
+}
+
 /// Term data for writing.
 pub struct TermData {
 	/// Main expression for the term.
@@ -377,8 +1484,11 @@ pub struct TermData {
 	pub score: i32,
 	/// Sequence number for the entry in the source dictionary.
 	pub sequence: u32,
-	/// Number of occurrences for the term in the frequency database (based only
-	/// on the expression). Zero if not available.
+	/// Position of the term in the source dictionary. This preserves the order
+	/// in which the source presents the senses for an entry.
+	pub order: u32,
+	/// Number of occurrences for the term in the frequency database (specific
+	/// to the reading, if available). Zero if not available.
 	pub frequency: u32,
 	/// English definitions for the term (interned strings).
 	pub glossary: Vec<u32>,
@@ -390,17 +1500,77 @@ pub struct TermData {
 	pub definition_tags: Vec<u32>,
 	/// Source database name.
 	pub source: u32,
+	/// Additional flags for the term.
+	pub flags: TermFlags,
+	/// Pitch accent positions for the term reading. Each position is the
+	/// mora after which the pitch drops, with zero for the flat pattern.
+	pub pitch: Vec<u32>,
+}
+
+/// Collects the problems found by [Writer::validate].
+struct Validator {
+	strings: usize,
+	tags: usize,
+	problems: Vec<String>,
+}
+
+impl Validator {
+	fn string(&mut self, item: &str, field: &str, index: u32) {
+		if index as usize >= self.strings {
+			let count = self.strings;
+			self.problems.push(format!("{}: {} string {} out of bounds ({} strings)", item, field, index, count));
+		}
+	}
+
+	fn strings(&mut self, item: &str, field: &str, list: &[u32]) {
+		for &index in list {
+			self.string(item, field, index);
+		}
+	}
+
+	fn tag(&mut self, item: &str, field: &str, index: u32) {
+		if index as usize >= self.tags {
+			let count = self.tags;
+			self.problems.push(format!("{}: {} tag {} out of bounds ({} tags)", item, field, index, count));
+		}
+	}
+
+	fn tags(&mut self, item: &str, field: &str, list: &[u32]) {
+		for &index in list {
+			self.tag(item, field, index);
+		}
+	}
 }
 
 /// Raw database structure used for building the database for write.
 #[derive(Default)]
 struct Raw {
+	header: Vec<HeaderRaw>,
 	tags: Vec<TagRaw>,
-	terms: Vec<TermRaw>,
+	tag_translations: Vec<TagTranslationRaw>,
+	term_expression: Vec<RawUint32>,
+	term_reading: Vec<RawUint32>,
+	term_frequency: Vec<RawUint32>,
+	term_source: Vec<RawUint32>,
+	term_flags: Vec<RawUint32>,
+	term_glossary: Vec<VecHandle>,
+	term_fields: Vec<RawUint32>,
+	term_payload: Vec<RawUint32>,
+	term_id: Vec<RawUint64>,
 	kanji: Vec<KanjiRaw>,
+	radicals: Vec<RadicalRaw>,
+	sources: Vec<SourceRaw>,
+	source_files: Vec<SourceFileRaw>,
+	glossary_configs: Vec<GlossaryConfigRaw>,
 	index_prefix_jp: Vec<TermIndex>,
+	index_prefix_dir: Vec<IndexShard>,
 	index_suffix_jp: Vec<TermIndex>,
 	index_chars_jp: Vec<CharIndex>,
+	key_variants: Vec<KeyVariant>,
+	index_term_id: Vec<RawUint32>,
+	embeddings: Vec<RawUint32>,
+	media: Vec<MediaRaw>,
+	media_data: Vec<u8>,
 	vector_data: Vec<u32>,
 	string_list: Vec<StrHandle>,
 	string_data: String,
@@ -411,12 +1581,32 @@ impl Raw {
 	///
 	/// See also [DB::load].
 	pub fn write<W: std::io::Write>(self, writer: &mut W) -> std::io::Result<()> {
+		write_all(writer, self.header)?;
 		write_all(writer, self.tags)?;
-		write_all(writer, self.terms)?;
+		write_all(writer, self.tag_translations)?;
+		write_all(writer, self.term_expression)?;
+		write_all(writer, self.term_reading)?;
+		write_all(writer, self.term_frequency)?;
+		write_all(writer, self.term_source)?;
+		write_all(writer, self.term_flags)?;
+		write_all(writer, self.term_glossary)?;
+		write_all(writer, self.term_fields)?;
+		write_all(writer, self.term_payload)?;
+		write_all(writer, self.term_id)?;
 		write_all(writer, self.kanji)?;
+		write_all(writer, self.radicals)?;
+		write_all(writer, self.sources)?;
+		write_all(writer, self.source_files)?;
+		write_all(writer, self.glossary_configs)?;
 		write_all(writer, self.index_prefix_jp)?;
+		write_all(writer, self.index_prefix_dir)?;
 		write_all(writer, self.index_suffix_jp)?;
 		write_all(writer, self.index_chars_jp)?;
+		write_all(writer, self.key_variants)?;
+		write_all(writer, self.index_term_id)?;
+		write_all(writer, self.embeddings)?;
+		write_all(writer, self.media)?;
+		write_bytes(writer, &self.media_data)?;
 		write_vec(writer, self.vector_data)?;
 		write_all(writer, self.string_list)?;
 		write_len(writer, self.string_data.len())?;
@@ -425,36 +1615,171 @@ impl Raw {
 	}
 }
 
+use super::check_header;
+use super::LazyIndexes;
+use super::Section;
 use super::DB;
 
 impl<'a> DB<'a> {
 	/// Load the database from a raw binary blob.
 	pub fn load(data: &'a [u8]) -> DB<'a> {
+		DB::load_sections(data, Section::ALL)
+	}
+
+	/// Load only the given sections of the database from a raw binary blob,
+	/// along with the sections they [require](Section::requires).
+	///
+	/// The data for the other sections is not touched, so it is never paged
+	/// in when the database is memory mapped. Those sections behave as empty
+	/// (e.g. loading only [Section::Kanji] allows kanji lookups while term
+	/// searches return no results). The header, sources, source files and
+	/// glossary configurations are always loaded.
+	///
+	/// Note that [check](DB::check) requires all sections.
+	pub fn load_sections(data: &'a [u8], sections: &[Section]) -> DB<'a> {
+		let sections = Section::with_requirements(sections);
+		let has = |section: Section| sections.contains(&section);
+
 		// Note that the order of operations must match the [Raw::write] method.
 		unsafe {
+			let (header, data) = read_slice::<HeaderRaw>(data);
+			assert!(header.len() == 1, "invalid database: missing header");
+			if let Err(err) = check_header(&header[0]) {
+				panic!("invalid database: {}", err);
+			}
+
 			let (tags, data) = read_slice::<TagRaw>(data);
-			let (terms, data) = read_slice::<TermRaw>(data);
+			let (tag_translations, data) = read_slice::<TagTranslationRaw>(data);
+			let (term_expression, data) = read_slice::<RawUint32>(data);
+			let (term_reading, data) = read_slice::<RawUint32>(data);
+			let (term_frequency, data) = read_slice::<RawUint32>(data);
+			let (term_source, data) = read_slice::<RawUint32>(data);
+			let (term_flags, data) = read_slice::<RawUint32>(data);
+			let (term_glossary, data) = read_slice::<VecHandle>(data);
+			let (term_fields, data) = read_slice::<RawUint32>(data);
+			let (term_payload, data) = read_slice::<RawUint32>(data);
+			let (term_id, data) = read_slice::<RawUint64>(data);
 			let (kanji, data) = read_slice::<KanjiRaw>(data);
+			let (radicals, data) = read_slice::<RadicalRaw>(data);
+			let (sources, data) = read_slice::<SourceRaw>(data);
+			let (source_files, data) = read_slice::<SourceFileRaw>(data);
+			let (glossary_configs, data) = read_slice::<GlossaryConfigRaw>(data);
 			let (index_prefix_jp, data) = read_slice::<TermIndex>(data);
+			let (index_prefix_dir, data) = read_slice::<IndexShard>(data);
 			let (index_suffix_jp, data) = read_slice::<TermIndex>(data);
 			let (index_chars_jp, data) = read_slice::<CharIndex>(data);
+			let (key_variants, data) = read_slice::<KeyVariant>(data);
+			let (index_term_id, data) = read_slice::<RawUint32>(data);
+			let (embeddings, data) = read_slice::<RawUint32>(data);
+			let (media, data) = read_slice::<MediaRaw>(data);
+			let (media_data, data) = read_bytes(data);
 			let (vector_data, data) = read_slice::<RawUint32>(data);
 			let (string_list, data) = read_slice::<StrHandle>(data);
 			let (string_data, _) = read_slice::<u8>(data);
 			let string_data = std::str::from_utf8_unchecked(string_data);
+
+			let load_tags = has(Section::Tags);
+			let load_terms = has(Section::Terms);
+			let load_prefix = has(Section::PrefixIndex);
+			let vector_data = only(has(Section::Vectors), vector_data);
 			DB {
-				tags: tags,
-				terms: terms,
-				kanji: kanji,
-				index_prefix_jp: index_prefix_jp,
-				index_suffix_jp: index_suffix_jp,
-				index_chars_jp: index_chars_jp,
+				header: &header[0],
+				tags: only(load_tags, tags),
+				tag_translations: only(load_tags, tag_translations),
+				terms: TermTable {
+					expression: only(load_terms, term_expression),
+					reading: only(load_terms, term_reading),
+					frequency: only(load_terms, term_frequency),
+					source: only(load_terms, term_source),
+					flags: only(load_terms, term_flags),
+					glossary: only(load_terms, term_glossary),
+					fields: only(load_terms, term_fields),
+					payload: only(load_terms, term_payload),
+					id: only(load_terms, term_id),
+					vector_data: vector_data,
+				},
+				kanji: only(has(Section::Kanji), kanji),
+				radicals: only(has(Section::Radicals), radicals),
+				sources: sources,
+				source_files: source_files,
+				glossary_configs: glossary_configs,
+				index_prefix_jp: only(load_prefix, index_prefix_jp),
+				index_prefix_dir: only(load_prefix, index_prefix_dir),
+				index_suffix_jp: only(has(Section::SuffixIndex), index_suffix_jp),
+				index_chars_jp: only(has(Section::CharsIndex), index_chars_jp),
+				key_variants: only(load_prefix, key_variants),
+				index_term_id: only(load_terms, index_term_id),
+				embeddings: only(has(Section::Embeddings), embeddings),
+				media: only(has(Section::Media), media),
+				media_data: only(has(Section::Media), media_data),
 				vector_data: vector_data,
-				string_list: string_list,
-				string_data: string_data,
+				string_list: only(has(Section::Strings), string_list),
+				string_data: if has(Section::Strings) { string_data } else { "" },
+				lazy: LazyIndexes::default(),
+				sections: sections,
 			}
 		}
 	}
+
+	/// Output profile the database was written with.
+	pub fn profile(&self) -> Profile {
+		let profile: u32 = self.header.profile.into();
+		Profile::from_raw(profile)
+	}
+}
+
+/// Computes the stable identifier for a term from the strings identifying
+/// its entry, the entry sequence number and the position of the term among
+/// the terms for the same entry.
+///
+/// This uses the 64-bit FNV-1a hash, which is simple and stable across
+/// platforms and versions (unlike the standard library hasher).
+fn term_id(fields: &[&str], sequence: u32, nth: u32) -> u64 {
+	const OFFSET: u64 = 0xcbf29ce484222325;
+	const PRIME: u64 = 0x100000001b3;
+
+	let mut hash = OFFSET;
+	let mut push = |bytes: &[u8]| {
+		for &byte in bytes {
+			hash = (hash ^ byte as u64).wrapping_mul(PRIME);
+		}
+	};
+	for field in fields {
+		push(field.as_bytes());
+		push(&[0]);
+	}
+	push(&sequence.to_le_bytes());
+	push(&nth.to_le_bytes());
+	hash
+}
+
+/// Returns the [TermFlags] for the script of a term expression.
+fn script_flags(expression: &str) -> TermFlags {
+	let is_kanji = |chr: char| match chr {
+		'\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' | '々' => true,
+		_ => false,
+	};
+	// The prolonged sound mark and middle dot are used with both kana
+	let is_katakana = |chr: char| match chr {
+		'\u{30A1}'..='\u{30FA}' | '\u{30FD}'..='\u{30FE}' | '\u{FF66}'..='\u{FF9F}' | 'ー' | '・' => true,
+		_ => false,
+	};
+	let is_kana = |chr: char| match chr {
+		'\u{3041}'..='\u{309F}' => true,
+		_ => is_katakana(chr),
+	};
+
+	let mut flags = TermFlags::default();
+	if expression.chars().any(is_kanji) {
+		flags.insert(TermFlags::KANJI);
+	}
+	if expression.len() > 0 && expression.chars().all(is_kana) {
+		flags.insert(TermFlags::KANA);
+		if expression.chars().all(is_katakana) {
+			flags.insert(TermFlags::KATAKANA);
+		}
+	}
+	flags
 }
 
 //
@@ -470,6 +1795,15 @@ fn write_vec<W: io::Write>(writer: &mut W, vec: Vec<u32>) -> Result<()> {
 	Ok(())
 }
 
+/// Writes a byte section padded to a multiple of 4 bytes, so the sections
+/// after it are aligned. See [read_bytes].
+#[inline]
+fn write_bytes<W: io::Write>(writer: &mut W, bytes: &[u8]) -> Result<()> {
+	write_len(writer, bytes.len())?;
+	writer.write_all(bytes)?;
+	writer.write_all(&[0; 4][..padding(bytes.len())])
+}
+
 #[inline]
 fn write_len<W: io::Write>(writer: &mut W, value: usize) -> Result<()> {
 	write_u32(writer, value as u32)
@@ -526,6 +1860,29 @@ unsafe fn read_slice<U>(src: &[u8]) -> (&[U], &[u8]) {
 	(cast_slice(data), next)
 }
 
+/// Reads a byte section written by [write_bytes], skipping the padding.
+#[inline]
+unsafe fn read_bytes(src: &[u8]) -> (&[u8], &[u8]) {
+	let (bytes, next) = read_slice::<u8>(src);
+	(bytes, &next[padding(bytes.len())..])
+}
+
+/// Number of padding bytes after a byte section of the given length.
+#[inline]
+fn padding(len: usize) -> usize {
+	(4 - len % 4) % 4
+}
+
+/// Returns the slice if its section is loaded, otherwise an empty slice.
+#[inline]
+fn only<T>(loaded: bool, data: &[T]) -> &[T] {
+	if loaded {
+		data
+	} else {
+		&[]
+	}
+}
+
 #[inline]
 unsafe fn cast_slice<T, U>(src: &[T]) -> &[U] {
 	let data_size = std::mem::size_of_val(src);
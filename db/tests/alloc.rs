@@ -0,0 +1,150 @@
+//! Checks that the core read paths (index searches and term views) don't
+//! allocate, using a counting global allocator.
+//!
+//! This is a separate test binary from the query tests, since the allocator
+//! applies to the whole binary. Allocations are counted per thread, so tests
+//! running in parallel don't interfere.
+
+extern crate db;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::fs;
+use std::path::PathBuf;
+
+use db::*;
+
+struct CountingAllocator;
+
+thread_local! {
+	static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count_allocation() {
+	// Ignore errors when the thread local is destroyed on thread exit
+	let _ = ALLOCATIONS.try_with(|x| x.set(x.get() + 1));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		count_allocation();
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		System.dealloc(ptr, layout)
+	}
+
+	unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+		count_allocation();
+		System.realloc(ptr, layout, new_size)
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Runs `f` returning its result and the number of allocations it made.
+fn allocations<R, F: FnOnce() -> R>(f: F) -> (R, usize) {
+	let start = ALLOCATIONS.with(|x| x.get());
+	let result = f();
+	let count = ALLOCATIONS.with(|x| x.get()) - start;
+	(result, count)
+}
+
+/// Builds the database from the terms in the mini dictionary used by the
+/// query tests (see `queries.rs`).
+fn build(profile: Profile) -> Vec<u8> {
+	let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	path.push("tests/data/mini-dict.tsv");
+	let input = fs::read_to_string(path).unwrap();
+
+	let mut w = Writer::new();
+	w.set_profile(profile);
+	let rows = input.lines().filter(|x| x.trim().len() > 0 && !x.starts_with('#'));
+	for (i, row) in rows.enumerate() {
+		let row: Vec<&str> = row.split('\t').collect();
+		let term = TermData {
+			expression: w.intern(row[0].to_string()),
+			reading: w.intern(row[1].to_string()),
+			search_key: 0,
+			score: 0,
+			sequence: i as u32,
+			order: 0,
+			frequency: row[2].parse().unwrap(),
+			glossary: row[4].split(';').map(|x| w.intern(x.to_string())).collect(),
+			rules: Vec::new(),
+			term_tags: Vec::new(),
+			definition_tags: Vec::new(),
+			source: w.intern(row[5].to_string()),
+			flags: TermFlags::default(),
+			pitch: Vec::new(),
+		};
+		w.push_term(term);
+	}
+
+	let mut data = Vec::new();
+	w.write(&mut data).unwrap();
+	data
+}
+
+/// Reads the fields of a term, returning a value so the reads are not
+/// optimized away.
+fn read_term(db: &DB, index: usize) -> usize {
+	let term = db.term(index).unwrap();
+	let mut out = term.expression().len() + term.reading().len() + term.search_key().len();
+	out += term.glossary().map(|x| x.len()).sum::<usize>();
+	out += term.definition_tags().map(|x| x.name().len()).count();
+	out += term.pitch().count() + term.frequency().unwrap_or(0) as usize + term.source().len();
+	out += term.flags().0 as usize + term.priority().0 as usize + term.length();
+	out
+}
+
+#[test]
+fn index_searches_do_not_allocate() {
+	let data = build(Profile::Full);
+	let db = DB::load(&data);
+
+	let (count, allocs) = allocations(|| db.search_term_iter("たべる").count());
+	assert_eq!((count, allocs), (2, 0));
+	let (count, allocs) = allocations(|| db.search_prefix_iter("たべ").count());
+	assert_eq!((count, allocs), (4, 0));
+	let (count, allocs) = allocations(|| db.search_suffix_iter("べる").count());
+	assert_eq!((count, allocs), (4, 0));
+	let (count, allocs) = allocations(|| db.search_term_iter("存在しない").count());
+	assert_eq!((count, allocs), (0, 0));
+
+	// Keys that are not normalized allocate for the normalization
+	let (count, allocs) = allocations(|| db.search_term_iter("ﾊﾟﾝ").count());
+	assert_eq!(count, db.search_term_iter("パン").count());
+	assert!(count > 0 && allocs > 0);
+}
+
+#[test]
+fn lazy_suffix_index_does_not_allocate_once_built() {
+	let data = build(Profile::Compact);
+	let db = DB::load(&data);
+	db.build_missing_indexes(|_, _| {});
+
+	let (count, allocs) = allocations(|| db.search_suffix_iter("べる").count());
+	assert_eq!((count, allocs), (4, 0));
+}
+
+#[test]
+fn term_views_do_not_allocate() {
+	let data = build(Profile::Full);
+	let db = DB::load(&data);
+
+	let (total, allocs) = allocations(|| {
+		let mut total = 0;
+		for index in db.search_prefix_iter("た") {
+			total += read_term(&db, index);
+		}
+		for index in 0..db.terms().len() {
+			total += read_term(&db, index);
+		}
+		total
+	});
+	assert!(total > 0);
+	assert_eq!(allocs, 0);
+}
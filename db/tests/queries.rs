@@ -0,0 +1,1312 @@
+//! Snapshot tests for query results over a small checked-in dictionary.
+//!
+//! The database is built from `tests/data/mini-dict.tsv` and the results of
+//! each query are compared against `tests/snapshots/queries.snap`.
+//!
+//! The database built from the same data is also checked in as
+//! `tests/snapshots/mini-dict.db`, to check that the loader reads it and the
+//! writer reproduces it byte for byte.
+//!
+//! To update the snapshots after an intended change in the results or the
+//! format, run the tests with `UPDATE_SNAPSHOTS=1` and review the diff.
+
+extern crate db;
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+
+use db::*;
+
+/// Queries for the snapshot. Lines are prefixed by the kind of search:
+///
+/// - `exact:`, `prefix:` and `suffix:` use the respective index search.
+/// - `top:` uses the prefix search limited to the [TOP_LIMIT] most relevant
+///   results.
+/// - `query:` uses the query language.
+/// - `group:` uses the query language and groups the results by headword.
+/// - `scan:` scans the text for terms.
+/// - `family:` groups the terms starting with a stem by conjugation class.
+/// - `related:` lists the related terms for the first exact match.
+const QUERIES: &[&str] = &[
+	"exact:たべる",
+	"exact:食べる",
+	"exact:ﾊﾟﾝ",
+	"exact:たべ",
+	"prefix:たべ",
+	"prefix:食べ",
+	"prefix:の",
+	"prefix:ﾊﾟ",
+	"prefix:ずっと",
+	"suffix:べる",
+	"suffix:ﾊﾟﾝ",
+	"suffix:猫",
+	"top:た",
+	"top:の",
+	"query:eat",
+	"query:\"to drink\"",
+	"query:glossary:student",
+	"query:glossary:ネコ科",
+	"query:tag:P",
+	"query:tag:vt -reading:のむ",
+	"query:reading:たべ* OR reading:のみ*",
+	"query:(tag:n tag:P) OR source:Other",
+	"query:expression:猫",
+	"query:go OR do",
+	"query:pitch:heiban",
+	"query:pitch:heiban morae:3 tag:n",
+	"query:pitch:atamadaka",
+	"query:pitch:nakadaka",
+	"query:pitch:odaka",
+	"query:pitch:2 morae:3",
+	"query:script:kana",
+	"query:script:katakana",
+	"query:script:kanji length:3",
+	"group:reading:たべる",
+	"scan:大学生は食べ物を食べる",
+	"scan:子猫と犬とパン",
+	"scan:パソコンでhttps://example.com/a?b=1を見た。2,000円とＡＢＣ-xyzとカメラ・ストラップ",
+	"scan:令和六年3月５日の三時半に犬を三匹と子猫を二十匹、3.5倍のA4",
+	"family:食べ",
+	"family:の",
+	"family:い",
+	"related:食べる",
+	"related:子猫",
+	"related:学生",
+];
+
+/// Tags in the mini dictionary that are conjugation classes, which are also
+/// added as the term rules.
+const CONJUGATION_TAGS: &[&str] = &["v1", "v5", "vs", "adj-i"];
+
+/// Limit for the `top:` queries.
+const TOP_LIMIT: usize = 2;
+
+/// Reference database built from the mini dictionary, checked in to catch
+/// accidental changes to the binary format (see `golden_database`).
+const GOLDEN_DATABASE: &str = "snapshots/mini-dict.db";
+
+fn data_path(name: &str) -> PathBuf {
+	let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	path.push("tests");
+	path.push(name);
+	path
+}
+
+/// Builds the database from the mini dictionary.
+fn build(profile: Profile) -> Vec<u8> {
+	build_with(profile, |_| {})
+}
+
+/// Builds the database from the mini dictionary, with additional writer
+/// settings.
+fn build_with<F: FnOnce(&mut Writer)>(profile: Profile, settings: F) -> Vec<u8> {
+	let input = fs::read_to_string(data_path("data/mini-dict.tsv")).unwrap();
+	let rows: Vec<Vec<&str>> = input
+		.lines()
+		.filter(|x| x.trim().len() > 0 && !x.starts_with('#'))
+		.map(|x| x.split('\t').collect())
+		.collect();
+
+	let mut w = Writer::new();
+	w.set_profile(profile);
+
+	let mut tags: Vec<&str> = rows.iter().flat_map(|x| x[3].split(',')).collect();
+	tags.sort();
+	tags.dedup();
+	for (order, &name) in tags.iter().enumerate() {
+		let name = w.intern(name.to_string());
+		w.push_tag(TagData {
+			name: name,
+			category: 0,
+			order: order as i32,
+			notes: 0,
+		});
+	}
+
+	let mut source_order: HashMap<&str, u32> = HashMap::new();
+	for (i, row) in rows.iter().enumerate() {
+		let order = source_order.entry(row[5]).or_insert(0);
+		let flags = if row.get(6) == Some(&"ja") {
+			TermFlags::MONOLINGUAL
+		} else {
+			TermFlags::default()
+		};
+		let term = TermData {
+			expression: w.intern(row[0].to_string()),
+			reading: w.intern(row[1].to_string()),
+			search_key: 0,
+			score: 0,
+			sequence: i as u32,
+			order: *order,
+			frequency: row[2].parse().unwrap(),
+			glossary: row[4].split(';').map(|x| w.intern(x.to_string())).collect(),
+			rules: w.get_tags(row[3].split(',').filter(|x| CONJUGATION_TAGS.contains(x))),
+			term_tags: Vec::new(),
+			definition_tags: w.get_tags(row[3].split(',')),
+			source: w.intern(row[5].to_string()),
+			flags: flags,
+			pitch: match row.get(7) {
+				Some(pitch) => pitch.split(',').map(|x| x.parse().unwrap()).collect(),
+				None => Vec::new(),
+			},
+		};
+		*order += 1;
+		w.push_term(term);
+	}
+
+	settings(&mut w);
+
+	let mut data = Vec::new();
+	w.write(&mut data).unwrap();
+	data
+}
+
+/// Returns a term from the `extra` source with the other fields empty, to
+/// be completed with the struct update syntax.
+fn term(w: &mut Writer, expression: &str, reading: &str) -> TermData {
+	TermData {
+		expression: w.intern(expression.to_string()),
+		reading: w.intern(reading.to_string()),
+		search_key: 0,
+		score: 0,
+		sequence: 0,
+		order: 0,
+		frequency: 0,
+		glossary: Vec::new(),
+		rules: Vec::new(),
+		term_tags: Vec::new(),
+		definition_tags: Vec::new(),
+		source: w.intern("extra".to_string()),
+		flags: TermFlags::default(),
+		pitch: Vec::new(),
+	}
+}
+
+/// Returns the expressions for the results of a query.
+fn query_expressions(db: &DB, query: &Query) -> Vec<String> {
+	let terms = db.query(query).into_iter().map(|x| db.term(x).unwrap());
+	terms.map(|x| x.expression().to_string()).collect()
+}
+
+/// Returns the expressions for the results of a query in the query
+/// language.
+fn expressions(db: &DB, query: &str) -> Vec<String> {
+	query_expressions(db, &Query::parse(query).unwrap())
+}
+
+/// Returns the expressions for the exact matches of a term.
+fn search_expressions(db: &DB, text: &str) -> Vec<String> {
+	let mut results = ResultSet::default();
+	db.search_term(text, &mut results);
+	results.iter().map(|x| db.term(x).unwrap().expression().to_string()).collect()
+}
+
+fn term_line(db: &DB, index: usize) -> String {
+	let term = db.term(index).unwrap();
+	format!(
+		"#{} {}【{}】 {} ({})",
+		index,
+		term.expression(),
+		term.reading(),
+		term.glossary().collect::<Vec<_>>().join("; "),
+		term.source()
+	)
+}
+
+fn run(db: &DB, query: &str) -> String {
+	let mut out = String::new();
+	let (kind, text) = match query.find(':') {
+		Some(pos) => (&query[..pos], &query[pos + 1..]),
+		None => panic!("invalid snapshot query: {}", query),
+	};
+
+	match kind {
+		"exact" | "prefix" | "suffix" => {
+			let mut results = ResultSet::default();
+			if kind == "exact" {
+				db.search_term(text, &mut results);
+			} else if kind == "prefix" {
+				db.search_prefix(text, &mut results);
+			} else {
+				db.search_suffix(text, &mut results);
+			}
+			for index in results.iter() {
+				writeln!(out, "  {}", term_line(db, index)).unwrap();
+			}
+		}
+		"query" => {
+			let query = Query::parse(text).unwrap();
+			for index in db.query(&query) {
+				writeln!(out, "  {}", term_line(db, index)).unwrap();
+			}
+		}
+		"group" => {
+			let query = Query::parse(text).unwrap();
+			for headword in db.group_by_headword(db.query(&query)) {
+				writeln!(out, "  {}【{}】", headword.expression, headword.reading).unwrap();
+				for source in headword.sources {
+					writeln!(out, "    {}: {}", source.source, source.glossary.join("; ")).unwrap();
+					for sense in source.senses {
+						let glossary = sense.glossary.join("; ");
+						writeln!(out, "      #{} {}:{} {}", sense.term, sense.source, sense.sequence, glossary).unwrap();
+					}
+				}
+			}
+		}
+		"scan" => {
+			for it in db.scan(text) {
+				let numeric = match it.numeric {
+					Some(ref numeric) => format!(" = {}「{}」", numeric.value, numeric.reading),
+					None => String::new(),
+				};
+				if it.kind != TokenKind::Term {
+					writeln!(out, "  {} ({}){}", it.text(text), it.kind.name(), numeric).unwrap();
+					continue;
+				}
+				let terms: Vec<_> = it.terms.iter().map(|x| format!("#{}", x)).collect();
+				writeln!(out, "  {} => {}{}", it.text(text), terms.join(" "), numeric).unwrap();
+			}
+		}
+		"family" => {
+			for family in db.word_family(text) {
+				writeln!(out, "  {}", family.class.unwrap_or("(other)")).unwrap();
+				for index in family.terms {
+					writeln!(out, "    {}", term_line(db, index)).unwrap();
+				}
+			}
+		}
+		"related" => {
+			let mut results = ResultSet::default();
+			db.search_term(text, &mut results);
+			if let Some(index) = results.iter().next() {
+				for index in db.related(index) {
+					writeln!(out, "  {}", term_line(db, index)).unwrap();
+				}
+			}
+		}
+		"top" => {
+			for index in db.search_prefix_top(text, TOP_LIMIT) {
+				writeln!(out, "  {}", term_line(db, index)).unwrap();
+			}
+		}
+		_ => panic!("invalid snapshot query kind: {}", kind),
+	}
+
+	if out.len() == 0 {
+		out.push_str("  (no results)\n");
+	}
+	out
+}
+
+fn run_all(db: &DB) -> String {
+	let mut output = String::new();
+	for query in QUERIES {
+		writeln!(output, "{}", query).unwrap();
+		output.push_str(&run(db, query));
+		output.push('\n');
+	}
+	output
+}
+
+#[test]
+fn query_snapshots() {
+	let data = build(Profile::Full);
+	let db = DB::load(&data);
+	let output = run_all(&db);
+
+	let path = data_path("snapshots/queries.snap");
+	if std::env::var("UPDATE_SNAPSHOTS").is_ok() {
+		fs::write(&path, &output).unwrap();
+		return;
+	}
+
+	let expected = fs::read_to_string(&path).unwrap_or_default();
+	if output != expected {
+		let actual = path.with_extension("snap.new");
+		fs::write(&actual, &output).unwrap();
+		panic!(
+			"query results do not match the snapshot (see {:?} for the actual \
+			 results, or run with UPDATE_SNAPSHOTS=1 to update the snapshot)",
+			actual
+		);
+	}
+}
+
+#[test]
+fn golden_database() {
+	let data = build_with(Profile::Full, |w| {
+		w.set_build_time(UNIX_EPOCH + Duration::from_secs(1717243200));
+	});
+	let path = data_path(GOLDEN_DATABASE);
+	if std::env::var("UPDATE_SNAPSHOTS").is_ok() {
+		fs::write(&path, &data).unwrap();
+		return;
+	}
+
+	// The version is read from the raw header, which comes after the entry
+	// count and the magic number.
+	let golden = fs::read(&path).unwrap();
+	let version = u32::from_le_bytes([golden[8], golden[9], golden[10], golden[11]]);
+	let db = DB::load(&data);
+	assert!(
+		version == db.info().version,
+		"the format version changed from {} to {}, run with UPDATE_SNAPSHOTS=1 to update the reference database",
+		version,
+		db.info().version
+	);
+
+	// The current loader reads the reference database
+	let golden_db = DB::load(&golden);
+	golden_db.check();
+	assert_eq!(run_all(&golden_db), run_all(&db));
+
+	// The current writer reproduces it, unless the format changed
+	assert!(
+		data == golden,
+		"the database output does not match the reference database: bump FORMAT_VERSION if the format \
+		 changed, then run with UPDATE_SNAPSHOTS=1 to update it"
+	);
+}
+
+#[test]
+fn compact_profile_builds_missing_indexes() {
+	let full = build(Profile::Full);
+	let full = DB::load(&full);
+	assert_eq!(full.profile(), Profile::Full);
+
+	let compact = build(Profile::Compact);
+	let compact = DB::load(&compact);
+	assert_eq!(compact.profile(), Profile::Compact);
+	assert!(!compact.has_suffix_index());
+
+	let mut calls = Vec::new();
+	compact.build_missing_indexes(|done, total| calls.push((done, total)));
+	assert!(compact.has_suffix_index());
+	assert!(calls.len() > 0);
+	assert_eq!(calls.last(), Some(&(calls[0].1, calls[0].1)));
+
+	assert_eq!(run_all(&compact), run_all(&full));
+}
+
+#[test]
+fn load_sections() {
+	let data = build_with(Profile::Full, |w| {
+		let kanji = KanjiData {
+			character: '猫',
+			frequency: 0,
+			meanings: vec![w.intern("cat".to_string())],
+			onyomi: Vec::new(),
+			kunyomi: Vec::new(),
+			tags: Vec::new(),
+			stats: Vec::new(),
+			similar: Vec::new(),
+			source: w.intern("kanjidic".to_string()),
+		};
+		w.push_kanji(kanji);
+	});
+
+	let full = DB::load(&data);
+	assert_eq!(full.loaded_sections(), Section::ALL);
+
+	let db = DB::load_sections(&data, &[Section::Kanji]);
+	let sections = vec![Section::Tags, Section::Kanji, Section::Vectors, Section::Strings];
+	assert_eq!(db.loaded_sections(), sections);
+	assert!(!db.is_loaded(Section::Terms));
+	assert_eq!(db.section_size(Section::PrefixIndex).bytes, 0);
+
+	let kanji = db.search_kanji('猫').unwrap();
+	assert_eq!(kanji.meanings().collect::<Vec<_>>(), vec!["cat"]);
+	assert_eq!(db.terms().len(), 0);
+	let mut results = ResultSet::default();
+	assert_eq!(db.search_term("ねこ", &mut results), 0);
+	assert_eq!(db.search_prefix("ね", &mut results), 0);
+
+	let db = DB::load_sections(&data, &[Section::PrefixIndex]);
+	assert!(db.is_loaded(Section::Terms));
+	assert!(!db.is_loaded(Section::Kanji));
+	assert!(db.search_kanji('猫').is_none());
+	assert_eq!(run(&db, "exact:ねこ"), run(&full, "exact:ねこ"));
+}
+
+#[test]
+fn media_files() {
+	let data = build_with(Profile::Full, |w| {
+		for (path, data) in [("img/cat.png", b"cat1"), ("img/dog.JPG", b"dog1"), ("img/cat.png", b"cat2")].iter() {
+			let media = MediaData {
+				source: w.intern("Test".to_string()),
+				path: w.intern(path.to_string()),
+				data: data.to_vec(),
+			};
+			w.push_media(media);
+		}
+	});
+
+	let db = DB::load(&data);
+	db.check();
+	assert_eq!(db.media_files().count(), 2);
+	assert_eq!(db.section_size(Section::Media).entries, 2);
+
+	let cat = db.media("Test", "img/cat.png").unwrap();
+	assert_eq!((cat.source(), cat.path(), cat.data()), ("Test", "img/cat.png", &b"cat1"[..]));
+	assert_eq!(cat.mime_type(), "image/png");
+	assert_eq!(db.media("Test", "img/dog.JPG").unwrap().mime_type(), "image/jpeg");
+	assert!(db.media("Other", "img/cat.png").is_none());
+	assert!(db.media("Test", "cat.png").is_none());
+
+	let db = DB::load_sections(&data, &[Section::Terms]);
+	assert!(db.media("Test", "img/cat.png").is_none());
+}
+
+#[test]
+fn media_files_odd_length() {
+	// The sections after the media data must stay aligned
+	let data = build_with(Profile::Full, |w| {
+		let media = MediaData {
+			source: w.intern("Test".to_string()),
+			path: w.intern("audio/neko.mp3".to_string()),
+			data: b"abc".to_vec(),
+		};
+		w.push_media(media);
+	});
+
+	let db = DB::load(&data);
+	db.check();
+	assert_eq!(db.media("Test", "audio/neko.mp3").unwrap().data(), &b"abc"[..]);
+	assert_eq!(db.section_size(Section::Media).entries, 1);
+	assert!(db.search_term_iter("ねこ").len() > 0);
+}
+
+#[test]
+fn validate_before_write() {
+	let mut w = Writer::new();
+	let term = TermData {
+		glossary: vec![99],
+		rules: vec![3],
+		..term(&mut w, "猫", "")
+	};
+	w.push_term(term);
+
+	let problems = w.validate();
+	assert_eq!(
+		problems,
+		vec![
+			"term 0: glossary string 99 out of bounds (3 strings)",
+			"term 0: rules tag 3 out of bounds (0 tags)",
+		]
+	);
+
+	w.set_validate(true);
+	let mut data = Vec::new();
+	let err = w.write(&mut data).unwrap_err();
+	assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+	assert_eq!(data.len(), 0);
+}
+
+#[test]
+fn header_info() {
+	let data = build_with(Profile::Compact, |w| {
+		w.set_build_time(UNIX_EPOCH + Duration::from_secs(1717243200));
+		w.set_source_hash([0xAB; 32]);
+		let name = w.intern("dict.zip".to_string());
+		w.push_source_file(SourceFileData { name: name, hash: [1; 32] });
+		let importer = w.intern("1.2.3".to_string());
+		w.set_importer(importer);
+	});
+	let db = DB::load(&data);
+
+	let info = DatabaseInfo::read(&mut &data[..DatabaseInfo::SIZE]).unwrap();
+	assert_eq!(info.terms, db.terms().len());
+	assert_eq!(info.profile, Profile::Compact);
+	assert_eq!(info.build_date(), "2024-06-01");
+	assert_eq!(info.source_hash_hex(), "ab".repeat(32));
+	assert_eq!(info.suffix_index, 0);
+	assert!(info.prefix_index > 0);
+	assert_eq!(db.info().prefix_index, info.prefix_index);
+	assert_eq!(db.importer(), "1.2.3");
+	let files: Vec<_> = db.source_files().map(|x| (x.name, x.hash)).collect();
+	assert_eq!(files, vec![("dict.zip", [1; 32])]);
+
+	assert!(DatabaseInfo::read(&mut &data[1..DatabaseInfo::SIZE + 1]).is_err());
+
+	// The compact profile has no suffix and per-character indexes
+	assert_eq!(info.capabilities, db.capabilities());
+	assert_eq!(info.capabilities.names(), vec!["pitch", "glossary_index"]);
+	let full = DB::load(&build(Profile::Full)).capabilities();
+	assert!(full.contains(Capabilities::SUFFIX_INDEX | Capabilities::CHARS_INDEX | Capabilities::PITCH));
+	assert!(!full.contains(Capabilities::EMBEDDINGS));
+	assert!(!full.contains(Capabilities::MEDIA));
+}
+
+#[test]
+fn string_stats() {
+	let data = build_with(Profile::Full, |w| {
+		w.intern("To  Eat".to_string());
+	});
+	let db = DB::load(&data);
+	let stats = db.string_stats();
+
+	assert_eq!(stats.histogram.iter().map(|x| x.count).sum::<usize>(), stats.count);
+	assert_eq!(stats.histogram.iter().map(|x| x.bytes).sum::<usize>(), stats.bytes);
+	assert_eq!(stats.bytes, stats.data_bytes);
+
+	let groups: Vec<_> = stats.near_duplicates.iter().map(|x| x.strings.clone()).collect();
+	assert!(groups.contains(&vec!["to eat".to_string(), "To  Eat".to_string()]));
+
+	let sources: Vec<_> = stats.sources.iter().map(|x| x.name.as_str()).collect();
+	assert_eq!(sources, vec!["JMdict", "国語", "Other"]);
+	let total = stats.sources.iter().map(|x| x.count).sum::<usize>() + stats.shared.count + stats.other.count;
+	assert_eq!(total, stats.count);
+	assert!(stats.shared.count > 0);
+}
+
+#[test]
+fn tag_translations() {
+	let data = build_with(Profile::Full, |w| {
+		let tag = w.get_tag("vt");
+		for &(language, notes) in &[("pt", "comum"), ("es", "común"), ("pt", "palavra comum")] {
+			let translation = TagTranslationData {
+				tag: tag,
+				language: w.intern(language.to_string()),
+				notes: w.intern(notes.to_string()),
+			};
+			w.push_tag_translation(translation);
+		}
+	});
+	let db = DB::load(&data);
+	assert_eq!(db.tag_languages(), vec!["es", "pt"]);
+
+	let mut results = ResultSet::default();
+	db.search_term("たべる", &mut results);
+	let term = db.term(results.iter().next().unwrap()).unwrap();
+	let tag = term.definition_tags().find(|x| x.name() == "vt").unwrap();
+	assert_eq!(tag.notes_in("pt"), "palavra comum");
+	assert_eq!(tag.notes_in("es"), "común");
+	assert_eq!(tag.notes_in("de"), tag.notes());
+}
+
+#[test]
+fn embeddings_nearest() {
+	let data = build_with(Profile::Full, |w| {
+		let vectors: &[(&str, [f32; 3])] = &[
+			("猫", [1.0, 0.1, 0.0]),
+			("子猫", [0.9, 0.2, 0.0]),
+			("犬", [0.7, 0.7, 0.0]),
+			("パン", [0.0, 0.1, 1.0]),
+		];
+		for &(expression, vector) in vectors {
+			let expression = w.find_string(expression).unwrap();
+			w.set_embedding(expression, vector.to_vec());
+		}
+	});
+	let db = DB::load(&data);
+	assert_eq!(db.embedding_dim(), 3);
+
+	let expression = |index: usize| db.term(index).unwrap().expression().to_string();
+	let mut results = ResultSet::default();
+	db.search_term("猫", &mut results);
+	let cat = results.iter().next().unwrap();
+
+	let similar: Vec<_> = db.similar_terms(cat, 2).into_iter().map(|x| expression(x.0)).collect();
+	assert_eq!(similar, vec!["子猫", "犬"]);
+
+	let nearest = db.nearest_terms(&[0.0, 0.0, 1.0], 1);
+	assert_eq!(expression(nearest[0].0), "パン");
+	assert!(nearest[0].1 > 0.99);
+
+	let mut results = ResultSet::default();
+	db.search_term("学生", &mut results);
+	assert_eq!(db.embedding(results.iter().next().unwrap()), None);
+}
+
+#[test]
+fn merged_senses() {
+	let data = build_with(Profile::Full, |w| {
+		let term = TermData {
+			glossary: vec![w.intern(" To eat".to_string())],
+			source: w.intern("Extra".to_string()),
+			..term(w, "食べる", "たべる")
+		};
+		w.push_term(term);
+	});
+	let db = DB::load(&data);
+
+	let mut results = ResultSet::default();
+	db.search_term("たべる", &mut results);
+	let headwords = db.group_by_headword(results.iter());
+	let senses = headwords[0].merged_senses();
+	assert_eq!(senses.len(), 2);
+	assert_eq!(senses[0].glossary, vec!["to eat"]);
+	assert_eq!(senses[0].sources, vec!["JMdict", "Extra"]);
+	assert_eq!(senses[0].terms.len(), 2);
+	assert_eq!(senses[1].glossary, vec!["eat (food)"]);
+	assert_eq!(senses[1].sources, vec!["Other"]);
+}
+
+#[test]
+fn tags_in_display_order() {
+	let data = build(Profile::Full);
+	let db = DB::load(&data);
+
+	// Rules are also definition tags, so `v5` is listed once
+	let mut results = ResultSet::default();
+	db.search_term("のむ", &mut results);
+	let term = db.term(results.iter().next().unwrap()).unwrap();
+	let tags: Vec<_> = term.tags().iter().map(|x| x.name()).collect();
+	assert_eq!(tags, vec!["v5", "vt"]);
+
+	let mut results = ResultSet::default();
+	db.search_term("ねこ", &mut results);
+	let term = db.term(results.iter().next().unwrap()).unwrap();
+	let tags: Vec<_> = term.tags().iter().map(|x| x.name()).collect();
+	assert_eq!(tags, vec!["P", "n"]);
+}
+
+#[test]
+fn stable_term_ids() {
+	let data = build(Profile::Full);
+	let db = DB::load(&data);
+
+	// A new term more frequent than all others shifts the indexes of the
+	// existing terms, but not their ids.
+	let shifted = build_with(Profile::Full, |w| {
+		let term = TermData {
+			frequency: u32::max_value(),
+			glossary: vec![w.intern("new word".to_string())],
+			..term(w, "新語", "しんご")
+		};
+		w.push_term(term);
+	});
+	let shifted = DB::load(&shifted);
+	shifted.check();
+
+	for term in db.terms() {
+		let found = db.term_by_id(term.id()).unwrap();
+		assert_eq!(found.index(), term.index());
+
+		let found = shifted.term_by_id(term.id()).unwrap();
+		assert_eq!(found.index(), term.index() + 1);
+		assert_eq!(found.expression(), term.expression());
+		assert_eq!(found.glossary().collect::<Vec<_>>(), term.glossary().collect::<Vec<_>>());
+	}
+	assert_eq!(shifted.term(0).unwrap().expression(), "新語");
+}
+
+#[test]
+fn deterministic_term_order() {
+	// Terms with the same frequency, ordered by sequence, source and
+	// expression regardless of the order they are added. `ichi1` and `news1`
+	// are different priorities with the same rank.
+	let words = [
+		("鳥", "とり", 2, "A", ""),
+		("魚", "さかな", 1, "B", ""),
+		("虫", "むし", 1, "A", ""),
+		("花", "はな", 1, "A", ""),
+		("猫", "ねこ", 4, "A", "news1"),
+		("犬", "いぬ", 3, "A", "ichi1"),
+	];
+	let write = |order: &[usize]| {
+		let mut w = Writer::new();
+		w.set_build_time(UNIX_EPOCH);
+		for &name in ["ichi1", "news1"].iter() {
+			let tag = TagData {
+				name: w.intern(name.to_string()),
+				category: 0,
+				order: 0,
+				notes: 0,
+			};
+			w.push_tag(tag);
+		}
+		// Strings are interned in the same order, so only the term order
+		// differs between the builds
+		for &(expression, reading, _, source, _) in words.iter() {
+			for &text in [expression, reading, source, "extra"].iter() {
+				w.intern(text.to_string());
+			}
+		}
+		for &i in order {
+			let (expression, reading, sequence, source, tags) = words[i];
+			let term = TermData {
+				sequence: sequence,
+				frequency: 100,
+				term_tags: w.get_tags(tags.split(',').filter(|x| x.len() > 0)),
+				source: w.intern(source.to_string()),
+				..term(&mut w, expression, reading)
+			};
+			w.push_term(term);
+		}
+		let mut data = Vec::new();
+		w.write(&mut data).unwrap();
+		data
+	};
+
+	let data = write(&[0, 1, 2, 3, 4, 5]);
+	assert!(data == write(&[5, 4, 3, 2, 1, 0]));
+	assert!(data == write(&[2, 0, 5, 3, 1, 4]));
+
+	let db = DB::load(&data);
+	db.check();
+	let terms: Vec<_> = db.terms().map(|x| x.expression()).collect();
+	assert_eq!(terms, vec!["犬", "猫", "花", "虫", "魚", "鳥"]);
+}
+
+#[test]
+fn diff_builds() {
+	let write = |words: &[(&str, &str, u32)], tags: &[&str]| {
+		let mut w = Writer::new();
+		for &name in tags {
+			let name = w.intern(name.to_string());
+			w.push_tag(TagData {
+				name: name,
+				category: 0,
+				order: 0,
+				notes: 0,
+			});
+		}
+		for &(expression, glossary, frequency) in words {
+			let term = TermData {
+				frequency: frequency,
+				glossary: vec![w.intern(glossary.to_string())],
+				definition_tags: vec![w.get_tag("n")],
+				source: w.intern("dict".to_string()),
+				..term(&mut w, expression, "")
+			};
+			w.push_term(term);
+		}
+		let mut data = Vec::new();
+		w.write(&mut data).unwrap();
+		data
+	};
+	let old = write(&[("猫", "cat", 100), ("犬", "dog", 50), ("魚", "fish", 20)], &["n"]);
+	let new = write(&[("猫", "cat", 100), ("魚", "fish; seafood", 20), ("鳥", "bird", 10)], &["n", "uk"]);
+	let (old, new) = (DB::load(&old), DB::load(&new));
+
+	let diff = old.diff(&new);
+	let expression = |db: &DB, index: usize| db.term(index).unwrap().expression().to_string();
+	let added: Vec<_> = diff.terms_added.iter().map(|&x| expression(&new, x)).collect();
+	let removed: Vec<_> = diff.terms_removed.iter().map(|&x| expression(&old, x)).collect();
+	assert_eq!(added, vec!["鳥"]);
+	assert_eq!(removed, vec!["犬"]);
+	assert_eq!(diff.terms_changed.len(), 1);
+	assert_eq!(expression(&new, diff.terms_changed[0].new), "魚");
+	assert_eq!(diff.terms_changed[0].fields, vec!["glossary"]);
+	assert_eq!(diff.tags_added, vec!["uk"]);
+	assert!(diff.tags_removed.is_empty());
+
+	let terms = diff.sections.iter().find(|x| x.section == Section::Terms).unwrap();
+	assert_eq!(terms.entries(), 0);
+	assert!(!diff.is_empty());
+	assert!(old.diff(&old).is_empty());
+}
+
+#[test]
+fn okurigana_variants_indexed() {
+	// `食べ物` has the `食物` variant, which is also a word on its own
+	let words = [
+		("受け付け", "うけつけ", ""),
+		("書き込む", "かきこむ", "v5"),
+		("食物", "しょくもつ", ""),
+	];
+	let data = build_with(Profile::Full, |w| {
+		for &(expression, reading, rules) in words.iter() {
+			let term = TermData {
+				rules: w.get_tags(rules.split(',').filter(|x| x.len() > 0)),
+				..term(w, expression, reading)
+			};
+			w.push_term(term);
+		}
+	});
+	let db = DB::load(&data);
+	db.check();
+
+	let search = |text: &str| search_expressions(&db, text);
+	assert_eq!(search("受付け"), vec!["受け付け"]);
+	assert_eq!(search("受付"), vec!["受け付け"]);
+	assert_eq!(search("書込む"), vec!["書き込む"]);
+	assert!(search("書込").is_empty());
+	assert_eq!(search("食物"), vec!["食物"]);
+
+	let text = "受付で書込む";
+	let matches: Vec<_> = db.scan(text).iter().map(|x| x.text(text).to_string()).collect();
+	assert_eq!(matches, vec!["受付", "書込む"]);
+}
+
+#[test]
+fn middle_dot_names_indexed() {
+	let words = [("マリー・アントワネット", ""), ("アントワネット", "")];
+	let data = build_with(Profile::Full, |w| {
+		for &(expression, reading) in words.iter() {
+			let term = term(w, expression, reading);
+			w.push_term(term);
+		}
+	});
+	let db = DB::load(&data);
+	db.check();
+
+	let search = |text: &str| search_expressions(&db, text);
+	assert_eq!(search("マリー・アントワネット"), vec!["マリー・アントワネット"]);
+	assert_eq!(search("マリーアントワネット"), vec!["マリー・アントワネット"]);
+	assert_eq!(search("ﾏﾘｰ･ｱﾝﾄﾜﾈｯﾄ"), vec!["マリー・アントワネット"]);
+	assert_eq!(search("マリー"), vec!["マリー・アントワネット"]);
+	assert_eq!(search("アントワネット"), vec!["アントワネット", "マリー・アントワネット"]);
+	assert_eq!(db.key_variants("マリーアントワネット"), vec!["マリー・アントワネット"]);
+
+	let text = "マリーアントワネットの話";
+	let matches: Vec<_> = db.scan(text).iter().map(|x| x.text(text).to_string()).collect();
+	assert_eq!(matches, vec!["マリーアントワネット"]);
+}
+
+#[test]
+fn index_pairs_unique() {
+	// The monolingual `ねこ` has the same expression and reading, so both
+	// produce the same index key
+	let data = build(Profile::Full);
+	let db = DB::load(&data);
+	let unique = |terms: Vec<usize>| {
+		let mut sorted = terms.clone();
+		sorted.sort();
+		sorted.dedup();
+		sorted.len() == terms.len()
+	};
+	for &text in ["ねこ", "ね", "こ"].iter() {
+		assert!(unique(db.search_prefix_iter(text).collect()), "duplicate prefix match for `{}`", text);
+		assert!(unique(db.search_suffix_iter(text).collect()), "duplicate suffix match for `{}`", text);
+	}
+	assert_eq!(db.search_term_iter("ねこ").len(), 2);
+}
+
+#[test]
+fn word_origins() {
+	let words = [("パン", ""), ("アルバイト", ""), ("天ぷら", "てんぷら"), ("食べる", "たべる")];
+	let origins = [
+		("パン", "", "por", "pão"),
+		("アルバイト", "", "ger", "Arbeit"),
+		("天ぷら", "てんぷら", "por", ""),
+	];
+	let data = build_with(Profile::Full, |w| {
+		for &(expression, reading) in words.iter() {
+			let term = term(w, expression, reading);
+			w.push_term(term);
+		}
+		for &(expression, reading, language, word) in origins.iter() {
+			let origin = OriginData {
+				expression: w.intern(expression.to_string()),
+				reading: w.intern(reading.to_string()),
+				language: w.intern(language.to_string()),
+				word: w.intern(word.to_string()),
+			};
+			w.push_origin(origin);
+		}
+	});
+	let db = DB::load(&data);
+	db.check();
+
+	let origins = |expression: &str| {
+		let mut results = ResultSet::default();
+		db.search_term(expression, &mut results);
+		let term = db.term(results.iter().next().unwrap()).unwrap();
+		term.origins().map(|(lang, word)| format!("{}:{}", lang, word)).collect::<Vec<_>>()
+	};
+	assert_eq!(origins("パン"), vec!["por:pão"]);
+	assert_eq!(origins("天ぷら"), vec!["por:"]);
+	assert!(origins("食べる").is_empty());
+
+	let query = |text: &str| expressions(&db, text);
+	// Origins apply to the terms from every source, including the パン from
+	// the base dictionary
+	assert_eq!(query("origin:por"), vec!["パン", "パン", "天ぷら"]);
+	assert_eq!(query("origin:GER"), vec!["アルバイト"]);
+	assert_eq!(query("origin:*").len(), 4);
+	assert!(query("origin:fre").is_empty());
+}
+
+#[test]
+fn domain_tags() {
+	let words = [("胃炎", "いえん", "med"), ("判例", "はんれい", "law"), ("病院", "びょういん", "n")];
+	let data = build_with(Profile::Full, |w| {
+		for &(name, notes) in [("med", "medicine"), ("law", "law")].iter() {
+			let tag = TagData {
+				name: w.intern(name.to_string()),
+				category: w.intern("domain".to_string()),
+				order: 0,
+				notes: w.intern(notes.to_string()),
+			};
+			w.push_tag(tag);
+		}
+		for &(expression, reading, tag) in words.iter() {
+			let term = TermData {
+				definition_tags: vec![w.get_tag(tag)],
+				..term(w, expression, reading)
+			};
+			w.push_term(term);
+		}
+	});
+	let db = DB::load(&data);
+	db.check();
+
+	let domains: Vec<_> = db.domains().iter().map(|x| format!("{} ({})", x.name(), x.notes())).collect();
+	assert_eq!(domains, vec!["law (law)", "med (medicine)"]);
+
+	let query = |text: &str| expressions(&db, text);
+	assert_eq!(query("domain:med"), vec!["胃炎"]);
+	assert_eq!(query("domain:LAW"), vec!["判例"]);
+	assert!(query("domain:n").is_empty());
+}
+
+#[test]
+fn list_tags_and_sources() {
+	let base = DB::load(&build(Profile::Full)).tags().len();
+	let data = build_with(Profile::Full, |w| {
+		for &(name, category) in [("med", "domain"), ("law", "domain"), ("news", "frequent")].iter() {
+			let tag = TagData {
+				name: w.intern(name.to_string()),
+				category: w.intern(category.to_string()),
+				order: 0,
+				notes: 0,
+			};
+			w.push_tag(tag);
+		}
+		for &(name, priority) in [("jmdict", 10), ("extra", 0)].iter() {
+			let name = w.intern(name.to_string());
+			w.push_source(SourceData { name: name, priority: priority });
+		}
+	});
+	let db = DB::load(&data);
+	db.check();
+
+	let tags: Vec<_> = db.tags().skip(base).map(|x| format!("{} [{}]", x.name(), x.category())).collect();
+	assert_eq!(tags, vec!["med [domain]", "law [domain]", "news [frequent]"]);
+
+	let categories: Vec<_> = db.tag_categories().iter().map(|x| (x.name, x.kind, x.tags)).collect();
+	assert_eq!(
+		categories,
+		vec![
+			("frequent", TagCategory::Frequent, 1),
+			("domain", TagCategory::Domain, 2),
+			("", TagCategory::Other, base),
+		]
+	);
+
+	let sources: Vec<_> = db.sources().map(|x| x.to_string()).collect();
+	assert_eq!(sources, vec!["jmdict (priority 10)", "extra (priority 0)"]);
+	assert_eq!(db.source("extra").map(|x| x.priority()), Some(0));
+	assert!(db.source("missing").is_none());
+}
+
+#[test]
+fn frequency_and_headword_limits() {
+	let data = build(Profile::Full);
+	let db = DB::load(&data);
+	let query = |query: Query| {
+		let terms = db.query(&query).into_iter().map(|x| db.term(x).unwrap());
+		terms.map(|x| format!("{} ({})", x.expression(), x.source())).collect::<Vec<_>>()
+	};
+
+	let prefix = || Query::new().with(Field::Reading, "たべ*");
+	assert_eq!(query(prefix()).len(), 4);
+	assert_eq!(
+		query(prefix().min_frequency(100)),
+		vec!["食べる (JMdict)", "食べる (Other)", "食べ物 (JMdict)"]
+	);
+	assert_eq!(
+		query(prefix().max_results_per_headword(1)),
+		vec!["食べる (JMdict)", "食べ物 (JMdict)", "食べ放題 (JMdict)"]
+	);
+	assert_eq!(query(prefix().max_results_per_headword(1).limit(2)), vec!["食べる (JMdict)", "食べ物 (JMdict)"]);
+
+	// Full scans stop at the frequency cutoff
+	let explain = db.explain(&Query::new().with(Field::Glossary, "drink").min_frequency(500));
+	assert_eq!(explain.inspected, 6);
+	assert_eq!(explain.results.len(), 1);
+	assert_eq!(
+		query(Query::new().with(Field::Glossary, "drink").min_frequency(400)),
+		vec!["飲む (JMdict)", "飲み物 (JMdict)"]
+	);
+}
+
+#[test]
+fn glossary_configs() {
+	let data = build(Profile::Full);
+	let db = DB::load(&data);
+	let languages: Vec<_> = db.glossary_configs().iter().map(|x| (x.language, x.tokenizer)).collect();
+	assert_eq!(languages, vec![("en", Tokenizer::Words), ("ja", Tokenizer::Text)]);
+	assert!(db.glossary_config("en").stop_words.contains(&"the"));
+	assert_eq!(db.glossary_config("de"), GlossaryConfig::default_for("de"));
+
+	let data = build_with(Profile::Full, |w| {
+		for &(language, stop_words) in [("en", "drink to"), ("pt", "de")].iter() {
+			let config = GlossaryConfigData {
+				language: w.intern(language.to_string()),
+				tokenizer: Tokenizer::Words,
+				min_length: 2,
+				stop_words: stop_words.split(' ').map(|x| w.intern(x.to_string())).collect(),
+			};
+			w.push_glossary_config(config);
+		}
+	});
+	let db = DB::load(&data);
+	db.check();
+
+	let languages: Vec<_> = db.glossary_configs().iter().map(|x| x.language).collect();
+	assert_eq!(languages, vec!["en", "ja", "pt"]);
+	assert_eq!(db.glossary_config("en").stop_words, vec!["drink", "to"]);
+	assert_eq!(db.glossary_config("pt").min_length, 2);
+
+	// Stop words are ignored by the glossary queries
+	let query = |query: Query| query_expressions(&db, &query);
+	assert_eq!(query(Query::new().with(Field::Glossary, "drink")), Vec::<String>::new());
+	assert_eq!(query(Query::new().with(Field::Glossary, "beverage")), vec!["飲み物"]);
+	assert_eq!(query(Query::new().with(Field::Glossary, "ネコ科")), vec!["ねこ"]);
+}
+
+#[test]
+fn diversified_results() {
+	let words = [
+		("傘", "かさ", "noun", "extra"),
+		("書く", "かく", "verb", "extra"),
+		("紙", "かみ", "noun", "extra"),
+		("買う", "かう", "verb", "other"),
+		("顔", "かお", "", "other"),
+		("川", "かわ", "noun", "other"),
+	];
+	let data = build_with(Profile::Full, |w| {
+		for &name in ["noun", "verb"].iter() {
+			let tag = TagData {
+				name: w.intern(name.to_string()),
+				category: w.intern("partOfSpeech".to_string()),
+				order: 0,
+				notes: 0,
+			};
+			w.push_tag(tag);
+		}
+		for (i, &(expression, reading, tag, source)) in words.iter().enumerate() {
+			let term = TermData {
+				frequency: 100 - i as u32,
+				definition_tags: if tag.len() > 0 { vec![w.get_tag(tag)] } else { Vec::new() },
+				source: w.intern(source.to_string()),
+				..term(w, expression, reading)
+			};
+			w.push_term(term);
+		}
+	});
+	let db = DB::load(&data);
+	db.check();
+
+	let query = |query: Query| query_expressions(&db, &query.with(Field::Reading, "か*"));
+	assert_eq!(query(Query::new()), vec!["傘", "書く", "紙", "買う", "顔", "川"]);
+	assert_eq!(query(Query::new().max_results_per_source(2)), vec!["傘", "書く", "買う", "顔"]);
+	assert_eq!(query(Query::new().max_results_per_pos(1)), vec!["傘", "書く", "顔"]);
+	assert_eq!(query(Query::new().max_results_per_pos(2).limit(4)), vec!["傘", "書く", "紙", "買う"]);
+
+	// Results over one limit do not count towards the others
+	let both = Query::new().max_results_per_source(2).max_results_per_pos(1);
+	assert_eq!(query(both), vec!["傘", "書く", "顔"]);
+	let both = Query::new().max_results_per_pos(1).max_results_per_source(1);
+	assert_eq!(query(both), vec!["傘", "買う"]);
+}
+
+#[test]
+fn scan_ranks_readings_in_context() {
+	let words = [
+		("家", "いえ", "n", 500),
+		("辛い", "からい", "adj-i", 400),
+		("田中", "たなか", "n-pr", 300),
+		("辛い", "つらい", "adj-i", 200),
+		("家", "け", "suf", 100),
+	];
+	let data = build_with(Profile::Full, |w| {
+		for &name in ["n-pr", "suf", "adj-i"].iter() {
+			let tag = TagData {
+				name: w.intern(name.to_string()),
+				category: w.intern("partOfSpeech".to_string()),
+				order: 0,
+				notes: 0,
+			};
+			w.push_tag(tag);
+		}
+		for &(expression, reading, tag, frequency) in words.iter() {
+			let term = TermData {
+				frequency: frequency,
+				rules: if tag == "adj-i" { vec![w.get_tag(tag)] } else { Vec::new() },
+				definition_tags: vec![w.get_tag(tag)],
+				..term(w, expression, reading)
+			};
+			w.push_term(term);
+		}
+	});
+	let db = DB::load(&data);
+	db.check();
+
+	let readings = |text: &str| {
+		let matches = db.scan(text);
+		let last = matches.last().unwrap();
+		last.ranked.iter().map(|&x| db.term(x).unwrap().reading()).collect::<Vec<_>>()
+	};
+	assert_eq!(readings("家"), vec!["いえ", "け"]);
+	assert_eq!(readings("田中家"), vec!["け", "いえ"]);
+	assert_eq!(readings("田中 家"), vec!["いえ", "け"]);
+	assert_eq!(readings("辛い"), vec!["からい", "つらい"]);
+}
+
+#[test]
+fn tag_flags() {
+	let words = [("パソコン", "abbr"), ("ソニー", "company"), ("ウォークマン", "product"), ("テレビ", "n")];
+	let data = build_with(Profile::Full, |w| {
+		for &name in ["abbr", "company", "product"].iter() {
+			let tag = TagData {
+				name: w.intern(name.to_string()),
+				category: 0,
+				order: 0,
+				notes: 0,
+			};
+			w.push_tag(tag);
+		}
+		for &(expression, tag) in words.iter() {
+			let term = TermData {
+				term_tags: if tag == "n" { Vec::new() } else { vec![w.get_tag(tag)] },
+				definition_tags: if tag == "n" { vec![w.get_tag(tag)] } else { Vec::new() },
+				..term(w, expression, "")
+			};
+			w.push_term(term);
+		}
+	});
+	let db = DB::load(&data);
+	db.check();
+
+	let query = |text: &str| query_expressions(&db, &Query::parse(text).unwrap().with(Field::Source, "extra"));
+	assert_eq!(query("flag:abbr"), vec!["パソコン"]);
+	assert_eq!(query("flag:company OR flag:product"), vec!["ウォークマン", "ソニー"]);
+	assert_eq!(query("-flag:abbr"), vec!["ウォークマン", "ソニー", "テレビ"]);
+	assert!(query("flag:other").is_empty());
+
+	let term = |text: &str| db.term(db.query(&Query::new().with(Field::Expression, text))[0]).unwrap();
+	assert!(term("パソコン").is_abbreviation());
+	assert!(term("ソニー").flags().contains(TermFlags::COMPANY | TermFlags::KATAKANA));
+	assert!(!term("テレビ").is_abbreviation());
+}
+
+#[test]
+fn priority_codes() {
+	let words = [
+		("端", "はし", vec![]),
+		("橋", "はし", vec!["news2", "nf20"]),
+		("箸", "はし", vec!["ichi1", "news1", "nf05"]),
+	];
+	let data = build_with(Profile::Full, |w| {
+		for &name in ["ichi1", "news1", "news2", "nf05", "nf20"].iter() {
+			let tag = TagData {
+				name: w.intern(name.to_string()),
+				category: 0,
+				order: 0,
+				notes: 0,
+			};
+			w.push_tag(tag);
+		}
+		for &(expression, reading, ref tags) in words.iter() {
+			let term = TermData {
+				term_tags: tags.iter().map(|&x| w.get_tag(x)).collect(),
+				..term(w, expression, reading)
+			};
+			w.push_term(term);
+		}
+	});
+	let db = DB::load(&data);
+	db.check();
+
+	let query = |text: &str| expressions(&db, text);
+	// Common words rank first, then by the `nfXX` rank
+	assert_eq!(query("reading:はし"), vec!["箸", "橋", "端"]);
+	assert_eq!(query("priority:common"), vec!["箸"]);
+	assert_eq!(query("priority:NEWS2"), vec!["橋"]);
+	assert_eq!(query("priority:nf05"), vec!["箸"]);
+	assert_eq!(query("priority:*"), vec!["箸", "橋"]);
+
+	let mut results = ResultSet::default();
+	db.search_term("箸", &mut results);
+	let term = db.term(results.iter().next().unwrap()).unwrap();
+	assert_eq!(term.priority().codes(), vec!["news1", "ichi1", "nf05"]);
+	assert_eq!(term.priority().nf(), Some(5));
+}
+
+#[test]
+fn sense_info() {
+	let senses = [("reckless", 0), ("rarely", 1)];
+	let data = build_with(Profile::Full, |w| {
+		for &(glossary, order) in senses.iter() {
+			let term = TermData {
+				sequence: 1000,
+				order: order,
+				glossary: vec![w.intern(glossary.to_string())],
+				..term(w, "滅多", "めった")
+			};
+			w.push_term(term);
+		}
+		let info = SenseInfoData {
+			source: w.intern("extra".to_string()),
+			sequence: 1000,
+			sense: 2,
+			info: w.intern("usu. in the negative".to_string()),
+		};
+		w.push_sense_info(info);
+	});
+	let db = DB::load(&data);
+	db.check();
+
+	let mut results = ResultSet::default();
+	db.search_term("滅多", &mut results);
+	let headwords = db.group_by_headword(results.iter());
+	let senses: Vec<_> = headwords[0].sources[0]
+		.senses
+		.iter()
+		.map(|x| format!("{} {:?}", x.glossary.join("; "), x.info))
+		.collect();
+	assert_eq!(senses, vec!["reckless []", "rarely [\"usu. in the negative\"]"]);
+
+	let term = db.term(headwords[0].sources[0].terms[1]).unwrap();
+	assert_eq!(term.info().collect::<Vec<_>>(), vec!["usu. in the negative"]);
+}
+
+#[test]
+fn sense_restrictions() {
+	// `to bear fruit` is restricted to the `生る` form of the entry
+	let senses = [
+		("成る", "to become", 0),
+		("生る", "to become", 1),
+		("生る", "to bear fruit", 2),
+	];
+	let data = build_with(Profile::Full, |w| {
+		for &(expression, glossary, order) in senses.iter() {
+			let term = TermData {
+				sequence: 1000,
+				order: order,
+				glossary: vec![w.intern(glossary.to_string())],
+				..term(w, expression, "なる")
+			};
+			w.push_term(term);
+		}
+	});
+	let db = DB::load(&data);
+	db.check();
+
+	let mut results = ResultSet::default();
+	db.search_term("なる", &mut results);
+	let mut senses: Vec<_> = results
+		.iter()
+		.map(|x| db.term(x).unwrap())
+		.map(|x| {
+			let forms: Vec<_> = x.restrictions().map(|(a, b)| format!("{}【{}】", a, b)).collect();
+			format!("{} {} {:?}", x.expression(), x.glossary().collect::<Vec<_>>().join("; "), forms)
+		})
+		.collect();
+	senses.sort();
+	assert_eq!(
+		senses,
+		vec![
+			"成る to become []",
+			"生る to bear fruit [\"生る【なる】\"]",
+			"生る to become []",
+		]
+	);
+}
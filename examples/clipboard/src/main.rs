@@ -0,0 +1,81 @@
+//! Watches the system clipboard and prints the dictionary lookups for any
+//! Japanese text copied to it.
+//!
+//! This is meant to be used along with texthooker tools that copy the text
+//! from games and visual novels to the clipboard.
+
+extern crate clipboard;
+
+extern crate jp_dict;
+
+use std::thread;
+use std::time::Duration;
+
+use clipboard::ClipboardContext;
+use clipboard::ClipboardProvider;
+
+/// Interval between clipboard checks.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Maximum number of terms to print for each match.
+const MAX_TERMS_PER_MATCH: usize = 3;
+
+fn main() {
+	let db = jp_dict::get_db();
+
+	let mut ctx: ClipboardContext = match ClipboardProvider::new() {
+		Ok(ctx) => ctx,
+		Err(err) => {
+			eprintln!("\nERROR: could not access the clipboard: {}\n", err);
+			std::process::exit(1);
+		}
+	};
+
+	println!("\nWatching the clipboard (press Ctrl+C to exit)...\n");
+
+	let mut last = ctx.get_contents().unwrap_or_default();
+	loop {
+		thread::sleep(POLL_INTERVAL);
+
+		let text = match ctx.get_contents() {
+			Ok(text) => text,
+			Err(_) => continue,
+		};
+		if text == last {
+			continue;
+		}
+		last = text;
+
+		let text = last.trim();
+		if !text.chars().any(is_japanese) {
+			continue;
+		}
+
+		println!("\n>> {}\n", text);
+		for it in db.scan(text) {
+			println!("{}", it.text(text));
+			for &index in it.ranked.iter().take(MAX_TERMS_PER_MATCH) {
+				let term = db.term(index).unwrap();
+				let reading = term.reading();
+				let glossary: Vec<_> = term.glossary().collect();
+				if reading.len() > 0 && reading != term.expression() {
+					print!("    {} 「{}」", term.expression(), reading);
+				} else {
+					print!("    {}", term.expression());
+				}
+				println!(" -- {}", glossary.join("; "));
+			}
+		}
+	}
+}
+
+/// Returns true for kana and kanji characters.
+fn is_japanese(chr: char) -> bool {
+	match chr {
+		'\u{3040}'..='\u{30FF}' => true, // hiragana and katakana
+		'\u{3400}'..='\u{4DBF}' => true, // CJK extension A
+		'\u{4E00}'..='\u{9FFF}' => true, // CJK unified ideographs
+		'\u{FF66}'..='\u{FF9F}' => true, // half-width katakana
+		_ => false,
+	}
+}
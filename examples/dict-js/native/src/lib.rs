@@ -10,10 +10,14 @@ use neon::prelude::*;
 
 #[derive(Serialize, Deserialize)]
 struct Term {
+	index: usize,
 	expression: String,
 	reading: String,
 	score: i32,
+	sequence: u32,
+	order: u32,
 	frequency: Option<u32>,
+	pitch: Vec<u32>,
 	source: String,
 	glossary: Vec<String>,
 	rules: Vec<Tag>,
@@ -25,46 +29,65 @@ struct Term {
 struct Tag {
 	name: String,
 	category: String,
+	order: i32,
 	notes: String,
 }
 
 export! {
+	/// Prefix search for terms. Returns the list of matching terms.
 	fn search_terms(input: String) -> Vec<Term> {
 		let db = jp_dict::get_db();
 		let mut set = jp_dict::ResultSet::default();
 		db.search_prefix(input, &mut set);
+		to_terms(&set)
+	}
 
-		let mut results = Vec::new();
-		for index in set.iter() {
-			let src = db.term(index).unwrap();
-			let term = Term{
-				expression: src.expression().to_string(),
-				reading: src.reading().to_string(),
-				score: src.score(),
-				frequency: src.frequency(),
-				source: src.source().to_string(),
-				glossary: src.glossary().map(|x| x.to_string()).collect(),
-				rules: src.rules().map(to_tag).collect(),
-				definition_tags: src.definition_tags().map(to_tag).collect(),
-				term_tags: src.term_tags().map(to_tag).collect(),
-			};
-			results.push(term);
-		}
-
-		return results;
+	/// Exact search for terms. Returns the list of matching terms.
+	fn search_exact(input: String) -> Vec<Term> {
+		let db = jp_dict::get_db();
+		let mut set = jp_dict::ResultSet::default();
+		db.search_term(input, &mut set);
+		to_terms(&set)
+	}
 
-		fn to_tag<'db, 'a>(item: jp_dict::Tag<'db, 'a>) -> Tag {
-			Tag {
-				name: item.name().to_string(),
-				category: item.category().to_string(),
-				notes: item.notes().to_string(),
-			}
-		}
+	/// Returns a single term by its index (as returned in the `index` field
+	/// of search results) or `null` if the index is not valid.
+	fn get_term(index: usize) -> Option<Term> {
+		let db = jp_dict::get_db();
+		db.term(index).map(|term| to_term(index, &term))
 	}
 }
 
-// fn hello(mut cx: FunctionContext) -> JsResult<JsString> {
-// 	Ok(cx.string("hello node"))
-// }
+fn to_terms(set: &jp_dict::ResultSet) -> Vec<Term> {
+	let db = jp_dict::get_db();
+	set.iter()
+		.map(|index| to_term(index, &db.term(index).unwrap()))
+		.collect()
+}
+
+fn to_term<'db, 'a>(index: usize, src: &'a jp_dict::Term<'db, 'a>) -> Term {
+	Term {
+		index: index,
+		expression: src.expression().to_string(),
+		reading: src.reading().to_string(),
+		score: src.score(),
+		sequence: src.sequence(),
+		order: src.order(),
+		frequency: src.frequency(),
+		pitch: src.pitch().collect(),
+		source: src.source().to_string(),
+		glossary: src.glossary().map(|x| x.to_string()).collect(),
+		rules: src.rules().map(to_tag).collect(),
+		definition_tags: src.definition_tags().map(to_tag).collect(),
+		term_tags: src.term_tags().map(to_tag).collect(),
+	}
+}
 
-// register_module!(mut cx, { cx.export_function("hello", hello) });
+fn to_tag<'db, 'a>(item: jp_dict::Tag<'db, 'a>) -> Tag {
+	Tag {
+		name: item.name().to_string(),
+		category: item.category().to_string(),
+		order: item.order(),
+		notes: item.notes().to_string(),
+	}
+}
@@ -0,0 +1,147 @@
+//! Query benchmark replaying a file of queries against the database.
+//!
+//! Each non-empty line in the file is a query in the format `KIND TEXT`,
+//! with `KIND` one of:
+//!
+//! - `exact`: exact term search.
+//! - `prefix`: prefix search.
+//! - `suffix`: suffix search.
+//! - `top`: prefix search for the 10 most relevant terms.
+//! - `query`: query language (see `Query::parse`).
+//! - `scan`: text scan.
+//!
+//! Lines starting with `#` are comments.
+//!
+//! Besides the timings, the benchmark reports the average number of heap
+//! allocations for each kind of query.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use jp_dict::{Query, ResultSet, DB};
+
+use crate::alloc;
+
+/// Number of results for the `top` and `query` kinds.
+const LIMIT: usize = 10;
+
+const KINDS: &'static [&'static str] = &["exact", "prefix", "suffix", "top", "query", "scan"];
+
+/// Runs the benchmark for the `bench FILE [REPEAT]` arguments.
+pub fn run(db: &DB, args: &[String]) -> Result<(), String> {
+	let file = match args.get(0) {
+		Some(file) => file,
+		None => return Err(format!("usage: query bench FILE [REPEAT]")),
+	};
+	let repeat = match args.get(1) {
+		Some(repeat) => repeat
+			.parse::<usize>()
+			.map_err(|_| format!("invalid repeat count `{}`", repeat))?,
+		None => 1,
+	};
+
+	let text = fs::read_to_string(file).map_err(|err| format!("reading {}: {}", file, err))?;
+	let queries = parse_queries(&text)?;
+	println!("Running {} queries from {} ({}x)...\n", queries.len(), file, repeat);
+
+	// Warm up so the first queries don't pay for paging in the database
+	db.warm(jp_dict::Section::ALL);
+	db.build_missing_indexes(|_, _| {});
+
+	let mut timings: BTreeMap<&str, Vec<Duration>> = BTreeMap::new();
+	let mut allocations: BTreeMap<&str, usize> = BTreeMap::new();
+	let start = Instant::now();
+	for _ in 0..repeat {
+		for &(kind, text) in queries.iter() {
+			let (elapsed, count) = run_query(db, kind, text);
+			timings.entry(kind).or_insert_with(Vec::new).push(elapsed);
+			*allocations.entry(kind).or_insert(0) += count;
+		}
+	}
+	let total = start.elapsed();
+
+	println!(
+		"{:<8} {:>8} {:>12} {:>12} {:>12} {:>12} {:>12} {:>8}",
+		"kind", "count", "p50", "p90", "p99", "max", "total", "allocs"
+	);
+	for (kind, times) in timings.iter_mut() {
+		times.sort();
+		let sum: Duration = times.iter().sum();
+		println!(
+			"{:<8} {:>8} {:>12} {:>12} {:>12} {:>12} {:>12} {:>8}",
+			kind,
+			times.len(),
+			format!("{:.1?}", percentile(times, 50)),
+			format!("{:.1?}", percentile(times, 90)),
+			format!("{:.1?}", percentile(times, 99)),
+			format!("{:.1?}", times[times.len() - 1]),
+			format!("{:.1?}", sum),
+			format!("{:.1}", allocations[kind] as f64 / times.len() as f64),
+		);
+	}
+	println!("\nFinished in {:?}", total);
+	Ok(())
+}
+
+fn parse_queries(text: &str) -> Result<Vec<(&str, &str)>, String> {
+	let mut out = Vec::new();
+	for (number, line) in text.lines().enumerate() {
+		let line = line.trim();
+		if line.len() == 0 || line.starts_with('#') {
+			continue;
+		}
+
+		let mut parts = line.splitn(2, char::is_whitespace);
+		let kind = parts.next().unwrap();
+		let text = parts.next().unwrap_or("").trim();
+		if !KINDS.contains(&kind) {
+			return Err(format!("line {}: unknown query kind `{}`", number + 1, kind));
+		}
+		if kind == "query" {
+			if let Err(err) = Query::parse(text) {
+				return Err(format!("line {}: invalid query: {}", number + 1, err));
+			}
+		}
+		out.push((kind, text));
+	}
+	Ok(out)
+}
+
+/// Runs a single query returning the elapsed time and the number of heap
+/// allocations. Query parsing is included for the `query` kind.
+fn run_query(db: &DB, kind: &str, text: &str) -> (Duration, usize) {
+	let allocations = alloc::allocations();
+	let start = Instant::now();
+	let mut results = ResultSet::default();
+	match kind {
+		"exact" => {
+			db.search_term(text, &mut results);
+		}
+		"prefix" => {
+			db.search_prefix(text, &mut results);
+		}
+		"suffix" => {
+			db.search_suffix(text, &mut results);
+		}
+		"top" => {
+			db.search_prefix_top(text, LIMIT);
+		}
+		"query" => {
+			let query = Query::parse(text).unwrap().limit(LIMIT);
+			db.query(&query);
+		}
+		"scan" => {
+			db.scan(text);
+		}
+		_ => unreachable!(),
+	}
+	(start.elapsed(), alloc::allocations() - allocations)
+}
+
+/// Returns the percentile from a sorted list of timings, using the
+/// nearest-rank method.
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+	let rank = (percent * sorted.len() + 99) / 100;
+	sorted[std::cmp::max(rank, 1) - 1]
+}
@@ -0,0 +1,126 @@
+//! Comparison of two database builds.
+//!
+//! Usage:
+//!
+//! ```text
+//! query diff OLD NEW [--limit N]
+//! ```
+//!
+//! Loads the `OLD` and `NEW` database files and prints the terms and kanji
+//! added, removed and changed, the tag changes and the size of each section
+//! with the difference. Only the first `N` entries of each list are printed
+//! (20 by default). See `DB::diff`.
+//!
+//! Exits with an error status if the databases differ, so it can be used to
+//! check that an importer change has no effect on the output.
+
+use std::fs;
+
+use jp_dict::{EntryChange, DB};
+
+const DEFAULT_LIMIT: usize = 20;
+
+/// Runs the comparison for the `diff OLD NEW [--limit N]` arguments.
+/// Returns true if the databases have the same entries and tags.
+pub fn run(args: &[String]) -> Result<bool, String> {
+	let mut files = Vec::new();
+	let mut limit = DEFAULT_LIMIT;
+	let mut args = args.iter();
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--limit" => {
+				let value = args.next().ok_or_else(|| format!("missing value for --limit"))?;
+				limit = value.parse().map_err(|_| format!("invalid limit `{}`", value))?;
+			}
+			_ => files.push(arg),
+		}
+	}
+	if files.len() != 2 {
+		return Err(format!("usage: query diff OLD NEW [--limit N]"));
+	}
+
+	let read = |name: &String| fs::read(name).map_err(|err| format!("reading {}: {}", name, err));
+	let (old, new) = (read(files[0])?, read(files[1])?);
+	let (old, new) = (DB::load(&old), DB::load(&new));
+	let (old_info, new_info) = (old.info(), new.info());
+	println!("old: {} (built {}, {} terms)", files[0], old_info.build_date(), old_info.terms);
+	println!("new: {} (built {}, {} terms)\n", files[1], new_info.build_date(), new_info.terms);
+
+	let diff = old.diff(&new);
+
+	let term = |db: &DB, index: usize| {
+		let term = db.term(index).unwrap();
+		format!("{}【{}】 ({})", term.expression(), term.reading(), term.source())
+	};
+	let kanji = |db: &DB, index: usize| {
+		let kanji = db.kanji(index).unwrap();
+		format!("{} ({})", kanji.character(), kanji.source())
+	};
+
+	println!(
+		"terms: {} added, {} removed, {} changed",
+		diff.terms_added.len(),
+		diff.terms_removed.len(),
+		diff.terms_changed.len()
+	);
+	print_list("+", diff.terms_added.iter().map(|&x| term(&new, x)), limit);
+	print_list("-", diff.terms_removed.iter().map(|&x| term(&old, x)), limit);
+	print_list("~", diff.terms_changed.iter().map(|x| change(term(&new, x.new), x)), limit);
+
+	println!(
+		"\nkanji: {} added, {} removed, {} changed",
+		diff.kanji_added.len(),
+		diff.kanji_removed.len(),
+		diff.kanji_changed.len()
+	);
+	print_list("+", diff.kanji_added.iter().map(|&x| kanji(&new, x)), limit);
+	print_list("-", diff.kanji_removed.iter().map(|&x| kanji(&old, x)), limit);
+	print_list("~", diff.kanji_changed.iter().map(|x| change(kanji(&new, x.new), x)), limit);
+
+	println!(
+		"\ntags: {} added, {} removed, {} changed",
+		diff.tags_added.len(),
+		diff.tags_removed.len(),
+		diff.tags_changed.len()
+	);
+	print_list("+", diff.tags_added.iter().cloned(), limit);
+	print_list("-", diff.tags_removed.iter().cloned(), limit);
+	print_list("~", diff.tags_changed.iter().cloned(), limit);
+
+	let columns = ("section", "entries", "delta", "old bytes", "new bytes", "delta");
+	println!(
+		"\n{:<12} {:>10} {:>10} {:>12} {:>12} {:>12}",
+		columns.0, columns.1, columns.2, columns.3, columns.4, columns.5
+	);
+	for it in diff.sections.iter() {
+		println!(
+			"{:<12} {:>10} {:>+10} {:>12} {:>12} {:>+12}",
+			format!("{:?}", it.section),
+			it.new.entries,
+			it.entries(),
+			it.old.bytes,
+			it.new.bytes,
+			it.bytes()
+		);
+	}
+	println!();
+
+	Ok(diff.is_empty())
+}
+
+fn change(name: String, change: &EntryChange) -> String {
+	format!("{} [{}]", name, change.fields.join(", "))
+}
+
+fn print_list<T: Iterator<Item = String>>(prefix: &str, items: T, limit: usize) {
+	let mut count = 0;
+	for it in items {
+		if count < limit {
+			println!("    {} {}", prefix, it);
+		}
+		count += 1;
+	}
+	if count > limit {
+		println!("    {} ... and {} more", prefix, count - limit);
+	}
+}
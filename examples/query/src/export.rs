@@ -0,0 +1,177 @@
+//! Export of the terms matching a query as CSV or TSV.
+//!
+//! Usage:
+//!
+//! ```text
+//! query export --query QUERY [--columns COLUMNS] [--format csv|tsv] [--limit N] [--output FILE]
+//! ```
+//!
+//! The query uses the query language (see `Query::parse`) and the results
+//! are written in order of relevance, one term per row, with a header row
+//! with the column names. The output is written to stdout by default.
+//!
+//! `COLUMNS` is a comma separated list of:
+//!
+//! - `index`: index of the term in the database.
+//! - `id`: stable identifier of the term, in hex (see `Term::id`).
+//! - `expression`, `reading`, `source`, `frequency`, `score` and `sequence`.
+//! - `glossary`: definitions for the term, separated by `; `.
+//! - `info`: supplemental information for the sense, separated by `; `.
+//! - `restrictions`: forms of the entry the sense is restricted to, as
+//!   `expression【reading】` separated by `, `.
+//! - `tags`: tag names for the term, separated by `, `.
+//! - `pitch`: pitch accent positions, separated by `, `.
+//! - `priority`: JMdict priority codes (e.g. `news1`), separated by `, `.
+//!
+//! The default columns are `expression,reading,glossary,frequency,tags`.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use jp_dict::{Query, Term, DB};
+
+const COLUMNS: &'static [&'static str] = &[
+	"index",
+	"id",
+	"expression",
+	"reading",
+	"source",
+	"frequency",
+	"score",
+	"sequence",
+	"glossary",
+	"info",
+	"restrictions",
+	"tags",
+	"pitch",
+	"priority",
+];
+
+const DEFAULT_COLUMNS: &'static str = "expression,reading,glossary,frequency,tags";
+
+const USAGE: &'static str =
+	"usage: query export --query QUERY [--columns COLUMNS] [--format csv|tsv] [--limit N] [--output FILE]";
+
+/// Runs the export for the `export ARGS...` arguments.
+pub fn run(db: &DB, args: &[String]) -> Result<(), String> {
+	let mut query = None;
+	let mut columns = DEFAULT_COLUMNS;
+	let mut separator = ',';
+	let mut limit = None;
+	let mut output = None;
+
+	let mut args = args.iter();
+	while let Some(arg) = args.next() {
+		let mut value = || match args.next() {
+			Some(value) => Ok(value.as_str()),
+			None => Err(format!("missing value for `{}`\n{}", arg, USAGE)),
+		};
+		match arg.as_str() {
+			"--query" => query = Some(value()?),
+			"--columns" => columns = value()?,
+			"--format" => {
+				separator = match value()? {
+					"csv" => ',',
+					"tsv" => '\t',
+					format => return Err(format!("invalid format `{}`", format)),
+				}
+			}
+			"--limit" => {
+				let value = value()?;
+				let value = value
+					.parse::<usize>()
+					.map_err(|_| format!("invalid limit `{}`", value))?;
+				limit = Some(value);
+			}
+			"--output" => output = Some(value()?),
+			_ => return Err(format!("unknown argument `{}`\n{}", arg, USAGE)),
+		}
+	}
+
+	let query = match query {
+		Some(query) => query,
+		None => return Err(String::from(USAGE)),
+	};
+	let mut query = Query::parse(query).map_err(|err| format!("invalid query: {}", err))?;
+	if let Some(limit) = limit {
+		query = query.limit(limit);
+	}
+
+	let columns: Vec<&str> = columns.split(',').map(|x| x.trim()).collect();
+	for &column in columns.iter() {
+		if !COLUMNS.contains(&column) {
+			return Err(format!("unknown column `{}` (expected one of {})", column, COLUMNS.join(", ")));
+		}
+	}
+
+	let results = db.query(&query);
+	let result = match output {
+		Some(file) => {
+			let file = File::create(file).map_err(|err| format!("creating {}: {}", file, err))?;
+			write_rows(db, &results, &columns, separator, &mut BufWriter::new(file))
+		}
+		None => {
+			let stdout = io::stdout();
+			let mut stdout = stdout.lock();
+			write_rows(db, &results, &columns, separator, &mut stdout)
+		}
+	};
+	result.map_err(|err| format!("writing output: {}", err))?;
+
+	if output.is_some() {
+		println!("Exported {} term(s)", results.len());
+	}
+	Ok(())
+}
+
+fn write_rows<W: Write>(db: &DB, results: &[usize], columns: &[&str], separator: char, out: &mut W) -> io::Result<()> {
+	write_row(out, columns.iter().map(|x| x.to_string()), separator)?;
+	for &index in results {
+		let term = db.term(index).unwrap();
+		write_row(out, columns.iter().map(|x| column_value(&term, x)), separator)?;
+	}
+	out.flush()
+}
+
+fn column_value(term: &Term, column: &str) -> String {
+	match column {
+		"index" => term.index().to_string(),
+		"id" => format!("{:016x}", term.id()),
+		"expression" => term.expression().to_string(),
+		"reading" => term.reading().to_string(),
+		"source" => term.source().to_string(),
+		"frequency" => term.frequency().map(|x| x.to_string()).unwrap_or_default(),
+		"score" => term.score().to_string(),
+		"sequence" => term.sequence().to_string(),
+		"glossary" => term.glossary().collect::<Vec<_>>().join("; "),
+		"info" => term.info().collect::<Vec<_>>().join("; "),
+		"restrictions" => term
+			.restrictions()
+			.map(|(expression, reading)| format!("{}【{}】", expression, reading))
+			.collect::<Vec<_>>()
+			.join(", "),
+		"tags" => term.tags().iter().map(|x| x.name()).collect::<Vec<_>>().join(", "),
+		"pitch" => term.pitch().map(|x| x.to_string()).collect::<Vec<_>>().join(", "),
+		"priority" => term.priority().codes().join(", "),
+		_ => unreachable!(),
+	}
+}
+
+fn write_row<W: Write, I: Iterator<Item = String>>(out: &mut W, values: I, separator: char) -> io::Result<()> {
+	let values: Vec<String> = values.map(|x| escape(&x, separator)).collect();
+	writeln!(out, "{}", values.join(&separator.to_string()))
+}
+
+/// Escapes a value for the output format.
+///
+/// CSV values are quoted as per RFC 4180 when needed. TSV has no quoting, so
+/// tabs and line breaks are replaced by spaces.
+fn escape(value: &str, separator: char) -> String {
+	if separator == '\t' {
+		value.replace(|c| c == '\t' || c == '\n' || c == '\r', " ")
+	} else if value.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+		format!("\"{}\"", value.replace('"', "\"\""))
+	} else {
+		value.to_string()
+	}
+}
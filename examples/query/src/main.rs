@@ -9,13 +9,75 @@ extern crate jp_dict;
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 
+mod alloc;
+mod bench;
+mod diff;
+mod export;
+mod strings;
+
+#[global_allocator]
+static ALLOCATOR: alloc::CountingAllocator = alloc::CountingAllocator;
+
 fn main() {
 	let start = std::time::Instant::now();
+	let args: Vec<String> = std::env::args().skip(1).collect();
+
+	// `query diff OLD NEW` compares two database files (see `diff`), so it
+	// doesn't need the embedded database.
+	if args.get(0).map(|x| x.as_str()) == Some("diff") {
+		match diff::run(&args[1..]) {
+			Ok(true) => println!("Databases have the same entries\n"),
+			Ok(false) => std::process::exit(3),
+			Err(err) => {
+				eprintln!("\nERROR: {}\n", err);
+				std::process::exit(1);
+			}
+		}
+		return;
+	}
+
 	let db = jp_dict::get_db();
+
+	// `query export --query QUERY ...` writes the query results as CSV or
+	// TSV (see `export`). This runs before any other output, since the
+	// export is written to stdout by default.
+	if args.get(0).map(|x| x.as_str()) == Some("export") {
+		if let Err(err) = export::run(db, &args[1..]) {
+			eprintln!("\nERROR: {}\n", err);
+			std::process::exit(1);
+		}
+		return;
+	}
+
 	println!("\nLoaded in {:?}\n", start.elapsed());
+	let info = db.info();
+	println!("Dictionary built {}, {} terms, {} kanji\n", info.build_date(), info.terms, info.kanji);
+
+	// `query bench FILE [REPEAT]` replays a file of queries and reports the
+	// latency for each kind of query (see `bench`).
+	if args.get(0).map(|x| x.as_str()) == Some("bench") {
+		if let Err(err) = bench::run(db, &args[1..]) {
+			eprintln!("\nERROR: {}\n", err);
+			std::process::exit(1);
+		}
+		return;
+	}
+
+	// `query strings [TOP]` analyzes the string table (see `strings`).
+	if args.get(0).map(|x| x.as_str()) == Some("strings") {
+		if let Err(err) = strings::run(db, &args[1..]) {
+			eprintln!("\nERROR: {}\n", err);
+			std::process::exit(1);
+		}
+		return;
+	}
+
 	db.check();
 	println!();
 
+	// Follow the https://no-color.org/ convention for disabling colors
+	let format = jp_dict::TextFormat::new().color(std::env::var_os("NO_COLOR").is_none());
+
 	let mut rl = Editor::<()>::new();
 	loop {
 		let input = rl.readline(">> ");
@@ -25,6 +87,13 @@ fn main() {
 				rl.add_history_entry(line);
 				println!();
 
+				// Lines starting with `?` use the query language
+				if line.starts_with('?') {
+					run_query(db, &format, &line[1..]);
+					println!();
+					continue;
+				}
+
 				let mut first = true;
 				for it in line.split(' ') {
 					let it = it.trim();
@@ -73,8 +142,9 @@ fn main() {
 									start.elapsed()
 								);
 
-								for index in results.iter().take(5) {
-									println!("\n{}", db.term(index).unwrap());
+								let indexes = results.iter().take(5);
+								for headword in db.group_by_headword(indexes) {
+									println!("\n{}", format.headword(db, &headword));
 								}
 							}
 						}
@@ -90,3 +160,20 @@ fn main() {
 		}
 	}
 }
+
+fn run_query(db: &jp_dict::DB, format: &jp_dict::TextFormat, input: &str) {
+	let query = match jp_dict::Query::parse(input) {
+		Ok(query) => query.limit(5),
+		Err(err) => {
+			println!("Invalid query: {}", err);
+			return;
+		}
+	};
+
+	let start = Instant::now();
+	let results = db.query(&query);
+	println!("Query found {} term(s) in {:?}", results.len(), start.elapsed());
+	for headword in db.group_by_headword(results) {
+		println!("\n{}", format.headword(db, &headword));
+	}
+}
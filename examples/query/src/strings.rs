@@ -0,0 +1,90 @@
+//! Analysis of the database string table.
+//!
+//! Usage:
+//!
+//! ```text
+//! query strings [TOP]
+//! ```
+//!
+//! Prints the string table size, a histogram of the string lengths, the
+//! strings used by each source dictionary and the `TOP` groups of strings
+//! that differ only in case or whitespace (10 by default). See
+//! `DB::string_stats`.
+
+use jp_dict::DB;
+
+const DEFAULT_TOP: usize = 10;
+
+/// Maximum number of characters to print for each near duplicate string.
+const MAX_CHARS: usize = 60;
+
+/// Runs the analysis for the `strings [TOP]` arguments.
+pub fn run(db: &DB, args: &[String]) -> Result<(), String> {
+	let top = match args.get(0) {
+		Some(top) => top.parse::<usize>().map_err(|_| format!("invalid count `{}`", top))?,
+		None => DEFAULT_TOP,
+	};
+
+	let stats = db.string_stats();
+	println!(
+		"{} strings / {} string bytes / {} data bytes\n",
+		stats.count, stats.bytes, stats.data_bytes
+	);
+
+	println!("{:<10} {:>10} {:>12} {:>8}", "length", "count", "bytes", "%");
+	let mut min = 0;
+	for bucket in stats.histogram.iter() {
+		let range = match bucket.max_length {
+			Some(max) if max == min => format!("{}", max),
+			Some(max) => format!("{}-{}", min, max),
+			None => format!("{}+", min),
+		};
+		println!(
+			"{:<10} {:>10} {:>12} {:>8}",
+			range,
+			bucket.count,
+			bucket.bytes,
+			percent(bucket.bytes, stats.bytes)
+		);
+		min = bucket.max_length.unwrap_or(min) + 1;
+	}
+
+	println!("\n{:<24} {:>10} {:>12} {:>8}", "source", "count", "bytes", "%");
+	let rows = stats
+		.sources
+		.iter()
+		.map(|x| (x.name.as_str(), x))
+		.chain(Some(("(shared)", &stats.shared)))
+		.chain(Some(("(other)", &stats.other)));
+	for (name, row) in rows {
+		println!(
+			"{:<24} {:>10} {:>12} {:>8}",
+			name,
+			row.count,
+			row.bytes,
+			percent(row.bytes, stats.bytes)
+		);
+	}
+
+	let wasted: usize = stats.near_duplicates.iter().map(|x| x.wasted_bytes).sum();
+	println!(
+		"\n{} near duplicate groups ({} bytes)",
+		stats.near_duplicates.len(),
+		wasted
+	);
+	for group in stats.near_duplicates.iter().take(top) {
+		println!("\n- {} bytes:", group.wasted_bytes);
+		for text in group.strings.iter() {
+			let mut short: String = text.chars().take(MAX_CHARS).collect();
+			if short.len() < text.len() {
+				short.push_str("...");
+			}
+			println!("    {:?}", short);
+		}
+	}
+	Ok(())
+}
+
+fn percent(value: usize, total: usize) -> String {
+	format!("{:.1}", (value as f64) * 100.0 / (std::cmp::max(total, 1) as f64))
+}
@@ -0,0 +1,792 @@
+//! JSON-RPC 2.0 server over stdio.
+//!
+//! Messages use the same framing as the Language Server Protocol: each
+//! message is preceded by a `Content-Length` header and an empty line.
+//!
+//! Supported methods:
+//!
+//! - `lookup` with `{ "text": "...", "mode": "exact" | "prefix" | "suffix", "limit": N }`
+//!   returns the list of matching terms. With `"romaji": true` the text is
+//!   converted from (possibly partially typed) romaji to kana first.
+//! - `query` with `{ "query": "...", "limit": N }` runs a query using the
+//!   query language and returns the list of matching terms.
+//! - `term` with `{ "index": N }` returns a single term or `null`. With
+//!   `"html": true` returns the term rendered as an HTML fragment instead,
+//!   with the tag descriptions in `"language"` (e.g. `"pt"`) if given.
+//!
+//!   The term can also be given as `{ "id": "..." }`, using the `id` string
+//!   returned with the terms. Unlike the index, the id is kept when the
+//!   database is rebuilt, so it can be stored to reference the term later.
+//! - `scan` with `{ "text": "..." }` returns the term matches for the text,
+//!   with the text not in the dictionary classified by `kind`.
+//! - `scan_glossary` with `{ "index": N }` returns the glossary for a term
+//!   with the term matches for each entry, as returned by `scan`. This allows
+//!   looking up words within J-J definitions.
+//! - `furigana` with `{ "text": "..." }` returns the text split into segments
+//!   with their furigana, as `[{ "text": "...", "reading": "...", "term": N }]`
+//!   (the `reading` and `term` are `null` when not available). With
+//!   `"html": true` returns the text as HTML with `<ruby>` annotations.
+//! - `kana` with `{ "text": "..." }` returns the text converted to kana using
+//!   the dictionary readings. Words with more than one reading have all of
+//!   them listed as `{いく|ゆく}`.
+//! - `family` with `{ "stem": "..." }` returns the terms starting with the
+//!   stem grouped by conjugation class, as `[{ "class": "v1", "terms": [...] }]`
+//!   (the class is `null` for terms that don't conjugate).
+//! - `related` with `{ "index": N }` returns the terms related to a term by
+//!   shared kanji and glossary keywords.
+//! - `similar` with `{ "index": N, "limit": N }` returns the terms with the
+//!   nearest embeddings to a term, with their similarity (the database must
+//!   be built with embeddings).
+//! - `radicals` returns the list of radicals for the radical lookup.
+//! - `domains` returns the domain tags (e.g. `med` or `law`) as
+//!   `[{ "name": "...", "notes": "..." }]`. Terms for a domain can be found
+//!   with a `domain:med` query.
+//! - `tags` returns all tags in table order, as
+//!   `[{ "name": "...", "category": "...", "notes": "..." }]`.
+//! - `tag_categories` returns the tag categories in display order with the
+//!   number of tags in each, as `[{ "name": "...", "tags": N }]`.
+//! - `sources` returns the source dictionaries with their priority, as
+//!   `[{ "name": "...", "priority": N }]`.
+//! - `capabilities` returns the names of the optional features built into
+//!   the database file, so clients can hide what is not available:
+//!   `suffix_index` and `chars_index` (the indexes are in the file),
+//!   `pitch` (pitch accent data), `embeddings` (required by `similar`),
+//!   `media` (media files such as images are stored) and `glossary_index`
+//!   (the related terms use the stored glossary configuration).
+//! - `audio` with `{ "index": N }` returns the audio for a term as
+//!   `{ "mime_type": "...", "data": "<base64>" }` or `null`.
+//! - `metrics` returns the request counts and latency histograms for each
+//!   method, with the loaded database version, as a string in the Prometheus
+//!   text format.
+//! - `rpc.discover` returns the [OpenRPC](https://open-rpc.org) document
+//!   describing the methods, with the schema for their parameters and
+//!   results (see `openrpc.json`). This can be used to generate clients.
+//! - `shutdown` stops the server after replying.
+//!
+//! The `query` method also accepts `"explain": true` to return details on how
+//! the query was executed (index lookups, number of candidates inspected and
+//! the ranking factors for each result) instead of the terms.
+//!
+//! Both `query` and `scan` accept a `"timeout": MS` parameter. Requests that
+//! take longer than the timeout fail with a `-32800` (request cancelled)
+//! error.
+//!
+//! Both `lookup` and `query` accept a `"group": true` parameter to return the
+//! results grouped by headword, with the entries from each source dictionary
+//! nested under the headword. Each source lists its `senses` with the term
+//! index and the `sequence` number in the source dictionary. With
+//! `"merge": true` each headword also lists the `merged` senses, with the
+//! identical senses from different sources collapsed into one.
+//!
+//! Audio is provided by an external text-to-speech command given with the
+//! `--tts-command CMD` argument. The command is called with the term reading
+//! (or expression, if the reading is empty) as the last argument and must
+//! output WAV audio to stdout.
+//!
+//! By default the server uses the database embedded in the library. With
+//! `--database FILE` it loads the database from the file instead and reloads
+//! it when the file is replaced (e.g. by the importer in `--watch` mode). The
+//! file is checked before each request, so a request always sees a complete
+//! database. If the new file is not a valid database the server keeps using
+//! the current one.
+
+#[macro_use]
+extern crate serde_json;
+
+extern crate jp_dict;
+
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::process::Command;
+use std::time::{Instant, SystemTime};
+
+use serde_json::Value;
+
+mod metrics;
+use metrics::Metrics;
+
+/// OpenRPC document describing the server methods.
+const OPENRPC_DOCUMENT: &'static str = include_str!("../openrpc.json");
+
+/// Default limit of terms returned per lookup.
+const DEFAULT_LIMIT: usize = 50;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const REQUEST_CANCELLED: i64 = -32800;
+
+fn main() {
+	let args: Vec<String> = std::env::args().collect();
+	if let Some(pos) = args.iter().position(|x| x == "--tts-command") {
+		match args.get(pos + 1) {
+			Some(command) => jp_dict::set_audio_provider(CommandAudio {
+				command: command.clone(),
+			}),
+			None => {
+				eprintln!("rpc: missing command for --tts-command");
+				std::process::exit(1);
+			}
+		}
+	}
+
+	let database = match args.iter().position(|x| x == "--database") {
+		Some(pos) => match args.get(pos + 1) {
+			Some(file) => Some(file.clone()),
+			None => {
+				eprintln!("rpc: missing file for --database");
+				std::process::exit(1);
+			}
+		},
+		None => None,
+	};
+
+	let stdin = io::stdin();
+	let mut input = stdin.lock();
+	let stdout = io::stdout();
+	let mut output = stdout.lock();
+
+	let file = match database {
+		Some(file) => file,
+		None => {
+			let mut metrics = Metrics::default();
+			serve(jp_dict::get_db(), &mut input, &mut output, &mut metrics, None, &mut || false);
+			return;
+		}
+	};
+
+	let mut data = match load_database(&file) {
+		Ok(data) => data,
+		Err(err) => {
+			eprintln!("rpc: {}", err);
+			std::process::exit(1);
+		}
+	};
+
+	// The importer replaces the file atomically, so a change in the file
+	// modification time means a complete new database is available.
+	let mut modified = modified_time(&file);
+	let mut changed = || {
+		let current = modified_time(&file);
+		if current != modified {
+			modified = current;
+			true
+		} else {
+			false
+		}
+	};
+
+	let mut metrics = Metrics::default();
+	let mut pending = None;
+	loop {
+		pending = {
+			let db = jp_dict::DB::load(&data);
+			serve(&db, &mut input, &mut output, &mut metrics, pending, &mut changed)
+		};
+		if pending.is_none() {
+			break;
+		}
+		match load_database(&file) {
+			Ok(new_data) => {
+				data = new_data;
+				metrics.record_reload();
+				eprintln!("rpc: reloaded database from {}", file);
+			}
+			Err(err) => eprintln!("rpc: keeping the current database: {}", err),
+		}
+	}
+}
+
+/// Serves requests until the input ends or the `shutdown` method is called,
+/// starting with the `pending` message if given.
+///
+/// The `changed` callback is called after reading each request. If it returns
+/// true, the request is returned without being handled so that the database
+/// can be reloaded before handling it.
+fn serve<R: BufRead, W: Write>(
+	db: &jp_dict::DB,
+	input: &mut R,
+	output: &mut W,
+	metrics: &mut Metrics,
+	mut pending: Option<String>,
+	changed: &mut dyn FnMut() -> bool,
+) -> Option<String> {
+	loop {
+		let message = match pending.take() {
+			Some(message) => message,
+			None => match read_message(input) {
+				Ok(Some(message)) if changed() => return Some(message),
+				Ok(Some(message)) => message,
+				Ok(None) => break,
+				Err(err) => {
+					eprintln!("rpc: error reading input: {}", err);
+					break;
+				}
+			},
+		};
+
+		let request: Value = match serde_json::from_str(&message) {
+			Ok(request) => request,
+			Err(err) => {
+				let response = error(Value::Null, PARSE_ERROR, err.to_string());
+				write_message(output, &response).unwrap();
+				continue;
+			}
+		};
+
+		let id = request.get("id").cloned().unwrap_or(Value::Null);
+		let method = match request.get("method").and_then(|x| x.as_str()) {
+			Some(method) => method,
+			None => {
+				let response = error(id, INVALID_REQUEST, "missing method");
+				write_message(output, &response).unwrap();
+				continue;
+			}
+		};
+
+		let params = request.get("params").cloned().unwrap_or(Value::Null);
+		let start = Instant::now();
+		let result = match method {
+			"lookup" => lookup(db, &params),
+			"query" => query(db, &params),
+			"term" => term(db, &params),
+			"scan" => scan(db, &params),
+			"scan_glossary" => scan_glossary(db, &params),
+			"furigana" => furigana(db, &params),
+			"kana" => param_str(&params, "text").map(|x| Value::String(jp_dict::kana_text(db, x))),
+			"family" => family(db, &params),
+			"related" => related(db, &params),
+			"similar" => similar(db, &params),
+			"radicals" => Ok(radicals(db)),
+			"domains" => Ok(domains(db)),
+			"tags" => Ok(tags(db)),
+			"tag_categories" => Ok(tag_categories(db)),
+			"sources" => Ok(sources(db)),
+			"capabilities" => Ok(json!(db.capabilities().names())),
+			"audio" => audio(db, &params),
+			"metrics" => Ok(Value::String(metrics.render(&db.info()))),
+			"rpc.discover" => Ok(serde_json::from_str(OPENRPC_DOCUMENT).unwrap()),
+			"shutdown" => Ok(Value::Null),
+			_ => Err((METHOD_NOT_FOUND, format!("unknown method `{}`", method))),
+		};
+
+		// Unknown methods are counted together, to keep the set of metric
+		// labels bounded.
+		let label = match result {
+			Err((METHOD_NOT_FOUND, _)) => "unknown",
+			_ => method,
+		};
+		metrics.record(label, start.elapsed(), result.is_err());
+
+		// Requests without an `id` are notifications and get no response
+		if request.get("id").is_some() {
+			let response = match result {
+				Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+				Err((code, message)) => error(id, code, message),
+			};
+			write_message(output, &response).unwrap();
+		}
+
+		if method == "shutdown" {
+			break;
+		}
+	}
+	None
+}
+
+/// Reads a database file, checking that it is valid.
+fn load_database(file: &str) -> Result<Vec<u8>, String> {
+	let data = fs::read(file).map_err(|err| format!("reading {}: {}", file, err))?;
+	jp_dict::DatabaseInfo::read(&mut &data[..]).map_err(|err| format!("invalid database {}: {}", file, err))?;
+	Ok(data)
+}
+
+fn modified_time(file: &str) -> Option<SystemTime> {
+	fs::metadata(file).and_then(|x| x.modified()).ok()
+}
+
+fn lookup(db: &jp_dict::DB, params: &Value) -> Result<Value, (i64, String)> {
+	let text = param_str(params, "text")?;
+	let limit = param_limit(params);
+	let text = if params.get("romaji").and_then(|x| x.as_bool()).unwrap_or(false) {
+		jp_dict::parse_romaji_input(text).kana
+	} else {
+		text.to_string()
+	};
+	let text = text.as_str();
+
+	let results = match params.get("mode").and_then(|x| x.as_str()) {
+		None | Some("exact") => {
+			let mut results = jp_dict::ResultSet::default();
+			db.search_term(text, &mut results);
+			results.iter().take(limit).collect()
+		}
+		Some("prefix") => db.search_prefix_top(text, limit),
+		Some("suffix") => {
+			let mut results = jp_dict::ResultSet::default();
+			db.search_suffix(text, &mut results);
+			results.iter().take(limit).collect()
+		}
+		Some(mode) => {
+			return Err((INVALID_PARAMS, format!("invalid lookup mode `{}`", mode)));
+		}
+	};
+
+	Ok(terms_json(db, results, params))
+}
+
+fn query(db: &jp_dict::DB, params: &Value) -> Result<Value, (i64, String)> {
+	let query = param_str(params, "query")?;
+	let query = jp_dict::Query::parse(query).map_err(|err| (INVALID_PARAMS, err.to_string()))?;
+	let mut query = query.limit(param_limit(params));
+	if let Some(frequency) = params.get("min_frequency").and_then(|x| x.as_u64()) {
+		query = query.min_frequency(frequency.min(std::u32::MAX as u64) as u32);
+	}
+	if let Some(count) = params.get("max_per_headword").and_then(|x| x.as_u64()) {
+		query = query.max_results_per_headword(count as usize);
+	}
+	if let Some(count) = params.get("max_per_source").and_then(|x| x.as_u64()) {
+		query = query.max_results_per_source(count as usize);
+	}
+	if let Some(count) = params.get("max_per_pos").and_then(|x| x.as_u64()) {
+		query = query.max_results_per_pos(count as usize);
+	}
+	let explain = params.get("explain").and_then(|x| x.as_bool()).unwrap_or(false);
+	if explain {
+		return Ok(explain_json(db.explain(&query)));
+	}
+	let results = db
+		.query_cancellable(&query, &param_cancel(params))
+		.map_err(|err| (REQUEST_CANCELLED, err.to_string()))?;
+	Ok(terms_json(db, results, params))
+}
+
+fn explain_json(explain: jp_dict::Explain) -> Value {
+	let lookups = explain.lookups.map(|list| {
+		list.into_iter()
+			.map(|it| match it {
+				jp_dict::IndexLookup::Exact(key) => json!({ "mode": "exact", "key": key }),
+				jp_dict::IndexLookup::Prefix(key) => json!({ "mode": "prefix", "key": key }),
+			})
+			.collect::<Vec<_>>()
+	});
+	let results: Vec<_> = explain
+		.results
+		.into_iter()
+		.map(|it| {
+			json!({
+				"index": it.index,
+				"frequency": it.frequency,
+				"score": it.score,
+				"preferred": it.preferred,
+			})
+		})
+		.collect();
+	json!({
+		"lookups": lookups,
+		"candidates": explain.candidates,
+		"inspected": explain.inspected,
+		"results": results,
+	})
+}
+
+fn term(db: &jp_dict::DB, params: &Value) -> Result<Value, (i64, String)> {
+	let term = match params.get("id").and_then(|x| x.as_str()) {
+		Some(id) => {
+			let id = u64::from_str_radix(id, 16).map_err(|_| (INVALID_PARAMS, format!("invalid `id`")))?;
+			db.term_by_id(id)
+		}
+		None => db.term(param_index(params)?),
+	};
+	let html = params.get("html").and_then(|x| x.as_bool()).unwrap_or(false);
+	let language = params.get("language").and_then(|x| x.as_str());
+	Ok(match term {
+		Some(ref term) if html => Value::String(match language {
+			Some(language) => jp_dict::html_term_in(term, language),
+			None => jp_dict::html_term(term),
+		}),
+		Some(term) => term_json(term.index(), &term),
+		None => Value::Null,
+	})
+}
+
+fn scan(db: &jp_dict::DB, params: &Value) -> Result<Value, (i64, String)> {
+	let text = param_str(params, "text")?;
+	let matches = db
+		.scan_cancellable(text, &param_cancel(params))
+		.map_err(|err| (REQUEST_CANCELLED, err.to_string()))?;
+	Ok(scan_json(text, matches))
+}
+
+fn furigana(db: &jp_dict::DB, params: &Value) -> Result<Value, (i64, String)> {
+	let text = param_str(params, "text")?;
+	if params.get("html").and_then(|x| x.as_bool()).unwrap_or(false) {
+		return Ok(Value::String(jp_dict::html_furigana(db, text)));
+	}
+	let segments: Vec<_> = jp_dict::annotate_furigana(db, text)
+		.into_iter()
+		.map(|it| {
+			json!({
+				"text": it.text,
+				"reading": it.reading,
+				"term": it.term,
+			})
+		})
+		.collect();
+	Ok(Value::Array(segments))
+}
+
+fn family(db: &jp_dict::DB, params: &Value) -> Result<Value, (i64, String)> {
+	let stem = param_str(params, "stem")?;
+	let families: Vec<_> = db
+		.word_family(stem)
+		.into_iter()
+		.map(|it| {
+			json!({
+				"class": it.class,
+				"terms": terms_json(db, it.terms, params),
+			})
+		})
+		.collect();
+	Ok(Value::Array(families))
+}
+
+fn related(db: &jp_dict::DB, params: &Value) -> Result<Value, (i64, String)> {
+	let index = param_index(params)?;
+	Ok(terms_json(db, db.related(index), params))
+}
+
+fn similar(db: &jp_dict::DB, params: &Value) -> Result<Value, (i64, String)> {
+	let index = param_index(params)?;
+	let similar = db.similar_terms(index, param_limit(params));
+	let indexes = similar.iter().map(|x| x.0).collect();
+	let similarity: Vec<_> = similar.iter().map(|x| x.1).collect();
+	Ok(json!({
+		"terms": terms_json(db, indexes, params),
+		"similarity": similarity,
+	}))
+}
+
+fn scan_glossary(db: &jp_dict::DB, params: &Value) -> Result<Value, (i64, String)> {
+	let index = param_index(params)?;
+	Ok(match db.scan_glossary(index) {
+		Some(glossary) => Value::Array(
+			glossary
+				.into_iter()
+				.map(|it| {
+					json!({
+						"text": it.text,
+						"matches": scan_json(it.text, it.matches),
+					})
+				})
+				.collect(),
+		),
+		None => Value::Null,
+	})
+}
+
+fn scan_json(text: &str, matches: Vec<jp_dict::ScanMatch>) -> Value {
+	let matches: Vec<_> = matches
+		.into_iter()
+		.map(|it| {
+			json!({
+				"start": it.start,
+				"end": it.end,
+				"text": it.text(text),
+				"kind": it.kind.name(),
+				"terms": it.ranked,
+				"numeric": it.numeric.map(|x| json!({ "value": x.value.to_string(), "reading": x.reading })),
+			})
+		})
+		.collect();
+	Value::Array(matches)
+}
+
+/// Returns the JSON for a list of terms, grouping them by headword if the
+/// `group` parameter is set.
+fn terms_json(db: &jp_dict::DB, indexes: Vec<usize>, params: &Value) -> Value {
+	let group = params.get("group").and_then(|x| x.as_bool()).unwrap_or(false);
+	let merge = params.get("merge").and_then(|x| x.as_bool()).unwrap_or(false);
+	if group {
+		let headwords: Vec<_> = db
+			.group_by_headword(indexes)
+			.into_iter()
+			.map(|headword| {
+				let sources: Vec<_> = headword
+					.sources
+					.iter()
+					.map(|it| {
+						let senses: Vec<_> = it
+							.senses
+							.iter()
+							.map(|sense| {
+								json!({
+									"term": sense.term,
+									"source": sense.source,
+									"sequence": sense.sequence,
+									"glossary": sense.glossary,
+									"info": sense.info,
+								})
+							})
+							.collect();
+						json!({
+							"source": it.source,
+							"terms": it.terms,
+							"glossary": it.glossary,
+							"senses": senses,
+						})
+					})
+					.collect();
+				let mut out = json!({
+					"expression": headword.expression,
+					"reading": headword.reading,
+					"sources": sources,
+				});
+				if merge {
+					let merged: Vec<_> = headword
+						.merged_senses()
+						.into_iter()
+						.map(|it| {
+							json!({
+								"glossary": it.glossary,
+								"sources": it.sources,
+								"terms": it.terms,
+							})
+						})
+						.collect();
+					out["merged"] = Value::Array(merged);
+				}
+				out
+			})
+			.collect();
+		Value::Array(headwords)
+	} else {
+		let terms: Vec<_> = indexes
+			.into_iter()
+			.map(|index| term_json(index, &db.term(index).unwrap()))
+			.collect();
+		Value::Array(terms)
+	}
+}
+
+fn audio(db: &jp_dict::DB, params: &Value) -> Result<Value, (i64, String)> {
+	let index = param_index(params)?;
+	let term = db
+		.term(index)
+		.ok_or_else(|| (INVALID_PARAMS, format!("invalid term index {}", index)))?;
+	Ok(match jp_dict::get_audio(&term) {
+		Some(audio) => json!({
+			"mime_type": audio.mime_type,
+			"data": base64(&audio.data),
+		}),
+		None => Value::Null,
+	})
+}
+
+/// Audio provider that runs an external text-to-speech command.
+struct CommandAudio {
+	command: String,
+}
+
+impl jp_dict::AudioProvider for CommandAudio {
+	fn audio(&self, expression: &str, reading: &str) -> Option<jp_dict::Audio> {
+		let text = if reading.len() > 0 { reading } else { expression };
+		let output = Command::new(&self.command).arg(text).output().ok()?;
+		if output.status.success() && output.stdout.len() > 0 {
+			Some(jp_dict::Audio {
+				mime_type: String::from("audio/wav"),
+				data: output.stdout,
+			})
+		} else {
+			None
+		}
+	}
+}
+
+fn base64(data: &[u8]) -> String {
+	const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+	let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+	for chunk in data.chunks(3) {
+		let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+		let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | (b[2] as u32);
+		for i in 0..4 {
+			if i <= chunk.len() {
+				out.push(CHARS[((n >> (18 - 6 * i)) & 0x3F) as usize] as char);
+			} else {
+				out.push('=');
+			}
+		}
+	}
+	out
+}
+
+fn radicals(db: &jp_dict::DB) -> Value {
+	let radicals: Vec<_> = db
+		.radicals()
+		.map(|it| {
+			json!({
+				"number": it.number(),
+				"character": it.character().to_string(),
+				"strokes": it.strokes(),
+				"name": it.name(),
+				"variants": it.variants().map(|x| x.to_string()).collect::<Vec<_>>(),
+			})
+		})
+		.collect();
+	Value::Array(radicals)
+}
+
+fn domains(db: &jp_dict::DB) -> Value {
+	let domains: Vec<_> = db
+		.domains()
+		.into_iter()
+		.map(|it| json!({ "name": it.name(), "notes": it.notes() }))
+		.collect();
+	Value::Array(domains)
+}
+
+fn tags(db: &jp_dict::DB) -> Value {
+	let tags: Vec<_> = db
+		.tags()
+		.map(|it| json!({ "name": it.name(), "category": it.category(), "notes": it.notes() }))
+		.collect();
+	Value::Array(tags)
+}
+
+fn tag_categories(db: &jp_dict::DB) -> Value {
+	let categories: Vec<_> = db
+		.tag_categories()
+		.into_iter()
+		.map(|it| json!({ "name": it.name, "tags": it.tags }))
+		.collect();
+	Value::Array(categories)
+}
+
+fn sources(db: &jp_dict::DB) -> Value {
+	let sources: Vec<_> = db
+		.sources()
+		.map(|it| json!({ "name": it.name(), "priority": it.priority() }))
+		.collect();
+	Value::Array(sources)
+}
+
+fn term_json<'db, 'a>(index: usize, term: &'a jp_dict::Term<'db, 'a>) -> Value {
+	json!({
+		"index": index,
+		// Ids are 64-bit, so they are returned as hex strings to avoid
+		// losing precision in JSON numbers.
+		"id": format!("{:016x}", term.id()),
+		"expression": term.expression(),
+		"reading": term.reading(),
+		"score": term.score(),
+		"sequence": term.sequence(),
+		"order": term.order(),
+		"frequency": term.frequency(),
+		"priority": term.priority().codes(),
+		"pitch": term.pitch().collect::<Vec<_>>(),
+		"source": term.source(),
+		"glossary": term.glossary().collect::<Vec<_>>(),
+		"truncated": term.is_truncated(),
+		"info": term.info().collect::<Vec<_>>(),
+		"restrictions": term
+			.restrictions()
+			.map(|(expression, reading)| json!({ "expression": expression, "reading": reading }))
+			.collect::<Vec<_>>(),
+		"rules": term.rules().map(|x| x.name()).collect::<Vec<_>>(),
+		"term_tags": term.term_tags().map(|x| x.name()).collect::<Vec<_>>(),
+		"definition_tags": term.definition_tags().map(|x| x.name()).collect::<Vec<_>>(),
+		"origins": term
+			.origins()
+			.map(|(language, word)| json!({ "language": language, "word": word }))
+			.collect::<Vec<_>>(),
+	})
+}
+
+fn param_str<'a>(params: &'a Value, name: &str) -> Result<&'a str, (i64, String)> {
+	params
+		.get(name)
+		.and_then(|x| x.as_str())
+		.ok_or_else(|| (INVALID_PARAMS, format!("missing `{}`", name)))
+}
+
+fn param_index(params: &Value) -> Result<usize, (i64, String)> {
+	params
+		.get("index")
+		.and_then(|x| x.as_u64())
+		.map(|x| x as usize)
+		.ok_or_else(|| (INVALID_PARAMS, String::from("missing `index`")))
+}
+
+fn param_limit(params: &Value) -> usize {
+	params
+		.get("limit")
+		.and_then(|x| x.as_u64())
+		.map(|x| x as usize)
+		.unwrap_or(DEFAULT_LIMIT)
+}
+
+/// Returns a cancel token for the optional `timeout` parameter (in ms).
+fn param_cancel(params: &Value) -> jp_dict::Cancel {
+	match params.get("timeout").and_then(|x| x.as_u64()) {
+		Some(timeout) => jp_dict::Cancel::timeout(std::time::Duration::from_millis(timeout)),
+		None => jp_dict::Cancel::new(),
+	}
+}
+
+fn error<S: Into<String>>(id: Value, code: i64, message: S) -> Value {
+	json!({
+		"jsonrpc": "2.0",
+		"id": id,
+		"error": { "code": code, "message": message.into() },
+	})
+}
+
+/// Reads a single message from the input. Returns `None` at the end of the
+/// input.
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+	let mut length = None;
+	loop {
+		let mut line = String::new();
+		if input.read_line(&mut line)? == 0 {
+			return Ok(None);
+		}
+
+		let line = line.trim();
+		if line.len() == 0 {
+			if length.is_some() {
+				break;
+			}
+			continue;
+		}
+
+		let mut parts = line.splitn(2, ':');
+		let name = parts.next().unwrap().trim();
+		let value = parts.next().unwrap_or("").trim();
+		if name.eq_ignore_ascii_case("Content-Length") {
+			match value.parse::<usize>() {
+				Ok(value) => length = Some(value),
+				Err(_) => {
+					return Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						format!("invalid content length: {}", value),
+					));
+				}
+			}
+		}
+	}
+
+	let mut buffer = vec![0; length.unwrap()];
+	input.read_exact(&mut buffer)?;
+	String::from_utf8(buffer)
+		.map(Some)
+		.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn write_message<W: Write>(output: &mut W, message: &Value) -> io::Result<()> {
+	let message = message.to_string();
+	write!(output, "Content-Length: {}\r\n\r\n{}", message.len(), message)?;
+	output.flush()
+}
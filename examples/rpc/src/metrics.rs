@@ -0,0 +1,101 @@
+//! Request metrics for the server, rendered in the Prometheus text exposition
+//! format.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Upper bounds in seconds for the request latency histogram buckets.
+const BUCKETS: [f64; 8] = [0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// Metrics collected while serving requests.
+#[derive(Default)]
+pub struct Metrics {
+	methods: BTreeMap<String, MethodMetrics>,
+	reloads: u64,
+}
+
+#[derive(Default)]
+struct MethodMetrics {
+	count: u64,
+	errors: u64,
+	/// Number of requests for each bucket, not cumulative. Requests over the
+	/// last bucket are only counted in `count`.
+	buckets: [u64; BUCKETS.len()],
+	sum: Duration,
+}
+
+impl Metrics {
+	/// Records a request for the method with its latency.
+	pub fn record(&mut self, method: &str, elapsed: Duration, error: bool) {
+		let entry = self.methods.entry(method.to_string()).or_default();
+		entry.count += 1;
+		if error {
+			entry.errors += 1;
+		}
+		entry.sum += elapsed;
+		let seconds = elapsed.as_secs_f64();
+		if let Some(bucket) = BUCKETS.iter().position(|&x| seconds <= x) {
+			entry.buckets[bucket] += 1;
+		}
+	}
+
+	/// Records a reload of the database file.
+	pub fn record_reload(&mut self) {
+		self.reloads += 1;
+	}
+
+	/// Renders the metrics, including the information for the loaded
+	/// database.
+	pub fn render(&self, info: &jp_dict::DatabaseInfo) -> String {
+		let mut out = String::new();
+
+		header(&mut out, "requests_total", "counter", "Number of requests by method.");
+		for (method, it) in self.methods.iter() {
+			let _ = writeln!(out, "jp_dict_requests_total{{method=\"{}\"}} {}", method, it.count);
+		}
+
+		header(&mut out, "request_errors_total", "counter", "Number of failed requests by method.");
+		for (method, it) in self.methods.iter() {
+			let _ = writeln!(out, "jp_dict_request_errors_total{{method=\"{}\"}} {}", method, it.errors);
+		}
+
+		header(&mut out, "request_duration_seconds", "histogram", "Request latency by method.");
+		for (method, it) in self.methods.iter() {
+			let name = "jp_dict_request_duration_seconds";
+			let mut total = 0;
+			for (bound, count) in BUCKETS.iter().zip(it.buckets.iter()) {
+				total += count;
+				let _ = writeln!(out, "{}_bucket{{method=\"{}\",le=\"{}\"}} {}", name, method, bound, total);
+			}
+			let _ = writeln!(out, "{}_bucket{{method=\"{}\",le=\"+Inf\"}} {}", name, method, it.count);
+			let _ = writeln!(out, "{}_sum{{method=\"{}\"}} {}", name, method, it.sum.as_secs_f64());
+			let _ = writeln!(out, "{}_count{{method=\"{}\"}} {}", name, method, it.count);
+		}
+
+		header(&mut out, "database_info", "gauge", "Loaded database, with the value always 1.");
+		let _ = writeln!(
+			out,
+			"jp_dict_database_info{{version=\"{}\",profile=\"{}\",build_date=\"{}\"}} 1",
+			info.version,
+			info.profile.name(),
+			info.build_date()
+		);
+
+		header(&mut out, "database_build_time_seconds", "gauge", "Build time of the loaded database.");
+		let _ = writeln!(out, "jp_dict_database_build_time_seconds {}", info.build_time);
+
+		header(&mut out, "database_terms", "gauge", "Number of terms in the loaded database.");
+		let _ = writeln!(out, "jp_dict_database_terms {}", info.terms);
+
+		header(&mut out, "database_reloads_total", "counter", "Number of database file reloads.");
+		let _ = writeln!(out, "jp_dict_database_reloads_total {}", self.reloads);
+
+		out
+	}
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+	let _ = writeln!(out, "# HELP jp_dict_{} {}", name, help);
+	let _ = writeln!(out, "# TYPE jp_dict_{} {}", name, kind);
+}
@@ -0,0 +1,156 @@
+//! Reading of plain text and EPUB books.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+use regex::Regex;
+
+/// A chapter from a book.
+pub struct Chapter {
+	/// Chapter title, if available.
+	pub title: String,
+	/// Plain text for the chapter.
+	pub text: String,
+}
+
+/// Reads the list of chapters from a `.txt` or `.epub` book.
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<Chapter>> {
+	let path = path.as_ref();
+	let is_epub = path
+		.extension()
+		.map(|x| x.to_string_lossy().to_lowercase() == "epub")
+		.unwrap_or(false);
+	if is_epub {
+		read_epub(path)
+	} else {
+		let text = fs::read_to_string(path)?;
+		Ok(split_text(text.trim_start_matches('\u{FEFF}')))
+	}
+}
+
+/// Splits a plain text book into chapters by looking for chapter headings
+/// (e.g. `第一章`) or form feed characters. If no chapter is found, the whole
+/// text is returned as a single chapter.
+fn split_text(text: &str) -> Vec<Chapter> {
+	lazy_static! {
+		static ref RE_HEADING: Regex =
+			Regex::new(r"^\s*第[0-9０-９一二三四五六七八九十百千]+[章話部回]").unwrap();
+	}
+
+	let mut out = Vec::new();
+	let mut title = String::new();
+	let mut current = String::new();
+	for line in text.lines() {
+		let is_break = line.starts_with('\u{000C}');
+		if is_break || RE_HEADING.is_match(line) {
+			if current.trim().len() > 0 {
+				out.push(Chapter {
+					title: title,
+					text: current,
+				});
+			}
+			title = line.trim_matches(|c: char| c == '\u{000C}' || c.is_whitespace()).to_string();
+			current = String::new();
+		}
+		current.push_str(line);
+		current.push('\n');
+	}
+
+	if current.trim().len() > 0 {
+		out.push(Chapter {
+			title: title,
+			text: current,
+		});
+	}
+	out
+}
+
+/// Reads an EPUB book, returning each document in the spine as a chapter.
+fn read_epub(path: &Path) -> io::Result<Vec<Chapter>> {
+	lazy_static! {
+		static ref RE_ROOTFILE: Regex = Regex::new(r#"<rootfile[^>]*full-path="([^"]+)""#).unwrap();
+		static ref RE_ITEM: Regex = Regex::new(r"<item\s[^>]*>").unwrap();
+		static ref RE_ITEMREF: Regex = Regex::new(r#"<itemref[^>]*idref="([^"]+)""#).unwrap();
+		static ref RE_ID: Regex = Regex::new(r#"\sid="([^"]+)""#).unwrap();
+		static ref RE_HREF: Regex = Regex::new(r#"\shref="([^"]+)""#).unwrap();
+		static ref RE_TITLE: Regex = Regex::new(r"(?s)<title[^>]*>(.*?)</title>").unwrap();
+	}
+
+	let file = fs::File::open(path)?;
+	let mut archive = zip::ZipArchive::new(file)?;
+
+	let container = read_entry(&mut archive, "META-INF/container.xml")?;
+	let rootfile = match RE_ROOTFILE.captures(&container) {
+		Some(caps) => caps[1].to_string(),
+		None => return Err(invalid_data("rootfile not found in container.xml")),
+	};
+	let package = read_entry(&mut archive, &rootfile)?;
+	let base = match rootfile.rfind('/') {
+		Some(pos) => &rootfile[..=pos],
+		None => "",
+	};
+
+	let mut manifest = HashMap::new();
+	for item in RE_ITEM.find_iter(&package) {
+		let item = item.as_str();
+		if let (Some(id), Some(href)) = (RE_ID.captures(item), RE_HREF.captures(item)) {
+			manifest.insert(id[1].to_string(), format!("{}{}", base, &href[1]));
+		}
+	}
+
+	let mut out = Vec::new();
+	for itemref in RE_ITEMREF.captures_iter(&package) {
+		let href = match manifest.get(&itemref[1]) {
+			Some(href) => href,
+			None => continue,
+		};
+		let document = read_entry(&mut archive, href)?;
+		let title = RE_TITLE
+			.captures(&document)
+			.map(|x| strip_html(&x[1]).trim().to_string())
+			.unwrap_or_default();
+		let text = strip_html(&document);
+		if text.trim().len() > 0 {
+			out.push(Chapter {
+				title: title,
+				text: text,
+			});
+		}
+	}
+	Ok(out)
+}
+
+fn read_entry(archive: &mut zip::ZipArchive<fs::File>, name: &str) -> io::Result<String> {
+	let mut entry = archive.by_name(name)?;
+	let mut out = String::new();
+	entry.read_to_string(&mut out)?;
+	Ok(out)
+}
+
+/// Strips the markup from an HTML document, returning its text. Furigana
+/// annotations in `<rt>` and `<rp>` tags are removed.
+fn strip_html(html: &str) -> String {
+	lazy_static! {
+		static ref RE_HEAD: Regex = Regex::new(r"(?s)<head[^>]*>.*?</head>").unwrap();
+		static ref RE_RUBY: Regex = Regex::new(r"(?s)<(rt|rp)[^>]*>.*?</(rt|rp)>").unwrap();
+		static ref RE_BLOCK: Regex = Regex::new(r"(?i)<(br|/p|/div|/h[1-6])[^>]*>").unwrap();
+		static ref RE_TAG: Regex = Regex::new(r"(?s)<[^>]*>").unwrap();
+	}
+
+	let text = RE_HEAD.replace_all(html, "");
+	let text = RE_RUBY.replace_all(&text, "");
+	let text = RE_BLOCK.replace_all(&text, "\n");
+	let text = RE_TAG.replace_all(&text, "");
+	text.replace("&lt;", "<")
+		.replace("&gt;", ">")
+		.replace("&quot;", "\"")
+		.replace("&nbsp;", " ")
+		.replace("&amp;", "&")
+}
+
+fn invalid_data(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,182 @@
+//! Vocabulary extraction tool.
+//!
+//! Usage:
+//!
+//!     vocab subs [--known FREQUENCY] [--romaji STYLE] FILES...
+//!     vocab book [--known FREQUENCY] [--romaji STYLE] FILE
+//!
+//! The `subs` command reads SRT or ASS subtitle files and outputs the list of
+//! words found in them as tab-separated values, sorted by frequency.
+//!
+//! The `book` command reads a `.txt` or `.epub` book and outputs a report for
+//! each chapter with the kanji coverage and the list of new words in the
+//! chapter (i.e. not seen in previous chapters).
+//!
+//! Words with a frequency equal or above `--known` are assumed to be known
+//! and are excluded from the output.
+//!
+//! With `--romaji` the word lists include a column with the reading in
+//! romaji, using either the `hepburn` or `wapuro` style.
+
+#[macro_use]
+extern crate lazy_static;
+extern crate regex;
+extern crate zip;
+
+extern crate jp_dict;
+
+use std::collections::HashSet;
+use std::fs;
+
+mod book;
+
+mod subs;
+
+mod vocab;
+use vocab::Vocabulary;
+
+fn main() {
+	let args: Vec<String> = std::env::args().skip(1).collect();
+	if args.len() == 0 {
+		usage();
+	}
+
+	let command = args[0].as_str();
+	let mut known = None;
+	let mut romaji = None;
+	let mut files = Vec::new();
+	let mut args = args[1..].iter();
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--known" => {
+				known = args.next().and_then(|x| x.parse::<u32>().ok());
+				if known.is_none() {
+					usage();
+				}
+			}
+			"--romaji" => {
+				romaji = args.next().and_then(|x| x.parse::<jp_dict::RomajiStyle>().ok());
+				if romaji.is_none() {
+					usage();
+				}
+			}
+			_ => files.push(arg.clone()),
+		}
+	}
+
+	match command {
+		"subs" => subs(files, known, romaji),
+		"book" if files.len() == 1 => book(&files[0], known, romaji),
+		_ => usage(),
+	}
+}
+
+fn usage() -> ! {
+	eprintln!("\nusage: vocab subs [--known FREQUENCY] [--romaji hepburn|wapuro] FILES...");
+	eprintln!("       vocab book [--known FREQUENCY] [--romaji hepburn|wapuro] FILE\n");
+	std::process::exit(1);
+}
+
+fn subs(files: Vec<String>, known: Option<u32>, romaji: Option<jp_dict::RomajiStyle>) {
+	let db = jp_dict::get_db();
+	let mut vocab = Vocabulary::default();
+	for file in files {
+		let contents = match fs::read_to_string(&file) {
+			Ok(contents) => contents,
+			Err(err) => {
+				eprintln!("\nERROR: could not read {}: {}\n", file, err);
+				std::process::exit(2);
+			}
+		};
+		for line in subs::parse(&contents) {
+			vocab.add_text(db, &line);
+		}
+	}
+
+	print_words(db, vocab.words(db, known), romaji);
+}
+
+fn book(file: &str, known: Option<u32>, romaji: Option<jp_dict::RomajiStyle>) {
+	let db = jp_dict::get_db();
+	let chapters = match book::read(file) {
+		Ok(chapters) => chapters,
+		Err(err) => {
+			eprintln!("\nERROR: could not read {}: {}\n", file, err);
+			std::process::exit(2);
+		}
+	};
+
+	let mut seen_words = HashSet::new();
+	let mut seen_kanji = HashSet::new();
+	for (index, chapter) in chapters.iter().enumerate() {
+		let mut vocab = Vocabulary::default();
+		vocab.add_text(db, &chapter.text);
+
+		let words: Vec<_> = vocab.words(db, known);
+		let total_words = words.len();
+		let new_words: Vec<_> = words
+			.into_iter()
+			.filter(|word| {
+				let term = db.term(word.index).unwrap();
+				seen_words.insert((term.expression(), term.reading()))
+			})
+			.collect();
+
+		let kanji: HashSet<char> = chapter
+			.text
+			.chars()
+			.filter(|&chr| vocab::is_kanji(chr))
+			.collect();
+		let new_kanji = kanji.iter().filter(|&&chr| seen_kanji.insert(chr)).count();
+		let known_kanji = kanji
+			.iter()
+			.filter(|&&chr| db.search_kanji(chr).is_some())
+			.count();
+
+		if chapter.title.len() > 0 {
+			println!("## Chapter {}: {}\n", index + 1, chapter.title);
+		} else {
+			println!("## Chapter {}\n", index + 1);
+		}
+		println!("- Words: {} ({} new)", total_words, new_words.len());
+		println!(
+			"- Kanji: {} ({} new / {} in the dictionary)",
+			kanji.len(),
+			new_kanji,
+			known_kanji
+		);
+		println!();
+		print_words(db, new_words, romaji);
+		println!();
+	}
+
+	println!("## Total\n");
+	println!("- Words: {}", seen_words.len());
+	println!("- Kanji: {}", seen_kanji.len());
+}
+
+fn print_words(db: &jp_dict::DB, words: Vec<&vocab::Word>, romaji: Option<jp_dict::RomajiStyle>) {
+	if romaji.is_some() {
+		println!("expression\treading\tromaji\tfrequency\tcount\tglossary");
+	} else {
+		println!("expression\treading\tfrequency\tcount\tglossary");
+	}
+	for word in words {
+		let term = db.term(word.index).unwrap();
+		let glossary: Vec<_> = term.glossary().collect();
+		let mut reading = term.reading().to_string();
+		if let Some(style) = romaji {
+			let kana = if reading.len() > 0 { term.reading() } else { term.expression() };
+			reading.push('\t');
+			reading.push_str(&jp_dict::to_romaji(kana, style));
+		}
+		println!(
+			"{}\t{}\t{}\t{}\t{}",
+			term.expression(),
+			reading,
+			term.frequency().unwrap_or(0),
+			word.count,
+			glossary.join("; ")
+		);
+	}
+}
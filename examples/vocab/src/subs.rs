@@ -0,0 +1,69 @@
+//! Parsing of subtitle files.
+
+use regex::Regex;
+
+/// Returns the text lines from a subtitle file. The format is detected from
+/// the file contents, supporting SRT and ASS/SSA subtitles.
+pub fn parse(contents: &str) -> Vec<String> {
+	let contents = contents.trim_start_matches('\u{FEFF}');
+	if contents.lines().any(|x| x.trim() == "[Events]") {
+		parse_ass(contents)
+	} else {
+		parse_srt(contents)
+	}
+}
+
+/// Parses a SRT file. Each entry consists of a counter line, a timing line
+/// and one or more lines of text followed by a blank line.
+fn parse_srt(contents: &str) -> Vec<String> {
+	lazy_static! {
+		static ref RE_TAGS: Regex = Regex::new(r"<[^>]*>|\{[^}]*\}").unwrap();
+	}
+
+	let mut out = Vec::new();
+	let mut in_text = false;
+	for line in contents.lines() {
+		let line = line.trim();
+		if line.len() == 0 {
+			in_text = false;
+		} else if line.contains("-->") {
+			in_text = true;
+		} else if in_text {
+			let line = RE_TAGS.replace_all(line, "");
+			if line.len() > 0 {
+				out.push(line.to_string());
+			}
+		}
+	}
+	out
+}
+
+/// Parses the `Dialogue` lines from the `[Events]` section of an ASS file.
+fn parse_ass(contents: &str) -> Vec<String> {
+	lazy_static! {
+		static ref RE_OVERRIDE: Regex = Regex::new(r"\{[^}]*\}").unwrap();
+	}
+
+	/// Text is the last field in a dialogue line, after the layer, start, end,
+	/// style, name, margin L/R/V and effect fields.
+	const TEXT_FIELD: usize = 9;
+
+	let mut out = Vec::new();
+	for line in contents.lines() {
+		let line = line.trim();
+		if !line.starts_with("Dialogue:") {
+			continue;
+		}
+		let fields = &line["Dialogue:".len()..];
+		if let Some(text) = fields.splitn(TEXT_FIELD + 1, ',').nth(TEXT_FIELD) {
+			let text = RE_OVERRIDE.replace_all(text, "");
+			for line in text.split("\\N").flat_map(|x| x.split("\\n")) {
+				let line = line.trim();
+				if line.len() > 0 {
+					out.push(line.to_string());
+				}
+			}
+		}
+	}
+	out
+}
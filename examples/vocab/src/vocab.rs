@@ -0,0 +1,91 @@
+//! Vocabulary collection from scanned text.
+
+use std::collections::HashMap;
+
+use jp_dict::DB;
+
+/// Word from a vocabulary list.
+pub struct Word {
+	/// Index of the term for this word in the database.
+	pub index: usize,
+	/// Number of occurrences of the word in the scanned text.
+	pub count: usize,
+}
+
+/// Collects a deduplicated list of words from scanned text.
+#[derive(Default)]
+pub struct Vocabulary {
+	words: Vec<Word>,
+	word_map: HashMap<(String, String), usize>,
+}
+
+impl Vocabulary {
+	/// Scans the text and adds all words found to the vocabulary.
+	pub fn add_text(&mut self, db: &DB, text: &str) {
+		for it in db.scan(text) {
+			if !it.text(text).chars().any(is_japanese) {
+				continue;
+			}
+
+			// Ranked terms are sorted by how likely they are in context, so
+			// we just pick the first one for the word.
+			if let Some(&index) = it.ranked.first() {
+				let term = db.term(index).unwrap();
+				let key = (term.expression().to_string(), term.reading().to_string());
+				let words = &mut self.words;
+				let pos = *self.word_map.entry(key).or_insert_with(|| {
+					words.push(Word {
+						index: index,
+						count: 0,
+					});
+					words.len() - 1
+				});
+				self.words[pos].count += 1;
+			}
+		}
+	}
+
+	/// Returns the list of words sorted by their frequency and number of
+	/// occurrences.
+	///
+	/// If `known` is given, words with a frequency equal or above it are
+	/// assumed to be known and excluded from the list.
+	pub fn words(&self, db: &DB, known: Option<u32>) -> Vec<&Word> {
+		let frequency = |word: &Word| db.term(word.index).unwrap().frequency();
+		let mut out: Vec<_> = self
+			.words
+			.iter()
+			.filter(|word| match (known, frequency(word)) {
+				(Some(known), Some(frequency)) => frequency < known,
+				_ => true,
+			})
+			.collect();
+		out.sort_by(|a, b| {
+			frequency(b)
+				.cmp(&frequency(a))
+				.then(b.count.cmp(&a.count))
+				.then(a.index.cmp(&b.index))
+		});
+		out
+	}
+}
+
+/// Returns true for kana and kanji characters.
+pub fn is_japanese(chr: char) -> bool {
+	match chr {
+		'\u{3040}'..='\u{30FF}' => true, // hiragana and katakana
+		'\u{3400}'..='\u{4DBF}' => true, // CJK extension A
+		'\u{4E00}'..='\u{9FFF}' => true, // CJK unified ideographs
+		'\u{FF66}'..='\u{FF9F}' => true, // half-width katakana
+		_ => false,
+	}
+}
+
+/// Returns true for kanji characters.
+pub fn is_kanji(chr: char) -> bool {
+	match chr {
+		'\u{3400}'..='\u{4DBF}' => true, // CJK extension A
+		'\u{4E00}'..='\u{9FFF}' => true, // CJK unified ideographs
+		_ => false,
+	}
+}
@@ -0,0 +1,181 @@
+//! Diagnostics collected during the import.
+//!
+//! Importers report problems with individual entries (e.g. a malformed row
+//! in a term bank) to [Diagnostics] and skip the entry, instead of failing
+//! the whole import. Each diagnostic has a [Severity] and the [Context] where
+//! it was found.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Maximum number of diagnostics printed for each severity. Any diagnostics
+/// over the limit are still counted in the summary.
+const PRINT_LIMIT: usize = 100;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+	/// An entry was skipped or imported with missing data.
+	Warning,
+	/// A whole file or dictionary was skipped.
+	Error,
+}
+
+impl fmt::Display for Severity {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Severity::Warning => write!(f, "WARNING"),
+			Severity::Error => write!(f, "ERROR"),
+		}
+	}
+}
+
+/// Location of a diagnostic in the import data.
+#[derive(Clone, Default, Debug)]
+pub struct Context {
+	/// Source file being imported.
+	pub source: String,
+	/// File inside the source archive, if any.
+	pub archive: Option<String>,
+	/// Line in the file, if available.
+	pub line: Option<usize>,
+	/// Index of the entry in the file, if available.
+	pub entry: Option<usize>,
+}
+
+impl Context {
+	pub fn new<S: Into<String>>(source: S) -> Context {
+		Context {
+			source: source.into(),
+			archive: None,
+			line: None,
+			entry: None,
+		}
+	}
+
+	/// Returns a copy of the context for a file inside the source archive.
+	pub fn archive<S: Into<String>>(&self, archive: S) -> Context {
+		Context {
+			archive: Some(archive.into()),
+			..self.clone()
+		}
+	}
+
+	/// Returns a copy of the context for the given line.
+	pub fn line(&self, line: usize) -> Context {
+		Context {
+			line: Some(line),
+			..self.clone()
+		}
+	}
+
+	/// Returns a copy of the context for the given entry index.
+	pub fn entry(&self, entry: usize) -> Context {
+		Context {
+			entry: Some(entry),
+			..self.clone()
+		}
+	}
+}
+
+impl fmt::Display for Context {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.source)?;
+		if let Some(ref archive) = self.archive {
+			write!(f, "/{}", archive)?;
+		}
+		if let Some(line) = self.line {
+			write!(f, ":{}", line)?;
+		}
+		if let Some(entry) = self.entry {
+			write!(f, " (entry {})", entry)?;
+		}
+		Ok(())
+	}
+}
+
+pub struct Diagnostic {
+	pub severity: Severity,
+	pub context: Context,
+	pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}: {} at {}", self.severity, self.message, self.context)
+	}
+}
+
+/// Collector for the import diagnostics.
+///
+/// Diagnostics are printed as they are reported, up to a limit for each
+/// severity, and kept for the final summary.
+#[derive(Default)]
+pub struct Diagnostics {
+	list: Vec<Diagnostic>,
+	totals: BTreeMap<Severity, usize>,
+}
+
+impl Diagnostics {
+	pub fn report<S: Into<String>>(&mut self, severity: Severity, context: Context, message: S) {
+		let diagnostic = Diagnostic {
+			severity: severity,
+			context: context,
+			message: message.into(),
+		};
+		let count = self.count(severity);
+		if count < PRINT_LIMIT {
+			eprintln!("{}", diagnostic);
+		} else if count == PRINT_LIMIT {
+			eprintln!("{}: too many diagnostics, see the summary at the end", severity);
+		}
+		self.list.push(diagnostic);
+		*self.totals.entry(severity).or_insert(0) += 1;
+	}
+
+	pub fn warning<S: Into<String>>(&mut self, context: Context, message: S) {
+		self.report(Severity::Warning, context, message);
+	}
+
+	pub fn error<S: Into<String>>(&mut self, context: Context, message: S) {
+		self.report(Severity::Error, context, message);
+	}
+
+	/// Adds the diagnostics collected separately (e.g. by another thread),
+	/// without printing them again.
+	pub fn append(&mut self, other: Diagnostics) {
+		for (severity, count) in other.totals {
+			*self.totals.entry(severity).or_insert(0) += count;
+		}
+		self.list.extend(other.list);
+	}
+
+	/// Number of diagnostics with the given severity.
+	pub fn count(&self, severity: Severity) -> usize {
+		self.totals.get(&severity).cloned().unwrap_or(0)
+	}
+
+	/// Number of diagnostics for each severity, by severity name.
+	pub fn counts(&self) -> BTreeMap<String, usize> {
+		self.totals
+			.iter()
+			.map(|(severity, count)| (severity.to_string().to_lowercase(), *count))
+			.collect()
+	}
+
+	/// Prints the number of diagnostics for each severity and source.
+	pub fn print_summary(&self) {
+		if self.list.len() == 0 {
+			return;
+		}
+
+		let mut summary: BTreeMap<(Severity, &str), usize> = BTreeMap::new();
+		for it in self.list.iter() {
+			*summary.entry((it.severity, it.context.source.as_str())).or_insert(0) += 1;
+		}
+
+		println!("\nDiagnostics:");
+		for ((severity, source), count) in summary.into_iter().rev() {
+			println!("... {} {}(s) in {}", count, severity, source);
+		}
+	}
+}
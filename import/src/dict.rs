@@ -36,6 +36,24 @@ pub struct Dict {
 	/// Frequency metadata for kanji.
 	#[serde(skip)]
 	pub meta_kanji: Vec<Meta>,
+
+	/// Pitch accent metadata for terms.
+	#[serde(skip)]
+	pub meta_pitch: Vec<Pitch>,
+
+	/// Media files bundled with the dictionary.
+	#[serde(skip)]
+	pub media: Vec<MediaFile>,
+}
+
+/// Media file bundled with a dictionary (e.g. an image referenced by a
+/// structured content definition).
+pub struct MediaFile {
+	/// Path of the file relative to the dictionary root.
+	pub path: String,
+
+	/// File contents.
+	pub data: Vec<u8>,
 }
 
 /// Dictionary entry for a term.
@@ -80,6 +98,12 @@ pub struct Term {
 
 	/// Source database name.
 	pub source: String,
+
+	/// True if the glossary is in Japanese (i.e. from a J-J dictionary).
+	pub monolingual: bool,
+
+	/// True if the glossary was shortened by the size limit (see `truncate`).
+	pub truncated: bool,
 }
 
 impl fmt::Display for Term {
@@ -218,6 +242,9 @@ pub struct Meta {
 	/// Kanji or term.
 	pub expression: String,
 
+	/// Reading for the term, if the frequency is specific to it.
+	pub reading: Option<String>,
+
 	/// Always `"freq"`.
 	pub mode: String,
 
@@ -227,10 +254,44 @@ pub struct Meta {
 
 impl fmt::Display for Meta {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		write!(f, "{} = {} ({})", self.expression, self.data, self.mode)
+		match self.reading {
+			Some(ref reading) => write!(
+				f,
+				"{}({}) = {} ({})",
+				self.expression, reading, self.data, self.mode
+			),
+			None => write!(f, "{} = {} ({})", self.expression, self.data, self.mode),
+		}
+	}
+}
+
+/// Pitch accent metadata for a term.
+pub struct Pitch {
+	/// Term expression.
+	pub expression: String,
+
+	/// Term reading.
+	pub reading: String,
+
+	/// Pitch accent positions for the reading. Each position is the mora
+	/// after which the pitch drops, with zero for the flat (heiban) pattern.
+	pub positions: Vec<u32>,
+}
+
+impl fmt::Display for Pitch {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let positions: Vec<_> = self.positions.iter().map(|x| x.to_string()).collect();
+		write!(
+			f,
+			"{}({}) = [{}] (pitch)",
+			self.expression,
+			self.reading,
+			positions.join(", ")
+		)
 	}
 }
 
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub enum DataKind {
 	Term,
 	Kanji,
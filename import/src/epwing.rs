@@ -0,0 +1,244 @@
+//! Import of EPWING (JIS X 4081) dictionaries.
+//!
+//! EPWING dictionaries are not read directly. Instead they must first be
+//! dumped to JSON using `zero-epwing` (https://github.com/FooSoft/zero-epwing),
+//! same as the Yomichan import tools:
+//!
+//!     zero-epwing CATALOGS > data/daijirin.epwing.json
+//!
+//! Characters that are not available in JIS X 0208 are stored as "gaiji"
+//! (external characters) and appear in the output as `{{w_XXXX}}` or
+//! `{{n_XXXX}}` for wide and narrow characters. Those are mapped to unicode
+//! using an optional tab-separated table with the same name as the dump file
+//! and a `.gaiji.tsv` extension, containing lines in the format:
+//!
+//!     w_XXXX	<unicode text>
+//!
+//! Unmapped gaiji are replaced with `〓`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use diagnostics::{Context, Diagnostics};
+use dict::{Dict, Term};
+use import::get_search_key;
+use source::{DictSource, Importer};
+
+/// Extension for the `zero-epwing` JSON dumps.
+const EPWING_EXTENSION: &'static str = ".epwing.json";
+
+/// Replacement for unmapped gaiji.
+const GAIJI_REPLACEMENT: &'static str = "〓";
+
+/// Importer for EPWING dictionaries dumped by `zero-epwing`.
+pub struct EpwingImporter;
+
+impl Importer for EpwingImporter {
+	fn name(&self) -> &str {
+		"EPWING"
+	}
+
+	fn can_import(&self, path: &Path) -> bool {
+		path.to_string_lossy().to_lowercase().ends_with(EPWING_EXTENSION)
+	}
+
+	fn import(&self, path: &Path, diag: &mut Diagnostics) -> io::Result<Box<dyn DictSource>> {
+		Ok(Box::new(import_epwing(path, diag)?))
+	}
+}
+
+#[derive(Deserialize)]
+struct Book {
+	subbooks: Vec<Subbook>,
+}
+
+#[derive(Deserialize)]
+struct Subbook {
+	#[serde(default)]
+	title: String,
+	#[serde(default)]
+	entries: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+	#[serde(default)]
+	heading: String,
+	#[serde(default)]
+	text: String,
+}
+
+fn import_epwing(path: &Path, diag: &mut Diagnostics) -> io::Result<Dict> {
+	let start = std::time::Instant::now();
+
+	let path_str = path.to_string_lossy();
+	println!("\n>>> Importing from {:}", path_str);
+	let context = Context::new(path_str.clone());
+
+	let gaiji = load_gaiji(path, diag)?;
+	let book: Book = serde_json::from_reader(io::BufReader::new(fs::File::open(path)?))?;
+
+	let file_name = path.file_name().unwrap().to_string_lossy();
+	let mut dict = Dict {
+		title: file_name[..file_name.len() - EPWING_EXTENSION.len()].to_string(),
+		format: 3,
+		revision: String::from("epwing"),
+		terms: Vec::new(),
+		kanji: Vec::new(),
+		tags: Vec::new(),
+		meta_terms: Vec::new(),
+		meta_kanji: Vec::new(),
+		meta_pitch: Vec::new(),
+		media: Vec::new(),
+	};
+
+	let mut unmapped = 0;
+	for subbook in book.subbooks {
+		let source = if subbook.title.len() > 0 {
+			subbook.title
+		} else {
+			dict.title.clone()
+		};
+		println!("... {:} ({} entries)", source, subbook.entries.len());
+
+		for (index, entry) in subbook.entries.into_iter().enumerate() {
+			let entry: Entry = match serde_json::from_value(entry) {
+				Ok(entry) => entry,
+				Err(err) => {
+					let context = context.archive(source.as_str()).entry(index);
+					diag.warning(context, format!("invalid entry, skipped ({})", err));
+					continue;
+				}
+			};
+			let heading = map_gaiji(&entry.heading, &gaiji, &mut unmapped);
+			let text = map_gaiji(&entry.text, &gaiji, &mut unmapped);
+
+			// The first line of the text repeats the heading
+			let glossary: Vec<String> = text
+				.lines()
+				.skip(1)
+				.map(|x| x.trim().to_string())
+				.filter(|x| x.len() > 0)
+				.collect();
+			if glossary.len() == 0 {
+				continue;
+			}
+
+			let (reading, expressions) = parse_heading(&heading);
+			for expression in expressions {
+				let search_key = get_search_key(if reading.len() > 0 {
+					reading.as_str()
+				} else {
+					expression.as_str()
+				});
+				dict.terms.push(Term {
+					expression: expression,
+					reading: reading.clone(),
+					search_key: search_key,
+					definition_tags: Vec::new(),
+					rules: Vec::new(),
+					score: 0,
+					glossary: glossary.clone(),
+					sequence: index as u32,
+					term_tags: Vec::new(),
+					source: source.clone(),
+					monolingual: true,
+					truncated: false,
+				});
+			}
+		}
+	}
+
+	if unmapped > 0 {
+		diag.warning(
+			context,
+			format!("{} unmapped gaiji replaced with `{}`", unmapped, GAIJI_REPLACEMENT),
+		);
+	}
+
+	println!("... Elapsed {:?}", start.elapsed());
+	println!("... Loaded {} terms", dict.terms.len());
+
+	Ok(dict)
+}
+
+/// Loads the gaiji mapping table for the dump file, if available.
+fn load_gaiji(path: &Path, diag: &mut Diagnostics) -> io::Result<HashMap<String, String>> {
+	let path_str = path.to_string_lossy();
+	let gaiji_path = format!(
+		"{}.gaiji.tsv",
+		&path_str[..path_str.len() - EPWING_EXTENSION.len()]
+	);
+
+	let mut out = HashMap::new();
+	let gaiji_path = Path::new(&gaiji_path);
+	if gaiji_path.is_file() {
+		let context = Context::new(gaiji_path.to_string_lossy());
+		let input = io::BufReader::new(fs::File::open(gaiji_path)?);
+		for (index, line) in input.lines().enumerate() {
+			let line = line?;
+			if line.trim().len() == 0 {
+				continue;
+			}
+			let mut fields = line.splitn(2, '\t');
+			if let (Some(code), Some(text)) = (fields.next(), fields.next()) {
+				out.insert(code.trim().to_lowercase(), text.trim().to_string());
+			} else {
+				diag.warning(context.line(index + 1), "invalid gaiji mapping, skipped");
+			}
+		}
+	}
+	Ok(out)
+}
+
+fn map_gaiji(text: &str, gaiji: &HashMap<String, String>, unmapped: &mut usize) -> String {
+	lazy_static! {
+		static ref RE_GAIJI: Regex = Regex::new(r"\{\{([nw]_[0-9a-fA-F]+)\}\}").unwrap();
+	}
+	RE_GAIJI
+		.replace_all(text, |caps: &regex::Captures| {
+			let code = caps[1].to_lowercase();
+			match gaiji.get(&code) {
+				Some(text) => text.clone(),
+				None => {
+					*unmapped += 1;
+					GAIJI_REPLACEMENT.to_string()
+				}
+			}
+		})
+		.to_string()
+}
+
+/// Parses an entry heading in the common format `よみ【表記・表記】` into the
+/// reading and the list of expressions.
+///
+/// Headings without a `【】` part are assumed to be kana only, in which case
+/// the heading itself is returned as the single expression.
+fn parse_heading(heading: &str) -> (String, Vec<String>) {
+	lazy_static! {
+		static ref RE_HEADING: Regex = Regex::new(r"^([^【]*)【([^】]+)】").unwrap();
+		static ref RE_READING_MARKS: Regex = Regex::new(r"[‐・‥\-\s]").unwrap();
+	}
+
+	let heading = heading.trim();
+	if let Some(caps) = RE_HEADING.captures(heading) {
+		let reading = RE_READING_MARKS.replace_all(&caps[1], "").to_string();
+		let expressions: Vec<String> = caps[2]
+			.split('・')
+			.map(|x| x.trim().to_string())
+			.filter(|x| x.len() > 0)
+			.collect();
+		if expressions.len() > 0 {
+			return (reading, expressions);
+		}
+	}
+
+	let expression = RE_READING_MARKS.replace_all(heading, "").to_string();
+	(String::new(), vec![expression])
+}
@@ -1,17 +1,46 @@
 //! Data structures for the organized dictionary data.
 
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fs;
+use std::io::BufRead;
 use std::io::BufWriter;
 use std::io::Result;
+use std::io::Write;
 
-use crate::dict::{Dict, Kanji, Tag, Term};
+use crate::dict::{Kanji, MediaFile, Tag, Term};
+use crate::pipeline::NormalizedDict;
+use crate::radicals;
+use crate::report::Report;
+use crate::sha256::Sha256;
+
+/// Path of the generated database file.
+pub const OUTPUT_FILE: &'static str = "data/dictionary.in";
+
+/// JMdict field tags, for the domain of specialized terms (e.g. `med` for
+/// medicine or `comp` for computing).
+const DOMAIN_TAGS: &[&str] = &[
+	"agric", "anat", "archeol", "archit", "art", "astron", "audvid", "aviat", "baseb", "biochem", "biol", "bot",
+	"Buddh", "bus", "cards", "chem", "Christn", "cloth", "comp", "cryst", "dent", "ecol", "econ", "elec", "electr",
+	"embryo", "engr", "ent", "film", "finc", "fish", "food", "gardn", "genet", "geogr", "geol", "geom", "go", "golf",
+	"gramm", "grmyth", "hanaf", "horse", "kabuki", "law", "ling", "logic", "MA", "mahj", "manga", "math", "mech",
+	"med", "met", "mil", "mining", "music", "noh", "ornith", "paleo", "pathol", "pharm", "phil", "photo", "physics",
+	"physiol", "politics", "print", "psy", "psyanal", "psych", "rail", "rommyth", "Shinto", "shogi", "ski", "sports",
+	"stat", "stockm", "sumo", "telec", "tradem", "tv", "vidg", "zool",
+];
 
 #[derive(Default)]
 pub struct Wrapper {
 	/// Frequency map of terms to number of appearances.
 	freq_terms: HashMap<String, u32>,
 
+	/// Frequency map of term expression and reading to number of appearances,
+	/// for frequency data that is specific to the reading.
+	freq_readings: HashMap<(String, String), u32>,
+
+	/// Map of term expression and reading to the pitch accent positions.
+	pitch: HashMap<(String, String), Vec<u32>>,
+
 	/// Frequency map of kanji to number of appearances.
 	freq_kanji: HashMap<String, u32>,
 
@@ -23,21 +52,77 @@ pub struct Wrapper {
 
 	/// Set of tags from all dictionaries by name.
 	tag_map: HashMap<String, Tag>,
+
+	/// Map of kanji to visually similar kanji.
+	similar_kanji: HashMap<char, Vec<char>>,
+
+	/// Source dictionary names in order of priority, from highest to lowest.
+	source_order: Vec<String>,
+
+	/// Localized tag notes as `(tag, language, notes)`.
+	tag_translations: Vec<(String, String, String)>,
+
+	/// Glossary configuration as `(language, tokenizer, min length, stop
+	/// words)`.
+	glossary_configs: Vec<(String, db::Tokenizer, u32, Vec<String>)>,
+
+	/// Embedding vectors by term expression.
+	embeddings: HashMap<String, Vec<f32>>,
+
+	/// Media files from all dictionaries, with the source dictionary name.
+	media: Vec<(String, MediaFile)>,
+
+	/// Loanword origins as `(expression, reading, language, word)`.
+	origins: Vec<(String, String, String, String)>,
+
+	/// Supplemental sense information as `(source, sequence, sense, info)`.
+	sense_info: Vec<(String, u32, u32, String)>,
+
+	/// Name and SHA-256 hash for the source files, recorded in the database
+	/// for provenance tracking.
+	source_files: Vec<(String, [u8; 32])>,
+
+	/// Import summary, completed and returned by [output](Wrapper::output).
+	report: Report,
 }
 
 impl Wrapper {
-	/// Imports dictionary data into the dictionary.
-	pub fn import_dict(&mut self, dict: Dict) {
+	/// Sets the name and hash for the source files, sorted by name.
+	pub fn set_source_files(&mut self, files: Vec<(String, [u8; 32])>) {
+		self.source_files = files;
+	}
+
+	/// Adds the normalized data from a dictionary (see `pipeline`).
+	pub fn add_dict(&mut self, dict: NormalizedDict) {
 		for it in dict.tags {
 			self.import_tag(it);
 		}
 
 		for it in dict.meta_terms {
-			self.freq_terms.insert(it.expression, it.data);
+			let replaced = match it.reading {
+				Some(reading) => self.freq_readings.insert((it.expression, reading), it.data),
+				None => self.freq_terms.insert(it.expression, it.data),
+			};
+			if replaced.is_some() {
+				self.report.merged.term_frequencies += 1;
+			}
+		}
+
+		for it in dict.meta_pitch {
+			let entry = self.pitch.entry((it.expression, it.reading)).or_insert_with(|| Vec::new());
+			for position in it.positions {
+				if !entry.contains(&position) {
+					entry.push(position);
+				} else {
+					self.report.dropped.pitch += 1;
+				}
+			}
 		}
 
 		for it in dict.meta_kanji {
-			self.freq_kanji.insert(it.expression, it.data);
+			if self.freq_kanji.insert(it.expression, it.data).is_some() {
+				self.report.merged.kanji_frequencies += 1;
+			}
 		}
 
 		for it in dict.terms {
@@ -52,13 +137,314 @@ impl Wrapper {
 			self.map_tags(it.stats.keys().cloned().collect());
 			self.kanji.push(it);
 		}
+
+		for it in dict.media {
+			self.media.push((dict.report.name.clone(), it));
+		}
+
+		self.report.sources.push(dict.report);
+	}
+
+	/// Imports a list of visually similar kanji.
+	///
+	/// Each line in the input lists a kanji followed by the kanji similar to
+	/// it, separated by whitespace. Lines starting with `#` are comments and
+	/// any non-kanji field (e.g. similarity scores) is ignored.
+	///
+	/// Similarity links are always added in both directions.
+	pub fn import_similar_kanji<R: BufRead>(&mut self, input: R) -> Result<()> {
+		for line in input.lines() {
+			let line = line?;
+			let line = line.trim();
+			if line.starts_with('#') {
+				continue;
+			}
+
+			let mut fields = line
+				.split(|c: char| c.is_whitespace() || c == ',')
+				.filter(|x| x.chars().count() == 1)
+				.map(|x| x.chars().next().unwrap())
+				.filter(|&x| !x.is_ascii());
+			if let Some(kanji) = fields.next() {
+				for other in fields {
+					if other != kanji {
+						self.add_similar_kanji(kanji, other);
+						self.add_similar_kanji(other, kanji);
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Imports the priority order for the source dictionaries.
+	///
+	/// Each line in the input contains a source dictionary name, from the
+	/// highest priority to the lowest. Lines starting with `#` are comments.
+	/// Dictionaries not listed have the lowest priority.
+	pub fn import_source_order<R: BufRead>(&mut self, input: R) -> Result<()> {
+		for line in input.lines() {
+			let line = line?;
+			let line = line.trim();
+			if line.len() > 0 && !line.starts_with('#') && !self.source_order.iter().any(|x| x == line) {
+				self.source_order.push(line.to_string());
+			}
+		}
+		Ok(())
+	}
+
+	/// Imports localized descriptions for the tags.
+	///
+	/// Each line in the input contains a tag name, a language code (e.g. `pt`)
+	/// and the tag notes in that language, separated by tabs. Lines starting
+	/// with `#` are comments.
+	pub fn import_tag_translations<R: BufRead>(&mut self, input: R) -> Result<()> {
+		for line in input.lines() {
+			let line = line?;
+			if line.trim().len() == 0 || line.starts_with('#') {
+				continue;
+			}
+			let mut fields = line.splitn(3, '\t').map(|x| x.trim());
+			match (fields.next(), fields.next(), fields.next()) {
+				(Some(tag), Some(language), Some(notes)) if tag.len() > 0 && language.len() > 0 => {
+					let translation = (tag.to_string(), language.to_string(), notes.to_string());
+					self.tag_translations.push(translation);
+				}
+				_ => {
+					let message = format!("invalid tag translation line: {}", line);
+					return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, message));
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Imports the glossary configuration for the glossary languages.
+	///
+	/// Each line in the input contains a language code (e.g. `en`), the
+	/// tokenizer (`words` or `text`), the minimum keyword length and the stop
+	/// words separated by spaces, separated by tabs. The stop words are
+	/// optional. Lines starting with `#` are comments.
+	pub fn import_glossary_config<R: BufRead>(&mut self, input: R) -> Result<()> {
+		for line in input.lines() {
+			let line = line?;
+			if line.trim().len() == 0 || line.starts_with('#') {
+				continue;
+			}
+			let mut fields = line.splitn(4, '\t').map(|x| x.trim());
+			let config = fields.next().and_then(|language| {
+				let tokenizer = fields.next()?.parse::<db::Tokenizer>().ok()?;
+				let min_length = fields.next()?.parse::<u32>().ok()?;
+				Some((language, tokenizer, min_length))
+			});
+			match config {
+				Some((language, tokenizer, min_length)) if language.len() > 0 => {
+					let stop_words = fields
+						.next()
+						.unwrap_or("")
+						.split_whitespace()
+						.map(|x| x.to_lowercase())
+						.collect();
+					let config = (language.to_string(), tokenizer, min_length, stop_words);
+					self.glossary_configs.push(config);
+				}
+				_ => {
+					let message = format!("invalid glossary configuration line: {}", line);
+					return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, message));
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Imports the origins of loanwords, from the JMdict `lsource` data.
+	///
+	/// Each line in the input contains a term expression, its reading, the
+	/// source language code (e.g. `por`) and optionally the word in that
+	/// language, separated by tabs. The reading can be empty to apply to any
+	/// reading. Lines starting with `#` are comments.
+	pub fn import_origins<R: BufRead>(&mut self, input: R) -> Result<()> {
+		for line in input.lines() {
+			let line = line?;
+			if line.trim().len() == 0 || line.starts_with('#') {
+				continue;
+			}
+			let mut fields = line.splitn(4, '\t').map(|x| x.trim());
+			match (fields.next(), fields.next(), fields.next(), fields.next()) {
+				(Some(expression), Some(reading), Some(language), word)
+					if expression.len() > 0 && language.len() > 0 =>
+				{
+					let word = word.unwrap_or("").to_string();
+					let origin = (expression.to_string(), reading.to_string(), language.to_string(), word);
+					self.origins.push(origin);
+				}
+				_ => {
+					let message = format!("invalid word origin line: {}", line);
+					return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, message));
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Imports the supplemental information for senses, from the JMdict
+	/// `s_inf` data.
+	///
+	/// Each line in the input contains the source dictionary name, the entry
+	/// sequence number, the sense number in the entry (starting at one) and
+	/// the information text, separated by tabs. Lines starting with `#` are
+	/// comments.
+	pub fn import_sense_info<R: BufRead>(&mut self, input: R) -> Result<()> {
+		for line in input.lines() {
+			let line = line?;
+			if line.trim().len() == 0 || line.starts_with('#') {
+				continue;
+			}
+			let fields: Vec<&str> = line.splitn(4, '\t').map(|x| x.trim()).collect();
+			let numbers = match fields.as_slice() {
+				&[source, sequence, sense, info] if source.len() > 0 && info.len() > 0 => {
+					sequence.parse::<u32>().ok().and_then(|x| sense.parse::<u32>().ok().map(|y| (x, y)))
+				}
+				_ => None,
+			};
+			match numbers {
+				Some((sequence, sense)) if sense > 0 => {
+					let info = (fields[0].to_string(), sequence, sense, fields[3].to_string());
+					self.sense_info.push(info);
+				}
+				_ => {
+					let message = format!("invalid sense info line: {}", line);
+					return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, message));
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Imports embedding vectors for the terms, for the semantic search.
+	///
+	/// The input uses the text format for word vectors from fastText and
+	/// word2vec, with a term expression followed by the vector values per
+	/// line, separated by spaces. A first line with the number of vectors and
+	/// dimensions is skipped. Vectors for expressions that are not in the
+	/// dictionary are ignored.
+	pub fn import_embeddings<R: BufRead>(&mut self, input: R) -> Result<()> {
+		let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+		let mut dim = 0;
+		for (number, line) in input.lines().enumerate() {
+			let line = line?;
+			let mut fields = line.split_whitespace();
+			let expression = match fields.next() {
+				Some(expression) => expression,
+				None => continue,
+			};
+			let vector = fields.map(|x| x.parse::<f32>()).collect::<std::result::Result<Vec<_>, _>>();
+			let vector = match vector {
+				Ok(vector) if number == 0 && vector.len() == 1 => continue,
+				Ok(vector) => vector,
+				Err(err) => return Err(invalid(format!("embeddings line {}: {}", number + 1, err))),
+			};
+			if dim == 0 {
+				dim = vector.len();
+			}
+			if vector.len() != dim {
+				let message = format!("embeddings line {}: expected {} dimensions", number + 1, dim);
+				return Err(invalid(message));
+			}
+			self.embeddings.insert(expression.to_string(), vector);
+		}
+		Ok(())
+	}
+
+	fn add_similar_kanji(&mut self, kanji: char, other: char) {
+		let entry = self.similar_kanji.entry(kanji).or_insert_with(|| Vec::new());
+		if !entry.contains(&other) {
+			entry.push(other);
+		}
 	}
 
 	/// Outputs all data to code files.
-	pub fn output(self) -> Result<()> {
+	///
+	/// Returns the import summary. The output layout and timing are not
+	/// filled by this method.
+	///
+	/// With `validate` the data is checked with `Writer::validate` before
+	/// writing, as is always done in debug builds.
+	pub fn output(mut self, profile: db::Profile, validate: bool) -> Result<Report> {
 		let mut w = db::Writer::new();
+		w.set_profile(profile);
+		if validate {
+			w.set_validate(true);
+		}
+
+		// The source hash covers all the source files, in order of file name,
+		// so it changes with any change to the database input.
+		let mut hasher = Sha256::new();
+		for (name, hash) in self.source_files {
+			hasher.update(&hash);
+			let name = w.intern(name);
+			w.push_source_file(db::SourceFileData { name: name, hash: hash });
+		}
+		w.set_source_hash(hasher.finish());
+
+		let importer = w.intern(env!("CARGO_PKG_VERSION").to_string());
+		w.set_importer(importer);
+
+		let mut report = self.report;
+
+		// Metadata for terms not in any source is not used
+		{
+			let expressions: HashSet<&str> = self.terms.iter().map(|x| x.expression.as_str()).collect();
+			let readings: HashSet<(&str, &str)> = self
+				.terms
+				.iter()
+				.map(|x| {
+					let reading = if x.reading.len() > 0 { &x.reading } else { &x.expression };
+					(x.expression.as_str(), reading.as_str())
+				})
+				.collect();
+			let has_reading = |key: &(String, String)| readings.contains(&(key.0.as_str(), key.1.as_str()));
+			report.dropped.term_frequencies = self
+				.freq_terms
+				.keys()
+				.filter(|x| !expressions.contains(x.as_str()))
+				.count() + self.freq_readings.keys().filter(|x| !has_reading(x)).count();
+			report.dropped.term_pitch = self.pitch.keys().filter(|x| !has_reading(x)).count();
+		}
+
+		// The Yomichan JMdict export has no category for the field tags, so
+		// they are categorized here to be searchable as domains.
+		for tag in self.tag_map.values_mut() {
+			if tag.category == "" && DOMAIN_TAGS.contains(&tag.name.as_str()) {
+				tag.category = "domain".to_string();
+			}
+		}
+
+		report.tags.total = self.tag_map.len();
+		for tag in self.tag_map.values() {
+			*report.tags.categories.entry(tag.category.clone()).or_insert(0) += 1;
+		}
+
+		let mut sources: Vec<&String> = self
+			.terms
+			.iter()
+			.map(|x| &x.source)
+			.chain(self.kanji.iter().map(|x| &x.source))
+			.collect();
+		sources.sort();
+		sources.dedup();
+		for name in sources {
+			let priority = match self.source_order.iter().position(|x| x == name) {
+				Some(pos) => (self.source_order.len() - pos) as i32,
+				None => 0,
+			};
+			let name = w.intern(name.clone());
+			w.push_source(db::SourceData {
+				name: name,
+				priority: priority,
+			});
+		}
 
-		let mut tag_order = HashMap::new();
 		let mut tag_map = HashMap::new();
 		for (index, (key, tag)) in self.tag_map.into_iter().enumerate() {
 			let tag = db::TagData {
@@ -69,14 +455,44 @@ impl Wrapper {
 			};
 			let index = index as u32;
 			tag_map.insert(key, index);
-			tag_order.insert(index, (tag.order, tag.name));
 			w.push_tag(tag);
 		}
 
-		let sort_tag = |a: &u32, b: &u32| {
-			let tag_a = tag_order[a];
-			let tag_b = tag_order[b];
-			tag_a.cmp(&tag_b)
+		for (tag, language, notes) in self.tag_translations {
+			match tag_map.get(&tag) {
+				Some(&index) => {
+					*report.tags.translations.entry(language.clone()).or_insert(0) += 1;
+					let translation = db::TagTranslationData {
+						tag: index,
+						language: w.intern(language),
+						notes: w.intern(notes),
+					};
+					w.push_tag_translation(translation);
+				}
+				None => report.tags.translations_unknown += 1,
+			}
+		}
+
+		for (language, tokenizer, min_length, stop_words) in self.glossary_configs {
+			let config = db::GlossaryConfigData {
+				language: w.intern(language),
+				tokenizer: tokenizer,
+				min_length: min_length,
+				stop_words: stop_words.into_iter().map(|x| w.intern(x)).collect(),
+			};
+			w.push_glossary_config(config);
+		}
+
+		// Without a kanji frequency list, derive the kanji frequency from the
+		// terms using each kanji.
+		let derived_kanji;
+		let freq_kanji = if self.freq_kanji.len() > 0 {
+			&self.freq_kanji
+		} else {
+			derived_kanji = derive_kanji_frequency(&self.kanji, &self.terms, &self.freq_terms, &self.freq_readings);
+			println!("... derived kanji frequency for {} kanji from the terms", derived_kanji.len());
+			report.derived_kanji_frequency = true;
+			&derived_kanji
 		};
 
 		for kanji in self.kanji {
@@ -84,12 +500,12 @@ impl Wrapper {
 			let kunyomi: Vec<_> = kanji.kunyomi.into_iter().map(|x| w.intern(x)).collect();
 			let onyomi: Vec<_> = kanji.onyomi.into_iter().map(|x| w.intern(x)).collect();
 
-			let mut tags: Vec<_> = kanji
+			// Tags are sorted in display order by the writer
+			let tags: Vec<_> = kanji
 				.tags
 				.into_iter()
 				.map(|x| tag_map.get(&x).cloned().unwrap())
 				.collect();
-			tags.sort_by(sort_tag);
 
 			let mut stats: Vec<_> = kanji.stats.into_iter().collect();
 			stats.sort_by(|a, b| a.0.cmp(&b.0));
@@ -99,10 +515,14 @@ impl Wrapper {
 				.collect();
 
 			let source = w.intern(kanji.source);
+			let similar = self
+				.similar_kanji
+				.get(&kanji.character)
+				.cloned()
+				.unwrap_or_default();
 			w.push_kanji(db::KanjiData {
 				character: kanji.character,
-				frequency: self
-					.freq_kanji
+				frequency: freq_kanji
 					.get(&kanji.character.to_string())
 					.map(|x| *x as u32)
 					.unwrap_or(0),
@@ -111,24 +531,64 @@ impl Wrapper {
 				onyomi: onyomi,
 				tags: tags,
 				stats: stats,
+				similar: similar,
 				source: source,
 			});
 		}
 
+		for &(number, character, strokes, name, variants) in radicals::RADICALS {
+			let name = w.intern(name.to_string());
+			w.push_radical(db::RadicalData {
+				number: number,
+				character: character,
+				strokes: strokes,
+				name: name,
+				variants: variants.chars().collect(),
+			});
+		}
+
+		let freq_terms = &self.freq_terms;
+		let mut source_order: HashMap<String, u32> = HashMap::new();
 		for term in self.terms {
+			// Terms are imported in the order they appear in each source
+			let order = {
+				let next = source_order.entry(term.source.clone()).or_insert(0);
+				*next += 1;
+				*next - 1
+			};
+
+			// Kana only terms may have an empty reading
+			let reading = if term.reading.len() > 0 {
+				term.reading.clone()
+			} else {
+				term.expression.clone()
+			};
+			let key = (term.expression.clone(), reading);
 			let frequency = self
-				.freq_terms
-				.get(&term.expression)
+				.freq_readings
+				.get(&key)
+				.or_else(|| freq_terms.get(&term.expression))
 				.map(|x| *x as u32)
 				.unwrap_or(0);
-			let mut term = db::TermData {
+			let pitch = self.pitch.get(&key).cloned().unwrap_or_default();
+			let mut flags = db::TermFlags::default();
+			if term.monolingual {
+				flags.insert(db::TermFlags::MONOLINGUAL);
+			}
+			if term.truncated {
+				flags.insert(db::TermFlags::TRUNCATED);
+			}
+			let term = db::TermData {
 				expression: w.intern(term.expression),
 				reading: w.intern(term.reading),
 				search_key: w.intern(term.search_key),
 				score: term.score,
 				sequence: term.sequence,
+				order: order,
 				frequency: frequency,
 				source: w.intern(term.source),
+				flags: flags,
+				pitch: pitch,
 				glossary: term.glossary.into_iter().map(|x| w.intern(x)).collect(),
 				rules: term
 					.rules
@@ -146,19 +606,89 @@ impl Wrapper {
 					.map(|x| tag_map.get(&x).cloned().unwrap())
 					.collect(),
 			};
-			term.rules.sort_by(sort_tag);
-			term.term_tags.sort_by(sort_tag);
-			term.definition_tags.sort_by(sort_tag);
 			w.push_term(term);
 		}
 
-		println!("... writing data/dictionary.in...");
-		let mut output = BufWriter::new(fs::File::create("data/dictionary.in")?);
-		w.write(&mut output)
+		// Only the origins for expressions in the dictionary are used
+		let mut origins = 0;
+		for (expression, reading, language, word) in self.origins {
+			if let Some(expression) = w.find_string(&expression) {
+				let origin = db::OriginData {
+					expression: expression,
+					reading: w.intern(reading),
+					language: w.intern(language),
+					word: w.intern(word),
+				};
+				w.push_origin(origin);
+				origins += 1;
+			}
+		}
+		if origins > 0 {
+			println!("... added {} word origins", origins);
+		}
+
+		// Only the sense info for source dictionaries that were imported is used
+		let mut sense_info = 0;
+		for (source, sequence, sense, info) in self.sense_info {
+			if let Some(source) = w.find_string(&source) {
+				let info = db::SenseInfoData {
+					source: source,
+					sequence: sequence,
+					sense: sense,
+					info: w.intern(info),
+				};
+				w.push_sense_info(info);
+				sense_info += 1;
+			}
+		}
+		if sense_info > 0 {
+			println!("... added {} sense notes", sense_info);
+		}
+
+		// Only the embeddings for expressions in the dictionary are used
+		let mut embeddings = 0;
+		for (expression, vector) in self.embeddings {
+			if let Some(expression) = w.find_string(&expression) {
+				w.set_embedding(expression, vector);
+				embeddings += 1;
+			}
+		}
+		if embeddings > 0 {
+			println!("... added {} term embeddings", embeddings);
+		}
+
+		let media = self.media.len();
+		for (source, file) in self.media {
+			let media = db::MediaData {
+				source: w.intern(source),
+				path: w.intern(file.path),
+				data: file.data,
+			};
+			w.push_media(media);
+		}
+		if media > 0 {
+			println!("... added {} media files", media);
+		}
+
+		// The database is written to a temporary file and then renamed over
+		// the output, so the output file is replaced atomically and readers
+		// never see a partially written database (e.g. in watch mode).
+		println!("... writing {}...", OUTPUT_FILE);
+		let temp_file = format!("{}.tmp", OUTPUT_FILE);
+		{
+			let mut output = BufWriter::new(fs::File::create(&temp_file)?);
+			w.write(&mut output)?;
+			output.flush()?;
+		}
+		fs::rename(&temp_file, OUTPUT_FILE)?;
+		Ok(report)
 	}
 
 	fn import_tag(&mut self, tag: Tag) {
 		if let Some(mut old_tag) = self.tag_map.get_mut(&tag.name) {
+			if tag.category != "" || tag.notes.len() > 0 {
+				self.report.merged.tags += 1;
+			}
 			if tag.notes.len() > 0 && tag.notes != old_tag.notes {
 				if old_tag.notes.len() > 0 {
 					old_tag.notes = format!("{} / {}", old_tag.notes, tag.notes);
@@ -171,7 +701,8 @@ impl Wrapper {
 					eprintln!(
 						"WARNING: overridden category of tag `{}` (was `{}`, with `{}`)",
 						tag.name, old_tag.category, tag.category,
-					)
+					);
+					self.report.merged.tag_categories += 1;
 				}
 				old_tag.category = tag.category;
 			}
@@ -182,6 +713,9 @@ impl Wrapper {
 
 	fn map_tags(&mut self, tags: Vec<String>) {
 		for name in tags {
+			if !self.tag_map.contains_key(&name) {
+				self.report.tags.undefined += 1;
+			}
 			self.import_tag(Tag {
 				name: name,
 				category: String::new(),
@@ -191,3 +725,39 @@ impl Wrapper {
 		}
 	}
 }
+
+/// Derives the kanji frequency from the appearances of each kanji in the term
+/// expressions, weighted by the term frequency.
+///
+/// Each expression is counted once, using the highest frequency for its
+/// readings. Terms without a frequency count as one appearance.
+fn derive_kanji_frequency(
+	kanji: &[Kanji],
+	terms: &[Term],
+	freq_terms: &HashMap<String, u32>,
+	freq_readings: &HashMap<(String, String), u32>,
+) -> HashMap<String, u32> {
+	let kanji: HashSet<char> = kanji.iter().map(|x| x.character).collect();
+	let mut expressions: HashMap<&str, u64> = HashMap::new();
+	for term in terms.iter() {
+		let reading = if term.reading.len() > 0 { &term.reading } else { &term.expression };
+		let frequency = freq_readings
+			.get(&(term.expression.clone(), reading.clone()))
+			.or_else(|| freq_terms.get(&term.expression))
+			.cloned()
+			.unwrap_or(0);
+		let weight = expressions.entry(term.expression.as_str()).or_insert(0);
+		*weight = std::cmp::max(*weight, std::cmp::max(frequency, 1) as u64);
+	}
+
+	let mut counts: HashMap<char, u64> = HashMap::new();
+	for (expression, weight) in expressions {
+		for chr in expression.chars().filter(|x| kanji.contains(x)) {
+			*counts.entry(chr).or_insert(0) += weight;
+		}
+	}
+	counts
+		.into_iter()
+		.map(|(chr, count)| (chr.to_string(), std::cmp::min(count, u32::max_value() as u64) as u32))
+		.collect()
+}
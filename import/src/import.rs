@@ -1,199 +1,430 @@
 //! Import of Yomichan compatible data.
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs;
 use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
 
 use regex::Regex;
 use serde::Deserialize;
 use serde_json;
+use serde_json::value::RawValue;
+use unicase::UniCase;
 
+use diagnostics::{Context, Diagnostics};
 use dict::*;
+use schema::{self, Schema};
+use source::{DictSource, Importer};
 
-/// Imports a `.zip` file containing Yomichan compatible dictionary data.
-pub fn import_file<P: AsRef<std::path::Path>>(path: P) -> Result<Dict, std::io::Error> {
-	/// The index file contains the basic information about the dictionary data.
-	const INDEX_FILE_NAME: &'static str = "index.json";
+/// Importer for Yomichan compatible `.zip` files.
+pub struct YomichanImporter;
+
+impl Importer for YomichanImporter {
+	fn name(&self) -> &str {
+		"Yomichan"
+	}
+
+	fn can_import(&self, path: &Path) -> bool {
+		match path.extension() {
+			Some(ext) => UniCase::new(ext.to_string_lossy()) == UniCase::new("zip"),
+			None => false,
+		}
+	}
+
+	fn import(&self, path: &Path, diag: &mut Diagnostics) -> io::Result<Box<dyn DictSource>> {
+		Ok(Box::new(import_file(path, diag)?))
+	}
+}
+
+/// The index file contains the basic information about the dictionary data.
+const INDEX_FILE_NAME: &'static str = "index.json";
+
+/// Maximum number of bank files read from the archive and waiting to be
+/// parsed.
+const BANK_QUEUE_SIZE: usize = 4;
+
+/// Extensions for the image files used by structured content definitions.
+const MEDIA_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "svg", "webp", "bmp", "avif"];
+
+/// Kind of a file inside a dictionary archive.
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum FileKind {
+	Index,
+	Bank(DataKind),
+	/// Image used by structured content definitions.
+	Media,
+	/// JSON file that is not a known bank, possibly a bank with an unexpected
+	/// name. Skipped with a warning.
+	Unknown,
+	/// Any other file (e.g. a license or stylesheet), which is skipped.
+	Other,
+}
 
+/// Imports a `.zip` file containing Yomichan compatible dictionary data.
+///
+/// The bank files are read and decompressed in a separate thread, so the
+/// archive IO overlaps with the JSON parsing.
+///
+/// Malformed bank files and entries are reported to `diag` and skipped.
+pub fn import_file<P: AsRef<std::path::Path>>(path: P, diag: &mut Diagnostics) -> Result<Dict, std::io::Error> {
 	let start = std::time::Instant::now();
 
 	let path = path.as_ref();
 	let path_str = path.to_string_lossy();
 	println!("\n>>> Importing from {:}", path_str);
+	let context = Context::new(path_str.clone());
 
-	let file = fs::File::open(path)?;
-	let mut archive = zip::ZipArchive::new(file)?;
+	let (sender, receiver) = mpsc::sync_channel(BANK_QUEUE_SIZE);
+	let reader = {
+		let path = path.to_owned();
+		thread::spawn(move || read_banks(path, sender))
+	};
 
-	let index_file = archive.by_name(INDEX_FILE_NAME)?;
-	let mut dict: Dict = serde_json::from_reader(index_file)?;
+	// The index is always the first file sent by the reader
+	let mut files = receiver.into_iter();
+	let mut dict: Dict = match files.next() {
+		Some((_, FileKind::Index, data)) => serde_json::from_slice(&data)?,
+		_ => {
+			reader.join().unwrap()?;
+			return Err(io::Error::new(io::ErrorKind::InvalidData, "missing index file"));
+		}
+	};
 
 	println!("... {:} -- {:}", dict.title, dict.revision);
 	if dict.format != 3 {
-		eprintln!(
-			"WARNING: format for `{:}` ({:}) is `{:}` (expected `3`)",
-			dict.title, path_str, dict.format
+		diag.warning(
+			context.archive(INDEX_FILE_NAME),
+			format!("format for `{:}` is `{:}` (expected `3`)", dict.title, dict.format),
 		);
 	}
 
-	for i in 0..archive.len() {
-		let file = archive.by_index(i)?;
-		if !file.is_file() {
-			continue;
-		}
-
-		let path = file.sanitized_name();
-		let name = path.to_string_lossy();
-		if name == INDEX_FILE_NAME {
-			continue;
-		}
-
-		if let Some(ext) = path.extension() {
-			if ext != "json" {
-				continue;
+	let mut other = 0;
+	for (name, kind, data) in files {
+		let context = context.archive(name.as_str());
+		match kind {
+			FileKind::Bank(kind) => import_entry(&mut dict, kind, &data, &context, diag),
+			FileKind::Media => dict.media.push(MediaFile { path: name, data: data }),
+			FileKind::Unknown => {
+				diag.warning(context, "unknown JSON file, skipped");
+				other += 1;
 			}
-		} else {
-			continue;
+			FileKind::Index | FileKind::Other => other += 1,
 		}
-
-		import_entry(&mut dict, &name, || Ok(file))?;
+	}
+	reader.join().unwrap()?;
+	if other > 0 {
+		println!("... Skipped {} other file(s)", other);
 	}
 
 	println!("... Elapsed {:?}", start.elapsed());
 
 	use std::cmp::max;
 	println!(
-		"... Loaded {} terms / {} kanji / {} tags",
+		"... Loaded {} terms / {} kanji / {} tags / {} media files",
 		max(dict.terms.len(), dict.meta_terms.len()),
 		max(dict.kanji.len(), dict.meta_kanji.len()),
-		dict.tags.len()
+		dict.tags.len(),
+		dict.media.len()
 	);
 
 	Ok(dict)
 }
 
-fn import_entry<F, R>(dict: &mut Dict, filename: &str, open: F) -> io::Result<()>
-where
-	F: FnOnce() -> io::Result<R>,
-	R: io::Read,
-{
-	let kind = get_kind(filename);
-	if let Some(kind) = kind {
-		let entry_file = open()?;
-		match kind {
-			DataKind::Term => {
-				#[derive(Deserialize)]
-				struct TermRow(
-					String,      // expression
-					String,      // reading
-					String,      // definition tags (CSV)
-					String,      // rules (CSV)
-					i32,         // score
-					Vec<String>, // glossary
-					u32,         // sequence
-					String,      // term tags (CSV)
-				);
-				let rows: Vec<TermRow> = serde_json::from_reader(entry_file)?;
-				for it in rows {
-					let expression = it.0;
-					let reading = if it.1 == "させ方" {
-						"させかた".to_string()
-					} else {
-						it.1
-					};
-					let search_key = get_search_key(if reading.len() > 0 {
-						reading.as_str()
-					} else {
-						expression.as_str()
-					});
-					dict.terms.push(Term {
-						expression: expression,
-						reading: reading,
-						search_key: search_key,
-						definition_tags: csv(&it.2),
-						rules: csv(&it.3),
-						score: it.4,
-						glossary: it.5,
-						sequence: it.6,
-						term_tags: csv(&it.7),
-						source: dict.title.clone(),
-					});
-				}
-			}
-			DataKind::Kanji => {
-				#[derive(Deserialize)]
-				struct KanjiRow(
-					char,                    // character
-					String,                  // onyomi (CSV)
-					String,                  // kunyomi (CSV)
-					String,                  // tags (CSV)
-					Vec<String>,             // meanings
-					HashMap<String, String>, // stats
-				);
-				let rows: Vec<KanjiRow> = serde_json::from_reader(entry_file)?;
-				for it in rows {
-					dict.kanji.push(Kanji {
-						character: it.0,
-						onyomi: csv(&it.1),
-						kunyomi: csv(&it.2),
-						tags: csv(&it.3),
-						meanings: it.4,
-						stats: it.5,
-						source: dict.title.clone(),
-					});
-				}
-			}
-			DataKind::Tag => {
-				#[derive(Deserialize)]
-				struct TagRow(
-					String, // name
-					String, // category
-					i32,    // order
-					String, // notes
-					i32,    // score (unused)
-				);
-				let rows: Vec<TagRow> = serde_json::from_reader(entry_file)?;
-				for it in rows {
-					dict.tags.push(Tag {
-						name: it.0,
-						category: it.1,
-						order: it.2,
-						notes: it.3,
-					});
-				}
+/// Reads the files from the archive, sending the name, kind and contents of
+/// each to `sender`, starting with the index. Only the index, bank and media
+/// files are read, the contents are empty for any other file.
+///
+/// Files are classified by name only (see `classify`), so the dictionary
+/// can be inside a directory in the archive. Names are sent relative to the
+/// directory with the index, as referenced by the definitions.
+///
+/// Stops early without an error if the receiver is dropped.
+fn read_banks(path: PathBuf, sender: mpsc::SyncSender<(String, FileKind, Vec<u8>)>) -> io::Result<()> {
+	let file = fs::File::open(path)?;
+	let mut archive = zip::ZipArchive::new(file)?;
+
+	let mut index = None;
+	for i in 0..archive.len() {
+		let file = archive.by_index(i)?;
+		if file.is_file() && classify(&file.sanitized_name()) == FileKind::Index {
+			index = Some(i);
+			break;
+		}
+	}
+	let index = match index {
+		Some(index) => index,
+		None => return Err(io::Error::new(io::ErrorKind::NotFound, format!("missing {}", INDEX_FILE_NAME))),
+	};
+
+	let root = archive.by_index(index)?.sanitized_name().parent().map(|x| x.to_owned()).unwrap_or_default();
+	let order = Some(index).into_iter().chain((0..archive.len()).filter(|&i| i != index));
+	for i in order {
+		let mut file = archive.by_index(i)?;
+		if !file.is_file() {
+			continue;
+		}
+
+		let path = file.sanitized_name();
+		let name = path.strip_prefix(&root).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+		let kind = if i == index { FileKind::Index } else { classify(&path) };
+		let mut data = Vec::new();
+		if let FileKind::Index | FileKind::Bank(_) | FileKind::Media = kind {
+			data.reserve(file.size() as usize);
+			file.read_to_end(&mut data)?;
+		}
+		if sender.send((name, kind, data)).is_err() {
+			break;
+		}
+	}
+	Ok(())
+}
+
+/// Classifies a file in the archive by its name.
+fn classify(path: &Path) -> FileKind {
+	let name = match path.file_name() {
+		Some(name) => name.to_string_lossy().to_lowercase(),
+		None => return FileKind::Other,
+	};
+
+	// Metadata and resource forks added by macOS archivers
+	if name.starts_with("._") || path.starts_with("__MACOSX") {
+		return FileKind::Other;
+	}
+
+	if name == INDEX_FILE_NAME {
+		FileKind::Index
+	} else if let Some(kind) = get_kind(&name) {
+		FileKind::Bank(kind)
+	} else if name.ends_with(".json") {
+		FileKind::Unknown
+	} else if MEDIA_EXTENSIONS.iter().any(|ext| name.ends_with(&format!(".{}", ext))) {
+		FileKind::Media
+	} else {
+		FileKind::Other
+	}
+}
+
+/// Imports the rows from a bank file with the given contents.
+///
+/// The row strings are borrowed from `data` (see `read_rows`), so only the
+/// fields kept in the dictionary are allocated.
+fn import_entry(dict: &mut Dict, kind: DataKind, data: &[u8], context: &Context, diag: &mut Diagnostics) {
+	match kind {
+		DataKind::Term => {
+			#[derive(Deserialize)]
+			struct TermRow<'a>(
+				#[serde(borrow)] Cow<'a, str>,      // expression
+				#[serde(borrow)] Cow<'a, str>,      // reading
+				#[serde(borrow)] Cow<'a, str>,      // definition tags (CSV)
+				#[serde(borrow)] Cow<'a, str>,      // rules (CSV)
+				i32,                                // score
+				#[serde(borrow)] Vec<Cow<'a, str>>, // glossary
+				u32,                                // sequence
+				#[serde(borrow)] Cow<'a, str>,      // term tags (CSV)
+			);
+			let rows: Vec<TermRow> = read_rows(data, schema::TERM, context, diag);
+			for it in rows {
+				let expression = it.0.into_owned();
+				let reading = if it.1 == "させ方" {
+					"させかた".to_string()
+				} else {
+					it.1.into_owned()
+				};
+				let search_key = get_search_key(if reading.len() > 0 {
+					reading.as_str()
+				} else {
+					expression.as_str()
+				});
+				let glossary: Vec<String> = it.5.into_iter().map(|x| x.into_owned()).collect();
+				let monolingual = is_monolingual(&glossary);
+				dict.terms.push(Term {
+					expression: expression,
+					reading: reading,
+					search_key: search_key,
+					definition_tags: csv(&it.2),
+					rules: csv(&it.3),
+					score: it.4,
+					glossary: glossary,
+					sequence: it.6,
+					term_tags: csv(&it.7),
+					source: dict.title.clone(),
+					monolingual: monolingual,
+					truncated: false,
+				});
 			}
-			DataKind::KanjiMeta => {
-				for it in read_meta(entry_file)? {
-					dict.meta_kanji.push(it);
-				}
+		}
+		DataKind::Kanji => {
+			#[derive(Deserialize)]
+			struct KanjiRow<'a>(
+				char,                               // character
+				#[serde(borrow)] Cow<'a, str>,      // onyomi (CSV)
+				#[serde(borrow)] Cow<'a, str>,      // kunyomi (CSV)
+				#[serde(borrow)] Cow<'a, str>,      // tags (CSV)
+				#[serde(borrow)] Vec<Cow<'a, str>>, // meanings
+				HashMap<String, String>,            // stats
+			);
+			let rows: Vec<KanjiRow> = read_rows(data, schema::KANJI, context, diag);
+			for it in rows {
+				dict.kanji.push(Kanji {
+					character: it.0,
+					onyomi: csv(&it.1),
+					kunyomi: csv(&it.2),
+					tags: csv(&it.3),
+					meanings: it.4.into_iter().map(|x| x.into_owned()).collect(),
+					stats: it.5,
+					source: dict.title.clone(),
+				});
 			}
-			DataKind::TermMeta => {
-				for it in read_meta(entry_file)? {
-					dict.meta_terms.push(it);
-				}
+		}
+		DataKind::Tag => {
+			#[derive(Deserialize)]
+			struct TagRow<'a>(
+				#[serde(borrow)] Cow<'a, str>, // name
+				#[serde(borrow)] Cow<'a, str>, // category
+				i32,                           // order
+				#[serde(borrow)] Cow<'a, str>, // notes
+				serde::de::IgnoredAny,         // score (unused)
+			);
+			let rows: Vec<TagRow> = read_rows(data, schema::TAG, context, diag);
+			for it in rows {
+				dict.tags.push(Tag {
+					name: it.0.into_owned(),
+					category: it.1.into_owned(),
+					order: it.2,
+					notes: it.3.into_owned(),
+				});
 			}
 		}
+		DataKind::KanjiMeta => {
+			let (meta, _) = read_meta(data, context, diag);
+			dict.meta_kanji.extend(meta);
+		}
+		DataKind::TermMeta => {
+			let (meta, pitch) = read_meta(data, context, diag);
+			dict.meta_terms.extend(meta);
+			dict.meta_pitch.extend(pitch);
+		}
 	}
+}
 
-	Ok(())
+/// Reads the rows from a bank file.
+///
+/// The bank is first split into the raw JSON for each row, which is then
+/// deserialized borrowing from `data`. Strings without escapes can be
+/// borrowed as `Cow<str>` fields, so rows don't allocate for fields that are
+/// discarded or split (e.g. the CSV tag lists).
+///
+/// Rows that don't match the expected format are reported as warnings and
+/// skipped, with the problem described using the schema for the rows (see
+/// `schema`). A bank file that is not a valid JSON array is reported as an
+/// error and skipped entirely.
+fn read_rows<'a, T>(data: &'a [u8], schema: Schema, context: &Context, diag: &mut Diagnostics) -> Vec<T>
+where
+	T: Deserialize<'a>,
+{
+	let rows: Vec<&'a RawValue> = match serde_json::from_slice(data) {
+		Ok(rows) => rows,
+		Err(err) => {
+			diag.error(context.line(err.line()), format!("invalid bank file, skipped ({})", err));
+			return Vec::new();
+		}
+	};
+
+	let mut result = Vec::with_capacity(rows.len());
+	for (index, row) in rows.into_iter().enumerate() {
+		match serde_json::from_str(row.get()) {
+			Ok(row) => result.push(row),
+			Err(err) => {
+				let message = schema::validate(row.get(), schema).unwrap_or_else(|| err.to_string());
+				diag.warning(context.entry(index), format!("invalid entry, skipped ({})", message));
+			}
+		}
+	}
+	result
 }
 
-fn read_meta<R: io::Read>(input: R) -> io::Result<Vec<Meta>> {
+/// Reads a meta bank, returning the frequency and pitch accent metadata.
+fn read_meta(data: &[u8], context: &Context, diag: &mut Diagnostics) -> (Vec<Meta>, Vec<Pitch>) {
 	#[derive(Deserialize)]
-	struct MetaRow(
-		String, // expression
-		String, // mode
-		u32,    // data
+	struct MetaRow<'a>(
+		#[serde(borrow)] Cow<'a, str>, // expression
+		#[serde(borrow)] Cow<'a, str>, // mode
+		serde_json::Value,             // data
 	);
-	let rows: Vec<MetaRow> = serde_json::from_reader(input)?;
+	let rows: Vec<MetaRow> = read_rows(data, schema::META, context, diag);
 	let mut result: Vec<Meta> = Vec::new();
+	let mut pitch: Vec<Pitch> = Vec::new();
 	for it in rows {
-		result.push(Meta {
-			expression: it.0,
-			mode: it.1,
-			data: it.2,
-		});
+		// Pitch data is in the format:
+		//
+		//     { "reading": "...", "pitches": [{ "position": N, "tags": [...] }] }
+		if it.1 == "pitch" {
+			let reading = it.2.get("reading").and_then(|x| x.as_str()).unwrap_or("");
+			let positions: Vec<u32> = match it.2.get("pitches").and_then(|x| x.as_array()) {
+				Some(list) => list
+					.iter()
+					.filter_map(|x| x.get("position").and_then(|x| x.as_u64()))
+					.map(|x| x as u32)
+					.collect(),
+				None => Vec::new(),
+			};
+			if positions.len() > 0 {
+				pitch.push(Pitch {
+					expression: it.0.into_owned(),
+					reading: reading.to_string(),
+					positions: positions,
+				});
+			}
+			continue;
+		}
+
+		// Ignore any other unknown metadata
+		if it.1 != "freq" {
+			continue;
+		}
+
+		// Version 3 dictionaries may provide the frequency for a specific
+		// reading as `{ "reading": "...", "frequency": ... }`
+		let (reading, data) = match it.2.get("reading").and_then(|x| x.as_str()) {
+			Some(reading) => (Some(reading.to_string()), &it.2["frequency"]),
+			None => (None, &it.2),
+		};
+		if let Some(data) = parse_frequency(data) {
+			result.push(Meta {
+				expression: it.0.into_owned(),
+				reading: reading,
+				mode: it.1.into_owned(),
+				data: data,
+			});
+		}
+	}
+	(result, pitch)
+}
+
+/// Parses a frequency value, which can be a number, a string or an object in
+/// the format `{ "value": N, "displayValue": "..." }`.
+fn parse_frequency(data: &serde_json::Value) -> Option<u32> {
+	match data {
+		&serde_json::Value::Number(ref value) => value.as_u64().map(|x| x as u32),
+		&serde_json::Value::String(ref value) => value.trim().parse().ok(),
+		&serde_json::Value::Object(ref value) => value.get("value").and_then(parse_frequency),
+		_ => None,
 	}
-	Ok(result)
+}
+
+/// Returns true if the glossary is in Japanese.
+///
+/// Yomichan dictionaries don't provide the language for the definitions, so
+/// we consider Japanese any glossary in which most entries contain hiragana,
+/// which is ubiquitous in Japanese text but never used in English glossaries.
+fn is_monolingual(glossary: &Vec<String>) -> bool {
+	let is_hiragana = |chr: char| chr >= '\u{3041}' && chr <= '\u{309F}';
+	let count = glossary.iter().filter(|x| x.chars().any(is_hiragana)).count();
+	count > 0 && count * 2 > glossary.len()
 }
 
 fn csv(ls: &str) -> Vec<String> {
@@ -228,7 +459,7 @@ fn get_kind(file_name: &str) -> Option<DataKind> {
 /// - The romaji conversion in the kana library handles the most corner cases
 ///   in terms of weird characters (e.g. iteration marks, old characters, etc).
 /// - It allows for incomplete syllables in romaji searches.
-fn get_search_key(term: &str) -> String {
+pub fn get_search_key(term: &str) -> String {
 	lazy_static! {
 		static ref RE_REPLACE: Regex = Regex::new(r"[-,'‘’/~]").unwrap();
 		static ref RE_VALIDATE: Regex = Regex::new(r"^[a-z0-9]+$").unwrap();
@@ -12,20 +12,162 @@ extern crate db;
 extern crate kana;
 
 use std::fs;
-
-use unicase::UniCase;
+use std::path::{Path, PathBuf};
 
 const IMPORT_DATA_DIRECTORY: &'static str = "data";
 
+/// Optional file in the data directory with the list of similar kanji.
+const SIMILAR_KANJI_FILE: &'static str = "similar-kanji.txt";
+
+/// Optional file in the data directory with the dictionary priority order.
+const SOURCE_ORDER_FILE: &'static str = "dictionary-order.txt";
+
+/// Optional file in the data directory with localized tag descriptions.
+const TAG_TRANSLATIONS_FILE: &'static str = "tag-translations.tsv";
+
+/// Optional file in the data directory with the glossary configuration for
+/// each glossary language.
+const GLOSSARY_CONFIG_FILE: &'static str = "glossary-languages.tsv";
+
+/// Optional file in the data directory with the loanword origins.
+const ORIGINS_FILE: &'static str = "word-origins.tsv";
+
+/// Optional file in the data directory with supplemental sense information.
+const SENSE_INFO_FILE: &'static str = "sense-info.tsv";
+
+/// Optional file in the data directory with the term embeddings.
+const EMBEDDINGS_FILE: &'static str = "embeddings.vec";
+
+/// Default size limit in bytes for the media files from each dictionary.
+const DEFAULT_MEDIA_LIMIT: usize = 64 * 1024 * 1024;
+
 mod generate;
 
 mod dict;
 
+mod radicals;
+
 mod import;
-use import::import_file;
+
+mod epwing;
+
+mod diagnostics;
+use diagnostics::{Diagnostics, Severity};
+
+mod source;
+use source::Registry;
+
+mod report;
+use report::Report;
+
+mod pipeline;
+
+mod sanitize;
+
+mod schema;
+
+mod truncate;
+
+mod sha256;
+
+mod watch;
+
+/// Returns the registry with all the supported importers.
+fn importers() -> Registry {
+	let mut registry = Registry::default();
+	registry.register(import::YomichanImporter);
+	registry.register(epwing::EpwingImporter);
+	registry
+}
 
 fn main() {
-	let start = std::time::Instant::now();
+	// Optional `--report FILE` argument for the JSON import summary and
+	// `--profile full|compact` for the output database. The `--verify` flag
+	// checks the generated database against the source files instead and
+	// `--no-sanitize` keeps the imported text as is. `--max-glossary BYTES`
+	// limits the glossary size for each term, shortened according to the
+	// `--glossary-policy truncate|summarize`. Media files bundled with the
+	// dictionaries are imported up to `--max-media BYTES` for each dictionary,
+	// or not at all with `--no-media`. The `--validate` flag checks the data
+	// invariants before writing in release builds (always done in debug). With
+	// `--watch` the import is repeated whenever the source files change:
+	let mut report_file = None;
+	let mut verify_only = false;
+	let mut watch = false;
+	let mut sanitize = true;
+	let mut profile = db::Profile::Full;
+	let mut max_glossary = None;
+	let mut glossary_policy = truncate::Policy::Truncate;
+	let mut media_limit = Some(DEFAULT_MEDIA_LIMIT);
+	let mut validate = false;
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--report" => match args.next() {
+				Some(file) => report_file = Some(file),
+				None => {
+					eprintln!("\nERROR: missing file for --report\n");
+					std::process::exit(1);
+				}
+			},
+			"--verify" => verify_only = true,
+			"--no-sanitize" => sanitize = false,
+			"--no-media" => media_limit = None,
+			"--validate" => validate = true,
+			"--watch" => watch = true,
+			"--profile" => match args.next().map(|x| x.parse()) {
+				Some(Ok(value)) => profile = value,
+				Some(Err(err)) => {
+					eprintln!("\nERROR: {}\n", err);
+					std::process::exit(1);
+				}
+				None => {
+					eprintln!("\nERROR: missing profile for --profile\n");
+					std::process::exit(1);
+				}
+			},
+			"--max-glossary" => match args.next().map(|x| x.parse::<usize>()) {
+				Some(Ok(value)) if value > 0 => max_glossary = Some(value),
+				Some(_) => {
+					eprintln!("\nERROR: invalid size for --max-glossary\n");
+					std::process::exit(1);
+				}
+				None => {
+					eprintln!("\nERROR: missing size for --max-glossary\n");
+					std::process::exit(1);
+				}
+			},
+			"--max-media" => match args.next().map(|x| x.parse::<usize>()) {
+				Some(Ok(value)) => media_limit = Some(value),
+				Some(Err(_)) => {
+					eprintln!("\nERROR: invalid size for --max-media\n");
+					std::process::exit(1);
+				}
+				None => {
+					eprintln!("\nERROR: missing size for --max-media\n");
+					std::process::exit(1);
+				}
+			},
+			"--glossary-policy" => match args.next().map(|x| x.parse()) {
+				Some(Ok(value)) => glossary_policy = value,
+				Some(Err(err)) => {
+					eprintln!("\nERROR: {}\n", err);
+					std::process::exit(1);
+				}
+				None => {
+					eprintln!("\nERROR: missing policy for --glossary-policy\n");
+					std::process::exit(1);
+				}
+			},
+			_ => {
+				eprintln!(
+					"\nERROR: invalid argument `{}` (usage: import [--report FILE] [--profile full|compact] [--no-sanitize] [--max-glossary BYTES [--glossary-policy truncate|summarize]] [--max-media BYTES | --no-media] [--validate] [--verify | --watch])\n",
+					arg
+				);
+				std::process::exit(1);
+			}
+		}
+	}
 
 	// Validate the import data directory:
 	let mut data_dir = std::env::current_dir().unwrap();
@@ -42,47 +184,279 @@ fn main() {
 		}
 	};
 
-	match import(data_dir) {
-		Ok(_) => {
-			println!("\nImporting finished after {:?}\n", start.elapsed());
+	if verify_only {
+		match verify(&data_dir) {
+			Ok(true) => println!("\nSource files match the database\n"),
+			Ok(false) => {
+				eprintln!("\nERROR: source files do not match the database\n");
+				std::process::exit(4);
+			}
+			Err(err) => {
+				eprintln!("\nERROR: verify failed: {:}\n", err);
+				std::process::exit(2);
+			}
+		}
+		return;
+	}
+
+	let options = ImportOptions {
+		profile: profile,
+		sanitize: sanitize,
+		glossary_limit: max_glossary.map(|x| (x, glossary_policy)),
+		media_limit: media_limit,
+		validate: validate,
+	};
+	let code = run_import(&data_dir, &options, report_file.as_ref().map(|x| x.as_str()));
+	if !watch {
+		if code != 0 {
+			std::process::exit(code);
 		}
+		return;
+	}
+
+	// In watch mode, import errors are reported but don't stop watching. The
+	// output database is replaced atomically (see `Wrapper::output`), so a
+	// failed import keeps the previous database.
+	let registry = importers();
+	let files = || -> std::io::Result<Vec<PathBuf>> {
+		let entries = dictionary_files(&data_dir, &registry)?;
+		Ok(source_files(&data_dir, &entries))
+	};
+	let mut snapshot = match files().and_then(watch::Snapshot::take) {
+		Ok(snapshot) => snapshot,
 		Err(err) => {
-			eprintln!("\nERROR: import failed: {:}\n", err);
+			eprintln!("\nERROR: watching {:} failed: {:}\n", data_dir.display(), err);
 			std::process::exit(2);
 		}
+	};
+	loop {
+		println!("Watching {:} for changes...", data_dir.display());
+		let (current, changes) = watch::wait_for_change(&snapshot, &files);
+		snapshot = current;
+		println!("\nChanged: {}", changes.join(", "));
+		println!("\nImporting from {:}...", data_dir.display());
+		run_import(&data_dir, &options, report_file.as_ref().map(|x| x.as_str()));
 	}
 }
 
-fn import<P: AsRef<std::path::Path>>(import_dir: P) -> std::io::Result<()> {
+/// Options for the import from the command line.
+struct ImportOptions {
+	/// Output profile for the database.
+	profile: db::Profile,
+	/// Sanitize the imported text (see `sanitize`).
+	sanitize: bool,
+	/// Maximum glossary size and policy (see `truncate`).
+	glossary_limit: Option<(usize, truncate::Policy)>,
+	/// Maximum media size for each dictionary, `None` to skip media.
+	media_limit: Option<usize>,
+	/// Validate the data before writing, even in release builds.
+	validate: bool,
+}
+
+/// Runs the import for the data directory, printing the results. Returns the
+/// process exit code.
+fn run_import(data_dir: &Path, options: &ImportOptions, report_file: Option<&str>) -> i32 {
 	let start = std::time::Instant::now();
-	let mut entries = Vec::new();
-	for entry in fs::read_dir(import_dir)? {
-		let entry = entry?;
-		if entry.file_type()?.is_file() {
-			let fullpath = entry.path();
-			if let Some(ext) = fullpath.extension() {
-				let ext = ext.to_string_lossy();
-				if UniCase::new(ext) == UniCase::new("zip") {
-					entries.push(fullpath);
+	let mut diag = Diagnostics::default();
+	let result = import(data_dir, options, &mut diag);
+	diag.print_summary();
+	match result {
+		Ok(mut report) => {
+			report.time("total", start.elapsed());
+			if let Some(file) = report_file {
+				if let Err(err) = report.write(file) {
+					eprintln!("\nERROR: writing report to {} failed: {:}\n", file, err);
+					return 2;
 				}
+				println!("\nWritten import report to {}", file);
+			}
+			println!("\nImporting finished after {:?}\n", start.elapsed());
+			let errors = diag.count(Severity::Error);
+			if errors > 0 {
+				eprintln!("ERROR: {} file(s) skipped with errors\n", errors);
+				return 3;
 			}
+			0
+		}
+		Err(err) => {
+			eprintln!("\nERROR: import failed: {:}\n", err);
+			2
 		}
 	}
+}
+
+fn import<P: AsRef<std::path::Path>>(
+	import_dir: P,
+	options: &ImportOptions,
+	diag: &mut Diagnostics,
+) -> std::io::Result<Report> {
+	let start = std::time::Instant::now();
+	let import_dir = import_dir.as_ref();
+	let registry = importers();
+	let entries = dictionary_files(import_dir, &registry)?;
 
 	println!("Found {} file(s) to import...", entries.len());
 
 	let mut wrapper = generate::Wrapper::default();
-	for fs in entries {
-		let dict = import_file(fs)?;
-		wrapper.import_dict(dict);
+	let normalizer = pipeline::Normalizer {
+		sanitize: options.sanitize,
+		glossary_limit: options.glossary_limit,
+		media_limit: options.media_limit,
+	};
+	pipeline::run(entries.clone(), registry, normalizer, &mut wrapper, diag);
+
+	let similar_kanji = import_dir.join(SIMILAR_KANJI_FILE);
+	if similar_kanji.is_file() {
+		println!("\n>>> Importing similar kanji from {:}", similar_kanji.to_string_lossy());
+		let input = std::io::BufReader::new(fs::File::open(similar_kanji)?);
+		wrapper.import_similar_kanji(input)?;
+	}
+
+	let source_order = import_dir.join(SOURCE_ORDER_FILE);
+	if source_order.is_file() {
+		println!("\n>>> Importing dictionary order from {:}", source_order.to_string_lossy());
+		let input = std::io::BufReader::new(fs::File::open(source_order)?);
+		wrapper.import_source_order(input)?;
+	}
+
+	let tag_translations = import_dir.join(TAG_TRANSLATIONS_FILE);
+	if tag_translations.is_file() {
+		println!("\n>>> Importing tag translations from {:}", tag_translations.to_string_lossy());
+		let input = std::io::BufReader::new(fs::File::open(tag_translations)?);
+		wrapper.import_tag_translations(input)?;
 	}
 
-	println!("\nImported database (elapsed {:?})", start.elapsed());
+	let glossary_config = import_dir.join(GLOSSARY_CONFIG_FILE);
+	if glossary_config.is_file() {
+		println!("\n>>> Importing glossary configuration from {:}", glossary_config.to_string_lossy());
+		let input = std::io::BufReader::new(fs::File::open(glossary_config)?);
+		wrapper.import_glossary_config(input)?;
+	}
+
+	let origins = import_dir.join(ORIGINS_FILE);
+	if origins.is_file() {
+		println!("\n>>> Importing word origins from {:}", origins.to_string_lossy());
+		let input = std::io::BufReader::new(fs::File::open(origins)?);
+		wrapper.import_origins(input)?;
+	}
+
+	let sense_info = import_dir.join(SENSE_INFO_FILE);
+	if sense_info.is_file() {
+		println!("\n>>> Importing sense information from {:}", sense_info.to_string_lossy());
+		let input = std::io::BufReader::new(fs::File::open(sense_info)?);
+		wrapper.import_sense_info(input)?;
+	}
+
+	let embeddings = import_dir.join(EMBEDDINGS_FILE);
+	if embeddings.is_file() {
+		println!("\n>>> Importing term embeddings from {:}", embeddings.to_string_lossy());
+		let input = std::io::BufReader::new(fs::File::open(embeddings)?);
+		wrapper.import_embeddings(input)?;
+	}
+
+	wrapper.set_source_files(hash_source_files(import_dir, &entries)?);
+
+	let import_elapsed = start.elapsed();
+	println!("\nImported database (elapsed {:?})", import_elapsed);
 
 	let start = std::time::Instant::now();
-	println!("\nExporting...");
-	wrapper.output()?;
+	println!("\nExporting ({} profile)...", options.profile.name());
+	let mut report = wrapper.output(options.profile, options.validate)?;
 	println!("... completed in {:?}", start.elapsed());
 
-	Ok(())
+	report.time("import", import_elapsed);
+	report.time("export", start.elapsed());
+	report.read_output(generate::OUTPUT_FILE)?;
+	report.diagnostics = diag.counts();
+	Ok(report)
+}
+
+/// Cross-checks the source files recorded in the generated database against
+/// the files in the import directory. Returns false if any file is changed,
+/// missing or not in the database.
+fn verify(import_dir: &Path) -> std::io::Result<bool> {
+	let data = fs::read(generate::OUTPUT_FILE)?;
+	let db = db::DB::load(&data);
+	let info = db.info();
+	println!(
+		"Verifying {} (built {} by import {})...\n",
+		generate::OUTPUT_FILE,
+		info.build_date(),
+		db.importer()
+	);
+
+	let registry = importers();
+	let entries = dictionary_files(import_dir, &registry)?;
+	let present = hash_source_files(import_dir, &entries)?;
+	let mut present: std::collections::BTreeMap<String, [u8; 32]> = present.into_iter().collect();
+
+	let mut valid = true;
+	for file in db.source_files() {
+		match present.remove(file.name) {
+			Some(hash) if hash == file.hash => println!("    OK       {}", file.name),
+			Some(_) => {
+				valid = false;
+				println!("    CHANGED  {}", file.name);
+			}
+			None => {
+				valid = false;
+				println!("    MISSING  {}", file.name);
+			}
+		}
+	}
+	for name in present.keys() {
+		valid = false;
+		println!("    NEW      {}", name);
+	}
+	Ok(valid)
+}
+
+/// Returns the dictionary files in the import directory supported by one of
+/// the importers.
+fn dictionary_files(import_dir: &Path, registry: &Registry) -> std::io::Result<Vec<PathBuf>> {
+	let mut entries = Vec::new();
+	for entry in fs::read_dir(import_dir)? {
+		let entry = entry?;
+		if entry.file_type()?.is_file() {
+			let fullpath = entry.path();
+			if registry.find(&fullpath).is_some() {
+				entries.push(fullpath);
+			}
+		}
+	}
+	Ok(entries)
+}
+
+/// Returns the file name and SHA-256 hash for the dictionary files and the
+/// supplemental files in the import directory, sorted by file name.
+fn hash_source_files(import_dir: &Path, entries: &[PathBuf]) -> std::io::Result<Vec<(String, [u8; 32])>> {
+	let mut out = Vec::new();
+	for path in source_files(import_dir, entries) {
+		let name = path.file_name().unwrap().to_string_lossy().to_string();
+		out.push((name, sha256::hash_file(&path)?));
+	}
+	out.sort();
+	Ok(out)
+}
+
+/// Returns the dictionary files with the supplemental files present in the
+/// import directory.
+fn source_files(import_dir: &Path, entries: &[PathBuf]) -> Vec<PathBuf> {
+	let mut files = entries.to_vec();
+	let names = [
+		SIMILAR_KANJI_FILE,
+		SOURCE_ORDER_FILE,
+		TAG_TRANSLATIONS_FILE,
+		GLOSSARY_CONFIG_FILE,
+		ORIGINS_FILE,
+		SENSE_INFO_FILE,
+		EMBEDDINGS_FILE,
+	];
+	for name in names.iter() {
+		let path = import_dir.join(name);
+		if path.is_file() {
+			files.push(path);
+		}
+	}
+	files
 }
@@ -0,0 +1,180 @@
+//! Concurrent import pipeline for the dictionary files.
+//!
+//! The import is split in stages, each running in its own thread and
+//! connected to the next by a bounded channel:
+//!
+//! 1. Load: reads and parses each file with its [Importer]. For Yomichan
+//!    archives, the zip reading also overlaps with the JSON parsing (see
+//!    `import::import_file`).
+//! 2. Normalize: sanitizes and truncates the entries and applies the media
+//!    size limit (see [Normalizer]).
+//! 3. Accumulate: adds the entries to the [Wrapper], in the calling thread.
+//!
+//! Each channel holds at most [QUEUE_SIZE] dictionaries, so a slow stage
+//! blocks the previous ones instead of having all dictionaries in memory at
+//! once. Dictionaries are accumulated in the order of the files.
+//!
+//! [Importer]: crate::source::Importer
+
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::diagnostics::{Context, Diagnostics};
+use crate::dict::{Kanji, MediaFile, Meta, Pitch, Tag, Term};
+use crate::generate::Wrapper;
+use crate::report::SourceReport;
+use crate::sanitize;
+use crate::source::{DictSource, Registry};
+use crate::truncate;
+
+/// Maximum number of dictionaries waiting between two stages.
+const QUEUE_SIZE: usize = 1;
+
+/// Dictionary loaded by the load stage.
+struct Loaded {
+	/// Name of the importer used to load the dictionary.
+	importer: String,
+	source: Box<dyn DictSource>,
+}
+
+/// Dictionary data from the normalize stage, added to the output with
+/// [Wrapper::add_dict].
+pub struct NormalizedDict {
+	/// Entry counts for the dictionary.
+	pub report: SourceReport,
+	pub tags: Vec<Tag>,
+	pub terms: Vec<Term>,
+	pub kanji: Vec<Kanji>,
+	pub meta_terms: Vec<Meta>,
+	pub meta_kanji: Vec<Meta>,
+	pub meta_pitch: Vec<Pitch>,
+	pub media: Vec<MediaFile>,
+}
+
+/// Options for the normalize stage.
+#[derive(Copy, Clone, Default)]
+pub struct Normalizer {
+	/// Sanitize the whitespace and control characters in the imported text
+	/// (see `sanitize`).
+	pub sanitize: bool,
+	/// Maximum glossary size in bytes for each term and the policy for the
+	/// glossaries over it (see `truncate`).
+	pub glossary_limit: Option<(usize, truncate::Policy)>,
+	/// Maximum total size in bytes for the media files imported from each
+	/// dictionary. Files are imported in archive order until the limit, any
+	/// other file is skipped. Media import is disabled if `None`.
+	pub media_limit: Option<usize>,
+}
+
+impl Normalizer {
+	/// Consumes the data from the source, returning the normalized entries.
+	pub fn normalize(&self, importer: &str, dict: &mut dyn DictSource) -> NormalizedDict {
+		let mut report = SourceReport::default();
+		report.name = dict.title().to_string();
+		report.importer = importer.to_string();
+
+		let mut tags: Vec<Tag> = dict.tags().collect();
+		let mut terms: Vec<Term> = dict.terms().collect();
+		let mut kanji: Vec<Kanji> = dict.kanji().collect();
+		let meta_terms: Vec<Meta> = dict.meta_terms().collect();
+		let meta_kanji: Vec<Meta> = dict.meta_kanji().collect();
+		let meta_pitch: Vec<Pitch> = dict.meta_pitch().collect();
+
+		let mut media: Vec<MediaFile> = Vec::new();
+		let mut available = self.media_limit.unwrap_or(0);
+		for it in dict.media() {
+			if self.media_limit.is_some() && it.data.len() <= available {
+				available -= it.data.len();
+				media.push(it);
+			} else {
+				report.media_skipped += 1;
+			}
+		}
+
+		if self.sanitize {
+			report.sanitized += tags.iter_mut().map(sanitize::sanitize_tag).sum::<usize>();
+			report.sanitized += terms.iter_mut().map(sanitize::sanitize_term).sum::<usize>();
+			report.sanitized += kanji.iter_mut().map(sanitize::sanitize_kanji).sum::<usize>();
+		}
+		if let Some((max_bytes, policy)) = self.glossary_limit {
+			for it in terms.iter_mut() {
+				if truncate::limit_glossary(it, max_bytes, policy) {
+					report.truncated += 1;
+				}
+			}
+		}
+
+		report.tags = tags.len();
+		report.terms = terms.len();
+		report.kanji = kanji.len();
+		report.term_frequencies = meta_terms.len();
+		report.kanji_frequencies = meta_kanji.len();
+		report.pitch = meta_pitch.len();
+		report.media = media.len();
+		NormalizedDict {
+			report: report,
+			tags: tags,
+			terms: terms,
+			kanji: kanji,
+			meta_terms: meta_terms,
+			meta_kanji: meta_kanji,
+			meta_pitch: meta_pitch,
+			media: media,
+		}
+	}
+}
+
+/// Imports the dictionary files into the wrapper through the pipeline.
+///
+/// Files that cannot be imported are reported to `diag` and skipped.
+pub fn run(
+	files: Vec<PathBuf>,
+	registry: Registry,
+	normalizer: Normalizer,
+	wrapper: &mut Wrapper,
+	diag: &mut Diagnostics,
+) {
+	let (loaded_sender, loaded) = mpsc::sync_channel(QUEUE_SIZE);
+	let (normalized_sender, normalized) = mpsc::sync_channel(QUEUE_SIZE);
+
+	let load = thread::spawn(move || {
+		let mut diag = Diagnostics::default();
+		for path in files {
+			let importer = registry.find(&path).unwrap();
+			println!("\n... using the {} importer", importer.name());
+			match importer.import(&path, &mut diag) {
+				Ok(source) => {
+					let loaded = Loaded {
+						importer: importer.name().to_string(),
+						source: source,
+					};
+					if loaded_sender.send(loaded).is_err() {
+						break;
+					}
+				}
+				Err(err) => {
+					let context = Context::new(path.to_string_lossy());
+					diag.error(context, format!("import failed, dictionary skipped ({})", err));
+				}
+			}
+		}
+		diag
+	});
+
+	let normalize = thread::spawn(move || {
+		for mut it in loaded {
+			let dict = normalizer.normalize(&it.importer, it.source.as_mut());
+			if normalized_sender.send(dict).is_err() {
+				break;
+			}
+		}
+	});
+
+	for dict in normalized {
+		wrapper.add_dict(dict);
+	}
+
+	normalize.join().expect("normalize stage failed");
+	diag.append(load.join().expect("load stage failed"));
+}
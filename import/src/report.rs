@@ -0,0 +1,162 @@
+//! Machine-readable summary of the import, written as JSON with the
+//! `--report FILE` argument.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufWriter, Result};
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Serialize;
+
+#[derive(Serialize, Default)]
+pub struct Report {
+	/// Entry counts for each imported source, in import order.
+	pub sources: Vec<SourceReport>,
+
+	/// Entries that were merged with or replaced by another entry.
+	pub merged: MergeReport,
+
+	/// Entries that were not included in the output.
+	pub dropped: DropReport,
+
+	/// Statistics for the tags in the output.
+	pub tags: TagReport,
+
+	/// True if the kanji frequency was derived from the terms, because no
+	/// kanji frequency list was imported.
+	pub derived_kanji_frequency: bool,
+
+	/// Number of import diagnostics by severity (see `diagnostics`).
+	pub diagnostics: BTreeMap<String, usize>,
+
+	/// Time spent on each import step, in milliseconds.
+	pub timing: BTreeMap<String, u64>,
+
+	/// Layout of the generated database file.
+	pub output: Option<OutputReport>,
+}
+
+#[derive(Serialize, Default)]
+pub struct SourceReport {
+	/// Source dictionary name.
+	pub name: String,
+	/// Name of the importer used for the source.
+	pub importer: String,
+	pub terms: usize,
+	pub kanji: usize,
+	pub tags: usize,
+	pub term_frequencies: usize,
+	pub kanji_frequencies: usize,
+	pub pitch: usize,
+	/// Strings changed by the text sanitization (see `sanitize`).
+	pub sanitized: usize,
+	/// Terms with the glossary shortened by the size limit (see `truncate`).
+	pub truncated: usize,
+	/// Media files imported.
+	pub media: usize,
+	/// Media files not imported because of the size limit or because media
+	/// import is disabled.
+	pub media_skipped: usize,
+}
+
+#[derive(Serialize, Default)]
+pub struct MergeReport {
+	/// Tags defined more than once (e.g. by different sources), for which
+	/// the notes are combined.
+	pub tags: usize,
+	/// Tags with a category overridden by a later definition.
+	pub tag_categories: usize,
+	/// Frequency entries replaced by a later entry for the same term.
+	pub term_frequencies: usize,
+	/// Frequency entries replaced by a later entry for the same kanji.
+	pub kanji_frequencies: usize,
+}
+
+#[derive(Serialize, Default)]
+pub struct DropReport {
+	/// Pitch accent positions already present for the same term.
+	pub pitch: usize,
+	/// Frequency entries for terms that are not in any source.
+	pub term_frequencies: usize,
+	/// Pitch accent entries for terms that are not in any source.
+	pub term_pitch: usize,
+}
+
+#[derive(Serialize, Default)]
+pub struct TagReport {
+	pub total: usize,
+	/// Tags used by entries without being defined by the source.
+	pub undefined: usize,
+	/// Number of tags for each category (empty for uncategorized tags).
+	pub categories: BTreeMap<String, usize>,
+	/// Number of tag translations for each language.
+	pub translations: BTreeMap<String, usize>,
+	/// Tag translations skipped because the tag is not in any source.
+	pub translations_unknown: usize,
+}
+
+#[derive(Serialize, Default)]
+pub struct OutputReport {
+	pub file: String,
+	pub bytes: u64,
+	/// Output profile (`full` or `compact`).
+	pub profile: String,
+	/// SHA-256 hash for the source files, as recorded in the header.
+	pub source_hash: String,
+	/// Importer version recorded in the database.
+	pub importer: String,
+	/// SHA-256 hash for each source file recorded in the database.
+	pub source_files: BTreeMap<String, String>,
+	/// Size of each section in the database file.
+	pub sections: Vec<SectionReport>,
+}
+
+#[derive(Serialize, Default)]
+pub struct SectionReport {
+	pub name: String,
+	pub entries: usize,
+	pub bytes: usize,
+}
+
+impl Report {
+	/// Records the time elapsed for an import step.
+	pub fn time(&mut self, step: &str, elapsed: Duration) {
+		self.timing.insert(step.to_string(), elapsed.as_millis() as u64);
+	}
+
+	/// Fills the output layout from the generated database file.
+	pub fn read_output<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+		let path = path.as_ref();
+		let data = fs::read(path)?;
+		let db = db::DB::load(&data);
+		let sections = db::Section::ALL
+			.iter()
+			.map(|&section| {
+				let size = db.section_size(section);
+				SectionReport {
+					name: format!("{:?}", section),
+					entries: size.entries,
+					bytes: size.bytes,
+				}
+			})
+			.collect();
+		self.output = Some(OutputReport {
+			file: path.to_string_lossy().to_string(),
+			bytes: data.len() as u64,
+			profile: db.profile().name().to_string(),
+			source_hash: db.info().source_hash_hex(),
+			importer: db.importer().to_string(),
+			source_files: db.source_files().map(|x| (x.name.to_string(), x.hash_hex())).collect(),
+			sections: sections,
+		});
+		Ok(())
+	}
+
+	/// Writes the report as JSON.
+	pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+		let output = BufWriter::new(fs::File::create(path)?);
+		serde_json::to_writer_pretty(output, self)?;
+		Ok(())
+	}
+}
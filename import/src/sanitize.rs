@@ -0,0 +1,178 @@
+//! Canonicalization of whitespace and control characters in the imported
+//! text, applied before the strings are interned.
+//!
+//! Some dictionaries contain stray control characters, byte order marks or
+//! inconsistent whitespace. Besides rendering badly, those fragment the
+//! string table on invisible differences.
+
+use crate::dict::{Kanji, Tag, Term};
+
+/// Sanitizes a text, returning `None` if it is already clean.
+///
+/// - Byte order marks, zero-width characters and control characters are
+///   removed, except for line breaks.
+/// - `\r\n` and `\r` line breaks are converted to `\n`.
+/// - Tabs and other Unicode spaces are converted to a regular space, except
+///   for the ideographic space (`U+3000`) which is meaningful in Japanese.
+/// - Runs of spaces are collapsed and spaces are trimmed from each line.
+/// - Leading and trailing line breaks are removed and runs of blank lines are
+///   collapsed to a single blank line.
+pub fn sanitize(text: &str) -> Option<String> {
+	if text.len() == 0 || is_clean(text) {
+		return None;
+	}
+
+	let lines_text = text.replace("\r\n", "\n").replace('\r', "\n");
+	let mut lines = Vec::new();
+	for line in lines_text.split('\n') {
+		let mut out = String::with_capacity(line.len());
+		for chr in line.chars() {
+			if is_space(chr) {
+				if out.len() > 0 && !out.ends_with(' ') {
+					out.push(' ');
+				}
+			} else if !is_invisible(chr) {
+				out.push(chr);
+			}
+		}
+		let out = out.trim_end_matches(' ');
+		if out.len() > 0 || lines.last().map(|x: &String| x.len() > 0).unwrap_or(false) {
+			lines.push(out.to_string());
+		}
+	}
+	while lines.last().map(|x| x.len() == 0).unwrap_or(false) {
+		lines.pop();
+	}
+
+	let out = lines.join("\n");
+	if out == text {
+		None
+	} else {
+		Some(out)
+	}
+}
+
+/// Sanitizes the text fields of a term, returning the number of changed
+/// strings.
+pub fn sanitize_term(term: &mut Term) -> usize {
+	let mut count = 0;
+	count += apply(&mut term.expression);
+	count += apply(&mut term.reading);
+	count += apply(&mut term.search_key);
+	for it in term.glossary.iter_mut() {
+		count += apply(it);
+	}
+	count
+}
+
+/// Sanitizes the text fields of a kanji, returning the number of changed
+/// strings.
+pub fn sanitize_kanji(kanji: &mut Kanji) -> usize {
+	let mut count = 0;
+	for it in kanji.meanings.iter_mut() {
+		count += apply(it);
+	}
+	for it in kanji.onyomi.iter_mut().chain(kanji.kunyomi.iter_mut()) {
+		count += apply(it);
+	}
+	count
+}
+
+/// Sanitizes the notes for a tag, returning the number of changed strings.
+pub fn sanitize_tag(tag: &mut Tag) -> usize {
+	apply(&mut tag.notes)
+}
+
+fn apply(text: &mut String) -> usize {
+	match sanitize(text) {
+		Some(out) => {
+			*text = out;
+			1
+		}
+		None => 0,
+	}
+}
+
+/// Fast path for the common case of a text that needs no changes.
+fn is_clean(text: &str) -> bool {
+	let mut last = '\n';
+	for chr in text.chars() {
+		let clean = match chr {
+			' ' => last != ' ' && last != '\n',
+			'\n' => last != ' ' && last != '\n',
+			_ => !is_space(chr) && !is_invisible(chr),
+		};
+		if !clean {
+			return false;
+		}
+		last = chr;
+	}
+	last != ' ' && last != '\n'
+}
+
+fn is_space(chr: char) -> bool {
+	chr != '\n' && chr != '\u{3000}' && chr.is_whitespace()
+}
+
+fn is_invisible(chr: char) -> bool {
+	match chr {
+		'\u{FEFF}' | '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' => true,
+		'\n' => false,
+		_ => chr.is_control(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn keeps_clean_text() {
+		assert_eq!(sanitize(""), None);
+		assert_eq!(sanitize("abc"), None);
+		assert_eq!(sanitize("食べる（たべる）"), None);
+		assert_eq!(sanitize("a\u{3000}b"), None);
+		assert_eq!(sanitize("\u{3000}a"), None);
+		assert_eq!(sanitize("a b\nc"), None);
+		assert_eq!(sanitize("a\n\nb"), None);
+	}
+
+	#[test]
+	fn removes_invisible_characters() {
+		assert_eq!(sanitize("\u{FEFF}abc").as_deref(), Some("abc"));
+		assert_eq!(sanitize("a\u{200B}b\u{200C}c\u{200D}d\u{2060}e").as_deref(), Some("abcde"));
+		assert_eq!(sanitize("a\u{7}b\u{0}c\u{7F}").as_deref(), Some("abc"));
+		assert_eq!(sanitize("a\u{85}b").as_deref(), Some("a b"));
+	}
+
+	#[test]
+	fn normalizes_spaces() {
+		assert_eq!(sanitize("a\tb").as_deref(), Some("a b"));
+		assert_eq!(sanitize("a\u{A0}b").as_deref(), Some("a b"));
+		assert_eq!(sanitize("a  \t b").as_deref(), Some("a b"));
+		assert_eq!(sanitize("  a  ").as_deref(), Some("a"));
+		assert_eq!(sanitize("a \n b").as_deref(), Some("a\nb"));
+	}
+
+	#[test]
+	fn normalizes_lines() {
+		assert_eq!(sanitize("a\r\nb\rc").as_deref(), Some("a\nb\nc"));
+		assert_eq!(sanitize("a\n\n\n\nb").as_deref(), Some("a\n\nb"));
+		assert_eq!(sanitize("a\n \t\n\nb").as_deref(), Some("a\n\nb"));
+		assert_eq!(sanitize("\n\na\n\n").as_deref(), Some("a"));
+		assert_eq!(sanitize(" \n ").as_deref(), Some(""));
+	}
+
+	#[test]
+	fn counts_changed_fields() {
+		let mut tag = Tag {
+			name: String::from("n"),
+			category: String::new(),
+			order: 0,
+			notes: String::from("noun\t(common)"),
+		};
+		assert_eq!(sanitize_tag(&mut tag), 1);
+		assert_eq!(tag.notes, "noun (common)");
+		assert_eq!(sanitize_tag(&mut tag), 0);
+	}
+}
@@ -0,0 +1,123 @@
+//! Schemas for the rows in the Yomichan bank files.
+//!
+//! Rows are deserialized directly into tuple structs (see `import`), which
+//! only gives generic errors for malformed rows (e.g. `invalid type: integer,
+//! expected a borrowed string`). When a row fails to deserialize, it is
+//! checked against its [Schema] to report the field with the problem, as in
+//! `expected string in field 2 (definition tags), got number`.
+//!
+//! Fields are numbered from zero, as the position in the row array.
+
+use serde_json::Value;
+
+/// Expected type for a row field.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Kind {
+	String,
+	/// String with a single character.
+	Char,
+	Integer,
+	/// Array of strings.
+	StringList,
+	/// Object with string values.
+	StringMap,
+	/// Any JSON value, validated by the importer.
+	Any,
+}
+
+/// Name and expected type for each field in a row, in order.
+pub type Schema = &'static [(&'static str, Kind)];
+
+pub const TERM: Schema = &[
+	("expression", Kind::String),
+	("reading", Kind::String),
+	("definition tags", Kind::String),
+	("rules", Kind::String),
+	("score", Kind::Integer),
+	("glossary", Kind::StringList),
+	("sequence", Kind::Integer),
+	("term tags", Kind::String),
+];
+
+pub const KANJI: Schema = &[
+	("character", Kind::Char),
+	("onyomi", Kind::String),
+	("kunyomi", Kind::String),
+	("tags", Kind::String),
+	("meanings", Kind::StringList),
+	("stats", Kind::StringMap),
+];
+
+pub const TAG: Schema = &[
+	("name", Kind::String),
+	("category", Kind::String),
+	("order", Kind::Integer),
+	("notes", Kind::String),
+	("score", Kind::Integer),
+];
+
+pub const META: Schema = &[("expression", Kind::String), ("mode", Kind::String), ("data", Kind::Any)];
+
+/// Checks the JSON for a row against the schema, returning a description of
+/// the first problem found. Returns `None` if the row is valid or is not
+/// valid JSON.
+pub fn validate(row: &str, schema: Schema) -> Option<String> {
+	let row: Value = serde_json::from_str(row).ok()?;
+	let fields = match row {
+		Value::Array(fields) => fields,
+		other => return Some(format!("expected array with {} fields, got {}", schema.len(), describe(&other))),
+	};
+	if fields.len() != schema.len() {
+		return Some(format!("expected {} fields, got {}", schema.len(), fields.len()));
+	}
+
+	for (index, (value, &(name, kind))) in fields.iter().zip(schema.iter()).enumerate() {
+		let field = format!("field {} ({})", index, name);
+		match kind {
+			Kind::String if !value.is_string() => {
+				return Some(format!("expected string in {}, got {}", field, describe(value)));
+			}
+			Kind::Char if value.as_str().map(|x| x.chars().count()) != Some(1) => {
+				return Some(format!("expected single character in {}, got {}", field, describe(value)));
+			}
+			Kind::Integer if !value.is_i64() && !value.is_u64() => {
+				return Some(format!("expected integer in {}, got {}", field, describe(value)));
+			}
+			Kind::StringList => {
+				let list = match value.as_array() {
+					Some(list) => list,
+					None => return Some(format!("expected array in {}, got {}", field, describe(value))),
+				};
+				if let Some(item) = list.iter().position(|x| !x.is_string()) {
+					let got = describe(&list[item]);
+					return Some(format!("expected string in {} item {}, got {}", field, item, got));
+				}
+			}
+			Kind::StringMap => {
+				let map = match value.as_object() {
+					Some(map) => map,
+					None => return Some(format!("expected object in {}, got {}", field, describe(value))),
+				};
+				if let Some((key, value)) = map.iter().find(|(_, x)| !x.is_string()) {
+					let got = describe(value);
+					return Some(format!("expected string in {} key `{}`, got {}", field, key, got));
+				}
+			}
+			_ => {}
+		}
+	}
+	None
+}
+
+/// Describes the type of a JSON value for the error messages.
+fn describe(value: &Value) -> &'static str {
+	match value {
+		Value::Null => "null",
+		Value::Bool(_) => "boolean",
+		Value::Number(number) if number.is_f64() => "non-integer number",
+		Value::Number(_) => "number",
+		Value::String(_) => "string",
+		Value::Array(_) => "array",
+		Value::Object(_) => "object",
+	}
+}
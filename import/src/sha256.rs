@@ -0,0 +1,170 @@
+//! SHA-256 hashing for the source dictionary files.
+
+use std::fs;
+use std::io::{Read, Result};
+use std::path::Path;
+
+const K: [u32; 64] = [
+	0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5, 0xd807aa98,
+	0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786,
+	0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8,
+	0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13,
+	0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819,
+	0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a,
+	0x5b9cca4f, 0x682e6ff3, 0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+	0xc67178f2,
+];
+
+const INIT: [u32; 8] = [
+	0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Incremental SHA-256 hasher.
+pub struct Sha256 {
+	state: [u32; 8],
+	block: [u8; 64],
+	block_len: usize,
+	total_len: u64,
+}
+
+impl Sha256 {
+	pub fn new() -> Sha256 {
+		Sha256 {
+			state: INIT,
+			block: [0; 64],
+			block_len: 0,
+			total_len: 0,
+		}
+	}
+
+	pub fn update(&mut self, mut data: &[u8]) {
+		self.total_len += data.len() as u64;
+		while data.len() > 0 {
+			let count = std::cmp::min(64 - self.block_len, data.len());
+			self.block[self.block_len..self.block_len + count].copy_from_slice(&data[..count]);
+			self.block_len += count;
+			data = &data[count..];
+			if self.block_len == 64 {
+				let block = self.block;
+				self.compress(&block);
+				self.block_len = 0;
+			}
+		}
+	}
+
+	pub fn finish(mut self) -> [u8; 32] {
+		let bit_len = self.total_len * 8;
+		self.update(&[0x80]);
+		while self.block_len != 56 {
+			self.update(&[0]);
+		}
+		self.update(&bit_len.to_be_bytes());
+
+		let mut out = [0u8; 32];
+		for (i, word) in self.state.iter().enumerate() {
+			out[i * 4..i * 4 + 4].copy_from_slice(&word.to_be_bytes());
+		}
+		out
+	}
+
+	fn compress(&mut self, block: &[u8; 64]) {
+		let mut w = [0u32; 64];
+		for i in 0..16 {
+			w[i] = u32::from_be_bytes([block[i * 4], block[i * 4 + 1], block[i * 4 + 2], block[i * 4 + 3]]);
+		}
+		for i in 16..64 {
+			let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+			let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+			w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+		}
+
+		let mut h = self.state;
+		for i in 0..64 {
+			let s1 = h[4].rotate_right(6) ^ h[4].rotate_right(11) ^ h[4].rotate_right(25);
+			let ch = (h[4] & h[5]) ^ (!h[4] & h[6]);
+			let t1 = h[7]
+				.wrapping_add(s1)
+				.wrapping_add(ch)
+				.wrapping_add(K[i])
+				.wrapping_add(w[i]);
+			let s0 = h[0].rotate_right(2) ^ h[0].rotate_right(13) ^ h[0].rotate_right(22);
+			let maj = (h[0] & h[1]) ^ (h[0] & h[2]) ^ (h[1] & h[2]);
+			let t2 = s0.wrapping_add(maj);
+			h = [
+				t1.wrapping_add(t2),
+				h[0],
+				h[1],
+				h[2],
+				h[3].wrapping_add(t1),
+				h[4],
+				h[5],
+				h[6],
+			];
+		}
+
+		for i in 0..8 {
+			self.state[i] = self.state[i].wrapping_add(h[i]);
+		}
+	}
+}
+
+/// Returns the SHA-256 hash for a file.
+pub fn hash_file<P: AsRef<Path>>(path: P) -> Result<[u8; 32]> {
+	let mut file = fs::File::open(path)?;
+	let mut hasher = Sha256::new();
+	let mut buffer = vec![0u8; 64 * 1024];
+	loop {
+		let count = file.read(&mut buffer)?;
+		if count == 0 {
+			break;
+		}
+		hasher.update(&buffer[..count]);
+	}
+	Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn hex(digest: [u8; 32]) -> String {
+		digest.iter().map(|x| format!("{:02x}", x)).collect()
+	}
+
+	fn hash(data: &[u8]) -> String {
+		let mut hasher = Sha256::new();
+		hasher.update(data);
+		hex(hasher.finish())
+	}
+
+	#[test]
+	fn known_answers() {
+		assert_eq!(hash(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+		assert_eq!(hash(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+		assert_eq!(
+			hash(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+			"248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+		);
+	}
+
+	#[test]
+	fn padding_boundaries() {
+		// The length fits in the last block up to 55 bytes
+		let a = [b'a'; 64];
+		assert_eq!(hash(&a[..55]), "9f4390f8d30c2dd92ec9f095b65e2b9ae9b0a925a5258e241c9f1e910f734318");
+		assert_eq!(hash(&a[..56]), "b35439a4ac6f0948b6d6f9e3c6af0f5f590ce20f1bde7090ef7970686ec6738a");
+		assert_eq!(hash(&a[..64]), "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb");
+	}
+
+	#[test]
+	fn chunked_update() {
+		// One million `a` in chunks that do not align with the blocks
+		let chunk = [b'a'; 7];
+		let mut hasher = Sha256::new();
+		for _ in 0..1_000_000 / 7 {
+			hasher.update(&chunk);
+		}
+		hasher.update(&chunk[..1_000_000 % 7]);
+		assert_eq!(hex(hasher.finish()), "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
+	}
+}
@@ -0,0 +1,127 @@
+//! Extension points for importing dictionary formats.
+//!
+//! Support for a dictionary format is added by implementing an [Importer]
+//! that loads a file into a [DictSource] and registering it in the
+//! [Registry].
+
+use std::io;
+use std::path::Path;
+
+use diagnostics::Diagnostics;
+use dict::{Dict, Kanji, MediaFile, Meta, Pitch, Tag, Term};
+
+/// Dictionary data loaded by an [Importer].
+///
+/// Each method consumes the respective data from the source, so it should be
+/// called only once.
+///
+/// Sources are sent between the threads of the import pipeline (see
+/// `pipeline`), so they must be [Send].
+pub trait DictSource: Send {
+	/// Dictionary name.
+	fn title(&self) -> &str;
+
+	/// Definition of tags used by the dictionary terms and kanji.
+	fn tags(&mut self) -> Box<dyn Iterator<Item = Tag>>;
+
+	/// Terms from the dictionary.
+	fn terms(&mut self) -> Box<dyn Iterator<Item = Term>>;
+
+	/// Kanji from the dictionary.
+	fn kanji(&mut self) -> Box<dyn Iterator<Item = Kanji>>;
+
+	/// Frequency metadata for terms.
+	fn meta_terms(&mut self) -> Box<dyn Iterator<Item = Meta>> {
+		Box::new(std::iter::empty())
+	}
+
+	/// Frequency metadata for kanji.
+	fn meta_kanji(&mut self) -> Box<dyn Iterator<Item = Meta>> {
+		Box::new(std::iter::empty())
+	}
+
+	/// Pitch accent metadata for terms.
+	fn meta_pitch(&mut self) -> Box<dyn Iterator<Item = Pitch>> {
+		Box::new(std::iter::empty())
+	}
+
+	/// Media files bundled with the dictionary.
+	fn media(&mut self) -> Box<dyn Iterator<Item = MediaFile>> {
+		Box::new(std::iter::empty())
+	}
+}
+
+impl DictSource for Dict {
+	fn title(&self) -> &str {
+		&self.title
+	}
+
+	fn tags(&mut self) -> Box<dyn Iterator<Item = Tag>> {
+		Box::new(std::mem::replace(&mut self.tags, Vec::new()).into_iter())
+	}
+
+	fn terms(&mut self) -> Box<dyn Iterator<Item = Term>> {
+		Box::new(std::mem::replace(&mut self.terms, Vec::new()).into_iter())
+	}
+
+	fn kanji(&mut self) -> Box<dyn Iterator<Item = Kanji>> {
+		Box::new(std::mem::replace(&mut self.kanji, Vec::new()).into_iter())
+	}
+
+	fn meta_terms(&mut self) -> Box<dyn Iterator<Item = Meta>> {
+		Box::new(std::mem::replace(&mut self.meta_terms, Vec::new()).into_iter())
+	}
+
+	fn meta_kanji(&mut self) -> Box<dyn Iterator<Item = Meta>> {
+		Box::new(std::mem::replace(&mut self.meta_kanji, Vec::new()).into_iter())
+	}
+
+	fn meta_pitch(&mut self) -> Box<dyn Iterator<Item = Pitch>> {
+		Box::new(std::mem::replace(&mut self.meta_pitch, Vec::new()).into_iter())
+	}
+
+	fn media(&mut self) -> Box<dyn Iterator<Item = MediaFile>> {
+		Box::new(std::mem::replace(&mut self.media, Vec::new()).into_iter())
+	}
+}
+
+/// Importer for a dictionary format.
+///
+/// Importers run in the load thread of the import pipeline (see `pipeline`),
+/// so they must be [Send].
+pub trait Importer: Send {
+	/// Name of the format, used for logging.
+	fn name(&self) -> &str;
+
+	/// Returns true if the importer supports the given file.
+	fn can_import(&self, path: &Path) -> bool;
+
+	/// Loads the dictionary data from the file.
+	///
+	/// Problems with individual entries should be reported to `diag` and the
+	/// entries skipped. An error should only be returned if the file cannot
+	/// be imported at all.
+	fn import(&self, path: &Path, diag: &mut Diagnostics) -> io::Result<Box<dyn DictSource>>;
+}
+
+/// Registry of the available importers.
+#[derive(Default)]
+pub struct Registry {
+	importers: Vec<Box<dyn Importer>>,
+}
+
+impl Registry {
+	/// Adds an importer to the registry. Importers registered first have
+	/// precedence when more than one supports a file.
+	pub fn register<T: Importer + 'static>(&mut self, importer: T) {
+		self.importers.push(Box::new(importer));
+	}
+
+	/// Returns the importer for the given file, if any.
+	pub fn find(&self, path: &Path) -> Option<&dyn Importer> {
+		self.importers
+			.iter()
+			.find(|x| x.can_import(path))
+			.map(|x| x.as_ref())
+	}
+}
@@ -0,0 +1,161 @@
+//! Size limit for the term glossaries, applied before the strings are
+//! interned.
+//!
+//! Some dictionaries have very long structured definitions (e.g. full
+//! encyclopedic articles), which bloat the database and the UI. With a limit
+//! set, glossaries over it are shortened and the term is flagged as
+//! [truncated](db::TermFlags::TRUNCATED).
+
+use std::str::FromStr;
+
+use crate::dict::Term;
+
+/// Appended to a definition that was cut.
+const ELLIPSIS: &str = "…";
+
+/// How to shorten a glossary over the size limit.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Policy {
+	/// Keeps the definitions in order up to the limit, cutting the last one
+	/// and dropping the rest.
+	Truncate,
+	/// Keeps only the first line of each definition, which for structured
+	/// definitions is usually the summary. The result is truncated if still
+	/// over the limit.
+	Summarize,
+}
+
+impl FromStr for Policy {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Policy, String> {
+		match s {
+			"truncate" => Ok(Policy::Truncate),
+			"summarize" => Ok(Policy::Summarize),
+			_ => Err(format!("invalid glossary policy `{}` (expected `truncate` or `summarize`)", s)),
+		}
+	}
+}
+
+/// Limits the total size of the glossary for a term to `max_bytes` using
+/// the given policy. Returns true if the glossary was shortened.
+pub fn limit_glossary(term: &mut Term, max_bytes: usize, policy: Policy) -> bool {
+	if glossary_len(&term.glossary) <= max_bytes {
+		return false;
+	}
+
+	if policy == Policy::Summarize {
+		for it in term.glossary.iter_mut() {
+			let summary = it.lines().map(|x| x.trim()).find(|x| x.len() > 0).unwrap_or("");
+			if summary.len() < it.trim().len() {
+				*it = format!("{}{}", summary, ELLIPSIS);
+			}
+		}
+	}
+
+	if glossary_len(&term.glossary) > max_bytes {
+		let mut available = max_bytes;
+		let mut glossary = Vec::new();
+		for it in term.glossary.drain(..) {
+			if it.len() <= available {
+				available -= it.len();
+				glossary.push(it);
+				continue;
+			}
+			let text = it.trim_end_matches(ELLIPSIS);
+			let mut end = std::cmp::min(available, text.len());
+			while !text.is_char_boundary(end) {
+				end -= 1;
+			}
+			// Always keep part of the first definition, so that the term is
+			// not left without a glossary.
+			if end == 0 && glossary.len() == 0 {
+				end = text.chars().next().map(|x| x.len_utf8()).unwrap_or(0);
+			}
+			if end > 0 {
+				glossary.push(format!("{}{}", text[..end].trim_end(), ELLIPSIS));
+			}
+			break;
+		}
+		term.glossary = glossary;
+	}
+
+	term.truncated = true;
+	true
+}
+
+fn glossary_len(glossary: &[String]) -> usize {
+	glossary.iter().map(|x| x.len()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn term(glossary: &[&str]) -> Term {
+		Term {
+			expression: String::from("語"),
+			reading: String::from("ご"),
+			search_key: String::from("ご"),
+			definition_tags: Vec::new(),
+			rules: Vec::new(),
+			score: 0,
+			glossary: glossary.iter().map(|x| x.to_string()).collect(),
+			sequence: 0,
+			term_tags: Vec::new(),
+			source: String::new(),
+			monolingual: false,
+			truncated: false,
+		}
+	}
+
+	fn limit(glossary: &[&str], max_bytes: usize, policy: Policy) -> Option<Vec<String>> {
+		let mut term = term(glossary);
+		let changed = limit_glossary(&mut term, max_bytes, policy);
+		assert_eq!(changed, term.truncated);
+		if changed {
+			Some(term.glossary)
+		} else {
+			assert_eq!(term.glossary, glossary);
+			None
+		}
+	}
+
+	#[test]
+	fn truncates_at_the_limit() {
+		assert_eq!(limit(&["abc", "de"], 5, Policy::Truncate), None);
+		assert_eq!(limit(&["abc", "de"], 4, Policy::Truncate), Some(vec![s("abc"), s("d…")]));
+		assert_eq!(limit(&["abc", "def", "ghi"], 3, Policy::Truncate), Some(vec![s("abc")]));
+		assert_eq!(limit(&["ab cd"], 3, Policy::Truncate), Some(vec![s("ab…")]));
+	}
+
+	#[test]
+	fn truncates_at_char_boundary() {
+		assert_eq!(limit(&["日本語"], 9, Policy::Truncate), None);
+		assert_eq!(limit(&["日本語"], 8, Policy::Truncate), Some(vec![s("日本…")]));
+		assert_eq!(limit(&["日本語"], 4, Policy::Truncate), Some(vec![s("日…")]));
+		// The first definition is never dropped completely.
+		assert_eq!(limit(&["日本語"], 2, Policy::Truncate), Some(vec![s("日…")]));
+		assert_eq!(limit(&["日本語", "x"], 0, Policy::Truncate), Some(vec![s("日…")]));
+	}
+
+	#[test]
+	fn summarizes_definitions() {
+		let glossary = ["\nsummary\ndetails long", "short"];
+		assert_eq!(limit(&glossary, 26, Policy::Summarize), None);
+		assert_eq!(limit(&glossary, 25, Policy::Summarize), Some(vec![s("summary…"), s("short")]));
+		assert_eq!(limit(&glossary, 10, Policy::Summarize), Some(vec![s("summary…")]));
+		assert_eq!(limit(&glossary, 6, Policy::Summarize), Some(vec![s("summar…")]));
+	}
+
+	#[test]
+	fn parses_policy() {
+		assert_eq!("truncate".parse(), Ok(Policy::Truncate));
+		assert_eq!("summarize".parse(), Ok(Policy::Summarize));
+		assert!("cut".parse::<Policy>().is_err());
+	}
+
+	fn s(text: &str) -> String {
+		text.to_string()
+	}
+}
@@ -0,0 +1,82 @@
+//! Watch mode for the importer, re-importing when the source files change.
+//!
+//! Changes are detected by polling the size and modification time of the
+//! source files, which is portable and cheap for the few files in the data
+//! directory. Since the source files are usually large archives, a change is
+//! only reported once the files stop changing between two polls, so that an
+//! archive being copied is not imported halfway.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Result;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// Interval between polls of the source files.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// State of the source files, used to detect changes.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Snapshot {
+	files: BTreeMap<PathBuf, (u64, Option<SystemTime>)>,
+}
+
+impl Snapshot {
+	/// Takes a snapshot of the given files.
+	pub fn take(files: Vec<PathBuf>) -> Result<Snapshot> {
+		let mut out = Snapshot::default();
+		for path in files {
+			let md = fs::metadata(&path)?;
+			out.files.insert(path, (md.len(), md.modified().ok()));
+		}
+		Ok(out)
+	}
+
+	/// Returns the names of the files that changed, were added or removed
+	/// since the `previous` snapshot.
+	pub fn changes(&self, previous: &Snapshot) -> Vec<String> {
+		let name = |path: &PathBuf| path.file_name().unwrap().to_string_lossy().to_string();
+		let mut out = Vec::new();
+		for (path, state) in self.files.iter() {
+			if previous.files.get(path) != Some(state) {
+				out.push(name(path));
+			}
+		}
+		for path in previous.files.keys() {
+			if !self.files.contains_key(path) {
+				out.push(name(path));
+			}
+		}
+		out.sort();
+		out
+	}
+}
+
+/// Blocks until the source files listed by `files` change from the `last`
+/// snapshot and then stay unchanged for a full poll interval.
+///
+/// Returns the new snapshot and the names of the changed files. Errors
+/// listing the files (e.g. a file removed while listing) are retried on the
+/// next poll.
+pub fn wait_for_change<F: Fn() -> Result<Vec<PathBuf>>>(last: &Snapshot, files: F) -> (Snapshot, Vec<String>) {
+	let take = || files().and_then(Snapshot::take);
+	let mut pending: Option<Snapshot> = None;
+	loop {
+		std::thread::sleep(POLL_INTERVAL);
+		let current = match take() {
+			Ok(current) => current,
+			Err(_) => {
+				pending = None;
+				continue;
+			}
+		};
+		match pending {
+			Some(ref pending) if *pending == current => {
+				let changes = current.changes(last);
+				return (current, changes);
+			}
+			_ if current != *last => pending = Some(current),
+			_ => pending = None,
+		}
+	}
+}
@@ -0,0 +1,135 @@
+//! Furigana annotation and kana rendering for arbitrary Japanese text.
+
+use db::DB;
+
+use format::{furigana, is_kana};
+use html::html_escape;
+
+/// Segment of a text annotated by [annotate_furigana].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuriganaSegment<'a> {
+	/// Text for the segment.
+	pub text: &'a str,
+	/// Furigana for the segment. This is `None` for kana and for text without
+	/// a matching term.
+	pub reading: Option<&'a str>,
+	/// Index of the term the segment was matched to, if any. Segments split
+	/// from the same match have the same term.
+	pub term: Option<usize>,
+}
+
+/// Annotates a text with the furigana for each word.
+///
+/// The text is split into words using [DB::scan], taking the reading from
+/// the most likely term in context (see [ranked](db::ScanMatch::ranked))
+/// with the exact matched text as expression. The
+/// furigana is then split by [furigana] so that the kana in the word (e.g.
+/// okurigana) is not annotated.
+///
+/// The returned segments cover the whole text in order, so concatenating
+/// their text results in the original text.
+pub fn annotate_furigana<'a>(db: &'a DB<'a>, text: &'a str) -> Vec<FuriganaSegment<'a>> {
+	let mut out = Vec::new();
+	let mut last = 0;
+	for it in db.scan(text) {
+		if it.start > last {
+			out.push(FuriganaSegment {
+				text: &text[last..it.start],
+				reading: None,
+				term: None,
+			});
+		}
+
+		let word = it.text(text);
+		let term = it.ranked.iter().filter_map(|&x| db.term(x)).find(|x| x.expression() == word);
+		match term {
+			Some(term) => {
+				for (segment, reading) in furigana(word, term.reading()) {
+					out.push(FuriganaSegment {
+						text: segment,
+						reading: reading,
+						term: Some(term.index()),
+					});
+				}
+			}
+			None => out.push(FuriganaSegment {
+				text: word,
+				reading: None,
+				term: it.ranked.first().cloned(),
+			}),
+		}
+		last = it.end;
+	}
+	if last < text.len() {
+		out.push(FuriganaSegment {
+			text: &text[last..],
+			reading: None,
+			term: None,
+		});
+	}
+	out
+}
+
+/// Renders a text as HTML with the furigana from [annotate_furigana] as
+/// `<ruby>` elements.
+pub fn html_furigana(db: &DB, text: &str) -> String {
+	let mut out = String::new();
+	for it in annotate_furigana(db, text) {
+		match it.reading {
+			Some(reading) => out.push_str(&format!(
+				"<ruby>{}<rp>(</rp><rt>{}</rt><rp>)</rp></ruby>",
+				html_escape(it.text),
+				html_escape(reading)
+			)),
+			None => out.push_str(&html_escape(it.text)),
+		}
+	}
+	out
+}
+
+/// Converts a text to kana using the readings from the dictionary, for
+/// learners that can't read kanji yet.
+///
+/// Words are split as in [annotate_furigana]. When the terms for a word have
+/// different readings, all of them are included as `{いく|ゆく}`, with the
+/// most likely reading in context first. Numbers, dates and times without a
+/// matching term use their reading from the scan (e.g. `さんじはん` for
+/// `三時半`). Words that are already kana and other text without a matching
+/// term are kept as is.
+pub fn kana_text(db: &DB, text: &str) -> String {
+	let mut out = String::new();
+	let mut last = 0;
+	for it in db.scan(text) {
+		out.push_str(&text[last..it.start]);
+
+		let word = it.text(text);
+		let mut readings: Vec<&str> = Vec::new();
+		if !word.chars().all(is_kana) {
+			for term in it.ranked.iter().filter_map(|&x| db.term(x)) {
+				if term.expression() == word {
+					let reading = if term.reading().len() > 0 { term.reading() } else { word };
+					if !readings.contains(&reading) {
+						readings.push(reading);
+					}
+				}
+			}
+		}
+		if let (0, Some(numeric)) = (readings.len(), &it.numeric) {
+			if !word.chars().all(is_kana) {
+				readings.push(&numeric.reading);
+			}
+		}
+		match readings.len() {
+			0 => out.push_str(word),
+			1 => out.push_str(readings[0]),
+			_ => {
+				out.push('{');
+				out.push_str(&readings.join("|"));
+				out.push('}');
+			}
+		}
+		last = it.end;
+	}
+	out.push_str(&text[last..]);
+	out
+}
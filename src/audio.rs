@@ -0,0 +1,46 @@
+//! Audio support for terms.
+//!
+//! The dictionary data does not include audio, so audio for terms is provided
+//! by the host application by registering an [AudioProvider] (e.g. wrapping a
+//! text-to-speech engine) with [set_audio_provider].
+
+use std::sync::RwLock;
+
+use db::Term;
+
+/// Audio data for a term.
+pub struct Audio {
+	/// MIME type for the audio data (e.g. `audio/mpeg`).
+	pub mime_type: String,
+	/// Raw audio data.
+	pub data: Vec<u8>,
+}
+
+/// Provides audio for a term given its expression and reading.
+pub trait AudioProvider: Send + Sync {
+	/// Returns the audio for a term, or `None` if not available. The `reading`
+	/// may be empty for terms without a reading.
+	fn audio(&self, expression: &str, reading: &str) -> Option<Audio>;
+}
+
+lazy_static! {
+	static ref PROVIDER: RwLock<Option<Box<dyn AudioProvider>>> = RwLock::new(None);
+}
+
+/// Registers the global audio provider, replacing any previous one.
+pub fn set_audio_provider<P: AudioProvider + 'static>(provider: P) {
+	*PROVIDER.write().unwrap() = Some(Box::new(provider));
+}
+
+/// Removes the global audio provider.
+pub fn clear_audio_provider() {
+	*PROVIDER.write().unwrap() = None;
+}
+
+/// Returns the audio for a term from the registered provider, if any.
+pub fn get_audio(term: &Term) -> Option<Audio> {
+	match *PROVIDER.read().unwrap() {
+		Some(ref provider) => provider.audio(term.expression(), term.reading()),
+		None => None,
+	}
+}
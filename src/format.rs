@@ -0,0 +1,252 @@
+//! Formatting of dictionary entries for terminal output.
+
+use db::{morae, Headword, Term, DB};
+
+const RESET: &'static str = "\x1B[0m";
+const BOLD: &'static str = "\x1B[1m";
+const DIM: &'static str = "\x1B[2m";
+const CYAN: &'static str = "\x1B[36m";
+const GREEN: &'static str = "\x1B[32m";
+const YELLOW: &'static str = "\x1B[33m";
+
+/// Maximum number of stars for the term frequency.
+pub(crate) const MAX_STARS: usize = 5;
+
+/// Formatter for rendering entries as text for terminal display.
+///
+/// The output looks like:
+///
+/// ```text
+/// 食[た]べる  たべ＼る  ★★★★☆
+///   JMdict
+///     1. to eat  (v1, vt)
+///     2. to live on (e.g. a salary)  (v1, vt)
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct TextFormat {
+	color: bool,
+	merge: bool,
+}
+
+impl TextFormat {
+	/// Returns a new formatter with the default options (no colors).
+	pub fn new() -> TextFormat {
+		TextFormat::default()
+	}
+
+	/// Enables ANSI colors in the output.
+	pub fn color(mut self, color: bool) -> TextFormat {
+		self.color = color;
+		self
+	}
+
+	/// Collapses the identical senses from different sources when rendering
+	/// a headword, listing the senses with their sources instead of grouping
+	/// them by source (see [Headword::merged_senses]).
+	pub fn merge_senses(mut self, merge: bool) -> TextFormat {
+		self.merge = merge;
+		self
+	}
+
+	/// Renders a headword with all its senses, grouped by source dictionary.
+	pub fn headword(&self, db: &DB, headword: &Headword) -> String {
+		let terms: Vec<_> = headword.terms().into_iter().map(|x| db.term(x).unwrap()).collect();
+		let pitch: Vec<u32> = terms
+			.iter()
+			.map(|x| x.pitch().collect::<Vec<_>>())
+			.find(|x| x.len() > 0)
+			.unwrap_or_default();
+		let frequency = terms.iter().filter_map(|x| x.frequency()).max();
+
+		let mut out = self.heading(headword.expression, headword.reading, &pitch, frequency);
+		if self.merge {
+			for (index, sense) in headword.merged_senses().iter().enumerate() {
+				out.push_str("\n    ");
+				out.push_str(&self.sense(index + 1, &db.term(sense.terms[0]).unwrap()));
+				out.push_str("  ");
+				out.push_str(&self.paint(DIM, &format!("[{}]", sense.sources.join(", "))));
+			}
+			return out;
+		}
+
+		let mut index = 0;
+		for source in headword.sources.iter() {
+			out.push_str("\n  ");
+			out.push_str(&self.paint(DIM, source.source));
+			for &term in source.terms.iter() {
+				index += 1;
+				out.push_str("\n    ");
+				out.push_str(&self.sense(index, &db.term(term).unwrap()));
+			}
+		}
+		out
+	}
+
+	/// Renders a single term.
+	pub fn term(&self, term: &Term) -> String {
+		let pitch: Vec<u32> = term.pitch().collect();
+		let mut out = self.heading(term.expression(), term.reading(), &pitch, term.frequency());
+		out.push_str("\n  ");
+		out.push_str(&self.paint(DIM, term.source()));
+		out.push_str("\n    ");
+		out.push_str(&self.sense(1, term));
+		out
+	}
+
+	fn heading(&self, expression: &str, reading: &str, pitch: &[u32], frequency: Option<u32>) -> String {
+		let mut out = String::new();
+		let mut text = String::new();
+		for (segment, ruby) in furigana(expression, reading) {
+			text.push_str(segment);
+			if let Some(ruby) = ruby {
+				text.push('[');
+				text.push_str(ruby);
+				text.push(']');
+			}
+		}
+		out.push_str(&self.paint(BOLD, &text));
+
+		let reading = if reading.len() > 0 { reading } else { expression };
+		if pitch.len() > 0 {
+			let pitch: Vec<_> = pitch.iter().map(|&x| pitch_text(reading, x)).collect();
+			out.push_str("  ");
+			out.push_str(&self.paint(CYAN, &pitch.join(" / ")));
+		}
+
+		if let Some(frequency) = frequency {
+			let stars = frequency_stars(frequency);
+			let mut text = String::new();
+			for i in 0..MAX_STARS {
+				text.push(if i < stars { '★' } else { '☆' });
+			}
+			out.push_str("  ");
+			out.push_str(&self.paint(YELLOW, &text));
+		}
+		out
+	}
+
+	fn sense(&self, index: usize, term: &Term) -> String {
+		let glossary: Vec<_> = term.glossary().collect();
+		let mut out = format!("{}. {}", index, glossary.join("; "));
+		if term.is_truncated() {
+			out.push_str("  ");
+			out.push_str(&self.paint(DIM, "[truncated]"));
+		}
+		let forms: Vec<_> = term.restrictions().map(|(x, y)| form_text(x, y)).collect();
+		if forms.len() > 0 {
+			out.push_str("  ");
+			out.push_str(&self.paint(DIM, &format!("[only {}]", forms.join(", "))));
+		}
+		let info: Vec<_> = term.info().collect();
+		if info.len() > 0 {
+			out.push_str("  ");
+			out.push_str(&self.paint(DIM, &format!("[{}]", info.join("; "))));
+		}
+		let tags: Vec<_> = term.tags().iter().map(|x| x.name()).collect();
+		if tags.len() > 0 {
+			out.push_str("  ");
+			out.push_str(&self.paint(GREEN, &format!("({})", tags.join(", "))));
+		}
+		out
+	}
+
+	fn paint(&self, style: &str, text: &str) -> String {
+		if self.color {
+			format!("{}{}{}", style, text, RESET)
+		} else {
+			text.to_string()
+		}
+	}
+}
+
+/// Renders a form of an entry as `expression【reading】`, or just the
+/// expression if it has no reading.
+pub(crate) fn form_text(expression: &str, reading: &str) -> String {
+	if reading.len() > 0 && reading != expression {
+		format!("{}【{}】", expression, reading)
+	} else {
+		expression.to_string()
+	}
+}
+
+/// Splits an expression into segments with their respective furigana, by
+/// matching the kana in the expression with the reading.
+///
+/// Returns a list of `(text, furigana)` where the furigana is `None` for kana
+/// segments or when no reading is available.
+pub fn furigana<'a>(expression: &'a str, reading: &'a str) -> Vec<(&'a str, Option<&'a str>)> {
+	if reading.len() == 0 || reading == expression || !expression.chars().any(|x| !is_kana(x)) {
+		return vec![(expression, None)];
+	}
+
+	// Strip the kana common to the start and end of expression and reading
+	let mut prefix = 0;
+	for (a, b) in expression.chars().zip(reading.chars()) {
+		if a != b || !is_kana(a) {
+			break;
+		}
+		prefix += a.len_utf8();
+	}
+
+	let mut suffix = 0;
+	for (a, b) in expression[prefix..].chars().rev().zip(reading[prefix..].chars().rev()) {
+		if a != b || !is_kana(a) {
+			break;
+		}
+		suffix += a.len_utf8();
+	}
+
+	if prefix + suffix >= reading.len() || prefix + suffix >= expression.len() {
+		return vec![(expression, Some(reading))];
+	}
+
+	let mut out = Vec::new();
+	if prefix > 0 {
+		out.push((&expression[..prefix], None));
+	}
+	out.push((
+		&expression[prefix..expression.len() - suffix],
+		Some(&reading[prefix..reading.len() - suffix]),
+	));
+	if suffix > 0 {
+		out.push((&expression[expression.len() - suffix..], None));
+	}
+	out
+}
+
+/// Renders the reading with a `＼` mark at the accent position (i.e. after
+/// the mora where the pitch drops). Heiban (position zero) is marked with a
+/// trailing `￣`.
+pub(crate) fn pitch_text(reading: &str, position: u32) -> String {
+	let mut out = String::new();
+	let morae = morae(reading);
+	for (i, mora) in morae.iter().enumerate() {
+		out.push_str(mora);
+		if i + 1 == position as usize {
+			out.push('＼');
+		}
+	}
+	if position == 0 {
+		out.push('￣');
+	}
+	out
+}
+
+/// Number of stars for a term frequency. Each star is an order of magnitude
+/// in the number of occurrences.
+pub(crate) fn frequency_stars(frequency: u32) -> usize {
+	let mut stars = 0;
+	let mut value = frequency;
+	while value >= 10 && stars < MAX_STARS {
+		value /= 10;
+		stars += 1;
+	}
+	std::cmp::max(stars, 1)
+}
+
+pub(crate) fn is_kana(chr: char) -> bool {
+	match chr {
+		'\u{3041}'..='\u{309F}' | '\u{30A0}'..='\u{30FF}' => true,
+		_ => false,
+	}
+}
@@ -0,0 +1,213 @@
+//! Rendering of dictionary entries as HTML fragments.
+//!
+//! The markup is semantic and has no inline styling, so it can be styled by
+//! the host application. The main classes are:
+//!
+//! - `entry` for the root `<article>` of an entry.
+//! - `headword`, with the expression as `<ruby>` and the `pitch` and
+//!   `frequency` (with a `data-stars` attribute).
+//! - `source` for the `<section>` with the senses of a source dictionary
+//!   (with a `data-source` attribute).
+//! - `senses` for the `<ol>` list of senses, each a `sense` item containing
+//!   the `glossary`, the supplemental `info` notes for the sense (e.g.
+//!   "usu. in the negative"), the `restrictions` with the forms of the entry
+//!   a sense is restricted to, and the `tags`. Senses have `data-term` and
+//!   `data-sequence` attributes with the term index and the sequence number
+//!   in the source dictionary. The `glossary` has a `data-truncated`
+//!   attribute if it was shortened on import.
+//! - `tag` for each tag chip, with a `data-category` attribute and the tag
+//!   notes as the title. The `_in` variants of the render functions use the
+//!   tag notes translated to a language, if available.
+//!
+//! Images bundled with a dictionary (see [DB::media]) can be embedded in the
+//! markup using [html_media_uri], so the definitions render offline.
+
+use db::{Headword, Media, Tag, Term, DB};
+
+use format::{form_text, frequency_stars, furigana, pitch_text, MAX_STARS};
+
+/// Renders a headword with all its senses, grouped by source dictionary.
+pub fn html_headword(db: &DB, headword: &Headword) -> String {
+	render_headword(db, headword, None)
+}
+
+/// Same as [html_headword] with the tag notes in the given language.
+pub fn html_headword_in(db: &DB, headword: &Headword, language: &str) -> String {
+	render_headword(db, headword, Some(language))
+}
+
+/// Renders a single term.
+pub fn html_term(term: &Term) -> String {
+	render_term(term, None)
+}
+
+/// Same as [html_term] with the tag notes in the given language.
+pub fn html_term_in(term: &Term, language: &str) -> String {
+	render_term(term, Some(language))
+}
+
+fn render_headword(db: &DB, headword: &Headword, language: Option<&str>) -> String {
+	let terms: Vec<_> = headword.terms().into_iter().map(|x| db.term(x).unwrap()).collect();
+	let pitch: Vec<u32> = terms
+		.iter()
+		.map(|x| x.pitch().collect::<Vec<_>>())
+		.find(|x| x.len() > 0)
+		.unwrap_or_default();
+	let frequency = terms.iter().filter_map(|x| x.frequency()).max();
+
+	let mut out = String::from("<article class=\"entry\">");
+	out.push_str(&html_heading(headword.expression, headword.reading, &pitch, frequency));
+	for source in headword.sources.iter() {
+		let terms: Vec<_> = source.senses.iter().map(|x| db.term(x.term).unwrap()).collect();
+		out.push_str(&html_source(source.source, &terms, language));
+	}
+	out.push_str("</article>");
+	out
+}
+
+fn render_term(term: &Term, language: Option<&str>) -> String {
+	let pitch: Vec<u32> = term.pitch().collect();
+	let mut out = String::from("<article class=\"entry\">");
+	out.push_str(&html_heading(
+		term.expression(),
+		term.reading(),
+		&pitch,
+		term.frequency(),
+	));
+	out.push_str(&html_source(term.source(), std::slice::from_ref(term), language));
+	out.push_str("</article>");
+	out
+}
+
+/// Escapes text for use in HTML content and attribute values.
+pub fn html_escape(text: &str) -> String {
+	let mut out = String::with_capacity(text.len());
+	for chr in text.chars() {
+		match chr {
+			'&' => out.push_str("&amp;"),
+			'<' => out.push_str("&lt;"),
+			'>' => out.push_str("&gt;"),
+			'"' => out.push_str("&quot;"),
+			'\'' => out.push_str("&#39;"),
+			_ => out.push(chr),
+		}
+	}
+	out
+}
+
+/// Returns a `data:` URI with the contents of a media file, for use as the
+/// `src` of an image.
+pub fn html_media_uri(media: &Media) -> String {
+	const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+	let data = media.data();
+	let mut out = format!("data:{};base64,", media.mime_type());
+	out.reserve((data.len() + 2) / 3 * 4);
+	for chunk in data.chunks(3) {
+		let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+		let value = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+		for i in 0..4 {
+			if i <= chunk.len() {
+				out.push(ALPHABET[(value >> (18 - 6 * i) & 0x3F) as usize] as char);
+			} else {
+				out.push('=');
+			}
+		}
+	}
+	out
+}
+
+fn html_heading(expression: &str, reading: &str, pitch: &[u32], frequency: Option<u32>) -> String {
+	let mut out = String::from("<header class=\"headword\"><span class=\"expression\">");
+	for (segment, ruby) in furigana(expression, reading) {
+		match ruby {
+			Some(ruby) => out.push_str(&format!(
+				"<ruby>{}<rp>(</rp><rt>{}</rt><rp>)</rp></ruby>",
+				html_escape(segment),
+				html_escape(ruby)
+			)),
+			None => out.push_str(&html_escape(segment)),
+		}
+	}
+	out.push_str("</span>");
+
+	let reading = if reading.len() > 0 { reading } else { expression };
+	for &position in pitch {
+		out.push_str(&format!(
+			"<span class=\"pitch\" data-position=\"{}\">{}</span>",
+			position,
+			html_escape(&pitch_text(reading, position))
+		));
+	}
+
+	if let Some(frequency) = frequency {
+		let stars = frequency_stars(frequency);
+		let mut text = String::new();
+		for i in 0..MAX_STARS {
+			text.push(if i < stars { '★' } else { '☆' });
+		}
+		out.push_str(&format!(
+			"<span class=\"frequency\" data-stars=\"{}\" title=\"{}\">{}</span>",
+			stars, frequency, text
+		));
+	}
+
+	out.push_str("</header>");
+	out
+}
+
+fn html_source<'db, 'a>(source: &str, terms: &[Term<'db, 'a>], language: Option<&str>) -> String {
+	let mut out = format!(
+		"<section class=\"source\" data-source=\"{}\"><h3>{}</h3><ol class=\"senses\">",
+		html_escape(source),
+		html_escape(source)
+	);
+	for term in terms {
+		out.push_str(&format!(
+			"<li class=\"sense\" data-term=\"{}\" data-sequence=\"{}\">",
+			term.index(),
+			term.sequence()
+		));
+		let lang = if term.is_monolingual() { "ja" } else { "en" };
+		let truncated = if term.is_truncated() { " data-truncated" } else { "" };
+		out.push_str(&format!("<ul class=\"glossary\" lang=\"{}\"{}>", lang, truncated));
+		for it in term.glossary() {
+			out.push_str(&format!("<li>{}</li>", html_escape(it)));
+		}
+		out.push_str("</ul>");
+
+		let forms: Vec<_> = term.restrictions().map(|(x, y)| html_escape(&form_text(x, y))).collect();
+		if forms.len() > 0 {
+			out.push_str(&format!("<span class=\"restrictions\">{}</span>", forms.join(", ")));
+		}
+
+		for it in term.info() {
+			out.push_str(&format!("<span class=\"info\">{}</span>", html_escape(it)));
+		}
+
+		let tags = term.tags();
+		if tags.len() > 0 {
+			out.push_str("<span class=\"tags\">");
+			for tag in tags {
+				out.push_str(&html_tag(&tag, language));
+			}
+			out.push_str("</span>");
+		}
+		out.push_str("</li>");
+	}
+	out.push_str("</ol></section>");
+	out
+}
+
+fn html_tag(tag: &Tag, language: Option<&str>) -> String {
+	let notes = match language {
+		Some(language) => tag.notes_in(language),
+		None => tag.notes(),
+	};
+	format!(
+		"<span class=\"tag\" data-category=\"{}\" title=\"{}\">{}</span>",
+		html_escape(tag.category()),
+		html_escape(notes),
+		html_escape(tag.name())
+	)
+}
@@ -6,3 +6,21 @@ pub use db::*;
 
 mod data;
 pub use data::get_db;
+
+mod audio;
+pub use audio::*;
+
+mod format;
+pub use format::*;
+
+mod html;
+pub use html::*;
+
+mod annotate;
+pub use annotate::*;
+
+mod romaji;
+pub use romaji::*;
+
+#[cfg(feature = "async")]
+pub mod nonblocking;
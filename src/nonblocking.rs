@@ -0,0 +1,115 @@
+//! Asynchronous facade for the database queries, enabled by the `async`
+//! feature.
+//!
+//! Each function runs the synchronous query from [get_db] on a separate
+//! thread and returns a `Send` future for the result. The futures don't
+//! depend on any particular executor, so they can be awaited from an async
+//! HTTP server or GUI framework without blocking its event loop.
+//!
+//! Long running queries can be abandoned with the `_cancellable` variants
+//! and a [Cancel] token. Dropping the future does not stop the query.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use db::{Cancel, Cancelled, Headword, Query, ResultSet, ScanMatch, DB};
+
+use data::get_db;
+
+/// Future for the result of a function running on a separate thread,
+/// returned by [spawn_blocking].
+///
+/// If the function panics, the panic is resumed when polling the future.
+pub struct Blocking<T> {
+	shared: Arc<Mutex<Shared<T>>>,
+}
+
+struct Shared<T> {
+	result: Option<thread::Result<T>>,
+	waker: Option<Waker>,
+}
+
+impl<T> Future for Blocking<T> {
+	type Output = T;
+
+	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+		let mut shared = self.shared.lock().unwrap();
+		match shared.result.take() {
+			Some(Ok(result)) => Poll::Ready(result),
+			Some(Err(panic)) => std::panic::resume_unwind(panic),
+			None => {
+				shared.waker = Some(cx.waker().clone());
+				Poll::Pending
+			}
+		}
+	}
+}
+
+/// Runs a function with the database on a separate thread, returning a
+/// future for its result.
+///
+/// This is the building block for the other functions in this module and
+/// can be used for any combination of queries not covered by them.
+pub fn spawn_blocking<T, F>(f: F) -> Blocking<T>
+where
+	F: FnOnce(&'static DB<'static>) -> T + Send + 'static,
+	T: Send + 'static,
+{
+	let shared = Arc::new(Mutex::new(Shared {
+		result: None,
+		waker: None,
+	}));
+	let thread_shared = shared.clone();
+	thread::spawn(move || {
+		let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(get_db())));
+		let mut shared = thread_shared.lock().unwrap();
+		shared.result = Some(result);
+		if let Some(waker) = shared.waker.take() {
+			waker.wake();
+		}
+	});
+	Blocking { shared: shared }
+}
+
+/// Async version of [DB::query].
+pub fn query(query: Query) -> Blocking<Vec<usize>> {
+	spawn_blocking(move |db| db.query(&query))
+}
+
+/// Async version of [DB::query_cancellable].
+pub fn query_cancellable(query: Query, cancel: Cancel) -> Blocking<Result<Vec<usize>, Cancelled>> {
+	spawn_blocking(move |db| db.query_cancellable(&query, &cancel))
+}
+
+/// Async version of [DB::query] returning the results grouped with
+/// [DB::group_by_headword].
+pub fn query_headwords(query: Query) -> Blocking<Vec<Headword<'static>>> {
+	spawn_blocking(move |db| db.group_by_headword(db.query(&query)))
+}
+
+/// Async version of [DB::search_term].
+pub fn search_term(term: String) -> Blocking<ResultSet> {
+	spawn_blocking(move |db| {
+		let mut out = ResultSet::default();
+		db.search_term(term, &mut out);
+		out
+	})
+}
+
+/// Async version of [DB::search_prefix_top].
+pub fn search_prefix_top(prefix: String, limit: usize) -> Blocking<Vec<usize>> {
+	spawn_blocking(move |db| db.search_prefix_top(prefix, limit))
+}
+
+/// Async version of [DB::scan].
+pub fn scan(text: String) -> Blocking<Vec<ScanMatch>> {
+	spawn_blocking(move |db| db.scan(text))
+}
+
+/// Async version of [DB::scan_cancellable].
+pub fn scan_cancellable(text: String, cancel: Cancel) -> Blocking<Result<Vec<ScanMatch>, Cancelled>> {
+	spawn_blocking(move |db| db.scan_cancellable(text, &cancel))
+}
@@ -0,0 +1,714 @@
+//! Conversion of kana to romaji for display and exports.
+//!
+//! This is meant for output (e.g. a romaji column in exported word lists),
+//! so it favors readable romanization over round-tripping. Characters that
+//! are not kana are kept as is.
+
+use std::str::FromStr;
+
+/// Romanization style for [to_romaji].
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum RomajiStyle {
+	/// Modified Hepburn with macrons for long vowels (e.g. `とうきょう` as
+	/// `tōkyō` and `ラーメン` as `rāmen`).
+	///
+	/// Long vowels are detected from the kana alone, so vowels spanning a
+	/// word boundary (e.g. `おもう` as `omō`) are also merged.
+	Hepburn,
+	/// Word processor (wāpuro) style, with one romaji sequence for each kana
+	/// as it would be typed (e.g. `とうきょう` as `toukyou`, `を` as `wo`
+	/// and `ー` as `-`).
+	Wapuro,
+}
+
+impl Default for RomajiStyle {
+	fn default() -> RomajiStyle {
+		RomajiStyle::Hepburn
+	}
+}
+
+impl FromStr for RomajiStyle {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<RomajiStyle, String> {
+		match s.to_lowercase().as_str() {
+			"hepburn" => Ok(RomajiStyle::Hepburn),
+			"wapuro" | "wāpuro" => Ok(RomajiStyle::Wapuro),
+			_ => Err(format!("invalid romaji style `{}` (expected `hepburn` or `wapuro`)", s)),
+		}
+	}
+}
+
+/// Converts the kana in the text to romaji using the given style.
+pub fn to_romaji(text: &str, style: RomajiStyle) -> String {
+	let chars: Vec<char> = text.chars().map(to_hiragana).collect();
+	let mut out = String::with_capacity(text.len() * 2);
+	let mut double_next = false;
+	let mut index = 0;
+	while index < chars.len() {
+		let chr = chars[index];
+		let after_wo = index > 0 && chars[index - 1] == 'を';
+		index += 1;
+
+		match chr {
+			'っ' => {
+				double_next = true;
+				continue;
+			}
+			'ー' => {
+				if style == RomajiStyle::Hepburn {
+					if let Some(last) = out.pop() {
+						match macron(last) {
+							Some(long) => out.push(long),
+							None => {
+								out.push(last);
+								out.push('-');
+							}
+						}
+					}
+				} else {
+					out.push('-');
+				}
+				continue;
+			}
+			'ん' => {
+				out.push('n');
+				let next = chars.get(index).and_then(|&x| syllable(x, style));
+				if let Some(next) = next {
+					if next.starts_with(|x: char| "aiueoy".contains(x)) {
+						out.push('\'');
+					}
+				}
+				continue;
+			}
+			_ => {}
+		}
+
+		let base = match syllable(chr, style) {
+			Some(base) => base,
+			None => {
+				if double_next && style == RomajiStyle::Wapuro {
+					out.push_str("xtu");
+				}
+				double_next = false;
+				out.push(chr);
+				continue;
+			}
+		};
+
+		// Combine with a following small kana (e.g. `きゃ` or `ファ`)
+		let mut romaji = base.to_string();
+		if let Some(&small) = chars.get(index) {
+			if let Some(combined) = combine(base, small) {
+				romaji = combined;
+				index += 1;
+			}
+		}
+
+		if double_next {
+			double_next = false;
+			if romaji.starts_with("ch") {
+				out.push(if style == RomajiStyle::Hepburn { 't' } else { 'c' });
+			} else if let Some(first) = romaji.chars().next() {
+				if !"aiueon".contains(first) {
+					out.push(first);
+				}
+			}
+		}
+
+		if style == RomajiStyle::Hepburn && romaji.len() == 1 {
+			// Long vowels: `aa`, `uu`, `ee`, `oo` and `ou`
+			let vowel = romaji.chars().next().unwrap();
+			let last = out.chars().next_back();
+			let long = match (last, vowel) {
+				(Some('a'), 'a') | (Some('u'), 'u') | (Some('e'), 'e') | (Some('o'), 'o') | (Some('o'), 'u') => true,
+				_ => false,
+			};
+			// The particle `を` is never part of a long vowel
+			if long && chr != 'を' && !after_wo {
+				let last = out.pop().unwrap();
+				out.push(macron(last).unwrap());
+				continue;
+			}
+		}
+
+		out.push_str(&romaji);
+	}
+
+	if double_next && style == RomajiStyle::Wapuro {
+		out.push_str("xtu");
+	}
+	out
+}
+
+/// Romaji for a single hiragana.
+fn syllable(chr: char, style: RomajiStyle) -> Option<&'static str> {
+	let hepburn = style == RomajiStyle::Hepburn;
+	let out = match chr {
+		'あ' => "a",
+		'い' => "i",
+		'う' => "u",
+		'え' => "e",
+		'お' => "o",
+		'か' => "ka",
+		'き' => "ki",
+		'く' => "ku",
+		'け' => "ke",
+		'こ' => "ko",
+		'が' => "ga",
+		'ぎ' => "gi",
+		'ぐ' => "gu",
+		'げ' => "ge",
+		'ご' => "go",
+		'さ' => "sa",
+		'し' => "shi",
+		'す' => "su",
+		'せ' => "se",
+		'そ' => "so",
+		'ざ' => "za",
+		'じ' => "ji",
+		'ず' => "zu",
+		'ぜ' => "ze",
+		'ぞ' => "zo",
+		'た' => "ta",
+		'ち' => "chi",
+		'つ' => "tsu",
+		'て' => "te",
+		'と' => "to",
+		'だ' => "da",
+		'ぢ' => {
+			if hepburn {
+				"ji"
+			} else {
+				"di"
+			}
+		}
+		'づ' => {
+			if hepburn {
+				"zu"
+			} else {
+				"du"
+			}
+		}
+		'で' => "de",
+		'ど' => "do",
+		'な' => "na",
+		'に' => "ni",
+		'ぬ' => "nu",
+		'ね' => "ne",
+		'の' => "no",
+		'は' => "ha",
+		'ひ' => "hi",
+		'ふ' => "fu",
+		'へ' => "he",
+		'ほ' => "ho",
+		'ば' => "ba",
+		'び' => "bi",
+		'ぶ' => "bu",
+		'べ' => "be",
+		'ぼ' => "bo",
+		'ぱ' => "pa",
+		'ぴ' => "pi",
+		'ぷ' => "pu",
+		'ぺ' => "pe",
+		'ぽ' => "po",
+		'ま' => "ma",
+		'み' => "mi",
+		'む' => "mu",
+		'め' => "me",
+		'も' => "mo",
+		'や' => "ya",
+		'ゆ' => "yu",
+		'よ' => "yo",
+		'ら' => "ra",
+		'り' => "ri",
+		'る' => "ru",
+		'れ' => "re",
+		'ろ' => "ro",
+		'わ' => "wa",
+		'ゐ' => "i",
+		'ゑ' => "e",
+		'を' => {
+			if hepburn {
+				"o"
+			} else {
+				"wo"
+			}
+		}
+		'ゔ' => "vu",
+		'ぁ' => "a",
+		'ぃ' => "i",
+		'ぅ' => "u",
+		'ぇ' => "e",
+		'ぉ' => "o",
+		'ゃ' => "ya",
+		'ゅ' => "yu",
+		'ょ' => "yo",
+		'ゎ' => "wa",
+		_ => return None,
+	};
+	Some(out)
+}
+
+/// Combines a syllable with a following small kana, returning `None` if
+/// the kana is not small or the combination is not valid.
+fn combine(base: &str, small: char) -> Option<String> {
+	let vowel = match small {
+		'ゃ' => "ya",
+		'ゅ' => "yu",
+		'ょ' => "yo",
+		'ぁ' => "a",
+		'ぃ' => "i",
+		'ぅ' => "u",
+		'ぇ' => "e",
+		'ぉ' => "o",
+		_ => return None,
+	};
+
+	// Consonant for the syllable (e.g. `sh` for `shi` and `f` for `fu`)
+	let consonant = match base {
+		"a" | "i" | "e" | "o" => return None,
+		"u" => "w",
+		_ => &base[..base.len() - 1],
+	};
+	if consonant.len() == 0 {
+		return None;
+	}
+
+	let palatal = consonant == "sh" || consonant == "ch" || consonant == "j";
+	let out = if vowel.starts_with('y') {
+		if !base.ends_with('i') {
+			return None;
+		}
+		if palatal {
+			format!("{}{}", consonant, &vowel[1..])
+		} else {
+			format!("{}{}", consonant, vowel)
+		}
+	} else if consonant == "ts" || consonant == "f" || consonant == "v" || consonant == "w" || palatal {
+		format!("{}{}", consonant, vowel)
+	} else if base == "te" || base == "de" || base == "to" || base == "do" {
+		// `ティ` as `ti` and `トゥ` as `tu`
+		format!("{}{}", consonant, vowel)
+	} else {
+		return None;
+	};
+	Some(out)
+}
+
+fn macron(vowel: char) -> Option<char> {
+	match vowel {
+		'a' => Some('ā'),
+		'i' => Some('ī'),
+		'u' => Some('ū'),
+		'e' => Some('ē'),
+		'o' => Some('ō'),
+		_ => None,
+	}
+}
+
+fn to_hiragana(chr: char) -> char {
+	match chr {
+		'\u{30A1}'..='\u{30F6}' => std::char::from_u32(chr as u32 - 0x60).unwrap(),
+		_ => chr,
+	}
+}
+
+/// Kana typed as romaji, as returned by [parse_romaji_input].
+#[derive(Clone, PartialEq, Debug)]
+pub struct RomajiInput {
+	/// Input converted to hiragana, without the pending romaji.
+	pub kana: String,
+	/// Trailing romaji that is not a complete kana yet (e.g. the `k` in
+	/// `tabek` or an ambiguous final `n`).
+	pub pending: String,
+}
+
+impl RomajiInput {
+	/// Returns true if the whole input was converted.
+	pub fn is_complete(&self) -> bool {
+		self.pending.len() == 0
+	}
+}
+
+/// Converts partially typed romaji to hiragana, for search-as-you-type
+/// without an input method.
+///
+/// The parsing is tolerant of the usual keyboard input:
+///
+/// - Both Hepburn and wāpuro spellings are accepted (e.g. `shi` and `si`).
+/// - `nn`, `n'` and `n` before a consonant are `ん`. A final `n` is pending,
+///   since it can still be the start of `な` or `にゃ`. Before a vowel or `y`
+///   the second `n` of `nn` starts the next kana, as in Hepburn (e.g. `onna`
+///   as `おんな` and `konnyaku` as `こんにゃく`), so `ほんや` is `hon'ya`.
+/// - Double consonants are `っ` (e.g. `kitte` or `matcha`) and `-` is `ー`.
+/// - Vowels with macrons are long vowels (e.g. `tōkyō` as `とうきょう`).
+///
+/// Incomplete trailing romaji is returned as [pending](RomajiInput::pending),
+/// so the [kana](RomajiInput::kana) can be used as a prefix query while the
+/// user is typing. Characters that are not romaji are kept as is.
+pub fn parse_romaji_input(text: &str) -> RomajiInput {
+	let chars: Vec<char> = text
+		.chars()
+		.flat_map(|x| x.to_lowercase())
+		.flat_map(|x| expand_macron(x).unwrap_or(&[x][..]).to_vec())
+		.collect();
+	let mut kana = String::with_capacity(text.len() * 3);
+	let mut index = 0;
+	while index < chars.len() {
+		let chr = chars[index];
+		let next = chars.get(index + 1).cloned();
+
+		if !chr.is_ascii_alphabetic() {
+			kana.push(if chr == '-' { 'ー' } else { chr });
+			index += 1;
+			continue;
+		}
+
+		if chr == 'n' {
+			match next {
+				None => break,
+				Some('n') => {
+					// Before a vowel `nn` can be `ん` followed by `な` (e.g.
+					// `konnichiha` or `onna`)
+					let after = chars.get(index + 2).cloned();
+					let before_vowel = after.map(|x| is_vowel(x) || x == 'y').unwrap_or(false);
+					kana.push('ん');
+					index += if before_vowel { 1 } else { 2 };
+					continue;
+				}
+				Some('\'') => {
+					kana.push('ん');
+					index += 2;
+					continue;
+				}
+				Some(next) if !is_vowel(next) && next != 'y' => {
+					kana.push('ん');
+					index += 1;
+					continue;
+				}
+				_ => {}
+			}
+		}
+
+		let before_ch = chr == 't' && next == Some('c') && chars.get(index + 2) == Some(&'h');
+		if (next == Some(chr) && !is_vowel(chr)) || before_ch {
+			kana.push('っ');
+			index += 1;
+			continue;
+		}
+
+		let matched = (1..=4).rev().find_map(|len| {
+			let key: String = chars.iter().skip(index).take(len).collect();
+			if key.chars().count() < len {
+				return None;
+			}
+			ROMAJI_KANA.iter().find(|x| x.0 == key).map(|x| (len, x.1))
+		});
+		match matched {
+			Some((len, value)) => {
+				kana.push_str(value);
+				index += len;
+			}
+			None => {
+				let rest: String = chars[index..].iter().collect();
+				if ROMAJI_KANA.iter().any(|x| x.0.starts_with(&rest)) {
+					break;
+				}
+				kana.push(chr);
+				index += 1;
+			}
+		}
+	}
+
+	RomajiInput {
+		kana: kana,
+		pending: chars[index..].iter().collect(),
+	}
+}
+
+fn is_vowel(chr: char) -> bool {
+	"aiueo".contains(chr)
+}
+
+/// Expands a vowel with a macron to the romaji for the long vowel.
+fn expand_macron(chr: char) -> Option<&'static [char]> {
+	match chr {
+		'ā' => Some(&['a', 'a']),
+		'ī' => Some(&['i', 'i']),
+		'ū' => Some(&['u', 'u']),
+		'ē' => Some(&['e', 'e']),
+		'ō' => Some(&['o', 'u']),
+		_ => None,
+	}
+}
+
+/// Romaji to hiragana table for [parse_romaji_input].
+const ROMAJI_KANA: &'static [(&'static str, &'static str)] = &[
+	("a", "あ"),
+	("i", "い"),
+	("u", "う"),
+	("e", "え"),
+	("o", "お"),
+	("ka", "か"),
+	("ki", "き"),
+	("ku", "く"),
+	("ke", "け"),
+	("ko", "こ"),
+	("kya", "きゃ"),
+	("kyu", "きゅ"),
+	("kyo", "きょ"),
+	("ga", "が"),
+	("gi", "ぎ"),
+	("gu", "ぐ"),
+	("ge", "げ"),
+	("go", "ご"),
+	("gya", "ぎゃ"),
+	("gyu", "ぎゅ"),
+	("gyo", "ぎょ"),
+	("sa", "さ"),
+	("shi", "し"),
+	("si", "し"),
+	("su", "す"),
+	("se", "せ"),
+	("so", "そ"),
+	("sha", "しゃ"),
+	("shu", "しゅ"),
+	("she", "しぇ"),
+	("sho", "しょ"),
+	("sya", "しゃ"),
+	("syu", "しゅ"),
+	("syo", "しょ"),
+	("za", "ざ"),
+	("ji", "じ"),
+	("zi", "じ"),
+	("zu", "ず"),
+	("ze", "ぜ"),
+	("zo", "ぞ"),
+	("ja", "じゃ"),
+	("ju", "じゅ"),
+	("je", "じぇ"),
+	("jo", "じょ"),
+	("jya", "じゃ"),
+	("jyu", "じゅ"),
+	("jyo", "じょ"),
+	("zya", "じゃ"),
+	("zyu", "じゅ"),
+	("zyo", "じょ"),
+	("ta", "た"),
+	("chi", "ち"),
+	("ti", "ち"),
+	("tsu", "つ"),
+	("tu", "つ"),
+	("te", "て"),
+	("to", "と"),
+	("cha", "ちゃ"),
+	("chu", "ちゅ"),
+	("che", "ちぇ"),
+	("cho", "ちょ"),
+	("tya", "ちゃ"),
+	("tyu", "ちゅ"),
+	("tyo", "ちょ"),
+	("cya", "ちゃ"),
+	("cyu", "ちゅ"),
+	("cyo", "ちょ"),
+	("da", "だ"),
+	("di", "ぢ"),
+	("du", "づ"),
+	("de", "で"),
+	("do", "ど"),
+	("na", "な"),
+	("ni", "に"),
+	("nu", "ぬ"),
+	("ne", "ね"),
+	("no", "の"),
+	("nya", "にゃ"),
+	("nyu", "にゅ"),
+	("nyo", "にょ"),
+	("ha", "は"),
+	("hi", "ひ"),
+	("fu", "ふ"),
+	("hu", "ふ"),
+	("he", "へ"),
+	("ho", "ほ"),
+	("hya", "ひゃ"),
+	("hyu", "ひゅ"),
+	("hyo", "ひょ"),
+	("fa", "ふぁ"),
+	("fi", "ふぃ"),
+	("fe", "ふぇ"),
+	("fo", "ふぉ"),
+	("ba", "ば"),
+	("bi", "び"),
+	("bu", "ぶ"),
+	("be", "べ"),
+	("bo", "ぼ"),
+	("bya", "びゃ"),
+	("byu", "びゅ"),
+	("byo", "びょ"),
+	("pa", "ぱ"),
+	("pi", "ぴ"),
+	("pu", "ぷ"),
+	("pe", "ぺ"),
+	("po", "ぽ"),
+	("pya", "ぴゃ"),
+	("pyu", "ぴゅ"),
+	("pyo", "ぴょ"),
+	("ma", "ま"),
+	("mi", "み"),
+	("mu", "む"),
+	("me", "め"),
+	("mo", "も"),
+	("mya", "みゃ"),
+	("myu", "みゅ"),
+	("myo", "みょ"),
+	("ya", "や"),
+	("yu", "ゆ"),
+	("yo", "よ"),
+	("ra", "ら"),
+	("ri", "り"),
+	("ru", "る"),
+	("re", "れ"),
+	("ro", "ろ"),
+	("rya", "りゃ"),
+	("ryu", "りゅ"),
+	("ryo", "りょ"),
+	("wa", "わ"),
+	("wi", "うぃ"),
+	("we", "うぇ"),
+	("wo", "を"),
+	("va", "ゔぁ"),
+	("vi", "ゔぃ"),
+	("vu", "ゔ"),
+	("ve", "ゔぇ"),
+	("vo", "ゔぉ"),
+	("xa", "ぁ"),
+	("xi", "ぃ"),
+	("xu", "ぅ"),
+	("xe", "ぇ"),
+	("xo", "ぉ"),
+	("xya", "ゃ"),
+	("xyu", "ゅ"),
+	("xyo", "ょ"),
+	("xtu", "っ"),
+	("xtsu", "っ"),
+	("la", "ぁ"),
+	("li", "ぃ"),
+	("lu", "ぅ"),
+	("le", "ぇ"),
+	("lo", "ぉ"),
+	("lya", "ゃ"),
+	("lyu", "ゅ"),
+	("lyo", "ょ"),
+	("ltu", "っ"),
+	("ltsu", "っ"),
+];
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn hepburn(text: &str) -> String {
+		to_romaji(text, RomajiStyle::Hepburn)
+	}
+
+	fn wapuro(text: &str) -> String {
+		to_romaji(text, RomajiStyle::Wapuro)
+	}
+
+	#[test]
+	fn long_vowels() {
+		assert_eq!(hepburn("とうきょう"), "tōkyō");
+		assert_eq!(wapuro("とうきょう"), "toukyou");
+		assert_eq!(hepburn("ラーメン"), "rāmen");
+		assert_eq!(wapuro("ラーメン"), "ra-men");
+		assert_eq!(hepburn("おかあさん"), "okāsan");
+		assert_eq!(hepburn("ー"), "");
+	}
+
+	#[test]
+	fn syllabic_n() {
+		assert_eq!(hepburn("きんえん"), "kin'en");
+		assert_eq!(wapuro("きんえん"), "kin'en");
+		assert_eq!(hepburn("こんや"), "kon'ya");
+		assert_eq!(hepburn("しんぶん"), "shinbun");
+		assert_eq!(hepburn("ほん"), "hon");
+	}
+
+	#[test]
+	fn sokuon() {
+		assert_eq!(hepburn("まっちゃ"), "matcha");
+		assert_eq!(wapuro("まっちゃ"), "maccha");
+		assert_eq!(hepburn("きって"), "kitte");
+		assert_eq!(hepburn("あっ"), "a");
+		assert_eq!(wapuro("あっ"), "axtu");
+		assert_eq!(wapuro("っ!"), "xtu!");
+	}
+
+	#[test]
+	fn particle_wo() {
+		assert_eq!(hepburn("を"), "o");
+		assert_eq!(wapuro("を"), "wo");
+		assert_eq!(hepburn("ほんを"), "hon'o");
+		assert_eq!(hepburn("これをおく"), "koreooku");
+	}
+
+	#[test]
+	fn small_kana() {
+		assert_eq!(hepburn("ティー"), "tī");
+		assert_eq!(wapuro("ティー"), "ti-");
+		assert_eq!(hepburn("ウェブ"), "webu");
+		assert_eq!(wapuro("ウェブ"), "webu");
+		assert_eq!(hepburn("きゃく"), "kyaku");
+		assert_eq!(hepburn("ファン"), "fan");
+	}
+
+	fn input(text: &str) -> (String, String) {
+		let input = parse_romaji_input(text);
+		(input.kana, input.pending)
+	}
+
+	fn kana(text: &str) -> String {
+		let input = parse_romaji_input(text);
+		assert!(input.is_complete(), "pending `{}` for `{}`", input.pending, text);
+		input.kana
+	}
+
+	#[test]
+	fn input_syllabic_n() {
+		assert_eq!(kana("konnichiha"), "こんにちは");
+		assert_eq!(kana("onna"), "おんな");
+		assert_eq!(kana("senn"), "せん");
+		assert_eq!(kana("kan'i"), "かんい");
+		assert_eq!(input("shinbun"), ("しんぶ".to_string(), "n".to_string()));
+		assert_eq!(kana("konnyaku"), "こんにゃく");
+		assert_eq!(kana("honnya"), "ほんにゃ");
+		assert_eq!(kana("hon'ya"), "ほんや");
+	}
+
+	#[test]
+	fn input_pending() {
+		assert_eq!(input("sen"), ("せ".to_string(), "n".to_string()));
+		assert_eq!(input("tabek"), ("たべ".to_string(), "k".to_string()));
+		assert_eq!(input("toky"), ("と".to_string(), "ky".to_string()));
+		assert_eq!(input(""), (String::new(), String::new()));
+	}
+
+	#[test]
+	fn input_spellings() {
+		assert_eq!(kana("matcha"), "まっちゃ");
+		assert_eq!(kana("kitte"), "きって");
+		assert_eq!(kana("tōkyō"), "とうきょう");
+		assert_eq!(kana("ra-mennda"), "らーめんだ");
+		assert_eq!(kana("shi si"), "し し");
+		assert_eq!(kana("TABERU"), "たべる");
+		assert_eq!(kana("Tōkyō"), "とうきょう");
+	}
+
+	#[test]
+	fn other_text() {
+		assert_eq!(hepburn("CDをかう"), "CDokau");
+		assert_eq!("wāpuro".parse::<RomajiStyle>(), Ok(RomajiStyle::Wapuro));
+		assert!("kunrei".parse::<RomajiStyle>().is_err());
+	}
+}